@@ -15,15 +15,27 @@ use std::path::PathBuf;
     List contents without downloading:
         dl-nzb -l file.nzb
 
+    Check the server has enough of it for PAR2 to repair:
+        dl-nzb -l --check file.nzb
+
     Show configuration:
         dl-nzb config
 
     Test connection:
         dl-nzb test
 
+    Fill in missing files from an earlier run:
+        dl-nzb retry file.nzb /downloads/file
+
+    Download a list of NZBs one after another:
+        dl-nzb --batch overnight.txt
+
+    Fill failed segments from another NZB of the same release:
+        dl-nzb --fill-from alt.nzb file.nzb
+
 For advanced options, edit ~/.config/dl-nzb/config.toml")]
 pub struct Cli {
-    /// NZB files to download
+    /// NZB files, or http(s):// links to them, to download
     #[arg(value_name = "FILE")]
     pub files: Vec<PathBuf>,
 
@@ -31,10 +43,19 @@ pub struct Cli {
     #[arg(short, long, value_name = "DIR")]
     pub output: Option<PathBuf>,
 
+    /// Name the downloaded file of a single-file NZB (PAR2 files keep their names);
+    /// deobfuscation leaves the name as given
+    #[arg(long, value_name = "NAME")]
+    pub output_name: Option<String>,
+
     /// List contents without downloading
     #[arg(short, long)]
     pub list: bool,
 
+    /// With --list: check article availability on the server and whether PAR2 can repair what's missing
+    #[arg(long, requires = "list")]
+    pub check: bool,
+
     /// Quiet mode (errors only)
     #[arg(short, long)]
     pub quiet: bool,
@@ -43,10 +64,14 @@ pub struct Cli {
     #[arg(short, long, action = clap::ArgAction::Count)]
     pub verbose: u8,
 
-    /// JSON output for scripting
+    /// JSON output for scripting (downloads also stream PAR2/extraction events)
     #[arg(long)]
     pub json: bool,
 
+    /// Log every NNTP command and response line (credentials redacted) at trace level
+    #[arg(long)]
+    pub trace_nntp: bool,
+
     /// Config file path
     #[arg(long, value_name = "FILE")]
     pub config: Option<PathBuf>,
@@ -55,6 +80,18 @@ pub struct Cli {
     #[arg(short, long)]
     pub force: bool,
 
+    /// Second NZB of the same release to fetch failed segments from
+    #[arg(long, value_name = "NZB")]
+    pub fill_from: Option<PathBuf>,
+
+    /// File listing NZB paths or URLs, one per line, to download one after another
+    #[arg(long, value_name = "FILE")]
+    pub batch: Option<PathBuf>,
+
+    /// With --batch: stop at the first NZB that fails instead of moving on
+    #[arg(long, requires = "batch")]
+    pub stop_on_error: bool,
+
     /// Subcommands
     #[command(subcommand)]
     pub command: Option<Commands>,
@@ -124,6 +161,25 @@ pub enum Commands {
     /// Test connection to Usenet server
     Test,
 
+    /// Re-download only the files missing or incomplete in an existing output directory
+    Retry {
+        /// NZB file used for the original download
+        nzb: PathBuf,
+
+        /// Output directory from the original download
+        dir: PathBuf,
+
+        /// Fetch from a different server (e.g. a backup provider)
+        #[arg(long, value_name = "HOST")]
+        server: Option<String>,
+    },
+
+    /// Print an NZB's segment map as JSON: each file's articles, sizes and groups in order
+    Segments {
+        /// NZB file to read
+        nzb: PathBuf,
+    },
+
     /// Show configuration
     Config,
 
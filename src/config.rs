@@ -1,3 +1,4 @@
+use chrono::{NaiveDate, NaiveTime};
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::path::{Path, PathBuf};
@@ -42,6 +43,10 @@ pub struct Config {
 
     #[serde(default)]
     pub tuning: TuningConfig,
+
+    /// Time-of-day windows with their own connection count
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<ScheduleWindow>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -49,13 +54,50 @@ pub struct UsenetConfig {
     pub server: String,
     pub port: u16,
     pub username: String,
+    #[serde(default)]
     pub password: String,
+    /// Read the password from this file at load time (e.g. a Docker secret)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_file: Option<PathBuf>,
+    /// Read the password from this environment variable at load time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_env: Option<String>,
     pub ssl: bool,
+    /// With `ssl` off, connect in plaintext and upgrade with STARTTLS before logging in
+    #[serde(default)]
+    pub starttls: bool,
+    /// Permit connecting without TLS, which sends credentials in cleartext
+    #[serde(default)]
+    pub allow_insecure_plaintext: bool,
     pub verify_ssl_certs: bool,
-    pub connections: u16,
+    /// Hostname sent as TLS SNI and checked against the certificate, when it differs from `server`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_sni: Option<String>,
+    /// Connection count, or `"auto"` to auto-tune up to 50
+    pub connections: Connections,
     pub timeout: u64, // seconds
     pub retry_attempts: u8,
     pub retry_delay: u64, // milliseconds
+    /// Make `retry_attempts` extra passes over each file's failed segments
+    #[serde(default)]
+    pub retry_failed_segments: bool,
+    #[serde(default = "default_true")]
+    pub mode_reader: bool,
+    /// Check yEnc part CRCs and retry looser decodes before failing a segment
+    #[serde(default)]
+    pub yenc_crc_retry: bool,
+    /// Resynchronize with a DATE sentinel after an unexpected pipelined response
+    #[serde(default = "default_true")]
+    pub pipeline_resync: bool,
+    /// Replace a connection after this many bodies in a row fail size or CRC checks (0 = never)
+    #[serde(default = "default_evict_after_invalid_bodies")]
+    pub evict_after_invalid_bodies: u32,
+    /// Extra commands sent after login and MODE READER (e.g. `MODE STREAM`)
+    #[serde(default)]
+    pub post_connect_commands: Vec<String>,
+    /// Binary group the `test` command selects to confirm the account can download (empty = skip)
+    #[serde(default)]
+    pub test_group: String,
 }
 
 // Custom Debug implementation to hide sensitive data
@@ -66,12 +108,27 @@ impl std::fmt::Debug for UsenetConfig {
             .field("port", &self.port)
             .field("username", &self.username)
             .field("password", &"<REDACTED>")
+            .field("password_file", &self.password_file)
+            .field("password_env", &self.password_env)
             .field("ssl", &self.ssl)
+            .field("starttls", &self.starttls)
+            .field("allow_insecure_plaintext", &self.allow_insecure_plaintext)
             .field("verify_ssl_certs", &self.verify_ssl_certs)
+            .field("tls_sni", &self.tls_sni)
             .field("connections", &self.connections)
             .field("timeout", &self.timeout)
             .field("retry_attempts", &self.retry_attempts)
             .field("retry_delay", &self.retry_delay)
+            .field("retry_failed_segments", &self.retry_failed_segments)
+            .field("mode_reader", &self.mode_reader)
+            .field("yenc_crc_retry", &self.yenc_crc_retry)
+            .field("pipeline_resync", &self.pipeline_resync)
+            .field(
+                "evict_after_invalid_bodies",
+                &self.evict_after_invalid_bodies,
+            )
+            .field("post_connect_commands", &self.post_connect_commands)
+            .field("test_group", &self.test_group)
             .finish()
     }
 }
@@ -80,9 +137,72 @@ impl std::fmt::Debug for UsenetConfig {
 pub struct DownloadConfig {
     pub dir: PathBuf,
     pub create_subfolders: bool,
+    /// Move each finished NZB folder into a subfolder named after its detected category
+    #[serde(default)]
+    pub category_folders: bool,
     pub user_agent: String,
     #[serde(default)]
     pub force_redownload: bool,
+    /// Check that the server carries each NZB group before requesting articles
+    #[serde(default)]
+    pub precheck_groups: bool,
+    /// Refuse to download into a directory holding files the NZB doesn't list
+    #[serde(default)]
+    pub protect_existing_files: bool,
+    /// Skip NZBs whose content hash is already in the processed-NZB history
+    #[serde(default)]
+    pub skip_duplicate_nzbs: bool,
+    /// Log post age and article availability per group to estimate the provider's retention
+    #[serde(default)]
+    pub track_retention: bool,
+    /// Regexes for files to download first, highest priority first (e.g. `\.part0*1\.rar$`)
+    #[serde(default)]
+    pub priority_patterns: Vec<String>,
+    /// Download PAR2 files ahead of content, index files before recovery volumes
+    #[serde(default)]
+    pub par2_first: bool,
+    /// Skip NZBs whose PAR2 recovery is below this percentage of the data; 0 = accept all
+    ///
+    /// Recovery is read from PAR2 volume names, so NZBs without recognizable volumes are kept.
+    #[serde(default)]
+    pub min_recovery_percent: f64,
+    /// How many NZBs to download at once; all of them share one connection pool
+    #[serde(default = "default_max_concurrent_nzbs")]
+    pub max_concurrent_nzbs: usize,
+    /// Check that each file's segment numbers run 1..N without gaps or duplicates
+    #[serde(default)]
+    pub segment_numbering: SegmentNumbering,
+    /// Flag files whose assembled size differs from the yEnc-declared size although no segment failed
+    #[serde(default)]
+    pub verify_assembled_size: bool,
+    /// Place segments by the part number in their yEnc header when it disagrees with the NZB,
+    /// failing any segment whose part was already written
+    #[serde(default)]
+    pub assemble_by_yenc_part: bool,
+    /// Only download files whose poster contains one of these (case-insensitive)
+    #[serde(default)]
+    pub posters: Vec<String>,
+    /// Only download files posted on or after this date (`YYYY-MM-DD`, UTC)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub posted_after: Option<String>,
+    /// Only download files posted before this date (`YYYY-MM-DD`, UTC)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub posted_before: Option<String>,
+    /// Subfolders for files matching a pattern; the first matching rule wins
+    #[serde(default)]
+    pub output_rules: Vec<OutputRule>,
+    /// Name for the content file of a single-file NZB; set per run from the CLI
+    #[serde(skip)]
+    pub output_name: Option<String>,
+}
+
+/// Send files whose name matches `pattern` to a subfolder of the download directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputRule {
+    /// Regex matched against the filename (case-insensitive)
+    pub pattern: String,
+    /// Subfolder relative to the download directory
+    pub path: PathBuf,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -90,6 +210,15 @@ pub struct MemoryConfig {
     pub max_segments_in_memory: usize,
     pub io_buffer_size: usize,
     pub max_concurrent_files: usize,
+    /// Spill out-of-order segments to a temp file when streaming to a non-seekable writer
+    #[serde(default)]
+    pub spill_to_temp: bool,
+    /// Decoded segments queued per file for writing before downloads wait for the disk
+    #[serde(default = "default_write_queue_depth")]
+    pub write_queue_depth: usize,
+    /// Output files downloads and extraction may hold open at once (0 = no limit)
+    #[serde(default)]
+    pub max_open_files: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -99,6 +228,76 @@ pub struct PostProcessingConfig {
     pub delete_rar_after_extract: bool,
     pub delete_par2_after_repair: bool,
     pub deobfuscate_file_names: bool,
+    #[serde(default)]
+    pub par2_verify_only: bool,
+    /// Run PAR2 separately for each set instead of once with the first PAR2 file
+    #[serde(default = "default_true")]
+    pub par2_per_set: bool,
+    /// Count files with failed segments as complete once PAR2 verifies or repairs them
+    #[serde(default)]
+    pub trust_par2_verification: bool,
+    #[serde(default)]
+    pub force_reextract: bool,
+    #[serde(default)]
+    pub flatten_extraction: bool,
+    /// Whether the extraction target tells apart names differing only in case
+    #[serde(default)]
+    pub filesystem_case: FilesystemCase,
+    /// What to do with the files an archive wrote before its extraction failed
+    #[serde(default)]
+    pub partial_extraction: PartialExtraction,
+    /// Also extract archives found inside extracted archives, up to this many levels deep
+    #[serde(default)]
+    pub nested_extraction_depth: usize,
+    /// Only extract archives whose names match one of these regexes (all when empty)
+    #[serde(default)]
+    pub extract_include: Vec<String>,
+    /// Never extract archives whose names match one of these regexes
+    #[serde(default)]
+    pub extract_exclude: Vec<String>,
+    /// Only extract archive entries whose paths match one of these globs (all when empty)
+    #[serde(default)]
+    pub extract_entry_include: Vec<String>,
+    /// Never extract archive entries whose paths match one of these globs
+    #[serde(default)]
+    pub extract_entry_exclude: Vec<String>,
+    /// Globs for files that RAR/PAR2 deletion and duplicate merging leave alone
+    ///
+    /// Deletion only ever removes RAR parts and PAR2 files, so for anything else (like the
+    /// default ReScene `.srr`) this only keeps duplicate merging off it.
+    #[serde(default = "default_preserve_files")]
+    pub preserve_files: Vec<String>,
+    #[serde(default)]
+    pub deobfuscate_match_nzb: bool,
+    /// Pass over PAR2/NFO/etc. files when picking the largest file to deobfuscate
+    #[serde(default = "default_true")]
+    pub deobfuscate_skip_excluded: bool,
+    /// Replace a popular extension that the file's magic bytes confidently contradict
+    #[serde(default)]
+    pub fix_mislabeled_extensions: bool,
+    /// Where deobfuscated files get their name when the NZB/directory name is generic or a hash
+    #[serde(default)]
+    pub poor_name_fallback: PoorNameFallback,
+    #[serde(default)]
+    pub verify_media_container: bool,
+    /// Write a JSON inventory of the finished download directory
+    #[serde(default)]
+    pub write_manifest: bool,
+    /// Program and arguments to run after each NZB is downloaded and post-processed
+    #[serde(default)]
+    pub post_download_command: Vec<String>,
+    /// Time (seconds) the post-download command may run before it is killed; 0 = no limit
+    #[serde(default = "default_post_download_timeout")]
+    pub post_download_timeout: u64,
+    /// Process each release set (archives/PAR2 sharing a base name) in a folder of its own
+    #[serde(default)]
+    pub split_release_sets: bool,
+    /// What to do with files whose contents are identical to another file in the download
+    #[serde(default)]
+    pub duplicate_files: DuplicateAction,
+    /// PAR2/extraction jobs allowed to run at once across concurrent NZBs
+    #[serde(default = "default_max_concurrent_jobs")]
+    pub max_concurrent_jobs: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -106,6 +305,24 @@ pub struct LoggingConfig {
     pub level: String,
     pub file: Option<PathBuf>,
     pub format: String,
+    /// Style of the result lines printed after downloading and each post-processing stage
+    #[serde(default)]
+    pub summary: SummaryFormat,
+}
+
+/// How download and post-processing results are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryFormat {
+    /// Colors, symbols and tree lines
+    #[default]
+    Fancy,
+    /// The same lines as plain ASCII text
+    Plain,
+    /// One line per NZB, plus a line for each stage that failed and each warning
+    Minimal,
+    /// No summaries
+    None,
 }
 
 /// Performance tuning parameters
@@ -114,12 +331,344 @@ pub struct LoggingConfig {
 pub struct TuningConfig {
     /// Number of segments to request per connection in a pipeline batch
     pub pipeline_size: usize,
+    /// Batch by total segment bytes instead, at most this many per batch; 0 = use `pipeline_size`
+    #[serde(default)]
+    pub pipeline_bytes: u64,
     /// Maximum time (seconds) to wait for a pool connection before skipping batch
     pub connection_wait_timeout: u64,
+    /// Time (seconds) a file may stall with batches waiting for connections; 0 = no limit
+    #[serde(default = "default_connection_wait_budget")]
+    pub connection_wait_budget: u64,
     /// Maximum concurrent connection creation attempts
     pub max_concurrent_connections: usize,
     /// File size threshold (bytes) above which to show progress during RAR extraction
     pub large_file_threshold: u64,
+    /// Cap the connection count at the number of pipeline batches for small NZBs
+    #[serde(default = "default_true")]
+    pub scale_pool_to_nzb: bool,
+    /// How segments are assigned to pipeline batches within a file
+    #[serde(default)]
+    pub segment_order: SegmentOrder,
+    /// Batches one file may download at once; 0 splits connections fairly between files
+    #[serde(default)]
+    pub max_batches_per_file: usize,
+    /// Completion (0.99 = 99% of segments) past which a file's failed segments are left to
+    /// PAR2 instead of being retried further; 0 = always use every retry. Only applies with
+    /// `retry_failed_segments` on, since otherwise nothing is retried.
+    #[serde(default)]
+    pub par2_handoff_threshold: f64,
+    /// Seconds a file past the threshold may still spend retrying before handing off
+    #[serde(default)]
+    pub max_retry_time: u64,
+    /// How the wait between attempts to get a pool connection grows
+    #[serde(default)]
+    pub backoff: BackoffStrategy,
+    /// First backoff delay (ms), and the step for linear backoff
+    #[serde(default = "default_backoff_base_ms")]
+    pub backoff_base_ms: u64,
+    /// Longest backoff delay (ms)
+    #[serde(default = "default_backoff_cap_ms")]
+    pub backoff_cap_ms: u64,
+    /// Random spread applied to each delay (0.2 = ±20%), so connections dropped together
+    /// don't all retry at the same moment
+    #[serde(default = "default_backoff_jitter")]
+    pub backoff_jitter: f64,
+}
+
+/// Growth of the delay between retries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackoffStrategy {
+    /// Double the delay each attempt, up to the cap
+    #[default]
+    Exponential,
+    /// Add the base delay each attempt, up to the cap
+    Linear,
+    /// Wait the base delay every time
+    Fixed,
+}
+
+/// Assignment of a file's segments to pipeline batches
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentOrder {
+    /// Consecutive runs of segments per batch
+    #[default]
+    Contiguous,
+    /// Round-robin across batches so no connection owns a contiguous region
+    Interleaved,
+}
+
+/// What to do with a file whose contents duplicate another one in the same download
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateAction {
+    /// Leave duplicates in place
+    #[default]
+    Keep,
+    /// Replace each duplicate with a hardlink to the copy that is kept
+    Hardlink,
+    /// Delete duplicates
+    Remove,
+}
+
+/// How the extraction target's filesystem compares names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilesystemCase {
+    /// Find out by probing the output directory
+    #[default]
+    Auto,
+    /// `a.txt` and `A.txt` are different files (Linux)
+    Sensitive,
+    /// `a.txt` and `A.txt` are the same file (macOS and Windows defaults)
+    Insensitive,
+}
+
+/// What happens to files written by an extraction that failed partway
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PartialExtraction {
+    /// Leave them in place, e.g. to inspect what went wrong
+    #[default]
+    Keep,
+    /// Delete them
+    Delete,
+    /// Move them into `.dl-nzb-partial/<archive>/` in the output directory
+    Quarantine,
+}
+
+/// Name source for deobfuscation when the NZB/directory name is generic or a hash
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PoorNameFallback {
+    /// Rename to it anyway
+    #[default]
+    Keep,
+    /// Take the title from the release's .nfo, or else the main file's type and a timestamp
+    Derive,
+    /// Leave the obfuscated names alone
+    Skip,
+}
+
+/// What to do when a file's segment numbers aren't a contiguous 1..N
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentNumbering {
+    /// Don't check
+    #[default]
+    Ignore,
+    /// Print a warning and download the file anyway
+    Warn,
+    /// Fail the file without downloading it
+    Strict,
+}
+
+/// A daily time window that overrides the connection count and download speed
+///
+/// Times are local `HH:MM`; a window whose end is before its start wraps past midnight.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleWindow {
+    pub start: String,
+    pub end: String,
+    /// Connections to use inside the window; 0 pauses downloading
+    pub connections: u16,
+    /// Download speed cap inside the window in KiB/s; 0 = unlimited
+    #[serde(default)]
+    pub max_speed_kib: u64,
+}
+
+impl ScheduleWindow {
+    fn parse_time(value: &str) -> Option<NaiveTime> {
+        NaiveTime::parse_from_str(value.trim(), "%H:%M").ok()
+    }
+
+    /// Check whether a time of day falls inside this window
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        let (Some(start), Some(end)) = (Self::parse_time(&self.start), Self::parse_time(&self.end))
+        else {
+            return false;
+        };
+
+        if start <= end {
+            time >= start && time < end
+        } else {
+            time >= start || time < end
+        }
+    }
+}
+
+/// Connection cap used by `connections = "auto"`
+const AUTO_CONNECTIONS_CAP: u16 = 50;
+
+/// The `connections` setting: a fixed count, or `"auto"` to auto-tune up to a cap of 50
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connections {
+    Fixed(u16),
+    Auto,
+}
+
+impl Connections {
+    /// Connections to open; with `Auto`, the most auto-tuning may ramp up to
+    pub fn count(self) -> u16 {
+        match self {
+            Connections::Fixed(count) => count,
+            Connections::Auto => AUTO_CONNECTIONS_CAP,
+        }
+    }
+}
+
+impl std::str::FromStr for Connections {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        if value.trim().eq_ignore_ascii_case("auto") {
+            return Ok(Connections::Auto);
+        }
+        value.trim().parse().map(Connections::Fixed).map_err(|_| {
+            format!(
+                "invalid connections '{}', expected a number or \"auto\"",
+                value
+            )
+        })
+    }
+}
+
+impl Serialize for Connections {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Connections::Fixed(count) => serializer.serialize_u16(*count),
+            Connections::Auto => serializer.serialize_str("auto"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Connections {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Count(u16),
+            Keyword(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Count(count) => Ok(Connections::Fixed(count)),
+            Raw::Keyword(keyword) => keyword.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Parse a `YYYY-MM-DD` post date filter into a Unix timestamp at midnight UTC
+pub fn parse_post_date(value: &str) -> Option<u64> {
+    let date = NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").ok()?;
+    let timestamp = date.and_hms_opt(0, 0, 0)?.and_utc().timestamp();
+    u64::try_from(timestamp).ok()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_evict_after_invalid_bodies() -> u32 {
+    5
+}
+
+fn default_preserve_files() -> Vec<String> {
+    vec!["*.srr".to_string()]
+}
+
+fn default_max_concurrent_nzbs() -> usize {
+    1
+}
+
+fn default_connection_wait_budget() -> u64 {
+    600
+}
+
+fn default_post_download_timeout() -> u64 {
+    3600
+}
+
+fn default_max_concurrent_jobs() -> usize {
+    1
+}
+
+fn default_write_queue_depth() -> usize {
+    64
+}
+
+fn default_backoff_base_ms() -> u64 {
+    1000
+}
+
+fn default_backoff_cap_ms() -> u64 {
+    8000
+}
+
+fn default_backoff_jitter() -> f64 {
+    0.2
+}
+
+impl UsenetConfig {
+    /// Whether connections are encrypted, either from the start or after STARTTLS
+    pub fn uses_tls(&self) -> bool {
+        self.ssl || self.starttls
+    }
+
+    /// Hostname for the TLS handshake: `tls_sni` if set, otherwise `server`
+    pub fn tls_host(&self) -> &str {
+        self.tls_sni.as_deref().unwrap_or(&self.server)
+    }
+
+    /// Refuse a cleartext connection unless `allow_insecure_plaintext` opts into it
+    pub fn check_plaintext_allowed(&self) -> Result<()> {
+        if self.uses_tls() || self.allow_insecure_plaintext {
+            return Ok(());
+        }
+
+        Err(ConfigError::Invalid {
+            field: "allow_insecure_plaintext".to_string(),
+            reason: "ssl and starttls are off, so credentials would be sent in cleartext; \
+                     enable ssl or starttls, or set allow_insecure_plaintext = true"
+                .to_string(),
+        }
+        .into())
+    }
+
+    /// Fill in the password from `password_file` or `password_env`, if either is set
+    ///
+    /// The resolved password is only kept in memory; the config file keeps the reference.
+    pub fn resolve_password(&mut self) -> Result<()> {
+        if let Some(path) = &self.password_file {
+            let path = expand_tilde(path);
+            let content = std::fs::read_to_string(&path).map_err(|e| ConfigError::Invalid {
+                field: "password_file".to_string(),
+                reason: format!("Could not read {}: {}", path.display(), e),
+            })?;
+            self.password = content.trim_end_matches(['\r', '\n']).to_string();
+        } else if let Some(var) = &self.password_env {
+            self.password = env::var(var).map_err(|_| ConfigError::Invalid {
+                field: "password_env".to_string(),
+                reason: format!("Environment variable {} is not set", var),
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PostProcessingConfig {
+    /// Whether PAR2 only verifies; `auto_par2_repair` wins when both are on
+    pub fn verifies_par2_only(&self) -> bool {
+        self.par2_verify_only && !self.auto_par2_repair
+    }
 }
 
 // Default implementations
@@ -130,12 +679,24 @@ impl Default for UsenetConfig {
             port: 563, // Default SSL port
             username: String::new(),
             password: String::new(),
+            password_file: None,
+            password_env: None,
             ssl: true, // Default to SSL
+            starttls: false,
+            allow_insecure_plaintext: false,
             verify_ssl_certs: true,
-            connections: 20,   // Conservative default (users can increase if needed)
-            timeout: 30,       // Reduced from 45s
-            retry_attempts: 2, // Faster failover
-            retry_delay: 500,  // Quick retries
+            tls_sni: None,
+            connections: Connections::Fixed(20), // Conservative default (users can increase if needed)
+            timeout: 30,                         // Reduced from 45s
+            retry_attempts: 2,                   // Faster failover
+            retry_delay: 500,                    // Quick retries
+            retry_failed_segments: false,
+            mode_reader: true,
+            yenc_crc_retry: false,
+            pipeline_resync: true,
+            evict_after_invalid_bodies: default_evict_after_invalid_bodies(),
+            post_connect_commands: Vec::new(),
+            test_group: String::new(),
         }
     }
 }
@@ -145,8 +706,25 @@ impl Default for DownloadConfig {
         Self {
             dir: PathBuf::from("downloads"),
             create_subfolders: true,
+            category_folders: false,
             user_agent: format!("dl-nzb/{}", env!("CARGO_PKG_VERSION")),
             force_redownload: false,
+            precheck_groups: false,
+            protect_existing_files: false,
+            skip_duplicate_nzbs: false,
+            track_retention: false,
+            priority_patterns: Vec::new(),
+            par2_first: false,
+            min_recovery_percent: 0.0,
+            max_concurrent_nzbs: 1,
+            segment_numbering: SegmentNumbering::Ignore,
+            verify_assembled_size: false,
+            assemble_by_yenc_part: false,
+            posters: Vec::new(),
+            posted_after: None,
+            posted_before: None,
+            output_rules: Vec::new(),
+            output_name: None,
         }
     }
 }
@@ -157,6 +735,9 @@ impl Default for MemoryConfig {
             max_segments_in_memory: 800, // Conservative: 800 concurrent segments (~20 per connection)
             io_buffer_size: 8 * 1024 * 1024, // 8MB buffer (reduced from 16MB)
             max_concurrent_files: 100,   // No longer throttles (downloader ignores this)
+            spill_to_temp: false,
+            write_queue_depth: default_write_queue_depth(),
+            max_open_files: 0,
         }
     }
 }
@@ -169,6 +750,30 @@ impl Default for PostProcessingConfig {
             delete_rar_after_extract: false,
             delete_par2_after_repair: false,
             deobfuscate_file_names: true,
+            par2_verify_only: false,
+            par2_per_set: true,
+            trust_par2_verification: false,
+            force_reextract: false,
+            flatten_extraction: false,
+            filesystem_case: FilesystemCase::Auto,
+            partial_extraction: PartialExtraction::Keep,
+            nested_extraction_depth: 0,
+            extract_include: Vec::new(),
+            extract_exclude: Vec::new(),
+            extract_entry_include: Vec::new(),
+            extract_entry_exclude: Vec::new(),
+            preserve_files: default_preserve_files(),
+            deobfuscate_match_nzb: false,
+            deobfuscate_skip_excluded: true,
+            fix_mislabeled_extensions: false,
+            poor_name_fallback: PoorNameFallback::Keep,
+            verify_media_container: false,
+            write_manifest: false,
+            post_download_command: Vec::new(),
+            post_download_timeout: default_post_download_timeout(),
+            split_release_sets: false,
+            duplicate_files: DuplicateAction::Keep,
+            max_concurrent_jobs: 1,
         }
     }
 }
@@ -179,6 +784,7 @@ impl Default for LoggingConfig {
             level: "info".to_string(),
             file: None,
             format: "pretty".to_string(),
+            summary: SummaryFormat::Fancy,
         }
     }
 }
@@ -187,9 +793,20 @@ impl Default for TuningConfig {
     fn default() -> Self {
         Self {
             pipeline_size: 50,                      // Segments per connection batch
+            pipeline_bytes: 0,                      // Batch by count
             connection_wait_timeout: 300,           // 5 minutes max wait
+            connection_wait_budget: 600,            // 10 minutes of stalling per file
             max_concurrent_connections: 10,         // Concurrent connection creation limit
             large_file_threshold: 10 * 1024 * 1024, // 10MB for progress monitoring
+            scale_pool_to_nzb: true,
+            segment_order: SegmentOrder::Contiguous,
+            max_batches_per_file: 0,
+            par2_handoff_threshold: 0.0,
+            max_retry_time: 0,
+            backoff: BackoffStrategy::Exponential,
+            backoff_base_ms: default_backoff_base_ms(),
+            backoff_cap_ms: default_backoff_cap_ms(),
+            backoff_jitter: default_backoff_jitter(),
         }
     }
 }
@@ -238,6 +855,18 @@ impl Config {
         Ok(config_dir.join("dl-nzb").join("config.toml"))
     }
 
+    /// Get the path of the processed-NZB history, next to the config file
+    pub fn history_path() -> Result<PathBuf> {
+        let config_path = Self::config_path()?;
+        Ok(config_path.with_file_name("history.txt"))
+    }
+
+    /// Get the path of the retention log, next to the config file
+    pub fn retention_path() -> Result<PathBuf> {
+        let config_path = Self::config_path()?;
+        Ok(config_path.with_file_name("retention.txt"))
+    }
+
     /// Load configuration from local or standard location
     pub fn load() -> Result<Self> {
         let local_config = PathBuf::from("dl-nzb.toml");
@@ -279,7 +908,8 @@ impl Config {
         let mut config: Config = toml::from_str(&content)
             .map_err(|e| ConfigError::ParseError(format!("Failed to parse config: {}", e)))?;
 
-        // Apply environment variable overrides
+        // Resolve credential references, then apply environment variable overrides
+        config.usenet.resolve_password()?;
         config = load_env_overrides(config);
 
         // Expand tilde in paths
@@ -316,20 +946,74 @@ impl Config {
 # server       - Your Usenet provider's server address (REQUIRED)
 # port         - Usually 563 for SSL, 119 for non-SSL
 # username     - Your Usenet account username (REQUIRED)
-# password     - Your Usenet account password (REQUIRED)
+# password     - Your Usenet account password (REQUIRED, unless one of the next two is set)
+# password_file - Read the password from a file instead (e.g. /run/secrets/usenet_password)
+# password_env  - Read the password from an environment variable instead
 # ssl          - Use encrypted SSL/TLS connection (recommended)
-# connections  - Number of connections (30-50 typical, check your provider's limit)
+# starttls     - With ssl = false, upgrade the plaintext connection using STARTTLS
+#                (for providers that offer TLS that way on port 119)
+# allow_insecure_plaintext - Required to connect with both ssl and starttls off; the
+#                username and password then cross the network in cleartext
+# tls_sni      - Hostname to send as SNI and verify the certificate against, when `server`
+#                is an IP or another name for the same host (unset = use `server`)
+# connections  - Number of connections (30-50 typical, check your provider's limit),
+#                or "auto" to ramp up until throughput plateaus (at most 50; not
+#                while a schedule is set)
 # timeout      - Connection timeout in seconds
 # retry_attempts - Number of times to retry failed downloads
+# retry_failed_segments - After a file's first pass, make `retry_attempts` more passes over
+#                its failed segments, `retry_delay` ms apart (not ones the server answered 430)
+# mode_reader  - Send MODE READER after connecting (disable for servers that reject it)
+# yenc_crc_retry - Verify each segment's yEnc CRC, retrying looser decodes before failing it
+# pipeline_resync - After a malformed response, realign the connection instead of losing the batch
+# evict_after_invalid_bodies - Replace a connection once this many bodies in a row come back
+#                truncated or failing their CRC, isolating a bad route (0 = never)
+# post_connect_commands - Commands to send after login, for providers that expect them
+#                (e.g. ["MODE STREAM"] for some transit setups; rejected commands are only logged)
+# test_group   - Group `dl-nzb test` selects to check the account can reach binary groups,
+#                e.g. "alt.binaries.test"; catches text-only or restricted accounts (empty = skip)
 #
 # [download]
 # dir               - Where to save downloads
 # create_subfolders - Create a subfolder for each NZB file
+# category_folders  - Sort finished NZB folders into movie/, tv/, music/ or other/ by
+#                     detected content (needs create_subfolders)
+# precheck_groups   - Confirm the server carries each group before downloading
+# protect_existing_files - Refuse to write into a directory with unrelated files
+#                     (ignored with --force or when create_subfolders is on)
+# skip_duplicate_nzbs - Skip NZBs already downloaded under any filename (matched by content;
+#                     --force downloads them again)
+# track_retention   - Log how old each NZB's posts were and whether the server still had
+#                     them, and show the provider's estimated retention for the group
+# priority_patterns - Regexes for files to fetch first, in order, before the largest-first
+#                     default (e.g. ["\\.part0*1\\.rar$", "\\.mkv$"])
+# par2_first        - Download PAR2 files before everything else, so repair can start as
+#                     soon as the content is done
+# min_recovery_percent - Skip NZBs with less PAR2 recovery than this, e.g. 10 for 10% of
+#                     the data (counted from the volume names, so NZBs whose names are
+#                     obfuscated aren't skipped; 0 accepts any)
+# max_concurrent_nzbs - How many NZBs to download at once (connections are shared)
+# segment_numbering - Check each file's segments run 1..N: "ignore", "warn", or "strict"
+#                     (strict fails the file instead of assembling one with holes)
+# verify_assembled_size - Flag files whose size differs from what their yEnc headers declare,
+#                     keeping them from extraction like files with failed segments
+# assemble_by_yenc_part - When a segment's yEnc part number disagrees with its NZB number,
+#                     write it where the yEnc header says; a second body for the same part
+#                     counts as failed (mismatches are always reported)
+# posters           - Only download files whose poster contains one of these (e.g. ["alice"])
+# posted_after      - Only download files posted on or after a date ("2024-01-31", UTC)
+# posted_before     - Only download files posted before a date
 #
 # [memory]
 # max_segments_in_memory - How many segments to buffer (affects memory usage)
 # io_buffer_size        - Buffer size in bytes (8MB recommended for performance)
 # max_concurrent_files  - How many files to download simultaneously
+# spill_to_temp         - When streaming a file to a pipe, park early segments in a temp file
+#                         instead of memory (library use via download_file_to)
+# write_queue_depth     - Decoded segments each file may have waiting to be written; once
+#                         full, downloads pause until the disk catches up (default 64)
+# max_open_files        - Output files downloads and extraction may hold open at once,
+#                         for low `ulimit -n` settings in containers (0 = no limit)
 #
 # [post_processing]
 # auto_par2_repair        - Automatically verify/repair with PAR2 files
@@ -337,6 +1021,70 @@ impl Config {
 # delete_rar_after_extract - Delete RAR files after successful extraction
 # delete_par2_after_repair - Delete PAR2 files after successful repair
 # deobfuscate_file_names  - Rename obfuscated files to meaningful names
+# par2_verify_only        - Always verify against PAR2 without repairing, reporting corrupt files;
+#                           only applies with auto_par2_repair = false
+# par2_per_set            - Verify/repair each PAR2 set on its own when an NZB has several
+# trust_par2_verification - Report files with failed segments as complete when the PAR2 set
+#                           they belong to verifies or repairs them, instead of "completed
+#                           with errors"
+# force_reextract         - Re-extract files that already exist with the expected size
+# flatten_extraction      - Extract every file to the top level, dropping the archive's folders
+#                           (colliding names get a _1, _2 suffix)
+# filesystem_case         - "auto" (probe the target directory), "sensitive" or "insensitive";
+#                           on an insensitive filesystem, archive entries differing only in
+#                           case (Readme.txt, README.TXT) get a _1 suffix instead of clobbering
+# partial_extraction      - Files left by an extraction that failed partway: "keep", "delete",
+#                           or "quarantine" (moved into .dl-nzb-partial/<archive>/)
+# nested_extraction_depth - Extract archives found inside archives, up to this many levels
+#                           (0 = off; extracted inner archives are deleted)
+# extract_include         - Regexes for archive names to extract; others are left as-is
+# extract_exclude         - Regexes for archive names never to extract (e.g. ["extras"])
+# extract_entry_include   - Globs for files inside archives to extract, e.g. ["*.mkv"]; other
+#                           entries are skipped (all are extracted when empty)
+# extract_entry_exclude   - Globs for files inside archives never to extract, e.g. ["*.nfo"]
+# preserve_files          - Globs for files that deleting RAR/PAR2 files and merging duplicates
+#                           never touch, e.g. ["*.par2"] to keep recovery data. The default
+#                           ["*.srr"] keeps ReScene files out of duplicate merging; they are
+#                           never deleted, as they aren't RAR or PAR2 files
+# deobfuscate_match_nzb   - Restore original names matched by PAR2 16 KiB hash or NZB-listed size
+# deobfuscate_skip_excluded - Deobfuscate the largest file that isn't PAR2/NFO/etc. (default);
+#                           false gives up when the largest file has such an extension
+# fix_mislabeled_extensions - Correct an extension the content clearly contradicts, e.g. a
+#                           Matroska video named .txt; only on a confident magic-byte match
+# poor_name_fallback      - When the NZB/directory name is itself generic ("download") or
+#                           a bare hash: "keep" using it (default), "derive" a name from the .nfo
+#                           title or the main file's type and a timestamp, or "skip" renaming
+# verify_media_container  - Check the main media file's MP4/MKV structure after processing
+# write_manifest          - Write dl-nzb-manifest.json listing every file left in the download
+#                           with its size, detected type, and whether it was extracted
+# post_download_command   - Program and arguments to run after each NZB finishes, e.g.
+#                           ["/usr/local/bin/sort.sh", "--notify"]; run directly, not via a
+#                           shell, in the output directory with DL_NZB_NZB, DL_NZB_NZB_HASH,
+#                           DL_NZB_OUTPUT_DIR, DL_NZB_CATEGORY, DL_NZB_STATUS (success/failure)
+#                           and DL_NZB_TOTAL_SIZE set
+# post_download_timeout   - Seconds the command may run before it is killed (0 = no limit)
+# split_release_sets      - When one NZB holds several releases (archives and PAR2 files with
+#                           different base names), move each into a folder named after it and
+#                           repair, extract and deobfuscate it on its own
+# duplicate_files         - Files identical to another in the download (compared byte for
+#                           byte, never by size alone): "keep", "hardlink", or "remove"
+# max_concurrent_jobs     - PAR2/extraction jobs run at once when several NZBs download together
+#
+# [logging]
+# summary                 - How results are printed: "fancy" (colors and symbols), "plain"
+#                           (no colors or symbols), "minimal" (one line per NZB plus failed
+#                           stages and warnings), or "none"
+#
+# [[schedule]] (optional, repeatable) - Limit connections during local time windows
+# start / end  - Window as "HH:MM"; an end before the start wraps past midnight
+# connections  - Connections inside the window (0 pauses downloading until it ends)
+# max_speed_kib - Download speed cap inside the window in KiB/s (0 = unlimited)
+#
+# [[download.output_rules]] (optional, repeatable) - Route matching files to a subfolder
+# pattern      - Regex matched against the filename, case-insensitive (e.g. "\\.srt$")
+# path         - Subfolder of the download directory (e.g. "Subs"); the first match wins,
+#                other files go to the download directory. Keep PAR2 and RAR files there
+#                so repair and extraction find them
 "#,
             content
         );
@@ -345,17 +1093,27 @@ impl Config {
         Ok(())
     }
 
+    /// The configuration as TOML for display, with the password redacted
+    ///
+    /// `password_file` and `password_env` are resolved at load time, so the password field
+    /// may hold a secret that never appears in the config file itself.
+    pub fn to_display_toml(&self) -> Result<String> {
+        let mut shown = self.clone();
+        if !shown.usenet.password.is_empty() {
+            shown.usenet.password = "<redacted>".to_string();
+        }
+        toml::to_string_pretty(&shown).map_err(|e| {
+            ConfigError::ParseError(format!("Failed to serialize config: {}", e)).into()
+        })
+    }
+
     /// Validate configuration (basic validation only)
     /// Server/credentials are validated separately when needed for downloads
     pub fn validate(&self) -> Result<()> {
         // Validate connection count if server is configured
-        if !self.usenet.server.is_empty()
-            && (self.usenet.connections == 0 || self.usenet.connections > 100)
-        {
-            return Err(ConfigError::InvalidConnections {
-                count: self.usenet.connections,
-            }
-            .into());
+        let connections = self.usenet.connections.count();
+        if !self.usenet.server.is_empty() && (connections == 0 || connections > 100) {
+            return Err(ConfigError::InvalidConnections { count: connections }.into());
         }
 
         // Validate memory settings
@@ -367,6 +1125,14 @@ impl Config {
             .into());
         }
 
+        if self.memory.write_queue_depth == 0 {
+            return Err(ConfigError::Invalid {
+                field: "write_queue_depth".to_string(),
+                reason: "Must be at least 1".to_string(),
+            }
+            .into());
+        }
+
         if self.memory.max_segments_in_memory == 0 {
             return Err(ConfigError::Invalid {
                 field: "max_segments_in_memory".to_string(),
@@ -384,9 +1150,235 @@ impl Config {
             .into());
         }
 
+        for window in &self.schedule {
+            for time in [&window.start, &window.end] {
+                if ScheduleWindow::parse_time(time).is_none() {
+                    return Err(ConfigError::Invalid {
+                        field: "schedule".to_string(),
+                        reason: format!("Invalid time '{}', expected HH:MM", time),
+                    }
+                    .into());
+                }
+            }
+        }
+
+        for pattern in &self.download.priority_patterns {
+            if let Err(e) = regex::Regex::new(pattern) {
+                return Err(ConfigError::Invalid {
+                    field: "priority_patterns".to_string(),
+                    reason: format!("Invalid pattern '{}': {}", pattern, e),
+                }
+                .into());
+            }
+        }
+
+        if !(0.0..=100.0).contains(&self.download.min_recovery_percent) {
+            return Err(ConfigError::Invalid {
+                field: "min_recovery_percent".to_string(),
+                reason: format!(
+                    "{} is not a percentage between 0 and 100",
+                    self.download.min_recovery_percent
+                ),
+            }
+            .into());
+        }
+
+        if !(0.0..=1.0).contains(&self.tuning.par2_handoff_threshold) {
+            return Err(ConfigError::Invalid {
+                field: "par2_handoff_threshold".to_string(),
+                reason: format!(
+                    "{} is not a fraction between 0 and 1",
+                    self.tuning.par2_handoff_threshold
+                ),
+            }
+            .into());
+        }
+
+        if !(0.0..=1.0).contains(&self.tuning.backoff_jitter) {
+            return Err(ConfigError::Invalid {
+                field: "backoff_jitter".to_string(),
+                reason: format!(
+                    "{} is not a fraction between 0 and 1",
+                    self.tuning.backoff_jitter
+                ),
+            }
+            .into());
+        }
+
+        for rule in &self.download.output_rules {
+            if let Err(e) = regex::Regex::new(&rule.pattern) {
+                return Err(ConfigError::Invalid {
+                    field: "output_rules".to_string(),
+                    reason: format!("Invalid pattern '{}': {}", rule.pattern, e),
+                }
+                .into());
+            }
+            let inside_dir = rule
+                .path
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)));
+            if !inside_dir {
+                return Err(ConfigError::Invalid {
+                    field: "output_rules".to_string(),
+                    reason: format!(
+                        "Path '{}' must be a subfolder of the download directory",
+                        rule.path.display()
+                    ),
+                }
+                .into());
+            }
+        }
+
+        for command in &self.usenet.post_connect_commands {
+            if command.trim().is_empty() || command.contains(['\r', '\n']) {
+                return Err(ConfigError::Invalid {
+                    field: "post_connect_commands".to_string(),
+                    reason: format!("Invalid command '{}', expected a single line", command),
+                }
+                .into());
+            }
+        }
+
+        if let Some(sni) = &self.usenet.tls_sni {
+            if sni.is_empty() || sni.contains(char::is_whitespace) {
+                return Err(ConfigError::Invalid {
+                    field: "tls_sni".to_string(),
+                    reason: format!("Invalid hostname '{}'", sni),
+                }
+                .into());
+            }
+        }
+
+        if self.usenet.test_group.contains(char::is_whitespace) {
+            return Err(ConfigError::Invalid {
+                field: "test_group".to_string(),
+                reason: format!("Invalid group name '{}'", self.usenet.test_group),
+            }
+            .into());
+        }
+
+        for (field, date) in [
+            ("posted_after", &self.download.posted_after),
+            ("posted_before", &self.download.posted_before),
+        ] {
+            if let Some(date) = date {
+                if parse_post_date(date).is_none() {
+                    return Err(ConfigError::Invalid {
+                        field: field.to_string(),
+                        reason: format!("Invalid date '{}', expected YYYY-MM-DD", date),
+                    }
+                    .into());
+                }
+            }
+        }
+
+        for (field, patterns) in [
+            ("extract_include", &self.post_processing.extract_include),
+            ("extract_exclude", &self.post_processing.extract_exclude),
+        ] {
+            for pattern in patterns {
+                if let Err(e) = regex::Regex::new(pattern) {
+                    return Err(ConfigError::Invalid {
+                        field: field.to_string(),
+                        reason: format!("Invalid pattern '{}': {}", pattern, e),
+                    }
+                    .into());
+                }
+            }
+        }
+
+        if self.download.max_concurrent_nzbs == 0 {
+            return Err(ConfigError::Invalid {
+                field: "max_concurrent_nzbs".to_string(),
+                reason: "Must be at least 1".to_string(),
+            }
+            .into());
+        }
+
+        if self
+            .post_processing
+            .post_download_command
+            .first()
+            .is_some_and(|program| program.trim().is_empty())
+        {
+            return Err(ConfigError::Invalid {
+                field: "post_download_command".to_string(),
+                reason: "The first entry must name the program to run".to_string(),
+            }
+            .into());
+        }
+
+        if self.post_processing.max_concurrent_jobs == 0 {
+            return Err(ConfigError::Invalid {
+                field: "max_concurrent_jobs".to_string(),
+                reason: "Must be at least 1".to_string(),
+            }
+            .into());
+        }
+
         Ok(())
     }
 
+    /// Settings that are valid but combine in a way that makes one of them do nothing
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        if self.post_processing.par2_verify_only && self.post_processing.auto_par2_repair {
+            warnings.push(
+                "par2_verify_only is ignored while auto_par2_repair is on; PAR2 repairs as usual"
+                    .to_string(),
+            );
+        }
+
+        if self.usenet.connections == Connections::Auto && !self.schedule.is_empty() {
+            warnings.push(
+                "connections = \"auto\" doesn't auto-tune while a schedule is set; \
+                 the schedule's counts apply, up to 50"
+                    .to_string(),
+            );
+        }
+
+        if self.tuning.par2_handoff_threshold > 0.0 && !self.usenet.retry_failed_segments {
+            warnings.push(
+                "par2_handoff_threshold has no effect without retry_failed_segments, \
+                 as failed segments aren't retried"
+                    .to_string(),
+            );
+        }
+
+        warnings
+    }
+
+    /// Whether `connections = "auto"` tunes the pool during downloads
+    ///
+    /// A schedule sets connection counts itself, so tuning is off while one is set.
+    pub fn auto_tunes_connections(&self) -> bool {
+        self.usenet.connections == Connections::Auto && self.schedule.is_empty()
+    }
+
+    /// Connection count the schedule allows at a time of day
+    ///
+    /// The first matching window wins and never raises the count above `connections`;
+    /// outside every window the full count is used. 0 means downloading is paused.
+    pub fn scheduled_connections(&self, time: NaiveTime, connections: usize) -> usize {
+        self.schedule
+            .iter()
+            .find(|window| window.contains(time))
+            .map(|window| (window.connections as usize).min(connections))
+            .unwrap_or(connections)
+    }
+
+    /// Download speed cap in bytes per second the schedule sets at a time of day
+    ///
+    /// Follows the same window as `scheduled_connections`; 0 means unlimited.
+    pub fn scheduled_speed_limit(&self, time: NaiveTime) -> u64 {
+        self.schedule
+            .iter()
+            .find(|window| window.contains(time))
+            .map(|window| window.max_speed_kib.saturating_mul(1024))
+            .unwrap_or(0)
+    }
+
     /// Validate that server credentials are configured (call before downloading)
     pub fn validate_for_download(&self) -> Result<()> {
         if self.usenet.server.is_empty() {
@@ -397,11 +1389,11 @@ impl Config {
             return Err(ConfigError::NoCredentials.into());
         }
 
-        if self.usenet.connections == 0 || self.usenet.connections > 100 {
-            return Err(ConfigError::InvalidConnections {
-                count: self.usenet.connections,
-            }
-            .into());
+        self.usenet.check_plaintext_allowed()?;
+
+        let connections = self.usenet.connections.count();
+        if connections == 0 || connections > 100 {
+            return Err(ConfigError::InvalidConnections { count: connections }.into());
         }
 
         Ok(())
@@ -429,7 +1421,7 @@ impl Config {
             self.usenet.port = port;
         }
         if let Some(connections) = overrides.connections {
-            self.usenet.connections = connections;
+            self.usenet.connections = Connections::Fixed(connections);
         }
         if let Some(ssl) = overrides.ssl {
             self.usenet.ssl = ssl;
@@ -461,7 +1453,7 @@ mod tests {
     #[test]
     fn test_default_config() {
         let config = Config::default();
-        assert_eq!(config.usenet.connections, 20); // Conservative default
+        assert_eq!(config.usenet.connections, Connections::Fixed(20)); // Conservative default
         assert_eq!(config.memory.io_buffer_size, 8 * 1024 * 1024);
     }
 
@@ -473,6 +1465,198 @@ mod tests {
 
         // But should fail download validation (no server)
         assert!(config.validate_for_download().is_err());
+
+        let mut config = Config::default();
+        config.usenet.test_group = "alt.binaries.test".to_string();
+        assert!(config.validate().is_ok());
+        config.usenet.test_group = "alt.binaries.test alt.binaries.misc".to_string();
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.usenet.server = "203.0.113.10".to_string();
+        assert_eq!(config.usenet.tls_host(), "203.0.113.10");
+        config.usenet.tls_sni = Some("news.example.com".to_string());
+        assert_eq!(config.usenet.tls_host(), "news.example.com");
+        assert!(config.validate().is_ok());
+        config.usenet.tls_sni = Some(String::new());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_par2_verify_only_yields_to_repair() {
+        let mut config = Config::default();
+        assert!(config.warnings().is_empty());
+
+        config.post_processing.par2_verify_only = true;
+        assert!(!config.post_processing.verifies_par2_only());
+        assert_eq!(config.warnings().len(), 1);
+
+        config.post_processing.auto_par2_repair = false;
+        assert!(config.post_processing.verifies_par2_only());
+        assert!(config.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_par2_handoff_threshold_needs_retries() {
+        let mut config = Config::default();
+        config.tuning.par2_handoff_threshold = 0.99;
+        assert_eq!(config.warnings().len(), 1);
+
+        config.usenet.retry_failed_segments = true;
+        assert!(config.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_output_rules_stay_inside_download_dir() {
+        let mut config = Config::default();
+        config.download.output_rules = vec![OutputRule {
+            pattern: r"\.srt$".to_string(),
+            path: PathBuf::from("Subs/English"),
+        }];
+        assert!(config.validate().is_ok());
+
+        for path in ["../Subs", "/tmp/Subs"] {
+            config.download.output_rules[0].path = PathBuf::from(path);
+            assert!(config.validate().is_err());
+        }
+
+        config.download.output_rules[0].path = PathBuf::from("Subs");
+        config.download.output_rules[0].pattern = "(".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_password_from_file_and_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let secret = dir.path().join("password");
+        std::fs::write(&secret, "from-file\n").unwrap();
+
+        let mut usenet = UsenetConfig {
+            password_file: Some(secret),
+            ..UsenetConfig::default()
+        };
+        usenet.resolve_password().unwrap();
+        assert_eq!(usenet.password, "from-file");
+
+        // Showing the config never reveals the resolved password
+        let config = Config {
+            usenet: usenet.clone(),
+            ..Config::default()
+        };
+        let shown = config.to_display_toml().unwrap();
+        assert!(!shown.contains("from-file"));
+        assert!(shown.contains("password = \"<redacted>\""));
+
+        env::set_var("DL_NZB_TEST_PASSWORD", "from-env");
+        let mut usenet = UsenetConfig {
+            password_env: Some("DL_NZB_TEST_PASSWORD".to_string()),
+            ..UsenetConfig::default()
+        };
+        usenet.resolve_password().unwrap();
+        assert_eq!(usenet.password, "from-env");
+
+        let mut usenet = UsenetConfig {
+            password_env: Some("DL_NZB_TEST_PASSWORD_UNSET".to_string()),
+            ..UsenetConfig::default()
+        };
+        assert!(usenet.resolve_password().is_err());
+    }
+
+    #[test]
+    fn test_auto_connections() {
+        let usenet_toml = |connections: &str| {
+            format!(
+                r#"[usenet]
+server = "news.example.com"
+port = 563
+username = "u"
+password = "p"
+ssl = true
+verify_ssl_certs = true
+connections = {}
+timeout = 30
+retry_attempts = 2
+retry_delay = 500
+"#,
+                connections
+            )
+        };
+
+        let mut config: Config = toml::from_str(&usenet_toml("\"auto\"")).unwrap();
+        assert!(config.auto_tunes_connections());
+        assert_eq!(config.usenet.connections.count(), AUTO_CONNECTIONS_CAP);
+        assert!(config.warnings().is_empty());
+
+        // "auto" survives saving and loading the config again
+        let saved = toml::to_string(&config).unwrap();
+        let reloaded: Config = toml::from_str(&saved).unwrap();
+        assert!(reloaded.auto_tunes_connections());
+
+        // A schedule sets the counts itself, so tuning is off and the user is told why
+        config.schedule.push(ScheduleWindow {
+            start: "18:00".to_string(),
+            end: "23:00".to_string(),
+            connections: 4,
+            max_speed_kib: 0,
+        });
+        assert!(!config.auto_tunes_connections());
+        assert_eq!(config.warnings().len(), 1);
+
+        // A count from the command line replaces "auto"
+        config.schedule.clear();
+        config.apply_overrides(ConfigOverrides {
+            connections: Some(12),
+            ..ConfigOverrides::default()
+        });
+        assert!(!config.auto_tunes_connections());
+
+        let config: Config = toml::from_str(&usenet_toml("30")).unwrap();
+        assert!(!config.auto_tunes_connections());
+        assert_eq!(config.usenet.connections, Connections::Fixed(30));
+
+        // An explicit 0 is an invalid count, not a way to ask for auto-tuning
+        let config: Config = toml::from_str(&usenet_toml("0")).unwrap();
+        assert!(!config.auto_tunes_connections());
+        assert!(config.validate().is_err());
+
+        assert!(toml::from_str::<Config>(&usenet_toml("\"many\"")).is_err());
+    }
+
+    #[test]
+    fn test_scheduled_connections() {
+        let mut config = Config {
+            schedule: vec![
+                ScheduleWindow {
+                    start: "18:00".to_string(),
+                    end: "23:00".to_string(),
+                    connections: 4,
+                    max_speed_kib: 512,
+                },
+                ScheduleWindow {
+                    start: "23:00".to_string(),
+                    end: "01:00".to_string(),
+                    connections: 0,
+                    max_speed_kib: 0,
+                },
+            ],
+            ..Config::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        assert_eq!(config.scheduled_connections(at(12, 0), 20), 20);
+        assert_eq!(config.scheduled_connections(at(18, 30), 20), 4);
+        assert_eq!(config.scheduled_connections(at(18, 30), 2), 2);
+        assert_eq!(config.scheduled_connections(at(23, 30), 20), 0);
+        assert_eq!(config.scheduled_connections(at(0, 30), 20), 0);
+        assert_eq!(config.scheduled_connections(at(1, 0), 20), 20);
+
+        assert_eq!(config.scheduled_speed_limit(at(18, 30)), 512 * 1024);
+        assert_eq!(config.scheduled_speed_limit(at(23, 30)), 0);
+        assert_eq!(config.scheduled_speed_limit(at(12, 0)), 0);
+
+        config.schedule[0].start = "6pm".to_string();
+        assert!(config.validate().is_err());
     }
 
     #[test]
@@ -485,4 +1669,21 @@ mod tests {
         config.usenet.password = "pass".to_string();
         assert!(config.validate_for_download().is_ok());
     }
+
+    #[test]
+    fn test_plaintext_requires_opt_in() {
+        let mut config = Config::default();
+        config.usenet.server = "news.example.org".to_string();
+        config.usenet.username = "user".to_string();
+        config.usenet.password = "pass".to_string();
+        config.usenet.ssl = false;
+        assert!(config.validate_for_download().is_err());
+
+        config.usenet.starttls = true;
+        assert!(config.validate_for_download().is_ok());
+
+        config.usenet.starttls = false;
+        config.usenet.allow_insecure_plaintext = true;
+        assert!(config.validate_for_download().is_ok());
+    }
 }
@@ -1,22 +1,42 @@
+use bytes::{Bytes, BytesMut};
 use futures::stream::{self, StreamExt};
 use indicatif::ProgressBar;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::fs::File;
-use tokio::io::{AsyncSeekExt, AsyncWriteExt};
-use tokio::sync::Mutex;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{watch, Mutex};
 
-use super::nzb::{Nzb, NzbFile};
-use crate::config::Config;
-use crate::error::{DlNzbError, DownloadError};
-use crate::nntp::{NntpPool, NntpPoolBuilder, NntpPoolExt, SegmentRequest};
-use crate::progress;
+use super::nzb::{FileFilter, Nzb, NzbFile};
+use crate::config::{
+    BackoffStrategy, Config, Connections, SegmentNumbering, SegmentOrder, TuningConfig,
+};
+use crate::error::{DlNzbError, DownloadError, NntpError};
+use crate::file_handles::FileHandleLimit;
+use crate::json_output::ProgressEvent;
+use crate::nntp::{
+    NntpPool, NntpPoolBuilder, NntpPoolExt, PartMismatch, PoolSizeLease, SegmentFailure,
+    SegmentRequest,
+};
+use crate::patterns::par2 as par2_patterns;
+use crate::processing::{clamp_filename, sanitize_name};
+use crate::progress::{self, DownloadProgressCallback, DownloadReporter};
 
 type Result<T> = std::result::Result<T, DlNzbError>;
 
+/// How often the connection schedule is re-evaluated during a download
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Connection auto-tuning: starting count, step size and measurement window
+const AUTO_TUNE_START: usize = 8;
+const AUTO_TUNE_STEP: usize = 4;
+const AUTO_TUNE_INTERVAL: Duration = Duration::from_secs(5);
+
 /// Result of downloading a file
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DownloadResult {
     pub filename: String,
     pub path: PathBuf,
@@ -26,33 +46,359 @@ pub struct DownloadResult {
     pub download_time: Duration,
     pub average_speed: f64,              // MB/s
     pub failed_message_ids: Vec<String>, // Track failed segments for potential retry
+    /// Why each segment in `failed_message_ids` failed, after retries
+    pub failure_reasons: HashMap<String, SegmentFailure>,
+    /// No segment failed, yet the assembled size differs from the size its yEnc headers declare
+    pub size_mismatch: bool,
+    /// Retries stopped early past `par2_handoff_threshold`, leaving the rest to PAR2
+    pub par2_handoff: bool,
+    /// Segments failed, but PAR2 verified or repaired this file afterwards (`trust_par2_verification`)
+    pub par2_repaired: bool,
+    /// Segments fetched from each group: one entry, unless some came from another group
+    /// (e.g. filled from a second NZB); empty when nothing had to be fetched
+    pub served_by: BTreeMap<String, usize>,
+    /// The file was named with `--output-name`, so post-processing keeps its name
+    pub named_by_user: bool,
+}
+
+impl DownloadResult {
+    /// Every segment arrived and the assembled size checks out, or PAR2 made up for it
+    pub fn is_complete(&self) -> bool {
+        (self.segments_failed == 0 && !self.size_mismatch) || self.par2_repaired
+    }
+
+    /// Failed segments counted by reason, e.g. 12 missing, 3 timeout, 1 CRC
+    pub fn failure_breakdown(&self) -> BTreeMap<SegmentFailure, usize> {
+        let mut breakdown = BTreeMap::new();
+        for reason in self.failure_reasons.values() {
+            *breakdown.entry(*reason).or_insert(0) += 1;
+        }
+        breakdown
+    }
+}
+
+/// What the connection schedule currently allows
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct ScheduleState {
+    /// No batch may start until this clears
+    paused: bool,
+    /// Download speed cap in bytes per second; 0 = unlimited
+    speed_limit: u64,
+}
+
+/// Decides when a file's next batch may go ahead
+///
+/// Batches wait while the schedule or the caller has downloading paused, and one file may
+/// only hold its share of the pool's connections so files downloading alongside it aren't
+/// starved. Under a scheduled speed limit, finished batches are held back long enough to
+/// keep the NZB at that speed.
+#[derive(Clone)]
+struct BatchGate {
+    schedule: watch::Receiver<ScheduleState>,
+    /// When the bytes received so far are paid off at the speed limit, shared by all files
+    speed_clock: Arc<std::sync::Mutex<Instant>>,
+    /// Set by `Downloader::pause` / `Downloader::resume`
+    paused: watch::Receiver<bool>,
+    /// Files of the NZB finished so far, shared by all of its files
+    completed_files: Arc<std::sync::atomic::AtomicUsize>,
+    total_files: usize,
+    /// Files downloaded at once
+    concurrent_files: usize,
+    /// Fixed per-file cap from `max_batches_per_file`, or 0 for a fair share
+    max_batches_per_file: usize,
+}
+
+impl BatchGate {
+    /// Wait until neither the schedule nor the caller has downloading paused
+    async fn wait_unpaused(&mut self) {
+        loop {
+            if self.paused.wait_for(|paused| !paused).await.is_err()
+                || self.schedule.wait_for(|state| !state.paused).await.is_err()
+                || !*self.paused.borrow()
+            {
+                return;
+            }
+        }
+    }
+
+    /// Hold a batch that received `bytes` until the speed limit allows for them
+    async fn throttle(&self, bytes: u64) {
+        let limit = self.schedule.borrow().speed_limit;
+        if limit == 0 || bytes == 0 {
+            return;
+        }
+
+        let until = {
+            let mut clock = self.speed_clock.lock().unwrap();
+            let start = (*clock).max(Instant::now());
+            *clock = start + Duration::from_secs_f64(bytes as f64 / limit as f64);
+            *clock
+        };
+        tokio::time::sleep_until(until.into()).await;
+    }
+
+    /// Batches one file may run at once, given the pool's connection count
+    ///
+    /// A fair share splits the connections between the files still downloading, so it
+    /// widens as the NZB's last files finish.
+    fn batch_limit(&self, connections: usize) -> usize {
+        if self.max_batches_per_file > 0 {
+            return self.max_batches_per_file;
+        }
+
+        let completed = self
+            .completed_files
+            .load(std::sync::atomic::Ordering::Relaxed);
+        let downloading = self
+            .concurrent_files
+            .min(self.total_files.saturating_sub(completed))
+            .max(1);
+        connections.div_ceil(downloading).max(1)
+    }
+}
+
+/// Wall-clock time a file has spent with at least one batch waiting for a connection
+///
+/// Waits of concurrent batches overlap and count once, so the total is how long the file
+/// was actually stalled, not the sum over its batches.
+#[derive(Debug, Default)]
+struct WaitClock {
+    /// Batches waiting right now
+    waiting: usize,
+    /// When the current stall began
+    since: Option<Instant>,
+    /// Stalls that already ended
+    total: Duration,
+}
+
+impl WaitClock {
+    fn start(&mut self) {
+        if self.waiting == 0 {
+            self.since = Some(Instant::now());
+        }
+        self.waiting += 1;
+    }
+
+    fn stop(&mut self) {
+        self.waiting = self.waiting.saturating_sub(1);
+        if self.waiting == 0 {
+            if let Some(since) = self.since.take() {
+                self.total += since.elapsed();
+            }
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.total + self.since.map_or(Duration::ZERO, |since| since.elapsed())
+    }
+}
+
+/// Delay before the next attempt to get a pool connection, from `tuning.backoff*`
+#[derive(Debug, Clone, Copy)]
+struct Backoff {
+    strategy: BackoffStrategy,
+    base: Duration,
+    cap: Duration,
+    jitter: f64,
+}
+
+impl Backoff {
+    fn from_config(tuning: &TuningConfig) -> Self {
+        Self {
+            strategy: tuning.backoff,
+            base: Duration::from_millis(tuning.backoff_base_ms),
+            cap: Duration::from_millis(tuning.backoff_cap_ms),
+            jitter: tuning.backoff_jitter,
+        }
+    }
+
+    /// Delay before retry number `attempt` (1 for the first retry), jitter included but
+    /// still no longer than the cap
+    fn delay(&self, attempt: u32) -> Duration {
+        let spread = if self.jitter > 0.0 {
+            // Uniform in -1..=1; RandomState is seeded randomly per instance
+            use std::hash::{BuildHasher, Hasher};
+            let random = std::collections::hash_map::RandomState::new()
+                .build_hasher()
+                .finish();
+            (random as f64 / u64::MAX as f64) * 2.0 - 1.0
+        } else {
+            0.0
+        };
+        self.base_delay(attempt)
+            .mul_f64((1.0 + self.jitter * spread).max(0.0))
+            .min(self.cap)
+    }
+
+    /// Delay before retry number `attempt`, without jitter
+    fn base_delay(&self, attempt: u32) -> Duration {
+        let steps = attempt.max(1);
+        let delay = match self.strategy {
+            BackoffStrategy::Exponential => self.base.saturating_mul(1 << (steps - 1).min(16)),
+            BackoffStrategy::Linear => self.base.saturating_mul(steps),
+            BackoffStrategy::Fixed => self.base,
+        };
+        delay.min(self.cap)
+    }
+
+    /// Delay after the server refused us for being over its limit
+    ///
+    /// Follows the server's own hint when its response named a wait; otherwise waits
+    /// several times the normal delay, since retrying a throttled account quickly only
+    /// prolongs the throttling.
+    fn throttled_delay(&self, attempt: u32, hint: Option<Duration>) -> Duration {
+        let delay = self.delay(attempt);
+        hint.unwrap_or_else(|| delay.saturating_mul(THROTTLE_BACKOFF_FACTOR))
+            .max(delay)
+    }
 }
 
+/// Connection attempts `Downloader::verify` makes while the server reports being busy
+const VERIFY_ATTEMPTS: u32 = 3;
+
+/// How much longer than usual to back off after throttling that didn't say how long
+const THROTTLE_BACKOFF_FACTOR: u32 = 4;
+
 /// Optimized downloader using connection pooling and streaming
 pub struct Downloader {
     pool: NntpPool,
+    /// Group existence results from pre-checks, cached for the lifetime of the downloader
+    group_cache: Mutex<HashMap<String, bool>>,
+    /// Receives progress instead of the terminal progress bar
+    progress_callback: Option<DownloadProgressCallback>,
+    /// Whether the caller has paused downloading
+    paused: watch::Sender<bool>,
+    /// Cap on output files open at once, shared with extraction
+    file_limit: Option<Arc<FileHandleLimit>>,
+    /// Delay between attempts when the server is busy at start-up
+    backoff: Backoff,
+    /// Emit a completion event as each file finishes
+    emit_events: bool,
+    /// Connections the last download ran with, once auto-tuning settled
+    connections_used: AtomicUsize,
 }
 
 impl Downloader {
     /// Create a new downloader with connection pool
     pub async fn new(config: Config) -> Result<Self> {
         let pool = NntpPoolBuilder::new(config.usenet.clone())
-            .max_size(config.usenet.connections as usize)
+            .max_size(config.usenet.connections.count() as usize)
             .build()?;
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            group_cache: Mutex::new(HashMap::new()),
+            progress_callback: None,
+            paused: watch::channel(false).0,
+            file_limit: None,
+            backoff: Backoff::from_config(&config.tuning),
+            emit_events: false,
+            connections_used: AtomicUsize::new(config.usenet.connections.count() as usize),
+        })
+    }
+
+    /// Open one connection and log in before any download work
+    ///
+    /// The pool connects lazily, so without this an unreachable server or bad credentials
+    /// would only surface as a warning from every batch. The error names the failing step:
+    /// DNS, connecting, TLS, authentication or the provider's connection limit.
+    ///
+    /// A server that is only busy for now (a 4xx greeting, throttling) gets a few more
+    /// tries with backoff before the error is reported.
+    pub async fn verify(&self) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.pool.get_connection().await {
+                Ok(_) => return Ok(()),
+                Err(DlNzbError::Nntp(e)) if e.is_transient() && attempt < VERIFY_ATTEMPTS => {
+                    let delay = self.backoff.throttled_delay(attempt, e.retry_after());
+                    tracing::warn!("{}; retrying in {:.0}s", e, delay.as_secs_f64());
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Stop handing out new batches until `resume` is called
+    ///
+    /// Batches already running finish; nothing is lost, and the download carries on where it
+    /// stopped once resumed. Pooled connections stay open meanwhile and are health-checked
+    /// before they're used again.
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    /// Let a paused download continue
+    pub fn resume(&self) {
+        self.paused.send_replace(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Report download progress through a callback instead of drawing a progress bar
+    pub fn with_progress_callback(mut self, callback: DownloadProgressCallback) -> Self {
+        self.progress_callback = Some(callback);
+        self
+    }
+
+    /// Emit a `file_complete` NDJSON event as each file finishes
+    pub fn with_events(mut self, enabled: bool) -> Self {
+        self.emit_events = enabled;
+        self
+    }
+
+    /// Wait for a slot from `limit` before opening each output file
+    pub fn with_file_limit(mut self, limit: Arc<FileHandleLimit>) -> Self {
+        self.file_limit = Some(limit);
+        self
+    }
+
+    /// The limit on open output files, to share with post-processing
+    pub fn file_limit(&self) -> Option<&Arc<FileHandleLimit>> {
+        self.file_limit.as_ref()
+    }
+
+    /// Wait for a slot to open an output file when a limit is set
+    async fn acquire_file_handle(&self) -> Option<tokio::sync::OwnedSemaphorePermit> {
+        match &self.file_limit {
+            Some(limit) => Some(limit.acquire().await),
+            None => None,
+        }
     }
 
     /// Download all files from an NZB, returns results and progress bar for reuse
     pub async fn download_nzb(
         &self,
         nzb: &Nzb,
-        config: Config,
+        mut config: Config,
     ) -> Result<(Vec<DownloadResult>, ProgressBar)> {
         config.ensure_dirs()?;
 
+        // Guard against clobbering unrelated files in a shared output directory
+        if config.download.protect_existing_files
+            && !config.download.create_subfolders
+            && !config.download.force_redownload
+        {
+            Self::check_no_unrelated_files(nzb, &config.download.dir)?;
+        }
+
         // Get all files to download (no separation between main and PAR2)
-        let all_files: Vec<&NzbFile> = nzb.files().iter().collect();
+        // Files the poster/date filter drops don't count toward any totals
+        let filter = FileFilter::from_config(&config.download);
+        let (all_files, mut filenames): (Vec<&NzbFile>, Vec<String>) = nzb
+            .files()
+            .iter()
+            .zip(nzb.unique_filenames())
+            .filter(|(file, _)| filter.matches(file))
+            .unzip();
+        let user_named = match &config.download.output_name {
+            Some(name) => Some(Self::apply_output_name(&mut filenames, name)?),
+            None => None,
+        };
 
         if all_files.is_empty() {
             return Err(DownloadError::InsufficientSegments {
@@ -62,26 +408,122 @@ impl Downloader {
             .into());
         }
 
-        // Create clean progress bar using centralized progress module
+        // Never open more connections than there are batches to download. The pool may be
+        // shared with other NZBs, so the size goes through a lease held for the whole download.
+        let total_segments = all_files.iter().map(|f| f.segments.segment.len()).sum();
         let total_bytes: u64 = all_files
             .iter()
             .flat_map(|f| &f.segments.segment)
-            .map(|s| s.bytes)
+            .map(|segment| segment.bytes)
             .sum();
+        let pool_lease = Arc::new(self.pool.size_lease());
+        let connections = Self::effective_connections(total_segments, total_bytes, &config);
+        let auto_tune = config.auto_tunes_connections();
+        pool_lease.want(connections);
+        config.usenet.connections = Connections::Fixed(connections as u16);
+
+        // Optionally confirm the server carries each group so dead groups fail fast
+        let missing_groups = if config.download.precheck_groups {
+            self.precheck_groups(&all_files).await
+        } else {
+            HashSet::new()
+        };
+
+        // Follow the time-of-day schedule while this NZB downloads
+        let (schedule_tx, schedule_rx) = watch::channel(ScheduleState::default());
+        let scheduler = (!config.schedule.is_empty()).then(|| {
+            let pool_lease = pool_lease.clone();
+            let schedule_config = config.clone();
+            tokio::spawn(async move {
+                let mut applied = None;
+                loop {
+                    let now = chrono::Local::now().time();
+                    let target = schedule_config.scheduled_connections(now, connections);
+                    // Pausing holds batches back but keeps the pool's connections open
+                    schedule_tx.send_replace(ScheduleState {
+                        paused: target == 0,
+                        speed_limit: schedule_config.scheduled_speed_limit(now),
+                    });
+                    if target > 0 && applied != Some(target) {
+                        tracing::info!("Schedule: using {} connections", target);
+                        pool_lease.want(target);
+                        applied = Some(target);
+                    }
+                    tokio::time::sleep(SCHEDULE_CHECK_INTERVAL).await;
+                }
+            })
+        });
 
+        // Create clean progress bar using centralized progress module
         let total_files = all_files.len();
-        let progress_bar =
-            progress::create_progress_bar(total_bytes, progress::ProgressStyle::Download);
+        // With a callback the bar only keeps count, so nothing is drawn on the terminal
+        let progress_bar = if self.progress_callback.is_some() {
+            ProgressBar::hidden()
+        } else {
+            progress::create_progress_bar(total_bytes, progress::ProgressStyle::Download)
+        };
+        progress_bar.set_length(total_bytes);
         progress_bar.set_message(format!("({}/{})", 0, total_files));
 
+        // Off a terminal the bar isn't drawn, so progress goes out as periodic plain lines
+        let plain_progress = (self.progress_callback.is_none() && !progress::is_interactive())
+            .then(|| tokio::spawn(progress::report_plain_progress(progress_bar.clone())));
+
+        // Ramp connections up from a modest start while throughput keeps improving
+        let tuned = Arc::new(AtomicUsize::new(connections));
+        let tuner = auto_tune.then(|| {
+            tuned.store(
+                pool_lease.want(AUTO_TUNE_START.min(connections)),
+                Ordering::Relaxed,
+            );
+            tokio::spawn(auto_tune_connections(
+                pool_lease.clone(),
+                progress_bar.clone(),
+                connections,
+                tuned.clone(),
+            ))
+        });
+
         // Download all files concurrently
         let results = self
-            .download_files_concurrent_with_config(&all_files, progress_bar.clone(), config)
-            .await?;
+            .download_files_concurrent_with_config(
+                &nzb.content_hash(),
+                all_files.iter().copied().zip(&filenames).collect(),
+                progress_bar.clone(),
+                config,
+                missing_groups,
+                schedule_rx,
+            )
+            .await;
+
+        if let Some(scheduler) = scheduler {
+            scheduler.abort();
+            pool_lease.want(connections);
+        }
+        if let Some(plain_progress) = plain_progress {
+            plain_progress.abort();
+        }
+        let auto_tuned = tuner.is_some();
+        if let Some(tuner) = tuner {
+            tuner.abort();
+        }
+        let connections_used = if auto_tuned {
+            tuned.load(Ordering::Relaxed)
+        } else {
+            connections.min(self.pool.status().max_size)
+        };
+        self.connections_used
+            .store(connections_used, Ordering::Relaxed);
+        let mut results = results?;
+        if let Some(name) = &user_named {
+            for result in results.iter_mut().filter(|r| &r.filename == name) {
+                result.named_by_user = true;
+            }
+        }
 
         // Finish the progress bar with clean formatting
         let total_downloaded: u64 = results.iter().map(|r| r.size).sum();
-        let failed_files = results.iter().filter(|r| r.segments_failed > 0).count();
+        let failed_files = results.iter().filter(|r| !r.is_complete()).count();
 
         progress_bar.set_position(total_bytes);
 
@@ -92,10 +534,13 @@ impl Downloader {
                 all_files.len()
             ));
 
-            // Print download summary on new line with color
-            println!(
-                "  └─ \x1b[32m✓ Downloaded {}\x1b[0m",
-                human_bytes::human_bytes(total_downloaded as f64)
+            progress::stage_summary(
+                true,
+                "\x1b[32m",
+                &format!(
+                    "Downloaded {}",
+                    human_bytes::human_bytes(total_downloaded as f64)
+                ),
             );
         } else {
             progress_bar.finish_with_message(format!(
@@ -104,59 +549,253 @@ impl Downloader {
                 all_files.len()
             ));
 
-            println!(
-                "  └─ \x1b[33m! Downloaded {} ({} file{} with errors)\x1b[0m",
-                human_bytes::human_bytes(total_downloaded as f64),
-                failed_files,
-                if failed_files == 1 { "" } else { "s" }
+            progress::stage_summary(
+                false,
+                "\x1b[33m",
+                &format!(
+                    "Downloaded {} ({} file{} with errors)",
+                    human_bytes::human_bytes(total_downloaded as f64),
+                    failed_files,
+                    if failed_files == 1 { "" } else { "s" }
+                ),
             );
         }
 
+        if auto_tuned {
+            progress::stage_detail(&format!("Auto-tuned to {} connections", connections_used));
+        }
+
         Ok((results, progress_bar))
     }
 
+    /// Connections the last download ran with, e.g. the count auto-tuning settled on
+    ///
+    /// The pool is shared by NZBs downloading side by side, so read this right after
+    /// [`Self::download_nzb`] returns.
+    pub fn connection_count(&self) -> usize {
+        self.connections_used.load(Ordering::Relaxed)
+    }
+
+    /// Connections replaced so far because their article bodies kept failing validation
+    pub fn evicted_connections(&self) -> usize {
+        self.pool.evicted_connections()
+    }
+
+    /// Fail if `dir` contains files other than the ones this NZB will write
+    ///
+    /// Hidden files (e.g. `.DS_Store`) are ignored.
+    fn check_no_unrelated_files(nzb: &Nzb, dir: &Path) -> Result<()> {
+        let expected: HashSet<String> = nzb.unique_filenames().into_iter().collect();
+
+        let count = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| !name.starts_with('.') && !expected.contains(name))
+            .count();
+
+        if count > 0 {
+            return Err(DownloadError::UnrelatedFilesPresent {
+                path: dir.to_path_buf(),
+                count,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Rename the one content file of the NZB to `name`, returning the name it was given
+    ///
+    /// PAR2 files keep their names, so the set still verifies (PAR2 tracks files by hash).
+    /// Fails when the NZB has no or several content files, or `name` sanitizes to nothing.
+    fn apply_output_name(filenames: &mut [String], name: &str) -> Result<String> {
+        let content: Vec<usize> = filenames
+            .iter()
+            .enumerate()
+            .filter(|(_, filename)| !par2_patterns::is_par2_file(Path::new(filename)))
+            .map(|(index, _)| index)
+            .collect();
+        if content.len() != 1 {
+            return Err(DownloadError::OutputNameNeedsSingleFile {
+                count: content.len(),
+            }
+            .into());
+        }
+
+        let sanitized = clamp_filename(sanitize_name(name).trim());
+        if sanitized.is_empty() || sanitized == "." || sanitized == ".." {
+            return Err(DownloadError::InvalidOutputName {
+                name: name.to_string(),
+            }
+            .into());
+        }
+
+        filenames[content[0]] = sanitized.clone();
+        Ok(sanitized)
+    }
+
+    /// Connection count to use for an NZB
+    ///
+    /// Small NZBs are capped at `ceil(total_segments / pipeline_size)` (or
+    /// `ceil(total_bytes / pipeline_bytes)` when batching by bytes), since extra connections
+    /// would only add handshake overhead without ever receiving a batch.
+    fn effective_connections(total_segments: usize, total_bytes: u64, config: &Config) -> usize {
+        let configured = config.usenet.connections.count() as usize;
+        if !config.tuning.scale_pool_to_nzb {
+            return configured;
+        }
+
+        let batches = match config.tuning.pipeline_bytes {
+            0 => total_segments.div_ceil(config.tuning.pipeline_size.max(1)),
+            target => total_bytes.div_ceil(target) as usize,
+        };
+        configured.min(batches).max(1)
+    }
+
+    /// Check which of the NZB's groups the server does not carry
+    ///
+    /// Each unique group is checked once with a single GROUP command and the result is cached,
+    /// so later NZBs sharing groups don't pay for the check again.
+    async fn precheck_groups(&self, files: &[&NzbFile]) -> HashSet<String> {
+        let mut unique_groups: Vec<&str> = files
+            .iter()
+            .flat_map(|f| &f.groups.group)
+            .map(|g| g.name.as_str())
+            .collect();
+        unique_groups.sort_unstable();
+        unique_groups.dedup();
+
+        let mut cache = self.group_cache.lock().await;
+        let unchecked: Vec<&str> = unique_groups
+            .iter()
+            .copied()
+            .filter(|g| !cache.contains_key(*g))
+            .collect();
+
+        if !unchecked.is_empty() {
+            let mut conn = match self.pool.get_connection().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    // Pre-check is best-effort; the download itself will surface connection errors
+                    tracing::debug!("Skipping group pre-check: {}", e);
+                    return HashSet::new();
+                }
+            };
+            for group in unchecked {
+                match conn.group_exists(group).await {
+                    Ok(exists) => {
+                        if !exists {
+                            tracing::warn!("Group not carried by server: {}", group);
+                        }
+                        cache.insert(group.to_string(), exists);
+                    }
+                    Err(e) => {
+                        // Inconclusive - leave unchecked groups to the normal download path
+                        tracing::debug!("Group pre-check failed for {}: {}", group, e);
+                        break;
+                    }
+                }
+            }
+        }
+
+        unique_groups
+            .into_iter()
+            .filter(|g| cache.get(*g) == Some(&false))
+            .map(String::from)
+            .collect()
+    }
+
     /// Download multiple files concurrently with custom config
     async fn download_files_concurrent_with_config(
         &self,
-        files: &[&NzbFile],
+        nzb_hash: &str,
+        mut files: Vec<(&NzbFile, &String)>,
         progress_bar: ProgressBar,
         config: Config,
+        missing_groups: HashSet<String>,
+        schedule: watch::Receiver<ScheduleState>,
     ) -> Result<Vec<DownloadResult>> {
         let total_files = files.len();
         let completed_count = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
 
         // Wrap config in Arc to avoid cloning per-file (Config contains strings and paths)
         let config = std::sync::Arc::new(config);
+        let missing_groups = Arc::new(missing_groups);
+
+        // PAR2 files go first with `par2_first`; then files matching a priority pattern, in
+        // pattern order; otherwise sort by size (largest first) to maximize initial throughput
+        let priority_rules: Vec<regex::Regex> = config
+            .download
+            .priority_patterns
+            .iter()
+            .filter_map(|pattern| filename_regex(pattern))
+            .collect();
+        files.sort_by_key(|(f, name)| {
+            let par2_rank = Self::par2_rank(name, config.download.par2_first);
+            let priority = priority_rules
+                .iter()
+                .position(|rule| rule.is_match(name))
+                .unwrap_or(priority_rules.len());
+            (
+                par2_rank,
+                priority,
+                std::cmp::Reverse(f.segments.segment.len()),
+            )
+        });
 
-        // Sort files by size (largest first) to maximize initial throughput
-        let mut sorted_files: Vec<&NzbFile> = files.iter().copied().collect();
-        sorted_files.sort_by_key(|f| std::cmp::Reverse(f.segments.segment.len()));
+        // Each file uses multiple connections for its batches, so limit concurrent files
+        // to avoid total_batches = files × batches_per_file >> pool_size
+        let max_concurrent_files = (config.usenet.connections.count() as usize / 5).max(2);
+        let gate = BatchGate {
+            schedule,
+            speed_clock: Arc::new(std::sync::Mutex::new(Instant::now())),
+            paused: self.paused.subscribe(),
+            completed_files: completed_count.clone(),
+            total_files,
+            concurrent_files: max_concurrent_files,
+            max_batches_per_file: config.tuning.max_batches_per_file,
+        };
 
-        let download_futures = sorted_files.iter().map(|file| {
+        let download_futures = files.iter().map(|(file, filename)| {
             let pool = self.pool.clone();
             let config = config.clone(); // Now clones Arc, not Config
             let file = (*file).clone();
+            let filename = (*filename).clone();
             let progress = progress_bar.clone();
             let completed = completed_count.clone();
+            let missing_groups = missing_groups.clone();
+            let gate = gate.clone();
+            let callback = self.progress_callback.clone();
 
             async move {
-                let result =
-                    Self::download_file_with_pool(file, &config, pool, progress.clone()).await;
+                // Held until the file is written, so its output counts against the limit
+                let _file_handle = self.acquire_file_handle().await;
+                let result = Self::download_file_with_pool(
+                    file,
+                    filename.clone(),
+                    &config,
+                    pool,
+                    DownloadReporter::new(progress.clone(), callback),
+                    &missing_groups,
+                    gate,
+                )
+                .await;
 
                 // Update file counter (only update every 5 files to reduce overhead)
                 let count = completed.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
                 if count % 5 == 0 || count == total_files {
                     progress.set_message(format!("({}/{})", count, total_files));
                 }
+                if self.emit_events {
+                    Self::file_complete_event(nzb_hash, &filename, &result, count, total_files)
+                        .emit();
+                }
 
                 result
             }
         });
 
         // Process downloads with bounded concurrency to prevent pool exhaustion
-        // Each file uses multiple connections for its batches, so limit concurrent files
-        // to avoid total_batches = files × batches_per_file >> pool_size
-        let max_concurrent_files = (config.usenet.connections as usize / 5).max(2);
         let results: Vec<Result<DownloadResult>> = stream::iter(download_futures)
             .buffer_unordered(max_concurrent_files)
             .collect()
@@ -174,46 +813,146 @@ impl Downloader {
         Ok(successful_results)
     }
 
+    /// The `file_complete` event for a file that finished, or failed outright
+    fn file_complete_event(
+        nzb_hash: &str,
+        filename: &str,
+        result: &Result<DownloadResult>,
+        files_completed: usize,
+        total_files: usize,
+    ) -> ProgressEvent {
+        match result {
+            Ok(r) => ProgressEvent::FileComplete {
+                nzb_hash: nzb_hash.to_string(),
+                filename: r.filename.clone(),
+                size: r.size,
+                segments_downloaded: r.segments_downloaded,
+                segments_failed: r.segments_failed,
+                success: r.is_complete(),
+                files_completed,
+                total_files,
+                error: None,
+            },
+            Err(e) => ProgressEvent::FileComplete {
+                nzb_hash: nzb_hash.to_string(),
+                filename: filename.to_string(),
+                size: 0,
+                segments_downloaded: 0,
+                segments_failed: 0,
+                success: false,
+                files_completed,
+                total_files,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+
+    /// Pick the group to fetch a file's articles from
+    ///
+    /// Uses the first group the server carries (all groups are candidates without a pre-check).
+    /// Malformed NZBs can list no groups at all, which is reported instead of indexing blindly.
+    fn pick_group<'a>(
+        file: &'a NzbFile,
+        filename: &str,
+        missing_groups: &HashSet<String>,
+    ) -> Result<&'a str> {
+        if file.groups.group.is_empty() {
+            return Err(DownloadError::NoGroups {
+                filename: filename.to_string(),
+            }
+            .into());
+        }
+
+        file.groups
+            .group
+            .iter()
+            .map(|g| g.name.as_str())
+            .find(|g| !missing_groups.contains(*g))
+            .ok_or_else(|| {
+                let groups = file
+                    .groups
+                    .group
+                    .iter()
+                    .map(|g| g.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                DownloadError::GroupNotCarried {
+                    filename: filename.to_string(),
+                    groups,
+                }
+                .into()
+            })
+    }
+
+    /// Download order of a file under `par2_first`: PAR2 index files, then recovery volumes,
+    /// then content; everything ranks the same with it off
+    fn par2_rank(filename: &str, par2_first: bool) -> u8 {
+        let path = Path::new(filename);
+        if !par2_first || !par2_patterns::is_par2_file(path) {
+            2
+        } else if par2_patterns::is_main_par2(path) {
+            0
+        } else {
+            1
+        }
+    }
+
     /// Download a single file using the connection pool
     async fn download_file_with_pool(
         file: NzbFile,
+        filename: String,
         config: &Config,
         pool: NntpPool,
-        progress_bar: ProgressBar,
+        reporter: DownloadReporter,
+        missing_groups: &HashSet<String>,
+        gate: BatchGate,
     ) -> Result<DownloadResult> {
-        let filename = Nzb::get_filename_from_subject(&file.subject)
-            .unwrap_or_else(|| format!("unknown_file_{}", file.date));
-
-        let output_path = config.download.dir.join(&filename);
+        let progress_bar = reporter.bar().clone();
+        let output_path = Self::output_path(config, &filename);
 
         // Check if file already exists with correct size (safe resume)
         // Size check is sufficient - corruption will be caught by PAR2 verification
         if !config.download.force_redownload {
             let expected_size: u64 = file.segments.segment.iter().map(|s| s.bytes).sum();
-            if let Ok(metadata) = tokio::fs::metadata(&output_path).await {
-                if metadata.len() == expected_size {
-                    // Log skip using progress bar for clean output
-                    if progress_bar.is_hidden() {
-                        eprintln!("  Skipping complete: {}", filename);
-                    } else {
-                        progress_bar.println(format!("  \x1b[90m↳ Skipping: {}\x1b[0m", filename));
-                    }
-                    return Ok(DownloadResult {
-                        filename,
-                        path: output_path,
-                        size: expected_size,
-                        segments_downloaded: file.segments.segment.len(),
-                        segments_failed: 0,
-                        download_time: Duration::from_secs(0),
-                        average_speed: 0.0,
-                        failed_message_ids: Vec::new(),
-                    });
+            if Self::is_complete_on_disk(&output_path, expected_size).await {
+                progress::bar_detail(&progress_bar, &format!("Skipping complete: {}", filename));
+                return Ok(DownloadResult {
+                    filename,
+                    path: output_path,
+                    size: expected_size,
+                    segments_downloaded: file.segments.segment.len(),
+                    segments_failed: 0,
+                    download_time: Duration::from_secs(0),
+                    average_speed: 0.0,
+                    failed_message_ids: Vec::new(),
+                    failure_reasons: HashMap::new(),
+                    size_mismatch: false,
+                    par2_handoff: false,
+                    par2_repaired: false,
+                    served_by: BTreeMap::new(),
+                    named_by_user: false,
+                });
+            }
+        }
+
+        let group = Self::pick_group(&file, &filename, missing_groups)?.to_string();
+
+        // Gaps in the numbering leave holes in the file however the download goes
+        if config.download.segment_numbering != SegmentNumbering::Ignore {
+            if let Some(details) = file.segment_numbering_issue() {
+                if config.download.segment_numbering == SegmentNumbering::Strict {
+                    return Err(DownloadError::SegmentNumbering { filename, details }.into());
                 }
+                progress::bar_warning(&progress_bar, &format!("{}: {}", filename, details));
             }
         }
 
         let start_time = Instant::now();
 
+        if let Some(parent) = output_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
         // Create shared file handle for concurrent writes
         let output_file = File::create(&output_path).await?;
 
@@ -223,9 +962,6 @@ impl Downloader {
 
         let shared_file = Arc::new(Mutex::new(output_file));
 
-        // Prepare segment downloads using pipelining
-        let group = &file.groups.group[0].name; // Use first group
-
         // Calculate segment offsets based on expected sizes (segments are 1-indexed)
         let segment_offsets: Vec<u64> = {
             let mut offsets = Vec::with_capacity(file.segments.segment.len());
@@ -255,44 +991,141 @@ impl Downloader {
             })
             .collect();
 
-        // Pipeline size: how many segments to request per connection
-        let pipeline_size = config.tuning.pipeline_size;
+        // Where each segment number goes, for segments whose yEnc header names another part
+        let offsets_by_number: Arc<HashMap<u32, u64>> = Arc::new(
+            segment_requests
+                .iter()
+                .map(|(req, offset)| (req.segment_number, *offset))
+                .collect(),
+        );
+        let by_yenc_part = config.download.assemble_by_yenc_part;
+        let part_mismatches = Arc::new(Mutex::new(Vec::<PartMismatch>::new()));
+        // yEnc parts already written, so two segments claiming one part can't overwrite each other
+        let claimed_parts = Arc::new(std::sync::Mutex::new(HashSet::<u32>::new()));
 
-        // Split into batches for pipelining
-        let num_connections = config.usenet.connections as usize;
-        let batches: Vec<Vec<(SegmentRequest, u64)>> = segment_requests
-            .chunks(pipeline_size)
-            .map(|chunk| chunk.to_vec())
-            .collect();
+        let segment_bytes = Arc::new(segment_sizes(&file));
+        let request_bytes =
+            |req: &SegmentRequest| segment_bytes.get(&req.segment_number).copied().unwrap_or(0);
+
+        // Split into batches for pipelining, by segment count or by total bytes
+        let num_connections = config.usenet.connections.count() as usize;
+        let batches: Vec<Vec<(SegmentRequest, u64)>> = pipeline_batches(
+            segment_requests.clone(),
+            |(req, _)| request_bytes(req),
+            &config.tuning,
+            config.tuning.segment_order,
+        );
 
         // Track download statistics
         let segments_downloaded = Arc::new(std::sync::atomic::AtomicUsize::new(0));
         let segments_failed = Arc::new(std::sync::atomic::AtomicUsize::new(0));
         let actual_size = Arc::new(std::sync::atomic::AtomicU64::new(0));
+        // Bytes of failed segments, kept off the bar until the file is done so speed stays honest
+        let failed_bytes = Arc::new(std::sync::atomic::AtomicU64::new(0));
         let failed_message_ids = Arc::new(Mutex::new(Vec::<String>::new()));
+        // Latest reason each failed segment failed for, by message-id
+        let failure_reasons = Arc::new(Mutex::new(HashMap::<String, SegmentFailure>::new()));
+        // Decoded file size announced by the segments' yEnc headers, once one has been read
+        let declared_size = Arc::new(std::sync::Mutex::new(None::<u64>));
 
         // Download batches in parallel using connection pool
         let connection_wait_timeout = config.tuning.connection_wait_timeout;
-        let batch_futures = batches.into_iter().map(|batch| {
-            let pool = pool.clone();
-            let progress = progress_bar.clone();
-            let segment_bytes: Vec<u64> = file.segments.segment.iter().map(|s| s.bytes).collect();
+        // Time this file has been stalled waiting for connections, shared by its batches
+        let wait_budget = (config.tuning.connection_wait_budget > 0)
+            .then(|| Duration::from_secs(config.tuning.connection_wait_budget));
+        let wait_clock = Arc::new(std::sync::Mutex::new(WaitClock::default()));
+        let backoff = Backoff::from_config(&config.tuning);
+
+        // Decoded segments go through a bounded queue to one writer, so when the disk falls
+        // behind the network, batches wait with their connection idle instead of piling up
+        // data in memory
+        let (write_tx, mut write_rx) =
+            tokio::sync::mpsc::channel::<(u32, u64, bytes::Bytes)>(config.memory.write_queue_depth);
+        let writer = {
             let shared_file = shared_file.clone();
             let segments_downloaded = segments_downloaded.clone();
             let segments_failed = segments_failed.clone();
             let actual_size = actual_size.clone();
+            let segment_bytes = segment_bytes.clone();
+            let reporter = reporter.clone();
+            let current_file = filename.clone();
+            tokio::spawn(async move {
+                while let Some((seg_num, offset, bytes)) = write_rx.recv().await {
+                    let written = {
+                        let mut file = shared_file.lock().await;
+                        file.seek(std::io::SeekFrom::Start(offset)).await.is_ok()
+                            && file.write_all(&bytes).await.is_ok()
+                    };
+                    if written {
+                        segments_downloaded.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        actual_size
+                            .fetch_add(bytes.len() as u64, std::sync::atomic::Ordering::Relaxed);
+                    } else {
+                        segments_failed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    }
+
+                    if let Some(&size) = segment_bytes.get(&seg_num) {
+                        reporter.advance(size, &current_file);
+                    }
+                }
+            })
+        };
+
+        // This file's share of the connections, widened as other files finish
+        let initial_limit = gate.batch_limit(num_connections);
+        let batch_slots = Arc::new(tokio::sync::Semaphore::new(initial_limit));
+        let slots_granted = Arc::new(std::sync::atomic::AtomicUsize::new(initial_limit));
+        let batch_futures = batches.into_iter().map(|batch| {
+            let pool = pool.clone();
+            let progress = progress_bar.clone();
+            let segment_bytes = segment_bytes.clone();
+            let write_tx = write_tx.clone();
+            let segments_failed = segments_failed.clone();
+            let failed_bytes = failed_bytes.clone();
             let failed_message_ids = failed_message_ids.clone();
+            let failure_reasons = failure_reasons.clone();
+            let offsets_by_number = offsets_by_number.clone();
+            let part_mismatches = part_mismatches.clone();
+            let claimed_parts = claimed_parts.clone();
+            let declared_size = declared_size.clone();
+            let wait_clock = wait_clock.clone();
+            let mut gate = gate.clone();
+            let batch_slots = batch_slots.clone();
+            let slots_granted = slots_granted.clone();
 
             async move {
+                // Hold the batch while downloading is paused
+                gate.wait_unpaused().await;
+
+                // Wait for one of this file's slots, adding any its share has grown by
+                let limit = gate.batch_limit(num_connections);
+                let granted = slots_granted.fetch_max(limit, std::sync::atomic::Ordering::Relaxed);
+                if limit > granted {
+                    batch_slots.add_permits(limit - granted);
+                }
+                let _slot = batch_slots.acquire().await;
+
                 // Get connection from pool with patient retry
                 let mut conn = None;
                 let mut attempt = 0u32;
                 let start = Instant::now();
                 let max_wait = Duration::from_secs(connection_wait_timeout);
 
+                let mut waiting = false;
+                // Set when the server throttled us, overriding the next backoff delay
+                let mut throttle_wait = None;
                 while conn.is_none() && start.elapsed() < max_wait {
                     if attempt > 0 {
-                        let delay = Duration::from_millis(500) * (1 << attempt.min(4));
+                        // Once the file's shared budget is spent, batches stop waiting
+                        let stalled = wait_clock.lock().unwrap().elapsed();
+                        if wait_budget.is_some_and(|budget| stalled >= budget) {
+                            break;
+                        }
+
+                        let delay = throttle_wait
+                            .take()
+                            .unwrap_or_else(|| backoff.delay(attempt))
+                            .min(max_wait.saturating_sub(start.elapsed()));
                         tokio::time::sleep(delay).await;
 
                         if attempt % 5 == 0 && !progress.is_hidden() {
@@ -308,31 +1141,67 @@ impl Downloader {
                         Ok(Ok(c)) => {
                             conn = Some(c);
                         }
+                        Ok(Err(DlNzbError::Nntp(NntpError::TooManyConnections {
+                            retry_after,
+                            ..
+                        }))) => {
+                            // Provider is at its connection cap: shrink the pool to what's open
+                            let status = pool.status();
+                            let open = status.size.max(1);
+                            if open < status.max_size {
+                                tracing::warn!(
+                                    "Server connection limit reached, reducing pool to {}",
+                                    open
+                                );
+                                pool.limit_to(open);
+                            }
+                            attempt += 1;
+                            throttle_wait = Some(backoff.throttled_delay(attempt, retry_after));
+                        }
+                        Ok(Err(DlNzbError::Nntp(
+                            e @ (NntpError::AuthThrottled { .. } | NntpError::ServerBusy { .. }),
+                        ))) => {
+                            attempt += 1;
+                            throttle_wait = Some(backoff.throttled_delay(attempt, e.retry_after()));
+                        }
                         Ok(Err(_)) | Err(_) => {
                             attempt += 1;
                         }
                     }
+
+                    if conn.is_none() && !waiting {
+                        wait_clock.lock().unwrap().start();
+                        waiting = true;
+                    }
+                }
+                if waiting {
+                    wait_clock.lock().unwrap().stop();
                 }
 
                 let mut conn = match conn {
                     Some(c) => c,
                     None => {
-                        if progress.is_hidden() {
-                            eprintln!(
-                                "  Warning: Could not get connection after {:?}",
-                                start.elapsed()
-                            );
-                        } else {
-                            progress.println(format!(
-                                "  \x1b[33m⚠ Connection unavailable, batch skipped\x1b[0m"
-                            ));
-                        }
+                        progress::bar_warning(
+                            &progress,
+                            &format!(
+                                "Connection unavailable after {:.0}s, batch skipped",
+                                start.elapsed().as_secs_f64()
+                            ),
+                        );
                         // Mark all segments in batch as failed
                         segments_failed
                             .fetch_add(batch.len(), std::sync::atomic::Ordering::Relaxed);
                         for (req, _) in &batch {
                             let mut failed = failed_message_ids.lock().await;
                             failed.push(req.message_id.clone());
+                            failure_reasons
+                                .lock()
+                                .await
+                                .insert(req.message_id.clone(), SegmentFailure::Connection);
+
+                            if let Some(&size) = segment_bytes.get(&req.segment_number) {
+                                failed_bytes.fetch_add(size, std::sync::atomic::Ordering::Relaxed);
+                            }
                         }
                         return;
                     }
@@ -342,73 +1211,91 @@ impl Downloader {
                 let requests: Vec<SegmentRequest> =
                     batch.iter().map(|(req, _)| req.clone()).collect();
 
+                // Index the batch by segment number to map results back in O(1)
+                let by_number: HashMap<u32, &(SegmentRequest, u64)> = batch
+                    .iter()
+                    .map(|entry| (entry.0.segment_number, entry))
+                    .collect();
+
                 // Download pipelined batch
                 match conn.download_segments_pipelined(&requests).await {
                     Ok(results) => {
-                        // Write each segment immediately using seek
+                        let relocated = record_part_mismatches(
+                            conn.take_part_mismatches(),
+                            by_yenc_part,
+                            &part_mismatches,
+                        )
+                        .await;
+                        if let Some(size) = conn.take_declared_file_size() {
+                            *declared_size.lock().unwrap() = Some(size);
+                        }
+                        let reasons: HashMap<u32, SegmentFailure> =
+                            conn.take_segment_failures().into_iter().collect();
+                        let received: u64 = results
+                            .iter()
+                            .filter_map(|(_, data)| data.as_ref())
+                            .map(|data| data.len() as u64)
+                            .sum();
+
+                        // Queue each segment for writing, waiting while the queue is full
                         for (seg_num, data) in results {
                             // Find the offset for this segment
-                            if let Some((_, offset)) =
-                                batch.iter().find(|(req, _)| req.segment_number == seg_num)
-                            {
-                                if let Some(bytes) = data {
-                                    // Write to file at correct offset
-                                    let mut file = shared_file.lock().await;
-                                    if file.seek(std::io::SeekFrom::Start(*offset)).await.is_ok() {
-                                        if file.write_all(&bytes).await.is_ok() {
-                                            segments_downloaded
-                                                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                                            actual_size.fetch_add(
-                                                bytes.len() as u64,
-                                                std::sync::atomic::Ordering::Relaxed,
-                                            );
-                                        } else {
-                                            segments_failed
-                                                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                                        }
-                                    } else {
-                                        segments_failed
-                                            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                                    }
-
-                                    // Update progress
-                                    if let Some(idx) = (seg_num as usize).checked_sub(1) {
-                                        if idx < segment_bytes.len() {
-                                            progress.inc(segment_bytes[idx]);
-                                        }
-                                    }
+                            if let Some((req, offset)) = by_number.get(&seg_num).copied() {
+                                let mismatch = relocated.get(&seg_num);
+                                let offset =
+                                    relocated_offset(mismatch, &offsets_by_number, *offset);
+                                let claimed = !by_yenc_part
+                                    || claimed_parts
+                                        .lock()
+                                        .unwrap()
+                                        .insert(mismatch.map_or(seg_num, |m| m.part));
+                                if let Some(bytes) = data.filter(|_| claimed) {
+                                    let _ = write_tx.send((seg_num, offset, bytes)).await;
                                 } else {
                                     segments_failed
                                         .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                                    let mut failed = failed_message_ids.lock().await;
-                                    if let Some((req, _)) =
-                                        batch.iter().find(|(r, _)| r.segment_number == seg_num)
-                                    {
-                                        failed.push(req.message_id.clone());
-                                    }
+                                    failed_message_ids.lock().await.push(req.message_id.clone());
+                                    // A body for a part already written is a duplicate post
+                                    let reason = if claimed {
+                                        reasons
+                                            .get(&seg_num)
+                                            .copied()
+                                            .unwrap_or(SegmentFailure::Connection)
+                                    } else {
+                                        SegmentFailure::Decode
+                                    };
+                                    failure_reasons
+                                        .lock()
+                                        .await
+                                        .insert(req.message_id.clone(), reason);
 
-                                    // Still update progress for failed segments
-                                    if let Some(idx) = (seg_num as usize).checked_sub(1) {
-                                        if idx < segment_bytes.len() {
-                                            progress.inc(segment_bytes[idx]);
-                                        }
+                                    // Counted once the file is done, so a retry can't count it twice
+                                    if let Some(&size) = segment_bytes.get(&seg_num) {
+                                        failed_bytes
+                                            .fetch_add(size, std::sync::atomic::Ordering::Relaxed);
                                     }
                                 }
                             }
                         }
+
+                        // Keep the connection until the speed limit has caught up
+                        gate.throttle(received).await;
                     }
-                    Err(_) => {
-                        // Failed - mark all as failed and update progress
+                    Err(e) => {
+                        // Failed - mark all as failed; their bytes reach the bar at the end
                         segments_failed
                             .fetch_add(batch.len(), std::sync::atomic::Ordering::Relaxed);
+                        let reason = SegmentFailure::from_error(&e);
                         for (req, _) in &batch {
                             let mut failed = failed_message_ids.lock().await;
                             failed.push(req.message_id.clone());
+                            failure_reasons
+                                .lock()
+                                .await
+                                .insert(req.message_id.clone(), reason);
 
-                            if let Some(idx) = (req.segment_number as usize).checked_sub(1) {
-                                if idx < segment_bytes.len() {
-                                    progress.inc(segment_bytes[idx]);
-                                }
+                            if let Some(&size) = segment_bytes.get(&req.segment_number) {
+                                failed_bytes.fetch_add(size, std::sync::atomic::Ordering::Relaxed);
                             }
                         }
                     }
@@ -422,11 +1309,186 @@ impl Downloader {
             .collect::<Vec<()>>()
             .await;
 
-        // Flush and close the file
-        {
-            let mut file = shared_file.lock().await;
-            file.flush().await?;
-        }
+        // Every batch has queued its segments; let the writer finish them
+        drop(write_tx);
+        let _ = writer.await;
+
+        // Retry what failed; a file past the handoff threshold stops early and leaves the
+        // rest to PAR2, which is usually faster than fighting dead articles
+        let total_segments = file.segments.segment.len();
+        let handoff_threshold = config.tuning.par2_handoff_threshold;
+        let max_retry_time = Duration::from_secs(config.tuning.max_retry_time);
+        let retry_start = Instant::now();
+        let mut par2_handoff = false;
+        let retry_passes = if config.usenet.retry_failed_segments {
+            config.usenet.retry_attempts
+        } else {
+            0
+        };
+        for _ in 0..retry_passes {
+            let failed: HashSet<String> = failed_message_ids.lock().await.iter().cloned().collect();
+            if failed.is_empty() {
+                break;
+            }
+
+            let completion = 1.0 - failed.len() as f64 / total_segments as f64;
+            if handoff_threshold > 0.0
+                && completion >= handoff_threshold
+                && retry_start.elapsed() >= max_retry_time
+            {
+                par2_handoff = true;
+                progress::bar_detail(
+                    &progress_bar,
+                    &format!(
+                        "{}: {:.1}% downloaded, leaving {} segment(s) to PAR2",
+                        filename,
+                        completion * 100.0,
+                        failed.len()
+                    ),
+                );
+                break;
+            }
+
+            // Articles the server already answered 430 for won't turn up on a retry
+            let retry: Vec<&(SegmentRequest, u64)> = segment_requests
+                .iter()
+                .filter(|(req, _)| {
+                    failed.contains(&req.message_id) && !pool.is_known_missing(&req.message_id)
+                })
+                .collect();
+            if retry.is_empty() {
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_millis(config.usenet.retry_delay)).await;
+            gate.clone().wait_unpaused().await;
+            let Ok(mut conn) = pool.get_connection().await else {
+                continue;
+            };
+
+            // Batched like the first pass, so pipeline_bytes applies to retries too
+            let retry_batches = pipeline_batches(
+                retry,
+                |(req, _)| request_bytes(req),
+                &config.tuning,
+                config.tuning.segment_order,
+            );
+            for chunk in retry_batches {
+                let requests: Vec<SegmentRequest> =
+                    chunk.iter().map(|(req, _)| req.clone()).collect();
+                let by_number: HashMap<u32, &(SegmentRequest, u64)> = chunk
+                    .iter()
+                    .map(|entry| (entry.0.segment_number, *entry))
+                    .collect();
+                let results = match conn.download_segments_pipelined(&requests).await {
+                    Ok(results) => results,
+                    Err(e) => {
+                        let reason = SegmentFailure::from_error(&e);
+                        let mut reasons = failure_reasons.lock().await;
+                        for req in &requests {
+                            reasons.insert(req.message_id.clone(), reason);
+                        }
+                        break;
+                    }
+                };
+                let relocated = record_part_mismatches(
+                    conn.take_part_mismatches(),
+                    by_yenc_part,
+                    &part_mismatches,
+                )
+                .await;
+                if let Some(size) = conn.take_declared_file_size() {
+                    *declared_size.lock().unwrap() = Some(size);
+                }
+                for (number, reason) in conn.take_segment_failures() {
+                    if let Some((req, _)) = by_number.get(&number) {
+                        failure_reasons
+                            .lock()
+                            .await
+                            .insert(req.message_id.clone(), reason);
+                    }
+                }
+
+                for (number, data) in results {
+                    let (Some(bytes), Some((req, offset))) =
+                        (data, by_number.get(&number).copied())
+                    else {
+                        continue;
+                    };
+                    let mismatch = relocated.get(&number);
+                    let offset = relocated_offset(mismatch, &offsets_by_number, *offset);
+                    if by_yenc_part
+                        && !claimed_parts
+                            .lock()
+                            .unwrap()
+                            .insert(mismatch.map_or(number, |m| m.part))
+                    {
+                        failure_reasons
+                            .lock()
+                            .await
+                            .insert(req.message_id.clone(), SegmentFailure::Decode);
+                        continue;
+                    }
+                    {
+                        let mut output = shared_file.lock().await;
+                        if output.seek(std::io::SeekFrom::Start(offset)).await.is_err()
+                            || output.write_all(&bytes).await.is_err()
+                        {
+                            continue;
+                        }
+                    }
+
+                    segments_downloaded.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    segments_failed.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+                    actual_size.fetch_add(bytes.len() as u64, std::sync::atomic::Ordering::Relaxed);
+                    failed_message_ids
+                        .lock()
+                        .await
+                        .retain(|id| *id != req.message_id);
+                    if let Some(&size) = segment_bytes.get(&number) {
+                        failed_bytes.fetch_sub(size, std::sync::atomic::Ordering::Relaxed);
+                        reporter.advance(size, &filename);
+                    }
+                }
+            }
+        }
+
+        // Flush and close the file
+        {
+            let mut file = shared_file.lock().await;
+            file.flush().await?;
+        }
+
+        // The NZB generator numbered segments differently from what the poster encoded
+        {
+            let mut mismatches = part_mismatches.lock().await;
+            mismatches.sort_unstable();
+            mismatches.dedup();
+            if let Some(&PartMismatch { segment, part, .. }) = mismatches.first() {
+                progress::bar_warning(
+                    &progress_bar,
+                    &format!(
+                        "{}: {} segment(s) carry a different yEnc part number than in the NZB \
+                         (segment {} is part {}); assembled by {}",
+                        filename,
+                        mismatches.len(),
+                        segment,
+                        part,
+                        if by_yenc_part {
+                            "yEnc part"
+                        } else {
+                            "NZB number"
+                        }
+                    ),
+                );
+            }
+        }
+
+        // Nothing is retried past this point, so failed bytes can fill out the bar
+        let final_failed_bytes = failed_bytes.load(std::sync::atomic::Ordering::Relaxed);
+        if final_failed_bytes > 0 {
+            reporter.advance(final_failed_bytes, &filename);
+        }
 
         // Extract final statistics
         let final_downloaded = segments_downloaded.load(std::sync::atomic::Ordering::Relaxed);
@@ -436,6 +1498,13 @@ impl Downloader {
             let ids = failed_message_ids.lock().await;
             ids.clone()
         };
+        let final_failure_reasons: HashMap<String, SegmentFailure> = {
+            let reasons = failure_reasons.lock().await;
+            final_failed_ids
+                .iter()
+                .filter_map(|id| reasons.get(id).map(|&reason| (id.clone(), reason)))
+                .collect()
+        };
 
         let download_time = start_time.elapsed();
         let average_speed = if download_time.as_secs() > 0 {
@@ -444,6 +1513,24 @@ impl Downloader {
             0.0
         };
 
+        // With nothing failed the decoded size must match what the poster's yEnc headers
+        // announced (NZB sizes count encoded bytes, so they can't be compared)
+        let declared_size = *declared_size.lock().unwrap();
+        let size_mismatch = config.download.verify_assembled_size
+            && final_failed == 0
+            && declared_size.is_some_and(|size| size != final_size);
+        if size_mismatch {
+            progress::bar_warning(
+                &progress_bar,
+                &format!(
+                    "{}: assembled {} bytes, yEnc header declares {} bytes",
+                    filename,
+                    final_size,
+                    declared_size.unwrap_or_default()
+                ),
+            );
+        }
+
         Ok(DownloadResult {
             filename,
             path: output_path,
@@ -453,9 +1540,428 @@ impl Downloader {
             download_time,
             average_speed,
             failed_message_ids: final_failed_ids,
+            failure_reasons: final_failure_reasons,
+            size_mismatch,
+            par2_handoff,
+            par2_repaired: false,
+            served_by: (final_downloaded > 0)
+                .then_some((group, final_downloaded))
+                .into_iter()
+                .collect(),
+            named_by_user: false,
         })
     }
 
+    /// Fetch segments that failed from a second NZB of the same release
+    ///
+    /// Files are matched by name, or by total size when the names differ, and must have the
+    /// same segment count so the segment numbers line up. Filled segments are written at the
+    /// primary's offsets, where the download placed them, and `results` is updated in place.
+    /// A segment is only written when its yEnc part is the one wanted and it fits the
+    /// primary's slot for it. Returns the number of segments filled.
+    pub async fn fill_from_nzb(
+        &self,
+        primary: &Nzb,
+        secondary: &Nzb,
+        results: &mut [DownloadResult],
+        config: &Config,
+    ) -> Result<usize> {
+        let primary_files: Vec<(&NzbFile, String)> = primary
+            .files()
+            .iter()
+            .zip(primary.unique_filenames())
+            .collect();
+        let secondary_files: Vec<(&NzbFile, String)> = secondary
+            .files()
+            .iter()
+            .zip(secondary.unique_filenames())
+            .collect();
+        let size = |file: &NzbFile| file.segments.segment.iter().map(|s| s.bytes).sum::<u64>();
+
+        let mut filled = 0;
+        for result in results.iter_mut().filter(|r| r.segments_failed > 0) {
+            let Some((file, _)) = primary_files
+                .iter()
+                .find(|(_, name)| *name == result.filename)
+            else {
+                continue;
+            };
+            let alternate = secondary_files
+                .iter()
+                .find(|(_, name)| *name == result.filename)
+                .or_else(|| secondary_files.iter().find(|(f, _)| size(f) == size(file)))
+                .map(|(f, _)| *f)
+                .filter(|f| f.segments.segment.len() == file.segments.segment.len());
+
+            if let Some(alternate) = alternate {
+                filled += self.fill_file(file, alternate, result, config).await?;
+            }
+        }
+
+        Ok(filled)
+    }
+
+    /// Fetch one file's failed segments from its counterpart in another NZB
+    async fn fill_file(
+        &self,
+        file: &NzbFile,
+        alternate: &NzbFile,
+        result: &mut DownloadResult,
+        config: &Config,
+    ) -> Result<usize> {
+        let failed: HashSet<&str> = result
+            .failed_message_ids
+            .iter()
+            .map(String::as_str)
+            .collect();
+
+        // Segment number -> (primary message-id, offset and size of its slot in the
+        // primary's layout)
+        let mut wanted: HashMap<u32, (String, u64, u64)> = HashMap::new();
+        let mut offset = 0u64;
+        for segment in &file.segments.segment {
+            if failed.contains(segment.message_id.as_str()) {
+                wanted.insert(
+                    segment.number,
+                    (segment.message_id.clone(), offset, segment.bytes),
+                );
+            }
+            offset += segment.bytes;
+        }
+
+        let group = Self::pick_group(alternate, &result.filename, &HashSet::new())?.to_string();
+        let requests: Vec<SegmentRequest> = alternate
+            .segments
+            .segment
+            .iter()
+            .filter(|segment| {
+                wanted.contains_key(&segment.number)
+                    && !self.pool.is_known_missing(&segment.message_id)
+            })
+            .map(|segment| SegmentRequest {
+                message_id: segment.message_id.clone(),
+                group: group.clone(),
+                segment_number: segment.number,
+            })
+            .collect();
+        if requests.is_empty() {
+            return Ok(0);
+        }
+
+        let _file_handle = self.acquire_file_handle().await;
+        let mut output = tokio::fs::OpenOptions::new()
+            .write(true)
+            .open(&result.path)
+            .await?;
+
+        // A batch that fails leaves its segments unfilled; the rest are still worth trying
+        let mut filled = 0;
+        for batch in requests.chunks(config.tuning.pipeline_size.max(1)) {
+            let mut conn = match self.pool.get_connection().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    tracing::debug!("No connection to fill {}: {}", result.filename, e);
+                    break;
+                }
+            };
+            let results = match conn.download_segments_pipelined(batch).await {
+                Ok(results) => results,
+                Err(e) => {
+                    tracing::debug!("Fill batch for {} failed: {}", result.filename, e);
+                    continue;
+                }
+            };
+            // The secondary's post may be laid out differently; a segment that claims
+            // another part, or won't fit where the primary's goes, would corrupt the file
+            let misplaced: HashSet<u32> = conn
+                .take_part_mismatches()
+                .into_iter()
+                .map(|mismatch| mismatch.segment)
+                .collect();
+
+            for (number, data) in results {
+                let (Some(data), Some((message_id, offset, slot))) = (data, wanted.get(&number))
+                else {
+                    continue;
+                };
+                if misplaced.contains(&number) || data.len() as u64 > *slot {
+                    tracing::debug!(
+                        "Not filling segment {} of {}: it doesn't match the primary's",
+                        number,
+                        result.filename
+                    );
+                    continue;
+                }
+                output.seek(std::io::SeekFrom::Start(*offset)).await?;
+                output.write_all(&data).await?;
+
+                filled += 1;
+                result.size += data.len() as u64;
+                result.failed_message_ids.retain(|id| id != message_id);
+                result.failure_reasons.remove(message_id);
+            }
+        }
+        output.flush().await?;
+
+        result.segments_downloaded += filled;
+        result.segments_failed -= filled;
+        if filled > 0 {
+            *result.served_by.entry(group).or_default() += filled;
+        }
+        Ok(filled)
+    }
+
+    /// Message-ids of the NZB's articles the server doesn't have
+    ///
+    /// Checks every segment with pipelined STAT, `pipeline_size` per batch, spread over the
+    /// pool's connections. Nothing is downloaded.
+    pub async fn missing_articles(
+        &self,
+        nzb: &Nzb,
+        pipeline_size: usize,
+    ) -> Result<HashSet<String>> {
+        let message_ids: Vec<String> = nzb
+            .files()
+            .iter()
+            .flat_map(|file| &file.segments.segment)
+            .map(|segment| segment.message_id.clone())
+            .collect();
+
+        let batches: Vec<Result<Vec<String>>> =
+            stream::iter(message_ids.chunks(pipeline_size.max(1)))
+                .map(|batch| async move {
+                    let mut conn = self.pool.get_connection().await?;
+                    let exists = conn.stat_articles(batch).await?;
+                    Ok::<_, DlNzbError>(
+                        batch
+                            .iter()
+                            .zip(exists)
+                            .filter(|(_, exists)| !exists)
+                            .map(|(id, _)| id.clone())
+                            .collect(),
+                    )
+                })
+                .buffer_unordered(self.pool.status().max_size.max(1))
+                .collect()
+                .await;
+
+        let mut missing = HashSet::new();
+        for batch in batches {
+            missing.extend(batch?);
+        }
+        Ok(missing)
+    }
+
+    /// Download a single file and write its assembled bytes to `writer`, in order
+    ///
+    /// For writers that can't seek, such as pipes into another process. Batches are fetched
+    /// contiguously and yielded in order, so at most one batch per connection is buffered.
+    /// With `memory.spill_to_temp`, batches are taken as they finish and any that arrive
+    /// early go to a temp file instead, so memory stays at one batch whatever the order.
+    /// A missing segment fails the stream rather than leaving a silent gap; returns bytes written.
+    pub async fn download_file_to<W: AsyncWrite + Unpin>(
+        &self,
+        file: &NzbFile,
+        writer: &mut W,
+        config: &Config,
+    ) -> Result<u64> {
+        self.stream_file_to(file, writer, config, &mut SpillFile::new())
+            .await
+    }
+
+    /// [`Self::download_file_to`] with early segments spilled to `spill`
+    async fn stream_file_to<W: AsyncWrite + Unpin>(
+        &self,
+        file: &NzbFile,
+        writer: &mut W,
+        config: &Config,
+        spill: &mut SpillFile,
+    ) -> Result<u64> {
+        let (order, batches) = Self::ordered_batches(file, config)?;
+        let total = order.len() as u32;
+        let concurrency = self.pool.status().max_size.max(1);
+        let batch_futures = stream::iter(batches).map(|batch| {
+            let pool = self.pool.clone();
+            async move {
+                let mut conn = pool.get_connection().await?;
+                conn.download_segments_pipelined(&batch).await
+            }
+        });
+        let mut batch_results = if config.memory.spill_to_temp {
+            batch_futures.buffer_unordered(concurrency).boxed()
+        } else {
+            batch_futures.buffered(concurrency).boxed()
+        };
+
+        let mut next = 0usize;
+        let mut written = 0u64;
+        while let Some(results) = batch_results.next().await {
+            for (number, data) in results? {
+                let data = data.ok_or_else(|| DownloadError::SegmentFailed {
+                    number,
+                    total,
+                    reason: "article not available".to_string(),
+                })?;
+
+                if order.get(next) != Some(&number) {
+                    spill.push(number, &data).await?;
+                    continue;
+                }
+
+                writer.write_all(&data).await?;
+                written += data.len() as u64;
+                next += 1;
+
+                // Catch up on segments that arrived early
+                while let Some(data) = match order.get(next) {
+                    Some(&number) => spill.take(number).await?,
+                    None => None,
+                } {
+                    writer.write_all(&data).await?;
+                    written += data.len() as u64;
+                    next += 1;
+                }
+            }
+        }
+        writer.flush().await?;
+
+        Ok(written)
+    }
+
+    /// Download a single file and return its assembled bytes without touching the filesystem
+    ///
+    /// Meant for small files such as NFOs or metadata a caller wants to parse directly.
+    /// Files whose NZB size exceeds `max_bytes` are refused up front, and the assembled
+    /// data is checked against the same cap as it grows. Batches are yielded in order, so
+    /// nothing is ever spilled to disk; a missing segment fails the download.
+    pub async fn download_file_to_memory(
+        &self,
+        file: &NzbFile,
+        config: &Config,
+        max_bytes: u64,
+    ) -> Result<Bytes> {
+        let filename =
+            Nzb::get_filename_from_subject(&file.subject).unwrap_or_else(|| file.subject.clone());
+        let too_large = |size: u64| DownloadError::TooLargeForMemory {
+            filename: filename.clone(),
+            size,
+            limit: max_bytes,
+        };
+        let expected_size: u64 = file.segments.segment.iter().map(|s| s.bytes).sum();
+        if expected_size > max_bytes {
+            return Err(too_large(expected_size).into());
+        }
+
+        let (order, batches) = Self::ordered_batches(file, config)?;
+        let total = order.len() as u32;
+        let concurrency = self.pool.status().max_size.max(1);
+        let mut batch_results = stream::iter(batches)
+            .map(|batch| {
+                let pool = self.pool.clone();
+                async move {
+                    let mut conn = pool.get_connection().await?;
+                    conn.download_segments_pipelined(&batch).await
+                }
+            })
+            .buffered(concurrency);
+
+        let mut buffer = BytesMut::with_capacity(expected_size as usize);
+        while let Some(results) = batch_results.next().await {
+            for (number, data) in results? {
+                let data = data.ok_or_else(|| DownloadError::SegmentFailed {
+                    number,
+                    total,
+                    reason: "article not available".to_string(),
+                })?;
+                let size = (buffer.len() + data.len()) as u64;
+                if size > max_bytes {
+                    return Err(too_large(size).into());
+                }
+                buffer.extend_from_slice(&data);
+            }
+        }
+
+        Ok(buffer.freeze())
+    }
+
+    /// Segment numbers in file order and the contiguous pipeline batches that fetch them
+    fn ordered_batches(
+        file: &NzbFile,
+        config: &Config,
+    ) -> Result<(Vec<u32>, Vec<Vec<SegmentRequest>>)> {
+        let group = Self::pick_group(file, &file.subject, &HashSet::new())?.to_string();
+
+        let mut segments: Vec<_> = file.segments.segment.iter().collect();
+        segments.sort_by_key(|s| s.number);
+        let order: Vec<u32> = segments.iter().map(|s| s.number).collect();
+        let requests: Vec<SegmentRequest> = segments
+            .into_iter()
+            .map(|segment| SegmentRequest {
+                message_id: segment.message_id.clone(),
+                group: group.clone(),
+                segment_number: segment.number,
+            })
+            .collect();
+
+        let sizes = segment_sizes(file);
+        let batches = pipeline_batches(
+            requests,
+            |req| sizes.get(&req.segment_number).copied().unwrap_or(0),
+            &config.tuning,
+            SegmentOrder::Contiguous,
+        );
+        Ok((order, batches))
+    }
+
+    /// Files from the NZB that are missing from the download directory or don't have their
+    /// expected size
+    ///
+    /// Applies the same filter, output name, output rules and size rule as the resume check,
+    /// so these are exactly the files a download with `config` would fetch.
+    pub async fn pending_files(nzb: &Nzb, config: &Config) -> Result<Vec<String>> {
+        let filter = FileFilter::from_config(&config.download);
+        let (files, mut filenames): (Vec<&NzbFile>, Vec<String>) = nzb
+            .files()
+            .iter()
+            .zip(nzb.unique_filenames())
+            .filter(|(file, _)| filter.matches(file))
+            .unzip();
+        if let Some(name) = &config.download.output_name {
+            Self::apply_output_name(&mut filenames, name)?;
+        }
+
+        let mut pending = Vec::new();
+        for (file, filename) in files.into_iter().zip(filenames) {
+            let expected_size: u64 = file.segments.segment.iter().map(|s| s.bytes).sum();
+            let path = Self::output_path(config, &filename);
+            if !Self::is_complete_on_disk(&path, expected_size).await {
+                pending.push(filename);
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Where a file is written: the subfolder of the first output rule matching its name,
+    /// or the download directory itself
+    fn output_path(config: &Config, filename: &str) -> PathBuf {
+        let dir = &config.download.dir;
+        config
+            .download
+            .output_rules
+            .iter()
+            .find(|rule| filename_regex(&rule.pattern).is_some_and(|re| re.is_match(filename)))
+            .map(|rule| dir.join(&rule.path).join(filename))
+            .unwrap_or_else(|| dir.join(filename))
+    }
+
+    /// Check whether a file already exists with its expected size
+    async fn is_complete_on_disk(path: &Path, expected_size: u64) -> bool {
+        tokio::fs::metadata(path)
+            .await
+            .map(|m| m.len() == expected_size)
+            .unwrap_or(false)
+    }
+
     /// Clean up partial files after failed download
     pub async fn cleanup_partial_files(results: &[DownloadResult]) -> Result<usize> {
         let mut cleaned_count = 0;
@@ -478,3 +1984,1058 @@ impl Downloader {
         Ok(cleaned_count)
     }
 }
+
+/// Grow the pool step by step while each step still raises throughput
+///
+/// Throughput is read from the download progress bar. Tuning stops and holds the count once
+/// a step gains less than 5%, the cap is reached, or the pool was shrunk because the
+/// provider refused connections; a step that made things clearly worse is undone.
+///
+/// `tuned` always holds the count currently in use.
+async fn auto_tune_connections(
+    lease: Arc<PoolSizeLease>,
+    progress: ProgressBar,
+    cap: usize,
+    tuned: Arc<AtomicUsize>,
+) {
+    let mut last_rate = 0.0;
+    let mut last_position = progress.position();
+    let mut wanted = AUTO_TUNE_START.min(cap);
+
+    loop {
+        tokio::time::sleep(AUTO_TUNE_INTERVAL).await;
+
+        let position = progress.position();
+        let rate = position.saturating_sub(last_position) as f64 / AUTO_TUNE_INTERVAL.as_secs_f64();
+        last_position = position;
+
+        // Renewing the request reports whether a provider limit now caps the pool below it
+        let current = lease.want(wanted);
+        tuned.store(current, Ordering::Relaxed);
+        if current < wanted {
+            tracing::info!(
+                "Auto-tune: provider limit reached, holding at {} connections",
+                current
+            );
+            return;
+        }
+
+        if wanted >= cap || rate < last_rate * 1.05 {
+            if rate < last_rate * 0.95 && wanted > AUTO_TUNE_STEP {
+                wanted -= AUTO_TUNE_STEP;
+                tuned.store(lease.want(wanted), Ordering::Relaxed);
+            }
+            tracing::info!("Auto-tune: settled on {} connections", wanted);
+            return;
+        }
+
+        last_rate = rate;
+        wanted = (wanted + AUTO_TUNE_STEP).min(cap);
+        tracing::debug!(
+            "Auto-tune: {:.1} MB/s, trying {} connections",
+            rate / 1_048_576.0,
+            wanted
+        );
+        tuned.store(lease.want(wanted), Ordering::Relaxed);
+    }
+}
+
+/// Compile a filename pattern from the download config (case-insensitive)
+fn filename_regex(pattern: &str) -> Option<regex::Regex> {
+    regex::RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .ok()
+}
+
+/// Temp file holding segments that arrived before the ones ahead of them
+///
+/// Segments are appended with an index of where each one landed; the file is only
+/// created once something actually needs spilling.
+struct SpillFile {
+    file: Option<File>,
+    len: u64,
+    index: HashMap<u32, (u64, usize)>,
+}
+
+impl SpillFile {
+    fn new() -> Self {
+        Self {
+            file: None,
+            len: 0,
+            index: HashMap::new(),
+        }
+    }
+
+    /// Append a segment to the end of the spill file
+    async fn push(&mut self, number: u32, data: &[u8]) -> Result<()> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(File::from_std(tempfile::tempfile()?)),
+        };
+        file.seek(std::io::SeekFrom::Start(self.len)).await?;
+        file.write_all(data).await?;
+        self.index.insert(number, (self.len, data.len()));
+        self.len += data.len() as u64;
+        Ok(())
+    }
+
+    /// Read back and forget a spilled segment, if it has arrived
+    async fn take(&mut self, number: u32) -> Result<Option<Vec<u8>>> {
+        let (Some(file), Some((offset, len))) = (&mut self.file, self.index.remove(&number)) else {
+            return Ok(None);
+        };
+        let mut data = vec![0u8; len];
+        file.seek(std::io::SeekFrom::Start(offset)).await?;
+        file.read_exact(&mut data).await?;
+        Ok(Some(data))
+    }
+}
+
+/// Split segment requests into pipeline batches as `tuning` asks: of at most
+/// `pipeline_bytes` when that is set, otherwise of at most `pipeline_size` requests
+fn pipeline_batches<T>(
+    requests: Vec<T>,
+    size: impl Fn(&T) -> u64,
+    tuning: &TuningConfig,
+    order: SegmentOrder,
+) -> Vec<Vec<T>> {
+    match tuning.pipeline_bytes {
+        0 => build_batches(requests, tuning.pipeline_size, order),
+        target => build_batches_by_bytes(requests, size, target, order),
+    }
+}
+
+/// Split segment requests into pipeline batches of at most `pipeline_size`
+///
+/// `Interleaved` deals requests round-robin so no single connection owns a contiguous
+/// (and possibly slow) region of the file, such as the tail.
+fn build_batches<T>(requests: Vec<T>, pipeline_size: usize, order: SegmentOrder) -> Vec<Vec<T>> {
+    let pipeline_size = pipeline_size.max(1);
+    let num_batches = requests.len().div_ceil(pipeline_size);
+    let mut batches: Vec<Vec<T>> = (0..num_batches)
+        .map(|_| Vec::with_capacity(pipeline_size))
+        .collect();
+
+    for (i, request) in requests.into_iter().enumerate() {
+        let batch = match order {
+            SegmentOrder::Contiguous => i / pipeline_size,
+            SegmentOrder::Interleaved => i % num_batches,
+        };
+        batches[batch].push(request);
+    }
+
+    batches
+}
+
+/// Split segment requests into pipeline batches of at most `target` bytes each
+///
+/// Segments vary in size, so equal counts can mean very unequal batches; equal bytes keep
+/// each connection busy for about the same time. A segment larger than `target` gets a
+/// batch of its own. `Interleaved` deals requests round-robin like [`build_batches`],
+/// skipping batches the request would push past `target` and opening a new one when
+/// none has room.
+fn build_batches_by_bytes<T>(
+    requests: Vec<T>,
+    size: impl Fn(&T) -> u64,
+    target: u64,
+    order: SegmentOrder,
+) -> Vec<Vec<T>> {
+    let target = target.max(1);
+    match order {
+        SegmentOrder::Contiguous => {
+            let mut batches: Vec<Vec<T>> = Vec::new();
+            let mut batch_bytes = 0u64;
+            for request in requests {
+                let bytes = size(&request);
+                match batches.last_mut() {
+                    Some(batch) if batch_bytes + bytes <= target => batch.push(request),
+                    _ => {
+                        batches.push(vec![request]);
+                        batch_bytes = 0;
+                    }
+                }
+                batch_bytes += bytes;
+            }
+            batches
+        }
+        SegmentOrder::Interleaved => {
+            let total: u64 = requests.iter().map(&size).sum();
+            let num_batches = (total.div_ceil(target) as usize).min(requests.len());
+            let mut batches: Vec<(u64, Vec<T>)> =
+                (0..num_batches).map(|_| (0, Vec::new())).collect();
+            let mut next = 0;
+            for request in requests {
+                let bytes = size(&request);
+                let open = (0..batches.len())
+                    .map(|i| (next + i) % batches.len())
+                    .find(|&i| batches[i].0 + bytes <= target);
+                match open {
+                    Some(i) => {
+                        batches[i].0 += bytes;
+                        batches[i].1.push(request);
+                        next = i + 1;
+                    }
+                    None => batches.push((bytes, vec![request])),
+                }
+            }
+            batches
+                .into_iter()
+                .map(|(_, batch)| batch)
+                .filter(|batch| !batch.is_empty())
+                .collect()
+        }
+    }
+}
+
+/// Keep a batch's yEnc part mismatches for the file's report and, with
+/// `assemble_by_yenc_part`, map each affected segment number to its mismatch
+async fn record_part_mismatches(
+    mismatches: Vec<PartMismatch>,
+    relocate: bool,
+    seen: &Mutex<Vec<PartMismatch>>,
+) -> HashMap<u32, PartMismatch> {
+    if mismatches.is_empty() {
+        return HashMap::new();
+    }
+    seen.lock().await.extend_from_slice(&mismatches);
+    if relocate {
+        mismatches.into_iter().map(|m| (m.segment, m)).collect()
+    } else {
+        HashMap::new()
+    }
+}
+
+/// Where a relocated segment's data goes: where the NZB puts the part it declared,
+/// else its own slot
+fn relocated_offset(
+    mismatch: Option<&PartMismatch>,
+    offsets_by_number: &HashMap<u32, u64>,
+    offset: u64,
+) -> u64 {
+    mismatch
+        .and_then(|m| offsets_by_number.get(&m.part).copied())
+        .unwrap_or(offset)
+}
+
+/// Map each segment number to its size, for O(1) lookups while results come in
+fn segment_sizes(file: &NzbFile) -> HashMap<u32, u64> {
+    file.segments
+        .segment
+        .iter()
+        .map(|segment| (segment.number, segment.bytes))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_gate_throttles_to_the_scheduled_speed() {
+        let (schedule_tx, schedule) = watch::channel(ScheduleState::default());
+        let gate = BatchGate {
+            schedule,
+            speed_clock: Arc::new(std::sync::Mutex::new(Instant::now())),
+            paused: watch::channel(false).1,
+            completed_files: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+            total_files: 1,
+            concurrent_files: 1,
+            max_batches_per_file: 0,
+        };
+
+        // Unlimited: no waiting
+        let start = Instant::now();
+        gate.throttle(1_000_000).await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        // Two batches of 1000 bytes at 10000 B/s take about 200ms between them
+        schedule_tx.send_replace(ScheduleState {
+            paused: false,
+            speed_limit: 10_000,
+        });
+        let start = Instant::now();
+        let other = gate.clone();
+        tokio::join!(gate.throttle(1000), other.throttle(1000));
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(190), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn test_pick_group_rejects_file_without_groups() {
+        let xml = r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="poster@example.com" date="1234567890" subject="&quot;file.bin&quot; yEnc (1/1)">
+                <groups/>
+                <segments><segment bytes="100" number="1">a@example.com</segment></segments>
+            </file>
+        </nzb>
+        "#;
+        let nzb: Nzb = xml.trim().parse().unwrap();
+
+        let result = Downloader::pick_group(&nzb.files()[0], "file.bin", &HashSet::new());
+        assert!(matches!(
+            result,
+            Err(DlNzbError::Download(DownloadError::NoGroups { .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_download_file_to_from_mock_server() {
+        use super::super::nzb::{NzbGroup, NzbGroups, NzbSegment, NzbSegments};
+        use crate::nntp::mock::MockServer;
+
+        let server = MockServer::start().await;
+        server.add_group("alt.binaries.test");
+        server.add_article("a@test", b"first ");
+        server.add_article("b@test", b"second");
+
+        let mut config = Config::default();
+        config.usenet = server.config();
+        config.tuning.pipeline_size = 1;
+        let file = NzbFile {
+            poster: "poster@example.com".to_string(),
+            date: 0,
+            subject: "\"file.bin\" yEnc (1/2)".to_string(),
+            groups: NzbGroups {
+                group: vec![NzbGroup {
+                    name: "alt.binaries.test".to_string(),
+                }],
+            },
+            segments: NzbSegments {
+                segment: ["b@test", "a@test"]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, id)| NzbSegment {
+                        bytes: 6,
+                        number: 2 - i as u32,
+                        message_id: id.to_string(),
+                    })
+                    .collect(),
+            },
+        };
+
+        let downloader = Downloader::new(config.clone()).await.unwrap();
+        let mut output = Vec::new();
+        let written = downloader
+            .download_file_to(&file, &mut output, &config)
+            .await
+            .unwrap();
+        assert_eq!(written, 12);
+        assert_eq!(output, b"first second");
+
+        let data = downloader
+            .download_file_to_memory(&file, &config, 12)
+            .await
+            .unwrap();
+        assert_eq!(&data[..], b"first second");
+        let err = downloader
+            .download_file_to_memory(&file, &config, 11)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            DlNzbError::Download(DownloadError::TooLargeForMemory {
+                size: 12,
+                limit: 11,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_assembled_size_is_checked_against_yenc_size() {
+        use crate::nntp::mock::MockServer;
+
+        let server = MockServer::start().await;
+        server.add_group("alt.binaries.test");
+        server.add_part("a@test", b"first ", 1, 1, 12);
+        server.add_part("b@test", b"second", 2, 7, 12);
+
+        // NZB sizes count encoded bytes, so they run larger than the decoded file
+        let xml = r#"
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="p" date="0" subject="&quot;file.bin&quot; yEnc (1/2)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments>
+                    <segment bytes="9" number="1">a@test</segment>
+                    <segment bytes="9" number="2">b@test</segment>
+                </segments>
+            </file>
+        </nzb>
+        "#;
+        let nzb: Nzb = xml.trim().parse().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.usenet = server.config();
+        config.download.dir = dir.path().to_path_buf();
+        config.download.verify_assembled_size = true;
+        config.download.force_redownload = true;
+
+        let downloader = Downloader::new(config.clone()).await.unwrap();
+        let (results, _) = downloader.download_nzb(&nzb, config.clone()).await.unwrap();
+        assert_eq!(results[0].segments_failed, 0);
+        assert_eq!(results[0].size, 12);
+        assert!(!results[0].size_mismatch);
+        assert_eq!(
+            results[0].served_by,
+            BTreeMap::from([("alt.binaries.test".to_string(), 2)])
+        );
+
+        server.add_part("a@test", b"first ", 1, 1, 20);
+        server.add_part("b@test", b"second", 2, 7, 20);
+        let (results, _) = downloader.download_nzb(&nzb, config).await.unwrap();
+        assert_eq!(results[0].segments_failed, 0);
+        assert!(results[0].size_mismatch);
+    }
+
+    #[tokio::test]
+    async fn test_fill_from_nzb_checks_segments_and_skips_failed_batches() {
+        use crate::nntp::mock::{Fault, MockServer};
+
+        let server = MockServer::start().await;
+        server.add_group("alt.binaries.test");
+        server.add_group("alt.binaries.fill");
+        server.add_article("s1@test", b"first ");
+        server.inject("s1@test", Fault::Disconnect);
+        server.add_part("s2@test", b"second", 2, 7, 18);
+        // Claims to be the first part, so it doesn't belong in the third's place
+        server.add_part("s3@test", b"third!", 1, 1, 18);
+
+        let nzb = |prefix: &str, group: &str| -> Nzb {
+            format!(
+                r#"
+                <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+                    <file poster="p" date="0" subject="&quot;file.bin&quot; yEnc (1/3)">
+                        <groups><group>{1}</group></groups>
+                        <segments>
+                            <segment bytes="6" number="1">{0}1@test</segment>
+                            <segment bytes="6" number="2">{0}2@test</segment>
+                            <segment bytes="6" number="3">{0}3@test</segment>
+                        </segments>
+                    </file>
+                </nzb>
+                "#,
+                prefix, group
+            )
+            .trim()
+            .parse()
+            .unwrap()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.bin");
+        std::fs::write(&path, [0u8; 18]).unwrap();
+
+        let mut config = Config::default();
+        config.usenet = server.config();
+        config.tuning.pipeline_size = 1;
+        let failed: Vec<String> = ["p1@test", "p2@test", "p3@test"].map(String::from).into();
+        let mut results = vec![DownloadResult {
+            filename: "file.bin".to_string(),
+            path: path.clone(),
+            size: 0,
+            segments_downloaded: 0,
+            segments_failed: 3,
+            download_time: Duration::ZERO,
+            average_speed: 0.0,
+            failed_message_ids: failed,
+            failure_reasons: HashMap::new(),
+            size_mismatch: false,
+            par2_handoff: false,
+            par2_repaired: false,
+            served_by: BTreeMap::new(),
+            named_by_user: false,
+        }];
+
+        let downloader = Downloader::new(config.clone()).await.unwrap();
+        let filled = downloader
+            .fill_from_nzb(
+                &nzb("p", "alt.binaries.test"),
+                &nzb("s", "alt.binaries.fill"),
+                &mut results,
+                &config,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(filled, 1);
+        assert_eq!(results[0].segments_failed, 2);
+        assert_eq!(results[0].failed_message_ids, ["p1@test", "p3@test"]);
+        assert_eq!(
+            results[0].served_by,
+            BTreeMap::from([("alt.binaries.fill".to_string(), 1)])
+        );
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(&data[6..12], b"second");
+        assert_eq!(&data[12..], [0u8; 6]);
+    }
+
+    #[tokio::test]
+    async fn test_relocated_segments_use_nzb_offsets_and_reject_duplicate_parts() {
+        use crate::nntp::mock::MockServer;
+
+        // Segments 1 and 2 carry each other's parts, segment 3 a second copy of part 1
+        let server = MockServer::start().await;
+        server.add_group("alt.binaries.test");
+        server.add_part("a@test", b"abcd", 2, 9, 12);
+        server.add_part("b@test", b"12345678", 1, 1, 12);
+        server.add_part("c@test", b"12345678", 1, 1, 12);
+
+        let xml = r#"
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="p" date="0" subject="&quot;file.bin&quot; yEnc (1/3)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments>
+                    <segment bytes="8" number="1">a@test</segment>
+                    <segment bytes="4" number="2">b@test</segment>
+                    <segment bytes="8" number="3">c@test</segment>
+                </segments>
+            </file>
+        </nzb>
+        "#;
+        let nzb: Nzb = xml.trim().parse().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.usenet = server.config();
+        config.download.dir = dir.path().to_path_buf();
+        config.download.assemble_by_yenc_part = true;
+        config.download.force_redownload = true;
+
+        let downloader = Downloader::new(config.clone()).await.unwrap();
+        let (results, _) = downloader.download_nzb(&nzb, config).await.unwrap();
+
+        // Each part lands in the NZB slot of the number it declared
+        assert_eq!(results[0].segments_failed, 1);
+        let data = std::fs::read(dir.path().join("file.bin")).unwrap();
+        assert_eq!(&data[..12], b"12345678abcd");
+    }
+
+    /// Two-segment NZB of `a@test` and `b@test`, plus a config downloading it into `dir`
+    fn retry_fixture(server: &crate::nntp::mock::MockServer, dir: &Path) -> (Nzb, Config) {
+        let xml = r#"
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="p" date="0" subject="&quot;file.bin&quot; yEnc (1/2)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments>
+                    <segment bytes="6" number="1">a@test</segment>
+                    <segment bytes="6" number="2">b@test</segment>
+                </segments>
+            </file>
+        </nzb>
+        "#;
+        let mut config = Config::default();
+        config.usenet = server.config();
+        config.usenet.retry_attempts = 2;
+        config.usenet.retry_delay = 0;
+        config.download.dir = dir.to_path_buf();
+        config.download.force_redownload = true;
+        (xml.trim().parse().unwrap(), config)
+    }
+
+    #[tokio::test]
+    async fn test_failed_segments_are_retried_only_when_enabled() {
+        use crate::nntp::mock::{Fault, MockServer};
+
+        let server = MockServer::start().await;
+        server.add_group("alt.binaries.test");
+        server.add_article("a@test", b"first ");
+        server.add_article("b@test", b"second");
+        let dir = tempfile::tempdir().unwrap();
+        let (nzb, mut config) = retry_fixture(&server, dir.path());
+        let downloader = Downloader::new(config.clone()).await.unwrap();
+
+        server.inject("b@test", Fault::Truncate);
+        let (results, _) = downloader.download_nzb(&nzb, config.clone()).await.unwrap();
+        assert_eq!(results[0].segments_failed, 1);
+
+        server.inject("b@test", Fault::Truncate);
+        config.usenet.retry_failed_segments = true;
+        let (results, _) = downloader.download_nzb(&nzb, config).await.unwrap();
+        assert_eq!(results[0].segments_failed, 0);
+        assert!(!results[0].par2_handoff);
+        assert_eq!(
+            std::fs::read(dir.path().join("file.bin")).unwrap(),
+            b"first second"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_nearly_complete_file_is_handed_off_to_par2() {
+        use crate::nntp::mock::{Fault, MockServer};
+
+        let server = MockServer::start().await;
+        server.add_group("alt.binaries.test");
+        server.add_article("a@test", b"first ");
+        server.add_article("b@test", b"second");
+        server.inject("b@test", Fault::Truncate);
+        let dir = tempfile::tempdir().unwrap();
+        let (nzb, mut config) = retry_fixture(&server, dir.path());
+        config.usenet.retry_failed_segments = true;
+        config.tuning.par2_handoff_threshold = 0.5;
+
+        let downloader = Downloader::new(config.clone()).await.unwrap();
+        let (results, _) = downloader.download_nzb(&nzb, config).await.unwrap();
+
+        assert!(results[0].par2_handoff);
+        assert_eq!(results[0].segments_failed, 1);
+        // Handed off before any retry was made
+        let requests = server
+            .commands()
+            .iter()
+            .filter(|c| c.as_str() == "BODY <b@test>")
+            .count();
+        assert_eq!(requests, 1);
+    }
+
+    #[test]
+    fn test_par2_rank_puts_index_before_volumes_before_content() {
+        let mut names = [
+            "movie.mkv",
+            "movie.vol03+04.par2",
+            "movie.par2",
+            "movie.nfo",
+        ];
+        names.sort_by_key(|name| Downloader::par2_rank(name, true));
+        assert_eq!(names[..2], ["movie.par2", "movie.vol03+04.par2"]);
+
+        assert_eq!(Downloader::par2_rank("movie.par2", false), 2);
+    }
+
+    #[test]
+    fn test_output_name_renames_single_content_file() {
+        let mut filenames: Vec<String> = ["a1b2c3.bin", "a1b2c3.par2", "a1b2c3.vol0+1.par2"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        Downloader::apply_output_name(&mut filenames, "My: Movie.mkv").unwrap();
+        assert_eq!(filenames[0], "My_ Movie.mkv");
+        assert_eq!(filenames[1], "a1b2c3.par2");
+
+        let mut two = vec!["one.mkv".to_string(), "two.mkv".to_string()];
+        assert!(matches!(
+            Downloader::apply_output_name(&mut two, "movie.mkv"),
+            Err(DlNzbError::Download(
+                DownloadError::OutputNameNeedsSingleFile { count: 2 }
+            ))
+        ));
+
+        let mut one = vec!["one.mkv".to_string()];
+        assert!(Downloader::apply_output_name(&mut one, "..").is_err());
+    }
+
+    #[test]
+    fn test_segment_sizes_match_linear_lookup() {
+        use super::super::nzb::{NzbGroups, NzbSegment, NzbSegments};
+
+        // Out of order, as some NZBs list them
+        let segment = |number: u32| NzbSegment {
+            bytes: 1000 + number as u64,
+            number,
+            message_id: format!("{}@example.com", number),
+        };
+        let file = NzbFile {
+            poster: "poster@example.com".to_string(),
+            date: 0,
+            subject: "\"file.bin\" yEnc (1/4)".to_string(),
+            groups: NzbGroups { group: Vec::new() },
+            segments: NzbSegments {
+                segment: vec![segment(2), segment(1), segment(4), segment(3)],
+            },
+        };
+
+        let sizes = segment_sizes(&file);
+        for number in 1..=5 {
+            let linear = file
+                .segments
+                .segment
+                .iter()
+                .find(|s| s.number == number)
+                .map(|s| s.bytes);
+            assert_eq!(sizes.get(&number).copied(), linear);
+        }
+    }
+
+    #[test]
+    fn test_batch_limit_shares_connections_between_files() {
+        let completed = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut gate = BatchGate {
+            schedule: watch::channel(ScheduleState::default()).1,
+            speed_clock: Arc::new(std::sync::Mutex::new(Instant::now())),
+            paused: watch::channel(false).1,
+            completed_files: completed.clone(),
+            total_files: 10,
+            concurrent_files: 4,
+            max_batches_per_file: 0,
+        };
+        assert_eq!(gate.batch_limit(20), 5);
+
+        // The last files get the connections the finished ones gave up
+        completed.store(8, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(gate.batch_limit(20), 10);
+        completed.store(9, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(gate.batch_limit(20), 20);
+
+        gate.max_batches_per_file = 3;
+        assert_eq!(gate.batch_limit(20), 3);
+    }
+
+    #[test]
+    fn test_backoff_strategies() {
+        let mut tuning = Config::default().tuning;
+        tuning.backoff_jitter = 0.0;
+        let delays = |tuning: &TuningConfig| -> Vec<u64> {
+            let backoff = Backoff::from_config(tuning);
+            (1..=5)
+                .map(|attempt| backoff.delay(attempt).as_millis() as u64)
+                .collect()
+        };
+
+        assert_eq!(delays(&tuning), [1000, 2000, 4000, 8000, 8000]);
+        tuning.backoff = BackoffStrategy::Linear;
+        assert_eq!(delays(&tuning), [1000, 2000, 3000, 4000, 5000]);
+        tuning.backoff = BackoffStrategy::Fixed;
+        tuning.backoff_base_ms = 250;
+        assert_eq!(delays(&tuning), [250; 5]);
+
+        // Throttling follows the server's hint, or backs off longer than usual without one
+        tuning.backoff = BackoffStrategy::Linear;
+        tuning.backoff_base_ms = 1000;
+        let backoff = Backoff::from_config(&tuning);
+        assert_eq!(
+            backoff.throttled_delay(2, Some(Duration::from_secs(30))),
+            Duration::from_secs(30)
+        );
+        assert_eq!(backoff.throttled_delay(2, None), Duration::from_secs(8));
+        assert_eq!(
+            backoff.throttled_delay(2, Some(Duration::from_millis(10))),
+            Duration::from_secs(2)
+        );
+
+        tuning.backoff = BackoffStrategy::Exponential;
+        tuning.backoff_base_ms = 250;
+        tuning.backoff_jitter = 0.2;
+        let backoff = Backoff::from_config(&tuning);
+        for _ in 0..20 {
+            let delay = backoff.delay(3).as_millis();
+            assert!((800..=1200).contains(&delay), "{}", delay);
+        }
+
+        // Jitter never pushes a capped delay past the cap
+        tuning.backoff_jitter = 1.0;
+        let backoff = Backoff::from_config(&tuning);
+        for _ in 0..20 {
+            assert!(backoff.delay(10) <= Duration::from_millis(tuning.backoff_cap_ms));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paused_gate_holds_batches_until_resumed() {
+        let (paused_tx, paused) = watch::channel(true);
+        let mut gate = BatchGate {
+            schedule: watch::channel(ScheduleState::default()).1,
+            speed_clock: Arc::new(std::sync::Mutex::new(Instant::now())),
+            paused,
+            completed_files: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+            total_files: 1,
+            concurrent_files: 1,
+            max_batches_per_file: 0,
+        };
+
+        let held = tokio::time::timeout(Duration::from_millis(50), gate.wait_unpaused()).await;
+        assert!(held.is_err());
+
+        paused_tx.send_replace(false);
+        tokio::time::timeout(Duration::from_secs(1), gate.wait_unpaused())
+            .await
+            .expect("resuming releases the batch");
+    }
+
+    #[test]
+    fn test_output_path_follows_first_matching_rule() {
+        use crate::config::OutputRule;
+
+        let mut config = Config::default();
+        config.download.dir = PathBuf::from("/downloads");
+        config.download.output_rules = vec![
+            OutputRule {
+                pattern: r"\.(srt|sub)$".to_string(),
+                path: PathBuf::from("Subs"),
+            },
+            OutputRule {
+                pattern: r"\.SRT$".to_string(),
+                path: PathBuf::from("Unused"),
+            },
+            OutputRule {
+                pattern: r"\.mkv$".to_string(),
+                path: PathBuf::from("Video/HD"),
+            },
+        ];
+
+        let path = |name: &str| Downloader::output_path(&config, name);
+        assert_eq!(
+            path("movie.EN.SRT"),
+            PathBuf::from("/downloads/Subs/movie.EN.SRT")
+        );
+        assert_eq!(
+            path("movie.mkv"),
+            PathBuf::from("/downloads/Video/HD/movie.mkv")
+        );
+        assert_eq!(path("movie.par2"), PathBuf::from("/downloads/movie.par2"));
+    }
+
+    #[test]
+    fn test_build_batches_contiguous() {
+        let batches = build_batches((1..=5).collect(), 2, SegmentOrder::Contiguous);
+        assert_eq!(batches, vec![vec![1, 2], vec![3, 4], vec![5]]);
+    }
+
+    #[test]
+    fn test_build_batches_interleaved() {
+        let batches = build_batches((1..=5).collect(), 2, SegmentOrder::Interleaved);
+        assert_eq!(batches, vec![vec![1, 4], vec![2, 5], vec![3]]);
+    }
+
+    #[test]
+    fn test_check_no_unrelated_files() {
+        let xml = r#"
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="p" date="0" subject="&quot;movie.mkv&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments><segment bytes="4" number="1">a@example.com</segment></segments>
+            </file>
+        </nzb>
+        "#;
+        let nzb: Nzb = xml.trim().parse().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Downloader::check_no_unrelated_files(&nzb, dir.path()).is_ok());
+
+        // The NZB's own files (e.g. from a resumed run) and hidden files are fine
+        std::fs::write(dir.path().join("movie.mkv"), b"12").unwrap();
+        std::fs::write(dir.path().join(".DS_Store"), b"").unwrap();
+        assert!(Downloader::check_no_unrelated_files(&nzb, dir.path()).is_ok());
+
+        std::fs::write(dir.path().join("other.mkv"), b"").unwrap();
+        std::fs::create_dir(dir.path().join("Other.Release")).unwrap();
+        let err = Downloader::check_no_unrelated_files(&nzb, dir.path()).unwrap_err();
+        assert!(matches!(
+            err,
+            DlNzbError::Download(DownloadError::UnrelatedFilesPresent { count: 2, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pending_files_lists_missing_and_short_files() {
+        let xml = r#"
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="p" date="0" subject="&quot;done.bin&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments><segment bytes="4" number="1">a@example.com</segment></segments>
+            </file>
+            <file poster="p" date="0" subject="&quot;short.bin&quot; yEnc (1/2)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments>
+                    <segment bytes="4" number="1">b@example.com</segment>
+                    <segment bytes="4" number="2">c@example.com</segment>
+                </segments>
+            </file>
+            <file poster="p" date="0" subject="&quot;missing.bin&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments><segment bytes="4" number="1">d@example.com</segment></segments>
+            </file>
+        </nzb>
+        "#;
+        let nzb: Nzb = xml.trim().parse().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.download.dir = dir.path().to_path_buf();
+        std::fs::write(dir.path().join("done.bin"), b"1234").unwrap();
+        std::fs::write(dir.path().join("short.bin"), b"1234").unwrap();
+
+        let pending = Downloader::pending_files(&nzb, &config).await.unwrap();
+        assert_eq!(pending, ["missing.bin", "short.bin"]);
+
+        std::fs::write(dir.path().join("short.bin"), b"12345678").unwrap();
+        std::fs::write(dir.path().join("missing.bin"), b"1234").unwrap();
+        assert!(Downloader::pending_files(&nzb, &config)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pending_files_follows_output_rules() {
+        use crate::config::OutputRule;
+
+        let xml = r#"
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="p" date="0" subject="&quot;movie.mkv&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments><segment bytes="4" number="1">a@example.com</segment></segments>
+            </file>
+            <file poster="p" date="0" subject="&quot;movie.srt&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments><segment bytes="4" number="1">b@example.com</segment></segments>
+            </file>
+        </nzb>
+        "#;
+        let nzb: Nzb = xml.trim().parse().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.download.dir = dir.path().to_path_buf();
+        config.download.output_rules = vec![OutputRule {
+            pattern: r"\.srt$".to_string(),
+            path: PathBuf::from("Subs"),
+        }];
+        std::fs::write(dir.path().join("movie.mkv"), b"1234").unwrap();
+        // Complete, but not where the rule puts it
+        std::fs::write(dir.path().join("movie.srt"), b"1234").unwrap();
+
+        let pending = Downloader::pending_files(&nzb, &config).await.unwrap();
+        assert_eq!(pending, ["movie.srt"]);
+
+        std::fs::create_dir(dir.path().join("Subs")).unwrap();
+        std::fs::write(dir.path().join("Subs").join("movie.srt"), b"1234").unwrap();
+        assert!(Downloader::pending_files(&nzb, &config)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_spill_file_returns_segments_out_of_order() {
+        let mut spill = SpillFile::new();
+        assert_eq!(spill.take(1).await.unwrap(), None);
+        // Nothing is written to disk until a segment needs spilling
+        assert!(spill.file.is_none());
+
+        spill.push(3, b"third").await.unwrap();
+        spill.push(2, b"second").await.unwrap();
+        assert_eq!(
+            spill.take(2).await.unwrap().as_deref(),
+            Some(&b"second"[..])
+        );
+        assert_eq!(spill.take(3).await.unwrap().as_deref(), Some(&b"third"[..]));
+
+        // Taken segments are forgotten
+        assert_eq!(spill.take(2).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_download_file_to_spills_segments_ahead_of_order() {
+        use super::super::nzb::{NzbGroup, NzbGroups, NzbSegment, NzbSegments};
+        use crate::nntp::mock::{Fault, MockServer};
+
+        let server = MockServer::start().await;
+        server.add_group("alt.binaries.test");
+        let bodies = [&b"one "[..], b"two ", b"three"];
+        for (i, body) in bodies.iter().enumerate() {
+            server.add_article(&format!("{}@test", i + 1), body);
+        }
+        // Segment 1 arrives last, so both later segments wait in the spill file
+        server.inject("1@test", Fault::Delay(Duration::from_millis(300)));
+
+        let mut config = Config::default();
+        config.usenet = server.config();
+        config.tuning.pipeline_size = 1;
+        config.memory.spill_to_temp = true;
+        let file = NzbFile {
+            poster: "poster@example.com".to_string(),
+            date: 0,
+            subject: "\"file.bin\" yEnc (1/3)".to_string(),
+            groups: NzbGroups {
+                group: vec![NzbGroup {
+                    name: "alt.binaries.test".to_string(),
+                }],
+            },
+            segments: NzbSegments {
+                segment: (1..=3u32)
+                    .rev()
+                    .map(|number| NzbSegment {
+                        bytes: bodies[number as usize - 1].len() as u64,
+                        number,
+                        message_id: format!("{}@test", number),
+                    })
+                    .collect(),
+            },
+        };
+
+        let downloader = Downloader::new(config.clone()).await.unwrap();
+        let mut output = Vec::new();
+        let mut spill = SpillFile::new();
+        let written = downloader
+            .stream_file_to(&file, &mut output, &config, &mut spill)
+            .await
+            .unwrap();
+        assert_eq!(written, 13);
+        assert_eq!(output, b"one two three");
+        assert_eq!(spill.len, (b"two ".len() + b"three".len()) as u64);
+    }
+
+    #[test]
+    fn test_wait_clock_counts_overlapping_waits_once() {
+        let mut clock = WaitClock::default();
+        assert_eq!(clock.elapsed(), Duration::ZERO);
+
+        // Three batches waiting side by side for ~30ms stall the file for ~30ms, not ~90ms
+        clock.start();
+        clock.start();
+        clock.start();
+        std::thread::sleep(Duration::from_millis(30));
+        clock.stop();
+        clock.stop();
+        clock.stop();
+        let stalled = clock.elapsed();
+        assert!(stalled >= Duration::from_millis(30), "{:?}", stalled);
+        assert!(stalled < Duration::from_millis(80), "{:?}", stalled);
+
+        // Nothing accrues while no batch is waiting
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(clock.elapsed(), stalled);
+    }
+
+    #[test]
+    fn test_pipeline_batches_follow_tuning() {
+        let mut tuning = TuningConfig {
+            pipeline_size: 2,
+            pipeline_bytes: 0,
+            ..TuningConfig::default()
+        };
+        let sizes = vec![300u64, 300, 500, 100];
+        assert_eq!(
+            pipeline_batches(sizes.clone(), |&n| n, &tuning, SegmentOrder::Contiguous),
+            vec![vec![300, 300], vec![500, 100]]
+        );
+
+        tuning.pipeline_bytes = 400;
+        assert_eq!(
+            pipeline_batches(sizes, |&n| n, &tuning, SegmentOrder::Contiguous),
+            vec![vec![300], vec![300], vec![500], vec![100]]
+        );
+    }
+
+    #[test]
+    fn test_build_batches_by_bytes() {
+        // Items are their own sizes
+        let sizes = vec![300u64, 300, 500, 100, 100, 100, 900];
+        let batches = build_batches_by_bytes(sizes.clone(), |&n| n, 600, SegmentOrder::Contiguous);
+        assert_eq!(
+            batches,
+            vec![vec![300, 300], vec![500, 100], vec![100, 100], vec![900]]
+        );
+
+        // Requests are dealt round-robin, skipping batches they would overfill
+        let batches = build_batches_by_bytes(sizes, |&n| n, 600, SegmentOrder::Interleaved);
+        assert_eq!(
+            batches,
+            vec![
+                vec![300, 100],
+                vec![300, 100],
+                vec![500],
+                vec![100],
+                vec![900]
+            ]
+        );
+    }
+}
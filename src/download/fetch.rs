@@ -0,0 +1,261 @@
+//! Fetching NZBs given by URL
+//!
+//! Indexers hand out NZBs as `http(s)://` links. They're fetched with reqwest over the
+//! native-tls stack the NNTP connections use, following redirects but never from https
+//! down to plain http, and saved under the name the server gives them, so the download
+//! folder is named as it would be for a local file.
+//!
+//! Indexer links carry the API key in their query string, so URLs in errors are redacted.
+
+use reqwest::header::{HeaderMap, CONTENT_DISPOSITION};
+use reqwest::{redirect, Url};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::{NzbError, Result};
+
+/// Redirects followed before giving up
+const MAX_REDIRECTS: usize = 5;
+/// Largest response accepted; real NZBs are a few MB at most
+const MAX_RESPONSE_BYTES: u64 = 64 * 1024 * 1024;
+/// Time allowed for the whole request, redirects and body included
+const FETCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Whether `path` is an `http://` or `https://` link rather than a file
+pub fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|p| p.starts_with("http://") || p.starts_with("https://"))
+}
+
+/// `url` without its credentials, query and fragment, safe to print or report
+pub fn redact_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut url) => {
+            url.set_query(None);
+            url.set_fragment(None);
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+            url.to_string()
+        }
+        Err(_) => url.split(['?', '#']).next().unwrap_or_default().to_string(),
+    }
+}
+
+/// Download the NZB at `url` into `dir`, returning where it was saved
+pub async fn fetch_nzb(url: &str, dir: &Path) -> Result<PathBuf> {
+    let fail = |reason: String| NzbError::Fetch {
+        url: redact_url(url),
+        reason,
+    };
+
+    let target = Url::parse(url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or_else(|| fail("not an http(s) URL".into()))?;
+
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("dl-nzb/", env!("CARGO_PKG_VERSION")))
+        .timeout(FETCH_TIMEOUT)
+        .redirect(redirect::Policy::custom(|attempt| {
+            match redirect_refusal(attempt.previous(), attempt.url()) {
+                Some(reason) => attempt.error(reason),
+                None => attempt.follow(),
+            }
+        }))
+        .build()
+        .map_err(|e| fail(describe(e)))?;
+
+    let mut response = client
+        .get(target)
+        .send()
+        .await
+        .map_err(|e| fail(describe(e)))?;
+    if !response.status().is_success() {
+        return Err(fail(format!("HTTP {}", response.status().as_u16())).into());
+    }
+    if response
+        .content_length()
+        .is_some_and(|len| len > MAX_RESPONSE_BYTES)
+    {
+        return Err(fail("response too large".into()).into());
+    }
+
+    let name = disposition_filename(response.headers())
+        .or_else(|| url_filename(response.url()))
+        .unwrap_or_else(|| "download.nzb".to_string());
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| fail(describe(e)))? {
+        body.extend_from_slice(&chunk);
+        if body.len() as u64 > MAX_RESPONSE_BYTES {
+            return Err(fail("response too large".into()).into());
+        }
+    }
+
+    tokio::fs::create_dir_all(dir).await?;
+    let path = dir.join(name);
+    tokio::fs::write(&path, &body).await?;
+    Ok(path)
+}
+
+/// Why a redirect to `next` must not be followed, if it mustn't
+fn redirect_refusal(previous: &[Url], next: &Url) -> Option<&'static str> {
+    if previous.len() > MAX_REDIRECTS {
+        Some("too many redirects")
+    } else if next.scheme() != "https" && previous.iter().any(|url| url.scheme() == "https") {
+        Some("refusing to follow a redirect from https to http")
+    } else {
+        None
+    }
+}
+
+/// A reqwest error's message and causes, without the URL it would otherwise include
+fn describe(e: reqwest::Error) -> String {
+    if e.is_timeout() {
+        return "timed out".to_string();
+    }
+    let e = e.without_url();
+    let mut reason = e.to_string();
+    let mut source = std::error::Error::source(&e);
+    while let Some(cause) = source {
+        reason.push_str(": ");
+        reason.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    reason
+}
+
+/// The file name from `Content-Disposition`, as indexers send it
+fn disposition_filename(headers: &HeaderMap) -> Option<String> {
+    let disposition = headers.get(CONTENT_DISPOSITION)?.to_str().ok()?;
+    let value = disposition
+        .split(';')
+        .map(str::trim)
+        .find_map(|part| part.strip_prefix("filename="))?;
+    sanitize(value.trim_matches('"'))
+}
+
+/// The last path segment of `url`, when it names an NZB
+fn url_filename(url: &Url) -> Option<String> {
+    let name = url.path_segments()?.next_back()?;
+    name.to_ascii_lowercase()
+        .ends_with(".nzb")
+        .then(|| sanitize(name))
+        .flatten()
+}
+
+/// A file name safe to join onto a directory, ending in `.nzb`
+fn sanitize(name: &str) -> Option<String> {
+    let name = Path::new(name).file_name()?.to_str()?;
+    if name.starts_with('.') {
+        return None;
+    }
+    Some(if name.to_ascii_lowercase().ends_with(".nzb") {
+        name.to_string()
+    } else {
+        format!("{}.nzb", name)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::DlNzbError;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answer one request per reply on a local port, returning the port
+    async fn serve(replies: Vec<String>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            for reply in replies {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0u8; 1024];
+                let _ = stream.read(&mut request).await.unwrap();
+                stream.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn test_redact_url_drops_api_key() {
+        assert_eq!(
+            redact_url("https://user:pw@indexer.example/api?t=get&apikey=secret#top"),
+            "https://indexer.example/api"
+        );
+        assert_eq!(
+            redact_url("http://[::1]:8080/get.nzb?apikey=secret"),
+            "http://[::1]:8080/get.nzb"
+        );
+    }
+
+    #[test]
+    fn test_redirects_never_downgrade_to_http() {
+        let url = |s: &str| Url::parse(s).unwrap();
+        let https = [url("https://indexer.example/api?id=1")];
+        let http = [url("http://indexer.example/api?id=1")];
+
+        assert!(redirect_refusal(&https, &url("https://cdn.example/x.nzb")).is_none());
+        assert!(redirect_refusal(&http, &url("https://cdn.example/x.nzb")).is_none());
+        assert!(redirect_refusal(&https, &url("http://cdn.example/x.nzb")).is_some());
+        let many = vec![url("http://indexer.example/"); MAX_REDIRECTS + 1];
+        assert!(redirect_refusal(&many, &url("http://indexer.example/")).is_some());
+    }
+
+    #[test]
+    fn test_filenames() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_DISPOSITION,
+            "attachment; filename=\"../Show.S01E01.nzb\""
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            disposition_filename(&headers).as_deref(),
+            Some("Show.S01E01.nzb")
+        );
+
+        let url = Url::parse("http://[::1]:8080/a/b.nzb?id=1").unwrap();
+        assert_eq!(url_filename(&url).as_deref(), Some("b.nzb"));
+        let url = Url::parse("https://indexer.example/api?t=get").unwrap();
+        assert_eq!(url_filename(&url), None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_follows_redirect_and_saves_nzb() {
+        let port = serve(vec![
+            "HTTP/1.1 302 Found\r\nLocation: /files/release.nzb\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+            "HTTP/1.1 200 OK\r\nContent-Length: 6\r\nConnection: close\r\n\r\n<nzb/>".to_string(),
+        ])
+        .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("http://127.0.0.1:{}/api?id=1", port);
+        let path = fetch_nzb(&url, dir.path()).await.unwrap();
+
+        assert_eq!(path, dir.path().join("release.nzb"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"<nzb/>");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_error_hides_api_key() {
+        let port = serve(vec![
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+        ])
+        .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("http://127.0.0.1:{}/api?t=get&apikey=secret", port);
+        let err = fetch_nzb(&url, dir.path()).await.unwrap_err();
+
+        let DlNzbError::Nzb(NzbError::Fetch { url, reason }) = &err else {
+            panic!("unexpected error: {}", err);
+        };
+        assert_eq!(url, &format!("http://127.0.0.1:{}/api", port));
+        assert_eq!(reason, "HTTP 404");
+        assert!(!err.to_string().contains("secret"));
+    }
+}
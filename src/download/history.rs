@@ -0,0 +1,61 @@
+//! Record of processed NZBs, keyed by content hash
+//!
+//! Stored as a plain text file with one hash per line next to the config file.
+
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::error::DlNzbError;
+
+type Result<T> = std::result::Result<T, DlNzbError>;
+
+/// Hashes of NZBs that have already been downloaded successfully
+pub struct NzbHistory {
+    path: PathBuf,
+    hashes: HashSet<String>,
+}
+
+impl NzbHistory {
+    /// Load the history file, treating a missing file as empty
+    pub fn load(path: &Path) -> Result<Self> {
+        let hashes = match std::fs::read_to_string(path) {
+            Ok(content) => content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(String::from)
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashSet::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            hashes,
+        })
+    }
+
+    /// Check whether an NZB with this hash was processed before
+    pub fn contains(&self, hash: &str) -> bool {
+        self.hashes.contains(hash)
+    }
+
+    /// Remember a processed NZB, appending it to the history file
+    pub fn record(&mut self, hash: &str) -> Result<()> {
+        if !self.hashes.insert(hash.to_string()) {
+            return Ok(());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", hash)?;
+        Ok(())
+    }
+}
@@ -4,7 +4,13 @@
 //! segment downloading, and file assembly.
 
 mod downloader;
+mod fetch;
+mod history;
 mod nzb;
+mod retention;
 
 pub use downloader::{DownloadResult, Downloader};
-pub use nzb::Nzb;
+pub use fetch::{fetch_nzb, is_url, redact_url};
+pub use history::NzbHistory;
+pub use nzb::{FileFilter, Nzb, NzbFile, NzbStats, RepairEstimate};
+pub use retention::{RetentionEstimate, RetentionLog, RetentionObservation};
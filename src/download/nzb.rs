@@ -1,9 +1,15 @@
 pub use nzb_rs::Nzb as NzbRs;
+use nzb_rs::ParseNzbError;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::path::Path;
 use std::str::FromStr;
 
+use crate::config::{parse_post_date, DownloadConfig};
 use crate::error::{DlNzbError, NzbError};
+use crate::patterns::par2 as par2_patterns;
+use crate::processing::is_probably_obfuscated;
 
 type Result<T> = std::result::Result<T, DlNzbError>;
 
@@ -39,6 +45,137 @@ pub struct NzbSegments {
     pub segment: Vec<NzbSegment>,
 }
 
+/// Segment numbers listed per kind of numbering issue before the rest are only counted
+const SHOWN_SEGMENT_NUMBERS: usize = 10;
+
+impl NzbFile {
+    /// Describe gaps and duplicates when the segment numbers aren't exactly 1..N
+    ///
+    /// A gap means the NZB itself is missing an article, so the assembled file will have
+    /// a hole no matter how the download goes.
+    pub fn segment_numbering_issue(&self) -> Option<String> {
+        let mut numbers: Vec<u32> = self.segments.segment.iter().map(|s| s.number).collect();
+        numbers.sort_unstable();
+
+        // Gaps can span billions of numbers in a hostile NZB, so only the first few are kept
+        let mut missing = Vec::new();
+        let mut missing_total = 0u64;
+        let mut duplicates = Vec::new();
+        let mut expected = 1u64;
+        for (i, &number) in numbers.iter().enumerate() {
+            if i > 0 && number == numbers[i - 1] {
+                duplicates.push(number);
+                continue;
+            }
+            let number = u64::from(number);
+            if number > expected {
+                missing_total += number - expected;
+                let room = SHOWN_SEGMENT_NUMBERS.saturating_sub(missing.len());
+                missing.extend((expected..number).take(room).map(|n| n as u32));
+            }
+            expected = number + 1;
+        }
+
+        let list = |numbers: &[u32], total: u64| {
+            let shown: Vec<String> = numbers
+                .iter()
+                .take(SHOWN_SEGMENT_NUMBERS)
+                .map(u32::to_string)
+                .collect();
+            if total > shown.len() as u64 {
+                format!("{}, ... ({} total)", shown.join(", "), total)
+            } else {
+                shown.join(", ")
+            }
+        };
+
+        let mut issues = Vec::new();
+        if !missing.is_empty() {
+            issues.push(format!(
+                "missing segment(s) {}",
+                list(&missing, missing_total)
+            ));
+        }
+        if !duplicates.is_empty() {
+            issues.push(format!(
+                "duplicate segment(s) {}",
+                list(&duplicates, duplicates.len() as u64)
+            ));
+        }
+        (!issues.is_empty()).then(|| issues.join("; "))
+    }
+}
+
+/// Picks files out of an NZB by poster and post date
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    /// Keep files whose poster contains one of these (case-insensitive); all when empty
+    pub posters: Vec<String>,
+    /// Keep files posted at or after this Unix time
+    pub posted_after: Option<u64>,
+    /// Keep files posted before this Unix time
+    pub posted_before: Option<u64>,
+}
+
+impl FileFilter {
+    /// Build the filter from `posters`, `posted_after` and `posted_before`
+    pub fn from_config(config: &DownloadConfig) -> Self {
+        Self {
+            posters: config.posters.iter().map(|p| p.to_lowercase()).collect(),
+            posted_after: config.posted_after.as_deref().and_then(parse_post_date),
+            posted_before: config.posted_before.as_deref().and_then(parse_post_date),
+        }
+    }
+
+    pub fn matches(&self, file: &NzbFile) -> bool {
+        let poster = file.poster.to_lowercase();
+        (self.posters.is_empty() || self.posters.iter().any(|p| poster.contains(p.as_str())))
+            && self.posted_after.map_or(true, |after| file.date >= after)
+            && self.posted_before.map_or(true, |before| file.date < before)
+    }
+}
+
+/// Summary of an NZB computed from its contents alone, without touching the network
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NzbStats {
+    pub total_files: usize,
+    pub total_bytes: u64,
+    pub total_segments: usize,
+    /// Segments missing from gaps in each file's segment numbering
+    pub missing_segments: usize,
+    pub par2_files: usize,
+    pub par2_bytes: u64,
+    /// PAR2 bytes as a fraction of non-PAR2 bytes (0.05 = 5% recovery)
+    pub par2_ratio: f64,
+    /// Recovery blocks named by the PAR2 volumes as a percentage of the data they protect;
+    /// unlike `par2_ratio` this leaves out index files and packet overhead. `None` when no
+    /// volume names carry a block count and the file names look obfuscated
+    pub recovery_percent: Option<f64>,
+    /// Number of files posted to each group
+    pub groups: BTreeMap<String, usize>,
+    /// Age of the oldest post in seconds
+    pub post_age_seconds: u64,
+}
+
+/// Whether PAR2 recovery data can make up for articles the server is missing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepairEstimate {
+    /// Missing articles of data files; missing PAR2 articles only reduce `recovery_blocks`
+    pub missing_articles: usize,
+    pub missing_bytes: u64,
+    /// PAR2 block size, estimated from volume sizes and the block counts in their names
+    pub block_size: u64,
+    /// Recovery blocks in the volumes, less those in missing volume articles
+    pub recovery_blocks: u64,
+    /// Blocks damaged by the missing articles
+    pub blocks_needed: u64,
+    pub repairable: bool,
+    /// Recovery blocks lacking for a repair, 0 when repairable
+    pub short_by: u64,
+    /// "complete", "repairable" or "not repairable (short by N blocks)"
+    pub verdict: String,
+}
+
 // Wrapper struct that provides the same interface as before
 #[derive(Debug, Clone)]
 pub struct Nzb {
@@ -48,13 +185,25 @@ pub struct Nzb {
 
 impl Nzb {
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = std::fs::read_to_string(path)?;
-        content.parse()
+        let bytes = std::fs::read(path)?;
+        decode_text(&bytes).parse()
     }
 
     fn parse_content(content: &str) -> Result<Self> {
-        let inner = NzbRs::parse(content)
-            .map_err(|e| NzbError::ParseError(format!("Failed to parse NZB: {}", e)))?;
+        let inner = match NzbRs::parse(content) {
+            Ok(inner) => inner,
+            // nzb-rs rejects the whole NZB when a single file lists no groups; read it
+            // directly so the other files stay usable, leaving those without groups to be
+            // reported at download time
+            Err(ParseNzbError::GroupsElement) => {
+                return Ok(Nzb {
+                    files: parse_files(content)?,
+                });
+            }
+            Err(e) => {
+                return Err(NzbError::ParseError(format!("Failed to parse NZB: {}", e)).into())
+            }
+        };
 
         // Convert nzb-rs structures to our compatible structures
         let files = inner
@@ -111,6 +260,257 @@ impl Nzb {
             .sum()
     }
 
+    /// Compute summary statistics, e.g. to filter NZBs before queuing them
+    pub fn stats(&self) -> NzbStats {
+        let mut missing_segments = 0;
+        let mut par2_files = 0;
+        let mut par2_bytes = 0;
+        let mut groups: BTreeMap<String, usize> = BTreeMap::new();
+
+        for file in &self.files {
+            let numbers: HashSet<u32> = file.segments.segment.iter().map(|s| s.number).collect();
+            let highest = numbers.iter().copied().max().unwrap_or(0) as usize;
+            missing_segments += highest.saturating_sub(numbers.len());
+
+            let is_par2 = Self::get_filename_from_subject(&file.subject)
+                .is_some_and(|name| name.to_lowercase().ends_with(".par2"));
+            if is_par2 {
+                par2_files += 1;
+                par2_bytes += file.segments.segment.iter().map(|s| s.bytes).sum::<u64>();
+            }
+
+            let file_groups: HashSet<&str> =
+                file.groups.group.iter().map(|g| g.name.as_str()).collect();
+            for group in file_groups {
+                *groups.entry(group.to_string()).or_default() += 1;
+            }
+        }
+
+        let total_bytes = self.total_size();
+        let data_bytes = total_bytes - par2_bytes;
+        let par2_ratio = if data_bytes > 0 {
+            par2_bytes as f64 / data_bytes as f64
+        } else {
+            0.0
+        };
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let oldest = self.files.iter().map(|f| f.date).min().unwrap_or(now);
+
+        NzbStats {
+            total_files: self.files.len(),
+            total_bytes,
+            total_segments: self.total_segments(),
+            missing_segments,
+            par2_files,
+            par2_bytes,
+            par2_ratio,
+            recovery_percent: self.recovery_percent(),
+            groups,
+            post_age_seconds: now.saturating_sub(oldest),
+        }
+    }
+
+    /// PAR2 volumes with the recovery block count in their names, and the block size
+    /// estimated from the volume with the most blocks (0 without volumes)
+    fn recovery_volumes(&self) -> (Vec<(&NzbFile, u64)>, u64) {
+        let volumes: Vec<(&NzbFile, u64)> = self
+            .files
+            .iter()
+            .filter_map(|file| {
+                let name = Self::get_filename_from_subject(&file.subject)?;
+                par2_patterns::recovery_block_count(&name).map(|blocks| (file, blocks as u64))
+            })
+            .filter(|(_, blocks)| *blocks > 0)
+            .collect();
+
+        let block_size = volumes
+            .iter()
+            .max_by_key(|(_, blocks)| *blocks)
+            .map(|(file, blocks)| file_size(file) / blocks)
+            .unwrap_or(0);
+
+        (volumes, block_size)
+    }
+
+    /// Recovery data as a percentage of the data files (10.0 = 10%), from the block counts
+    /// in the PAR2 volume names
+    ///
+    /// Without such volumes it is 0 when every file name is readable, as nothing could be
+    /// a volume in disguise. `None` when some names look obfuscated: they may hide volumes,
+    /// so the amount of recovery data is unknown rather than zero.
+    pub fn recovery_percent(&self) -> Option<f64> {
+        let (volumes, block_size) = self.recovery_volumes();
+        if volumes.is_empty() {
+            let readable = self.files.iter().all(|file| {
+                Self::get_filename_from_subject(&file.subject)
+                    .is_some_and(|name| !is_probably_obfuscated(&name))
+            });
+            return readable.then_some(0.0);
+        }
+        let recovery_blocks: u64 = volumes.iter().map(|(_, blocks)| blocks).sum();
+        let data_bytes: u64 = self
+            .files
+            .iter()
+            .filter(|file| {
+                !Self::get_filename_from_subject(&file.subject)
+                    .is_some_and(|name| name.to_lowercase().ends_with(".par2"))
+            })
+            .map(file_size)
+            .sum();
+
+        if data_bytes == 0 {
+            return Some(0.0);
+        }
+        Some((recovery_blocks * block_size) as f64 / data_bytes as f64 * 100.0)
+    }
+
+    /// Estimate whether PAR2 can repair the data lost to the `missing` message-ids
+    ///
+    /// The block size comes from the volume with the most blocks, where per-packet overhead
+    /// matters least. Each missing article damages the blocks it covers plus one it straddles,
+    /// up to its file's block count, and a volume loses recovery blocks in proportion to its
+    /// missing bytes. This works from NZB sizes alone, so it is an estimate, not a PAR2 verify.
+    pub fn repair_estimate(&self, missing: &HashSet<String>) -> RepairEstimate {
+        let lost_bytes = |file: &NzbFile| -> Vec<u64> {
+            file.segments
+                .segment
+                .iter()
+                .filter(|segment| missing.contains(&segment.message_id))
+                .map(|segment| segment.bytes)
+                .collect()
+        };
+
+        let (volumes, block_size) = self.recovery_volumes();
+
+        let mut recovery_blocks = 0;
+        for (file, blocks) in &volumes {
+            let total = file_size(file);
+            let lost: u64 = lost_bytes(file).iter().sum();
+            recovery_blocks += (blocks * total.saturating_sub(lost))
+                .checked_div(total)
+                .unwrap_or(0);
+        }
+
+        let mut missing_articles = 0;
+        let mut missing_bytes = 0;
+        let mut blocks_needed = 0;
+        for file in &self.files {
+            let is_par2 = Self::get_filename_from_subject(&file.subject)
+                .is_some_and(|name| name.to_lowercase().ends_with(".par2"));
+            let lost = lost_bytes(file);
+            if is_par2 || lost.is_empty() {
+                continue;
+            }
+
+            missing_articles += lost.len();
+            missing_bytes += lost.iter().sum::<u64>();
+            blocks_needed += if block_size > 0 {
+                let damaged: u64 = lost
+                    .iter()
+                    .map(|bytes| bytes.div_ceil(block_size) + 1)
+                    .sum();
+                damaged.min(file_size(file).div_ceil(block_size))
+            } else {
+                lost.len() as u64
+            };
+        }
+
+        let short_by = blocks_needed.saturating_sub(recovery_blocks);
+        let verdict = if missing_articles == 0 {
+            "complete".to_string()
+        } else if short_by == 0 {
+            "repairable".to_string()
+        } else {
+            format!("not repairable (short by {} blocks)", short_by)
+        };
+
+        RepairEstimate {
+            missing_articles,
+            missing_bytes,
+            block_size,
+            recovery_blocks,
+            blocks_needed,
+            repairable: short_by == 0,
+            short_by,
+            verdict,
+        }
+    }
+
+    /// Stable hash of the NZB's content, used to detect duplicate submissions
+    ///
+    /// Only the sorted, de-duplicated message-ids are hashed (64-bit FNV-1a), so renamed NZBs,
+    /// reordered files and cosmetic XML differences produce the same hash.
+    pub fn content_hash(&self) -> String {
+        const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        let mut ids: Vec<&str> = self
+            .files
+            .iter()
+            .flat_map(|file| &file.segments.segment)
+            .map(|segment| segment.message_id.trim())
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+
+        let mut hash = FNV_OFFSET;
+        for id in ids {
+            for &byte in id.as_bytes().iter().chain(b"\n") {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+        }
+
+        format!("{:016x}", hash)
+    }
+
+    /// Output filename for each file, in NZB order
+    ///
+    /// Files whose subjects resolve to the same name get a numeric suffix (`name_1.ext`)
+    /// so a later file doesn't overwrite an earlier one.
+    pub fn unique_filenames(&self) -> Vec<String> {
+        let names: Vec<String> = self
+            .files
+            .iter()
+            .map(|file| {
+                Self::get_filename_from_subject(&file.subject)
+                    .unwrap_or_else(|| format!("unknown_file_{}", file.date))
+            })
+            .collect();
+
+        // Reserve every original name up front so a suffixed name can't take another file's name
+        let reserved: HashSet<&str> = names.iter().map(String::as_str).collect();
+        let mut used: HashSet<String> = HashSet::with_capacity(names.len());
+
+        names
+            .iter()
+            .map(|name| {
+                if used.insert(name.clone()) {
+                    return name.clone();
+                }
+
+                let path = Path::new(name);
+                let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name);
+                let ext = path.extension().and_then(|s| s.to_str());
+                let unique = (1..)
+                    .map(|i| match ext {
+                        Some(ext) => format!("{}_{}.{}", stem, i, ext),
+                        None => format!("{}_{}", stem, i),
+                    })
+                    .find(|candidate| {
+                        !reserved.contains(candidate.as_str()) && !used.contains(candidate)
+                    })
+                    .expect("unbounded suffix search always finds a free name");
+                used.insert(unique.clone());
+                unique
+            })
+            .collect()
+    }
+
     pub fn get_filename_from_subject(subject: &str) -> Option<String> {
         // Extract filename from subject line like: [1/9] - "filename.ext" yEnc (1/5202)
         // Handle both regular quotes and HTML entities (&quot;)
@@ -121,6 +521,87 @@ impl Nzb {
     }
 }
 
+/// Read the files of an NZB that nzb-rs refused because some of them list no groups
+///
+/// Keeps what nzb-rs would: groups sorted without duplicates and segments in number order,
+/// skipping segments without a size, number or message ID.
+fn parse_files(content: &str) -> Result<Vec<NzbFile>> {
+    let parse_error =
+        |reason: &str| NzbError::ParseError(format!("Failed to parse NZB: {}", reason));
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..roxmltree::ParsingOptions::default()
+    };
+    let doc = roxmltree::Document::parse_with_options(content, options)
+        .map_err(|e| parse_error(&e.to_string()))?;
+
+    let mut files = Vec::new();
+    for file in elements(doc.root_element(), "file") {
+        let groups: BTreeSet<&str> = elements(file, "group")
+            .filter_map(|group| group.text())
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect();
+        let mut segments: Vec<NzbSegment> = elements(file, "segment")
+            .filter_map(|segment| {
+                Some(NzbSegment {
+                    bytes: segment.attribute("bytes")?.parse().ok()?,
+                    number: segment.attribute("number")?.parse().ok()?,
+                    message_id: segment.text()?.trim().to_string(),
+                })
+            })
+            .filter(|segment| !segment.message_id.is_empty())
+            .collect();
+        if segments.is_empty() {
+            return Err(parse_error("a file lists no segments").into());
+        }
+        segments.sort_by_key(|segment| segment.number);
+
+        files.push(NzbFile {
+            poster: file.attribute("poster").unwrap_or_default().to_string(),
+            date: file
+                .attribute("date")
+                .and_then(|date| date.parse().ok())
+                .unwrap_or(0),
+            subject: file.attribute("subject").unwrap_or_default().to_string(),
+            groups: NzbGroups {
+                group: groups
+                    .into_iter()
+                    .map(|name| NzbGroup {
+                        name: name.to_string(),
+                    })
+                    .collect(),
+            },
+            segments: NzbSegments { segment: segments },
+        });
+    }
+
+    if files.is_empty() {
+        return Err(parse_error("no files listed").into());
+    }
+    Ok(files)
+}
+
+/// Elements below `node` named `name`, whatever their namespace
+fn elements<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    node.descendants()
+        .filter(move |n| n.is_element() && n.tag_name().name() == name)
+}
+
+/// Decode NZB bytes as UTF-8, falling back to latin-1
+///
+/// Older posts often carry latin-1 subjects; reading those as UTF-8 either fails or mangles
+/// accented filenames. Every byte is a valid latin-1 character, so the fallback can't fail.
+pub(crate) fn decode_text(bytes: &[u8]) -> Cow<'_, str> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Cow::Borrowed(text),
+        Err(_) => Cow::Owned(bytes.iter().map(|&b| b as char).collect()),
+    }
+}
+
 impl FromStr for Nzb {
     type Err = DlNzbError;
 
@@ -129,6 +610,11 @@ impl FromStr for Nzb {
     }
 }
 
+/// Total bytes of a file's segments
+fn file_size(file: &NzbFile) -> u64 {
+    file.segments.segment.iter().map(|s| s.bytes).sum()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -171,4 +657,333 @@ mod tests {
         println!("Meta title: {:?}", nzb_rs.meta.title);
         println!("Meta category: {:?}", nzb_rs.meta.category);
     }
+
+    #[test]
+    fn test_file_without_groups_parses() {
+        let xml = r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="test@example.com" date="1234567890" subject="&quot;a.bin&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments><segment bytes="1024" number="1">a@example.com</segment></segments>
+            </file>
+            <file poster="test@example.com" date="1234567890" subject="&quot;b.bin&quot; yEnc (1/1)">
+                <groups></groups>
+                <segments><segment bytes="2048" number="1">b@example.com</segment></segments>
+            </file>
+        </nzb>
+        "#;
+
+        let nzb: Nzb = xml.trim().parse().unwrap();
+        let files = nzb.files();
+        assert_eq!(files.len(), 2);
+        let groups = |file: &NzbFile| {
+            file.groups
+                .group
+                .iter()
+                .map(|g| g.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(groups(&files[0]), vec!["alt.binaries.test"]);
+        assert!(groups(&files[1]).is_empty());
+        assert_eq!(nzb.total_size(), 3072);
+    }
+
+    #[test]
+    fn test_unique_filenames_disambiguates_duplicates() {
+        let xml = r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="test@example.com" date="1234567890" subject="[1/3] - &quot;disc.iso&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments><segment bytes="1024" number="1">a@example.com</segment></segments>
+            </file>
+            <file poster="test@example.com" date="1234567890" subject="[2/3] - &quot;disc.iso&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments><segment bytes="1024" number="1">b@example.com</segment></segments>
+            </file>
+            <file poster="test@example.com" date="1234567890" subject="[3/3] - &quot;disc_1.iso&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments><segment bytes="1024" number="1">c@example.com</segment></segments>
+            </file>
+        </nzb>
+        "#;
+
+        let nzb: Nzb = xml.trim().parse().unwrap();
+        assert_eq!(
+            nzb.unique_filenames(),
+            vec!["disc.iso", "disc_2.iso", "disc_1.iso"]
+        );
+    }
+
+    #[test]
+    fn test_content_hash_ignores_file_order_and_metadata() {
+        let first = r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <head><meta type="title">First</meta></head>
+            <file poster="a@example.com" date="1234567890" subject="&quot;a.bin&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments><segment bytes="1024" number="1">a@example.com</segment></segments>
+            </file>
+            <file poster="a@example.com" date="1234567890" subject="&quot;b.bin&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments><segment bytes="1024" number="1">b@example.com</segment></segments>
+            </file>
+        </nzb>
+        "#;
+        let reordered = r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="other@example.com" date="1234567899" subject="&quot;b.bin&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.other</group></groups>
+                <segments><segment bytes="1024" number="1">b@example.com</segment></segments>
+            </file>
+            <file poster="other@example.com" date="1234567899" subject="&quot;a.bin&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.other</group></groups>
+                <segments><segment bytes="1024" number="1">a@example.com</segment></segments>
+            </file>
+        </nzb>
+        "#;
+        let different = first.replace("b@example.com", "c@example.com");
+
+        let first: Nzb = first.trim().parse().unwrap();
+        let reordered: Nzb = reordered.trim().parse().unwrap();
+        let different: Nzb = different.trim().parse().unwrap();
+
+        assert_eq!(first.content_hash(), reordered.content_hash());
+        assert_ne!(first.content_hash(), different.content_hash());
+    }
+
+    #[test]
+    fn test_latin1_subject_falls_back() {
+        let xml = "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>
+        <nzb xmlns=\"http://www.newzbin.com/DTD/2003/nzb\">
+            <file poster=\"test@example.com\" date=\"1234567890\" subject=\"&quot;Caf\u{e9} Cr\u{e8}me.mkv&quot; yEnc (1/1)\">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments><segment bytes=\"1024\" number=\"1\">a@example.com</segment></segments>
+            </file>
+        </nzb>";
+        // Encode as latin-1: every char here is below U+0100
+        let latin1: Vec<u8> = xml.chars().map(|c| c as u8).collect();
+        assert!(std::str::from_utf8(&latin1).is_err());
+
+        let nzb: Nzb = decode_text(&latin1).parse().unwrap();
+        assert_eq!(nzb.unique_filenames(), vec!["Caf\u{e9} Cr\u{e8}me.mkv"]);
+
+        // Valid UTF-8 is used as-is
+        assert_eq!(decode_text("Caf\u{e9}".as_bytes()), "Caf\u{e9}");
+    }
+
+    #[test]
+    fn test_stats() {
+        let xml = r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="test@example.com" date="1234567890" subject="&quot;movie.mkv&quot; yEnc (1/3)">
+                <groups><group>alt.binaries.a</group><group>alt.binaries.b</group></groups>
+                <segments>
+                    <segment bytes="1000" number="1">a1@example.com</segment>
+                    <segment bytes="1000" number="3">a3@example.com</segment>
+                </segments>
+            </file>
+            <file poster="test@example.com" date="1234567899" subject="&quot;movie.vol0+1.par2&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.a</group></groups>
+                <segments><segment bytes="100" number="1">p1@example.com</segment></segments>
+            </file>
+        </nzb>
+        "#;
+
+        let nzb: Nzb = xml.trim().parse().unwrap();
+        let stats = nzb.stats();
+
+        assert_eq!(stats.total_files, 2);
+        assert_eq!(stats.total_bytes, 2100);
+        assert_eq!(stats.total_segments, 3);
+        assert_eq!(stats.missing_segments, 1);
+        assert_eq!(stats.par2_files, 1);
+        assert_eq!(stats.par2_bytes, 100);
+        assert!((stats.par2_ratio - 0.05).abs() < f64::EPSILON);
+        assert_eq!(stats.groups.get("alt.binaries.a"), Some(&2));
+        assert_eq!(stats.groups.get("alt.binaries.b"), Some(&1));
+        assert!(stats.post_age_seconds > 0);
+    }
+
+    #[test]
+    fn test_recovery_percent_unknown_for_obfuscated_volumes() {
+        let xml = r#"
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="p" date="0" subject="&quot;a8f3e1c90b.bin&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.a</group></groups>
+                <segments><segment bytes="1000" number="1">a1@example.com</segment></segments>
+            </file>
+            <file poster="p" date="0" subject="&quot;5d2e8a77f4.bin&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.a</group></groups>
+                <segments><segment bytes="100" number="1">p1@example.com</segment></segments>
+            </file>
+        </nzb>
+        "#;
+        let nzb: Nzb = xml.trim().parse().unwrap();
+        assert_eq!(nzb.recovery_percent(), None);
+        assert_eq!(nzb.stats().recovery_percent, None);
+    }
+
+    #[test]
+    fn test_recovery_percent_zero_without_par2() {
+        let xml = r#"
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="p" date="0" subject="&quot;Great.Movie.2023.mkv&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.a</group></groups>
+                <segments><segment bytes="1000" number="1">a1@example.com</segment></segments>
+            </file>
+            <file poster="p" date="0" subject="&quot;Great.Movie.2023.nfo&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.a</group></groups>
+                <segments><segment bytes="100" number="1">n1@example.com</segment></segments>
+            </file>
+        </nzb>
+        "#;
+        let nzb: Nzb = xml.trim().parse().unwrap();
+        assert_eq!(nzb.recovery_percent(), Some(0.0));
+    }
+
+    #[test]
+    fn test_repair_estimate() {
+        let xml = r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="test@example.com" date="1234567890" subject="&quot;movie.mkv&quot; yEnc (1/3)">
+                <groups><group>alt.binaries.a</group></groups>
+                <segments>
+                    <segment bytes="1000" number="1">a1@example.com</segment>
+                    <segment bytes="1000" number="2">a2@example.com</segment>
+                    <segment bytes="1000" number="3">a3@example.com</segment>
+                </segments>
+            </file>
+            <file poster="test@example.com" date="1234567890" subject="&quot;movie.vol00+02.par2&quot; yEnc (1/2)">
+                <groups><group>alt.binaries.a</group></groups>
+                <segments>
+                    <segment bytes="1000" number="1">p1@example.com</segment>
+                    <segment bytes="1000" number="2">p2@example.com</segment>
+                </segments>
+            </file>
+        </nzb>
+        "#;
+        let nzb: Nzb = xml.trim().parse().unwrap();
+        let missing = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect();
+
+        // Two 1000-byte recovery blocks for 3000 bytes of data
+        assert!((nzb.recovery_percent().unwrap() - 200.0 / 3.0).abs() < 1e-9);
+
+        let complete = nzb.repair_estimate(&missing(&[]));
+        assert!(complete.repairable);
+        assert_eq!(complete.verdict, "complete");
+
+        let estimate = nzb.repair_estimate(&missing(&["a2@example.com"]));
+        assert_eq!(estimate.block_size, 1000);
+        assert_eq!(estimate.recovery_blocks, 2);
+        assert_eq!(estimate.blocks_needed, 2);
+        assert_eq!(estimate.verdict, "repairable");
+
+        let estimate = nzb.repair_estimate(&missing(&["a2@example.com", "p1@example.com"]));
+        assert_eq!(estimate.missing_articles, 1);
+        assert_eq!(estimate.recovery_blocks, 1);
+        assert!(!estimate.repairable);
+        assert_eq!(estimate.verdict, "not repairable (short by 1 blocks)");
+    }
+
+    #[test]
+    fn test_segment_numbering_issue() {
+        let xml = r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="test@example.com" date="1234567890" subject="&quot;gap.bin&quot; yEnc (1/4)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments>
+                    <segment bytes="1000" number="1">g1@example.com</segment>
+                    <segment bytes="1000" number="2">g2@example.com</segment>
+                    <segment bytes="1000" number="4">g4@example.com</segment>
+                </segments>
+            </file>
+            <file poster="test@example.com" date="1234567890" subject="&quot;whole.bin&quot; yEnc (1/2)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments>
+                    <segment bytes="1000" number="2">w2@example.com</segment>
+                    <segment bytes="1000" number="1">w1@example.com</segment>
+                </segments>
+            </file>
+        </nzb>
+        "#;
+
+        let nzb: Nzb = xml.trim().parse().unwrap();
+        let gapped = nzb
+            .files()
+            .iter()
+            .find(|f| f.subject.contains("gap.bin"))
+            .unwrap();
+        let whole = nzb
+            .files()
+            .iter()
+            .find(|f| f.subject.contains("whole.bin"))
+            .unwrap();
+
+        assert_eq!(
+            gapped.segment_numbering_issue().as_deref(),
+            Some("missing segment(s) 3")
+        );
+        assert_eq!(whole.segment_numbering_issue(), None);
+
+        // Huge gaps are counted, not listed, and the top segment number doesn't overflow
+        let mut huge = whole.clone();
+        huge.segments.segment[0].number = u32::MAX;
+        assert_eq!(
+            huge.segment_numbering_issue().as_deref(),
+            Some("missing segment(s) 1, 3, 4, 5, 6, 7, 8, 9, 10, 11, ... (4294967293 total)")
+        );
+    }
+
+    #[test]
+    fn test_file_filter_by_poster_and_date() {
+        // 1700000000 = 2023-11-14, 1710000000 = 2024-03-09
+        let xml = r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="Alice &lt;alice@example.com&gt;" date="1700000000" subject="&quot;a.bin&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments><segment bytes="100" number="1">a1@example.com</segment></segments>
+            </file>
+            <file poster="Bob &lt;bob@example.com&gt;" date="1710000000" subject="&quot;b.bin&quot; yEnc (1/1)">
+                <groups><group>alt.binaries.test</group></groups>
+                <segments><segment bytes="100" number="1">b1@example.com</segment></segments>
+            </file>
+        </nzb>
+        "#;
+        let nzb: Nzb = xml.trim().parse().unwrap();
+        let kept = |filter: &FileFilter| -> Vec<String> {
+            nzb.files()
+                .iter()
+                .filter(|f| filter.matches(f))
+                .filter_map(|f| Nzb::get_filename_from_subject(&f.subject))
+                .collect()
+        };
+
+        assert_eq!(kept(&FileFilter::default()), vec!["a.bin", "b.bin"]);
+
+        let config = DownloadConfig {
+            posters: vec!["ALICE".to_string()],
+            ..DownloadConfig::default()
+        };
+        assert_eq!(kept(&FileFilter::from_config(&config)), vec!["a.bin"]);
+
+        let config = DownloadConfig {
+            posted_after: Some("2024-01-01".to_string()),
+            ..DownloadConfig::default()
+        };
+        assert_eq!(kept(&FileFilter::from_config(&config)), vec!["b.bin"]);
+
+        let config = DownloadConfig {
+            posted_before: Some("2024-01-01".to_string()),
+            ..DownloadConfig::default()
+        };
+        assert_eq!(kept(&FileFilter::from_config(&config)), vec!["a.bin"]);
+    }
 }
@@ -0,0 +1,275 @@
+//! Provider retention estimated from past downloads
+//!
+//! Stored as a plain text file next to the config file, one line per download:
+//! the server, the group, how many days old its posts were, and how many of its
+//! segments the server no longer had. Providers keep articles for different lengths
+//! of time, so each server's downloads only inform its own estimate.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use super::{DownloadResult, Nzb};
+use crate::error::DlNzbError;
+use crate::nntp::SegmentFailure;
+
+type Result<T> = std::result::Result<T, DlNzbError>;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// What one download says about how long the server keeps articles in a group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionObservation {
+    pub server: String,
+    pub group: String,
+    pub age_days: u64,
+    pub segments: usize,
+    pub missing: usize,
+}
+
+impl RetentionObservation {
+    /// Summarize a finished download from `server`, if the NZB dates its posts
+    ///
+    /// Only articles the server reported as gone count as missing; timeouts and damaged
+    /// bodies say nothing about retention. The group is the one most files were posted to.
+    pub fn from_download(
+        nzb: &Nzb,
+        results: &[DownloadResult],
+        server: &str,
+        now: u64,
+    ) -> Option<Self> {
+        let posted = nzb
+            .files()
+            .iter()
+            .map(|f| f.date)
+            .filter(|&date| date > 0)
+            .min()?;
+
+        let mut groups: HashMap<&str, usize> = HashMap::new();
+        for file in nzb.files() {
+            if let Some(group) = file.groups.group.first() {
+                *groups.entry(group.name.as_str()).or_default() += 1;
+            }
+        }
+        let group = groups
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0)))?
+            .0
+            .to_string();
+
+        let segments = results
+            .iter()
+            .map(|r| r.segments_downloaded + r.segments_failed)
+            .sum();
+        let missing = results
+            .iter()
+            .flat_map(|r| r.failure_reasons.values())
+            .filter(|&&reason| reason == SegmentFailure::Missing)
+            .count();
+        if segments == 0 {
+            return None;
+        }
+
+        Some(Self {
+            server: server.to_string(),
+            group,
+            age_days: now.saturating_sub(posted) / SECS_PER_DAY,
+            segments,
+            missing,
+        })
+    }
+
+    /// At most 1% of the segments were gone, so the posts are still within retention
+    fn intact(&self) -> bool {
+        self.missing * 100 <= self.segments
+    }
+
+    /// Most of the segments were gone, so the posts have likely aged out
+    fn expired(&self) -> bool {
+        self.missing * 2 > self.segments
+    }
+}
+
+/// Retention of one group on one server, as bounded by the downloads seen so far
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionEstimate {
+    pub server: String,
+    pub group: String,
+    /// Age of the oldest posts that downloaded intact
+    pub oldest_available_days: Option<u64>,
+    /// Age of the newest posts that were mostly gone, when it's past the oldest intact ones
+    pub newest_expired_days: Option<u64>,
+    /// Downloads from this group and server the estimate is based on
+    pub observations: usize,
+}
+
+impl RetentionEstimate {
+    /// One-line description for the download summary
+    pub fn describe(&self) -> String {
+        let plural = if self.observations == 1 { "" } else { "s" };
+        let bounds = match (self.oldest_available_days, self.newest_expired_days) {
+            (Some(kept), Some(gone)) => format!("{}-{} days", kept, gone),
+            (Some(kept), None) => format!("at least {} days", kept),
+            (None, Some(gone)) => format!("under {} days", gone),
+            (None, None) => "unknown".to_string(),
+        };
+        format!(
+            "Retention for {} on {}: {} ({} download{})",
+            self.group, self.server, bounds, self.observations, plural
+        )
+    }
+}
+
+/// Retention observations accumulated across runs
+pub struct RetentionLog {
+    observations: Vec<RetentionObservation>,
+}
+
+impl RetentionLog {
+    /// Load the log, treating a missing file as empty and skipping malformed lines
+    pub fn load(path: &Path) -> Result<Self> {
+        let observations = match std::fs::read_to_string(path) {
+            Ok(content) => content.lines().filter_map(parse_line).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { observations })
+    }
+
+    /// Append an observation to the log file
+    ///
+    /// Opens the file per call, so downloads running at once can record without sharing a handle.
+    pub fn record(path: &Path, observation: &RetentionObservation) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(
+            file,
+            "{}\t{}\t{}\t{}\t{}",
+            observation.server,
+            observation.group,
+            observation.age_days,
+            observation.segments,
+            observation.missing
+        )?;
+        Ok(())
+    }
+
+    /// Bound the group's retention on `server` by the oldest intact and newest expired
+    /// downloads from it
+    ///
+    /// An expired download younger than an intact one is treated as a takedown rather
+    /// than retention, so it doesn't narrow the estimate.
+    pub fn estimate(&self, server: &str, group: &str) -> Option<RetentionEstimate> {
+        let seen: Vec<_> = self
+            .observations
+            .iter()
+            .filter(|o| o.server == server && o.group == group)
+            .collect();
+        if seen.is_empty() {
+            return None;
+        }
+
+        let oldest_available_days = seen.iter().filter(|o| o.intact()).map(|o| o.age_days).max();
+        let newest_expired_days = seen
+            .iter()
+            .filter(|o| o.expired())
+            .map(|o| o.age_days)
+            .filter(|&age| oldest_available_days.map_or(true, |kept| age > kept))
+            .min();
+
+        Some(RetentionEstimate {
+            server: server.to_string(),
+            group: group.to_string(),
+            oldest_available_days,
+            newest_expired_days,
+            observations: seen.len(),
+        })
+    }
+}
+
+fn parse_line(line: &str) -> Option<RetentionObservation> {
+    let mut fields = line.split('\t');
+    let observation = RetentionObservation {
+        server: fields.next()?.trim().to_string(),
+        group: fields.next()?.trim().to_string(),
+        age_days: fields.next()?.trim().parse().ok()?,
+        segments: fields.next()?.trim().parse().ok()?,
+        missing: fields.next()?.trim().parse().ok()?,
+    };
+    (!observation.server.is_empty() && !observation.group.is_empty()).then_some(observation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observation(age_days: u64, missing: usize) -> RetentionObservation {
+        RetentionObservation {
+            server: "news.example.com".to_string(),
+            group: "alt.binaries.test".to_string(),
+            age_days,
+            segments: 100,
+            missing,
+        }
+    }
+
+    #[test]
+    fn test_estimate_bounds_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("retention.txt");
+        for o in [
+            observation(300, 0),
+            observation(1200, 1),
+            observation(900, 90),
+            observation(2000, 80),
+            observation(2500, 100),
+        ] {
+            RetentionLog::record(&path, &o).unwrap();
+        }
+        // Another provider's expiry says nothing about this one's
+        let other_server = RetentionObservation {
+            server: "news.other.example".to_string(),
+            ..observation(400, 100)
+        };
+        RetentionLog::record(&path, &other_server).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .and_then(|mut f| writeln!(f, "garbage line"))
+            .unwrap();
+
+        let log = RetentionLog::load(&path).unwrap();
+        let estimate = log
+            .estimate("news.example.com", "alt.binaries.test")
+            .unwrap();
+        assert_eq!(estimate.oldest_available_days, Some(1200));
+        // The 900-day takedown is younger than an intact download, so it's ignored
+        assert_eq!(estimate.newest_expired_days, Some(2000));
+        assert_eq!(estimate.observations, 5);
+        assert_eq!(
+            estimate.describe(),
+            "Retention for alt.binaries.test on news.example.com: 1200-2000 days (5 downloads)"
+        );
+        assert!(log
+            .estimate("news.example.com", "alt.binaries.other")
+            .is_none());
+        let other = log
+            .estimate("news.other.example", "alt.binaries.test")
+            .unwrap();
+        assert_eq!(other.newest_expired_days, Some(400));
+        assert_eq!(other.observations, 1);
+    }
+
+    #[test]
+    fn test_load_missing_log_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = RetentionLog::load(&dir.path().join("retention.txt")).unwrap();
+        assert!(log
+            .estimate("news.example.com", "alt.binaries.test")
+            .is_none());
+    }
+}
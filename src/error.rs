@@ -4,6 +4,7 @@
 //! and context preservation.
 
 use std::path::PathBuf;
+use std::time::Duration;
 use thiserror::Error;
 
 /// Top-level error type for the dl-nzb application
@@ -54,6 +55,9 @@ pub enum NzbError {
 
     #[error("Invalid segment: {0}")]
     InvalidSegment(String),
+
+    #[error("Failed to fetch NZB from {url}: {reason}")]
+    Fetch { url: String, reason: String },
 }
 
 /// NNTP protocol and connection errors
@@ -66,6 +70,12 @@ pub enum NntpError {
         source: std::io::Error,
     },
 
+    #[error("Could not resolve server {server}: {source}")]
+    DnsFailed {
+        server: String,
+        source: std::io::Error,
+    },
+
     #[error("Connection timeout after {seconds}s")]
     Timeout { seconds: u64 },
 
@@ -75,6 +85,25 @@ pub enum NntpError {
     #[error("Authentication failed: {0}")]
     AuthFailed(String),
 
+    #[error("Authentication throttled by server ({code}), retry later")]
+    AuthThrottled {
+        code: String,
+        /// How long the server asked us to wait, when its response said so
+        retry_after: Option<Duration>,
+    },
+
+    #[error("Too many connections: server connection limit reached ({code})")]
+    TooManyConnections {
+        code: String,
+        retry_after: Option<Duration>,
+    },
+
+    #[error("Server temporarily unavailable ({code}), retry later")]
+    ServerBusy {
+        code: String,
+        retry_after: Option<Duration>,
+    },
+
     #[error("Protocol error: {0}")]
     ProtocolError(String),
 
@@ -90,10 +119,36 @@ pub enum NntpError {
     #[error("YEnc decode error: {0}")]
     YencDecode(String),
 
+    #[error("yEnc CRC mismatch (expected {expected:08x})")]
+    CrcMismatch { expected: u32 },
+
     #[error("Connection unhealthy")]
     UnhealthyConnection,
 }
 
+impl NntpError {
+    /// Whether the server turned us away for now, so trying again later may succeed
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::ServerBusy { .. }
+                | Self::TooManyConnections { .. }
+                | Self::AuthThrottled { .. }
+                | Self::Timeout { .. }
+        )
+    }
+
+    /// How long the server asked us to wait before trying again, if it said
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::ServerBusy { retry_after, .. }
+            | Self::TooManyConnections { retry_after, .. }
+            | Self::AuthThrottled { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
 /// Configuration validation errors
 #[derive(Error, Debug)]
 pub enum ConfigError {
@@ -138,6 +193,39 @@ pub enum DownloadError {
     #[error("Insufficient segments: {available}/{required} available")]
     InsufficientSegments { available: usize, required: usize },
 
+    #[error("No groups listed for {filename} in the NZB")]
+    NoGroups { filename: String },
+
+    #[error("Incomplete segment numbering for {filename} in the NZB: {details}")]
+    SegmentNumbering { filename: String, details: String },
+
+    #[error("No carried group for {filename}: server does not carry {groups}")]
+    GroupNotCarried { filename: String, groups: String },
+
+    #[error("Refusing to write into {path}: {count} unrelated file(s) present (use --force or enable create_subfolders)")]
+    UnrelatedFilesPresent { path: PathBuf, count: usize },
+
+    #[error("NZB already downloaded (hash {hash}); use --force to download it again")]
+    DuplicateNzb { hash: String },
+
+    #[error(
+        "PAR2 recovery is {percent:.1}% of the data, below min_recovery_percent ({required}%)"
+    )]
+    InsufficientRecovery { percent: f64, required: f64 },
+
+    #[error("{filename} is {size} bytes, over the {limit} byte limit for in-memory downloads")]
+    TooLargeForMemory {
+        filename: String,
+        size: u64,
+        limit: u64,
+    },
+
+    #[error("--output-name needs an NZB with one content file, this one has {count}")]
+    OutputNameNeedsSingleFile { count: usize },
+
+    #[error("Invalid output name {name:?}")]
+    InvalidOutputName { name: String },
+
     #[error("Connection pool exhausted")]
     PoolExhausted,
 
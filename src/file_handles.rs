@@ -0,0 +1,78 @@
+//! Cap on output files open at once
+//!
+//! Every file being downloaded keeps its output open, and so does every archive being
+//! extracted. With several NZBs downloading and extracting together this can run into a
+//! low `ulimit -n`, as is common in containers, and fail with EMFILE. Downloads and
+//! extraction take a slot from a shared [`FileHandleLimit`] before opening their output
+//! and wait while none is free.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Shared limit on open output files
+#[derive(Debug)]
+pub struct FileHandleLimit {
+    permits: Arc<Semaphore>,
+    limit: usize,
+    /// Times anything had to wait for a slot
+    waits: AtomicUsize,
+}
+
+impl FileHandleLimit {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(limit.max(1))),
+            limit: limit.max(1),
+            waits: AtomicUsize::new(0),
+        }
+    }
+
+    /// Wait for a slot to open an output file; it's freed when the permit is dropped
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        if let Ok(permit) = self.permits.clone().try_acquire_owned() {
+            return permit;
+        }
+        self.waits.fetch_add(1, Ordering::Relaxed);
+        self.permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("file handle semaphore is never closed")
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Whether the cap was reached at some point, making a download or extraction wait
+    pub fn was_reached(&self) -> bool {
+        self.waits() > 0
+    }
+
+    /// How many times a download or extraction had to wait for a slot; the limit is shared
+    /// between NZBs, so compare counts taken before and after one to attribute waits to it
+    pub fn waits(&self) -> usize {
+        self.waits.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_waits_once_the_cap_is_reached() {
+        let limit = FileHandleLimit::new(1);
+        let first = limit.acquire().await;
+        assert!(!limit.was_reached());
+
+        let waiting = tokio::time::timeout(std::time::Duration::from_millis(20), limit.acquire());
+        assert!(waiting.await.is_err());
+        assert!(limit.was_reached());
+        assert_eq!(limit.waits(), 1);
+
+        drop(first);
+        let _second = limit.acquire().await;
+    }
+}
@@ -1,6 +1,35 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+use crate::download::{NzbStats, RepairEstimate, RetentionEstimate};
+use crate::processing::{Category, Duplicate, HookOutput};
+
+/// Version of the JSON output contract, bumped on breaking changes to any payload
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Versioned wrapper around every JSON payload
+///
+/// `kind` identifies the payload type (`list`, `download`, `batch`, `retry`, `segments`,
+/// `test`, `error`, `event`)
+/// so consumers can route output and detect format changes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Envelope<T> {
+    pub schema_version: u32,
+    pub kind: String,
+    pub data: T,
+}
+
+impl<T> Envelope<T> {
+    pub fn new(kind: &str, data: T) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            kind: kind.to_string(),
+            data,
+        }
+    }
+}
+
 /// JSON output for list mode
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NzbInfo {
@@ -8,6 +37,10 @@ pub struct NzbInfo {
     pub total_files: usize,
     pub total_size: u64,
     pub total_segments: usize,
+    pub stats: NzbStats,
+    /// With --check: articles missing on the server and whether PAR2 can repair them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repair: Option<RepairEstimate>,
     pub files: Vec<FileInfo>,
 }
 
@@ -23,13 +56,87 @@ pub struct FileInfo {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DownloadSummary {
     pub nzb: PathBuf,
+    pub nzb_hash: String,
     pub output_dir: PathBuf,
     pub success: bool,
+    /// Connections the download ran with (the auto-tuned count with `connections = "auto"`)
+    pub connections: usize,
     pub total_size: u64,
     pub download_time_seconds: f64,
     pub average_speed_mbps: f64,
+    /// Segments fetched from the `--fill-from` NZB after the main download
+    pub segments_filled: usize,
+    /// Connections replaced while this NZB downloaded, after their article bodies kept
+    /// failing size or CRC checks
+    pub evicted_connections: usize,
+    /// `max_open_files` was reached while this NZB downloaded or extracted, so some of it
+    /// waited for a file handle
+    pub max_open_files_reached: bool,
+    /// Detected content: "movie", "tv", "music" or "other"
+    pub category: Category,
     pub files: Vec<DownloadFileResult>,
     pub post_processing: PostProcessingResult,
+    /// Estimated retention of the NZB's main group, with `track_retention` and a dated NZB
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionEstimate>,
+    /// Result of `post_download_command`, when one is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_download_command: Option<HookOutput>,
+}
+
+/// JSON output for `--batch`: every NZB's result, in the order they ran
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchReport {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub nzbs: Vec<BatchEntry>,
+}
+
+/// One NZB of a batch, tagged like the envelope it would get on its own
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "data", rename_all = "snake_case")]
+pub enum BatchEntry {
+    Download(Box<DownloadSummary>),
+    Error {
+        nzb: PathBuf,
+        #[serde(flatten)]
+        error: ErrorOutput,
+    },
+}
+
+/// JSON output for the retry command
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RetrySummary {
+    pub nzb: PathBuf,
+    pub output_dir: PathBuf,
+    pub pending: Vec<String>,
+    pub still_incomplete: Vec<String>,
+}
+
+/// JSON output for the segments command: every file's articles, in segment order
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SegmentMap {
+    pub nzb: PathBuf,
+    pub files: Vec<SegmentMapFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SegmentMapFile {
+    /// Name the downloader would write the file as
+    pub filename: String,
+    pub subject: String,
+    pub total_size: u64,
+    pub groups: Vec<String>,
+    pub segments: Vec<SegmentMapEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SegmentMapEntry {
+    pub number: u32,
+    pub message_id: String,
+    pub bytes: u64,
+    /// The file's first group, which the downloader selects unless the server lacks it
+    pub group: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -40,6 +147,20 @@ pub struct DownloadFileResult {
     pub segments_downloaded: usize,
     pub segments_failed: usize,
     pub success: bool,
+    pub download_time_seconds: f64,
+    /// This file's own speed, to spot slow files next to the aggregate
+    pub average_speed_mbps: f64,
+    /// Failed segments were left to PAR2 repair instead of being retried further
+    pub par2_handoff: bool,
+    /// Segments failed, but PAR2 verified the download, so `success` is still true
+    #[serde(default)]
+    pub par2_repaired: bool,
+    /// Segments fetched from each newsgroup, to see which groups the server covers
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub served_by: BTreeMap<String, usize>,
+    /// Failed segments by reason: missing, timeout, crc, decode or connection
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub failure_reasons: BTreeMap<String, usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -48,6 +169,20 @@ pub struct PostProcessingResult {
     pub par2_repaired: bool,
     pub rar_extracted: bool,
     pub files_renamed: usize,
+    /// Files merged into an identical copy with `duplicate_files`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicates_merged: Vec<Duplicate>,
+    /// Bytes freed by merging duplicates
+    pub bytes_saved: u64,
+    /// Archives not extracted because of `extract_include` / `extract_exclude`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archives_skipped: Vec<String>,
+    /// Archive entries not extracted because of `extract_entry_include` / `extract_entry_exclude`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entries_skipped: Vec<String>,
+    /// Files from failed extractions that `partial_extraction` deleted or quarantined
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub partial_files_cleaned: Vec<PathBuf>,
 }
 
 /// JSON output for test command
@@ -59,6 +194,20 @@ pub struct TestResult {
     pub connected: bool,
     pub authenticated: bool,
     pub healthy: bool,
+    pub connect_time_ms: Option<u64>,
+    pub capabilities: Vec<String>,
+    /// Result of selecting `test_group`, when one is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_access: Option<GroupAccess>,
+    pub error: Option<String>,
+}
+
+/// Whether the account could select the configured test group
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupAccess {
+    pub group: String,
+    pub accessible: bool,
+    /// Why the group couldn't be selected
     pub error: Option<String>,
 }
 
@@ -86,3 +235,62 @@ impl ErrorOutput {
         }
     }
 }
+
+/// Streaming download and post-processing event, emitted as one NDJSON line per event
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    /// A file finished downloading, complete or not; the NZB's summary still follows
+    FileComplete {
+        /// The NZB the file belongs to, as `nzb_hash` in its summary
+        nzb_hash: String,
+        filename: String,
+        size: u64,
+        segments_downloaded: usize,
+        segments_failed: usize,
+        success: bool,
+        /// Files finished so far, this one included
+        files_completed: usize,
+        total_files: usize,
+        /// Why the file couldn't be downloaded at all
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    Par2Progress {
+        files_verified: u64,
+        total_files: u64,
+    },
+    Par2Result {
+        status: String,
+        repair_needed: bool,
+    },
+    ExtractProgress {
+        archive: String,
+        bytes_extracted: u64,
+        total_bytes: u64,
+    },
+    ExtractResult {
+        archives_extracted: usize,
+        archives_total: usize,
+        /// Files extracted across all archives, including nested ones
+        files_extracted: u64,
+        /// Archives `extract_include` / `extract_exclude` left alone
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        skipped_archives: Vec<String>,
+        /// Archive entries `extract_entry_include` / `extract_entry_exclude` left out
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        skipped_entries: Vec<String>,
+        /// Files from failed extractions that `partial_extraction` deleted or quarantined
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        partial_files_cleaned: Vec<PathBuf>,
+    },
+}
+
+impl ProgressEvent {
+    /// Write the event to stdout as a single JSON line
+    pub fn emit(&self) {
+        if let Ok(line) = serde_json::to_string(&Envelope::new("event", self)) {
+            println!("{}", line);
+        }
+    }
+}
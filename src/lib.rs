@@ -29,6 +29,7 @@
 pub mod cli;
 pub mod config;
 pub mod error;
+pub mod file_handles;
 pub mod json_output;
 pub mod patterns;
 pub mod progress;
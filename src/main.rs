@@ -1,19 +1,32 @@
+use futures::stream::{self, StreamExt};
 use human_bytes::human_bytes;
 use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
 use tracing_subscriber::EnvFilter;
 
 use dl_nzb::{
     cli::{Cli, Commands},
-    config::Config,
-    download::{Downloader, Nzb},
-    error::{ConfigError, DlNzbError},
+    config::{Config, DuplicateAction, SummaryFormat, UsenetConfig},
+    download::{
+        fetch_nzb, is_url, redact_url, Downloader, Nzb, NzbHistory, RepairEstimate,
+        RetentionEstimate, RetentionLog, RetentionObservation,
+    },
+    eoutln,
+    error::{DlNzbError, DownloadError},
+    file_handles::FileHandleLimit,
     json_output::{
-        DownloadFileResult, DownloadSummary, ErrorOutput, FileInfo, NzbInfo, PostProcessingResult,
-        TestResult,
+        BatchEntry, BatchReport, DownloadFileResult, DownloadSummary, Envelope, ErrorOutput,
+        FileInfo, GroupAccess, NzbInfo, PostProcessingResult, RetrySummary, SegmentMap,
+        SegmentMapEntry, SegmentMapFile, TestResult,
+    },
+    nntp::{AsyncNntpConnection, TRANSCRIPT_TARGET},
+    outln,
+    processing::{
+        classify_download, run_post_download_command, Category, HookContext, PostProcessor,
     },
-    nntp::AsyncNntpConnection,
-    processing::PostProcessor,
-    serde_json,
+    progress, serde_json,
 };
 
 type Result<T> = std::result::Result<T, DlNzbError>;
@@ -31,7 +44,7 @@ async fn main() {
             let error_output = ErrorOutput::from_error(&e);
             eprintln!(
                 "{}",
-                serde_json::to_string_pretty(&error_output)
+                serde_json::to_string_pretty(&Envelope::new("error", &error_output))
                     .unwrap_or_else(|_| { format!(r#"{{"error": "Failed to serialize error"}}"#) })
             );
         } else {
@@ -49,6 +62,7 @@ async fn main() {
 async fn run(cli: Cli) -> Result<()> {
     // Initialize logging
     init_logging(&cli)?;
+    progress::set_json_stdout(cli.json);
 
     // Handle special commands first
     if let Some(command) = &cli.command {
@@ -76,14 +90,18 @@ async fn run(cli: Cli) -> Result<()> {
 
     // Validate configuration
     config.validate()?;
+    progress::set_summary_format(config.logging.summary);
+    for warning in config.warnings() {
+        progress::stage_warning(&warning);
+    }
 
     // Handle list mode
     if cli.list {
-        return handle_list_mode(&cli).await;
+        return handle_list_mode(&cli, &config).await;
     }
 
     // Check if we have files to download
-    if cli.files.is_empty() {
+    if cli.files.is_empty() && cli.batch.is_none() {
         eprintln!("No NZB files specified. Use 'dl-nzb --help' for usage information.");
         return Ok(());
     }
@@ -96,6 +114,14 @@ async fn run(cli: Cli) -> Result<()> {
 fn init_logging(cli: &Cli) -> Result<()> {
     let filter = EnvFilter::try_new(cli.get_log_level()).unwrap_or_else(|_| EnvFilter::new("info"));
 
+    // The protocol transcript is too noisy for -vv and only appears when asked for
+    let transcript = if cli.trace_nntp { "trace" } else { "off" };
+    let filter = filter.add_directive(
+        format!("{}={}", TRANSCRIPT_TARGET, transcript)
+            .parse()
+            .expect("valid directive"),
+    );
+
     let subscriber = tracing_subscriber::fmt()
         .with_env_filter(filter)
         .with_target(false);
@@ -120,51 +146,132 @@ async fn handle_command(command: &Commands, cli: &Cli) -> Result<()> {
     match command {
         Commands::Test => {
             let config = Config::load()?;
-            let test_config = config.usenet.clone();
 
-            if cli.json {
-                // JSON output mode
-                let mut result = TestResult {
-                    server: test_config.server.clone(),
-                    port: test_config.port,
-                    ssl: test_config.ssl,
-                    connected: false,
-                    authenticated: false,
-                    healthy: false,
-                    error: None,
-                };
+            if !cli.json {
+                println!("Testing connection to {}...", config.usenet.server);
+            }
+            let (result, error) = test_server(&config.usenet).await;
 
-                match AsyncNntpConnection::connect(&test_config, None).await {
-                    Ok(mut conn) => {
-                        result.connected = true;
-                        result.authenticated = true;
-                        result.healthy = conn.is_healthy().await;
-                        let _ = conn.close().await;
-                    }
-                    Err(e) => {
-                        result.error = Some(e.to_string());
+            if cli.json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&Envelope::new("test", &result))?
+                );
+            } else {
+                match &result.error {
+                    None => {
+                        println!("✓ Successfully connected to {}", result.server);
+                        println!("   Authentication: OK");
+                        if let Some(ms) = result.connect_time_ms {
+                            println!("   Connect time: {}ms", ms);
+                        }
+                        if result.healthy {
+                            println!("   Server status: Healthy");
+                        }
+                        if !result.capabilities.is_empty() {
+                            println!("   Capabilities: {}", result.capabilities.join(", "));
+                        }
+                        match &result.group_access {
+                            Some(access) if access.accessible => {
+                                println!("   Group access: OK ({})", access.group)
+                            }
+                            Some(access) => println!(
+                                "   ⚠ Group access: cannot select {} ({})",
+                                access.group,
+                                access.error.as_deref().unwrap_or("unknown error")
+                            ),
+                            None => {}
+                        }
                     }
+                    Some(e) => eprintln!("❌ Connection to {} failed: {}", result.server, e),
                 }
+            }
 
-                println!("{}", serde_json::to_string_pretty(&result)?);
-            } else {
-                // Human-readable output
-                println!("Testing connection to Usenet server...");
+            match error {
+                Some(e) if !cli.json => Err(e),
+                _ => Ok(()),
+            }
+        }
 
-                match AsyncNntpConnection::connect(&test_config, None).await {
-                    Ok(mut conn) => {
-                        println!("✓ Successfully connected to {}", test_config.server);
-                        println!("   Authentication: OK");
+        Commands::Retry { nzb, dir, server } => {
+            let mut config = Config::load()?;
+            config.apply_overrides(cli.get_config_overrides());
+            if let Some(server) = server {
+                config.usenet.server = server.clone();
+            }
+            if let Some(username) = &cli.username {
+                config.usenet.username = username.clone();
+            }
+            if let Some(password) = &cli.password {
+                config.usenet.password = password.clone();
+            }
+            config.validate_for_download()?;
 
-                        if conn.is_healthy().await {
-                            println!("   Server status: Healthy");
-                        }
+            config.download.dir = dir.clone();
+            config.download.force_redownload = false;
 
-                        let _ = conn.close().await;
+            let nzb_data = Nzb::from_file(nzb)?;
+            let pending = Downloader::pending_files(&nzb_data, &config).await?;
+
+            if !cli.json {
+                if pending.is_empty() {
+                    println!("✓ All {} files already complete", nzb_data.files().len());
+                } else {
+                    println!(
+                        "{} of {} files need fetching:",
+                        pending.len(),
+                        nzb_data.files().len()
+                    );
+                    for filename in &pending {
+                        println!("  • {}", filename);
                     }
-                    Err(e) => {
-                        eprintln!("❌ Connection failed: {}", e);
-                        return Err(e);
+                }
+            }
+
+            // Complete files are skipped by the downloader's resume check
+            let still_incomplete = if pending.is_empty() {
+                Vec::new()
+            } else {
+                let downloader = Downloader::new(config.clone()).await?;
+                downloader.verify().await?;
+                let (results, _progress_bar) = downloader.download_nzb(&nzb_data, config).await?;
+                let mut incomplete: Vec<String> = results
+                    .iter()
+                    .filter(|r| r.segments_failed > 0)
+                    .map(|r| r.filename.clone())
+                    .collect();
+                // Files that errored out entirely produce no result
+                incomplete.extend(
+                    pending
+                        .iter()
+                        .filter(|name| !results.iter().any(|r| &r.filename == *name))
+                        .cloned(),
+                );
+                incomplete
+            };
+
+            if cli.json {
+                let summary = RetrySummary {
+                    nzb: nzb.clone(),
+                    output_dir: dir.clone(),
+                    pending,
+                    still_incomplete,
+                };
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&Envelope::new("retry", &summary))?
+                );
+            } else if !pending.is_empty() {
+                if still_incomplete.is_empty() {
+                    outln!("\x1b[1;32m✓ All missing files recovered\x1b[0m");
+                } else {
+                    outln!(
+                        "\x1b[1;33m! {} file{} still incomplete\x1b[0m",
+                        still_incomplete.len(),
+                        if still_incomplete.len() == 1 { "" } else { "s" }
+                    );
+                    for filename in &still_incomplete {
+                        outln!("  \x1b[90m└─\x1b[0m {}", filename);
                     }
                 }
             }
@@ -172,6 +279,16 @@ async fn handle_command(command: &Commands, cli: &Cli) -> Result<()> {
             Ok(())
         }
 
+        Commands::Segments { nzb } => {
+            let nzb_data = Nzb::from_file(nzb)?;
+            let map = segment_map(nzb, &nzb_data);
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&Envelope::new("segments", &map))?
+            );
+            Ok(())
+        }
+
         Commands::Config => {
             let config_path = Config::config_path()?;
 
@@ -183,10 +300,7 @@ async fn handle_command(command: &Commands, cli: &Cli) -> Result<()> {
                 println!("Current configuration:");
                 println!("{}", "─".repeat(60));
                 let config = Config::load()?;
-                let toml = toml::to_string_pretty(&config).map_err(|e| {
-                    ConfigError::ParseError(format!("Failed to serialize config: {}", e))
-                })?;
-                println!("{}", toml);
+                println!("{}", config.to_display_toml()?);
                 println!("{}", "─".repeat(60));
             } else {
                 println!("Configuration file does not exist yet.");
@@ -211,8 +325,99 @@ async fn handle_command(command: &Commands, cli: &Cli) -> Result<()> {
     }
 }
 
+/// Connect to a server and collect connection, auth, health, capability and group access details
+async fn test_server(server: &UsenetConfig) -> (TestResult, Option<DlNzbError>) {
+    let mut result = TestResult {
+        server: server.server.clone(),
+        port: server.port,
+        ssl: server.ssl,
+        connected: false,
+        authenticated: false,
+        healthy: false,
+        connect_time_ms: None,
+        capabilities: Vec::new(),
+        group_access: None,
+        error: None,
+    };
+
+    let start = std::time::Instant::now();
+    match AsyncNntpConnection::connect(server, None).await {
+        Ok(mut conn) => {
+            result.connect_time_ms = Some(start.elapsed().as_millis() as u64);
+            result.connected = true;
+            result.authenticated = true;
+            result.healthy = conn.is_healthy().await;
+            result.capabilities = conn.capabilities().await.unwrap_or_default();
+            if !server.test_group.is_empty() {
+                let (accessible, error) = match conn.group_exists(&server.test_group).await {
+                    Ok(true) => (true, None),
+                    Ok(false) => (false, Some("no such group".to_string())),
+                    Err(e) => (false, Some(e.to_string())),
+                };
+                result.group_access = Some(GroupAccess {
+                    group: server.test_group.clone(),
+                    accessible,
+                    error,
+                });
+            }
+            let _ = conn.close().await;
+        }
+        Err(e) => {
+            result.error = Some(e.to_string());
+            return (result, Some(e));
+        }
+    }
+
+    (result, None)
+}
+
+/// Every file's segments in order, for the segments command
+fn segment_map(nzb_path: &Path, nzb: &Nzb) -> SegmentMap {
+    let files = nzb
+        .files()
+        .iter()
+        .zip(nzb.unique_filenames())
+        .map(|(file, filename)| {
+            let groups: Vec<String> = file.groups.group.iter().map(|g| g.name.clone()).collect();
+            let group = groups.first().cloned().unwrap_or_default();
+            let mut segments: Vec<_> = file.segments.segment.iter().collect();
+            segments.sort_by_key(|segment| segment.number);
+
+            SegmentMapFile {
+                filename,
+                subject: file.subject.clone(),
+                total_size: segments.iter().map(|segment| segment.bytes).sum(),
+                segments: segments
+                    .into_iter()
+                    .map(|segment| SegmentMapEntry {
+                        number: segment.number,
+                        message_id: segment.message_id.clone(),
+                        bytes: segment.bytes,
+                        group: group.clone(),
+                    })
+                    .collect(),
+                groups,
+            }
+        })
+        .collect();
+
+    SegmentMap {
+        nzb: nzb_path.to_path_buf(),
+        files,
+    }
+}
+
 /// Handle list mode
-async fn handle_list_mode(cli: &Cli) -> Result<()> {
+async fn handle_list_mode(cli: &Cli, config: &Config) -> Result<()> {
+    let downloader = if cli.check {
+        config.validate_for_download()?;
+        let downloader = Downloader::new(config.clone()).await?;
+        downloader.verify().await?;
+        Some(downloader)
+    } else {
+        None
+    };
+
     if cli.json {
         // JSON output mode
         let mut results = Vec::new();
@@ -243,11 +448,16 @@ async fn handle_list_mode(cli: &Cli) -> Result<()> {
                 total_files: nzb.files().len(),
                 total_size: nzb.total_size(),
                 total_segments: nzb.total_segments(),
+                stats: nzb.stats(),
+                repair: check_repair(downloader.as_ref(), &nzb, config).await?,
                 files,
             });
         }
 
-        println!("{}", serde_json::to_string_pretty(&results)?);
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&Envelope::new("list", &results))?
+        );
     } else {
         // Human-readable output
         for nzb_path in &cli.files {
@@ -261,6 +471,59 @@ async fn handle_list_mode(cli: &Cli) -> Result<()> {
             println!("Total size: {}", human_bytes(nzb.total_size() as f64));
             println!("Total segments: {}", nzb.total_segments());
 
+            let stats = nzb.stats();
+            if stats.missing_segments > 0 {
+                println!("Missing segments: {}", stats.missing_segments);
+            }
+            println!(
+                "PAR2: {} file(s), {:.1}% recovery",
+                stats.par2_files,
+                stats.par2_ratio * 100.0
+            );
+            match stats.recovery_percent {
+                Some(percent) => println!("Recovery blocks: {:.1}% of the data", percent),
+                None => println!("Recovery blocks: unknown (obfuscated file names)"),
+            }
+            let required = config.download.min_recovery_percent;
+            if required > 0.0 && stats.recovery_percent.is_some_and(|p| p < required) {
+                outln!(
+                    "\x1b[31m✗\x1b[0m Below min_recovery_percent ({}%), would be skipped",
+                    required
+                );
+            }
+            println!(
+                "Groups: {}",
+                stats
+                    .groups
+                    .iter()
+                    .map(|(group, files)| format!("{} ({})", group, files))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            println!("Age: {} days", stats.post_age_seconds / 86_400);
+
+            if let Some(repair) = check_repair(downloader.as_ref(), &nzb, config).await? {
+                println!(
+                    "Missing on server: {} article(s), {}",
+                    repair.missing_articles,
+                    human_bytes(repair.missing_bytes as f64)
+                );
+                if repair.repairable {
+                    outln!(
+                        "\x1b[36m✓\x1b[0m {} ({} of {} recovery blocks needed)",
+                        repair.verdict,
+                        repair.blocks_needed,
+                        repair.recovery_blocks
+                    );
+                } else {
+                    outln!(
+                        "\x1b[31m✗\x1b[0m {} ({} recovery blocks available)",
+                        repair.verdict,
+                        repair.recovery_blocks
+                    );
+                }
+            }
+
             println!("\nFiles:");
             for file in nzb.files() {
                 let filename = Nzb::get_filename_from_subject(&file.subject)
@@ -284,6 +547,21 @@ async fn handle_list_mode(cli: &Cli) -> Result<()> {
     Ok(())
 }
 
+/// With --check, STAT the NZB's articles and estimate whether PAR2 can repair what's missing
+async fn check_repair(
+    downloader: Option<&Downloader>,
+    nzb: &Nzb,
+    config: &Config,
+) -> Result<Option<RepairEstimate>> {
+    let Some(downloader) = downloader else {
+        return Ok(None);
+    };
+    let missing = downloader
+        .missing_articles(nzb, config.tuning.pipeline_size)
+        .await?;
+    Ok(Some(nzb.repair_estimate(&missing)))
+}
+
 /// Handle download mode
 async fn handle_download_mode(cli: &Cli, mut config: Config) -> Result<()> {
     // Validate server credentials before attempting download
@@ -324,7 +602,9 @@ async fn handle_download_mode(cli: &Cli, mut config: Config) -> Result<()> {
 
     // Create downloader with spinner (unless JSON output)
     let downloader = if cli.json {
-        Downloader::new(config.clone()).await?
+        let downloader = Downloader::new(config.clone()).await?;
+        downloader.verify().await?;
+        downloader
     } else {
         use indicatif::{ProgressBar, ProgressStyle};
         let spinner = ProgressBar::new_spinner();
@@ -337,136 +617,707 @@ async fn handle_download_mode(cli: &Cli, mut config: Config) -> Result<()> {
         spinner.set_message("Connecting to server...");
 
         let downloader = Downloader::new(config.clone()).await?;
+        let verified = downloader.verify().await;
 
         spinner.finish_and_clear();
+        verified?;
         downloader
     };
 
-    // Process each NZB file
-    let mut all_results = Vec::new();
+    // Output files open at once, shared by downloads and extraction
+    let file_limit = (config.memory.max_open_files > 0)
+        .then(|| Arc::new(FileHandleLimit::new(config.memory.max_open_files)));
+    let downloader = match &file_limit {
+        Some(limit) => downloader.with_file_limit(limit.clone()),
+        None => downloader,
+    }
+    .with_events(cli.json);
+
+    let history = if config.download.skip_duplicate_nzbs {
+        Some(Mutex::new(NzbHistory::load(&Config::history_path()?)?))
+    } else {
+        None
+    };
 
-    for nzb_path in &cli.files {
-        let nzb = match Nzb::from_file(nzb_path) {
-            Ok(nzb) => nzb,
-            Err(e) => {
-                eprintln!("Failed to load {}: {}", nzb_path.display(), e);
-                continue;
+    // PAR2/extraction slots shared by every NZB, separate from download concurrency
+    let job_permits = Arc::new(Semaphore::new(
+        config.post_processing.max_concurrent_jobs.max(1),
+    ));
+
+    if let Some(batch_path) = &cli.batch {
+        run_batch(
+            cli,
+            &config,
+            &downloader,
+            history.as_ref(),
+            &job_permits,
+            batch_path,
+        )
+        .await?;
+    } else {
+        // Process NZB files, running up to max_concurrent_nzbs at once on the shared pool
+        let nzb_limit = config.download.max_concurrent_nzbs.max(1);
+        let outcomes: Vec<Result<bool>> = stream::iter(&cli.files)
+            .map(|nzb_path| {
+                process_nzb(
+                    cli,
+                    &config,
+                    &downloader,
+                    history.as_ref(),
+                    &job_permits,
+                    nzb_path,
+                    None,
+                )
+            })
+            .buffered(nzb_limit)
+            .collect()
+            .await;
+        outcomes.into_iter().collect::<Result<Vec<bool>>>()?;
+    }
+
+    if let Some(limit) = file_limit.filter(|limit| limit.was_reached()) {
+        if !cli.json {
+            progress::stage_warning(&format!(
+                "Reached max_open_files ({}); downloads and extraction waited for file handles",
+                limit.limit()
+            ));
+        }
+    }
+
+    // Terminal bell to notify completion (skip in quiet/json mode)
+    if !cli.quiet && !cli.json {
+        print!("\x07");
+    }
+
+    Ok(())
+}
+
+/// Download the NZBs on the command line, then those in a batch file, one after another
+///
+/// The pool stays connected from one NZB to the next. With `--json`, a single `batch`
+/// report replaces the per-NZB payloads.
+async fn run_batch(
+    cli: &Cli,
+    config: &Config,
+    downloader: &Downloader,
+    history: Option<&Mutex<NzbHistory>>,
+    job_permits: &Arc<Semaphore>,
+    batch_path: &Path,
+) -> Result<()> {
+    let mut nzb_paths = cli.files.clone();
+    nzb_paths.extend(read_batch_file(batch_path)?);
+
+    let report = Mutex::new(Vec::new());
+    let json_report = cli.json.then_some(&report);
+    let mut succeeded = 0;
+    let mut failed = 0;
+
+    for nzb_path in &nzb_paths {
+        let ok = process_nzb(
+            cli,
+            config,
+            downloader,
+            history,
+            job_permits,
+            nzb_path,
+            json_report,
+        )
+        .await?;
+
+        if ok {
+            succeeded += 1;
+        } else {
+            failed += 1;
+            if cli.stop_on_error {
+                if !cli.json {
+                    eprintln!("Stopping the batch after {} failed", nzb_path.display());
+                }
+                break;
             }
+        }
+    }
+
+    if cli.json {
+        let report = BatchReport {
+            succeeded,
+            failed,
+            nzbs: report.into_inner().expect("batch report lock"),
         };
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&Envelope::new("batch", &report))?
+        );
+    } else {
+        let not_started = nzb_paths.len() - succeeded - failed;
+        print!("\nBatch: {} succeeded, {} failed", succeeded, failed);
+        if not_started > 0 {
+            print!(", {} not started", not_started);
+        }
+        println!();
+    }
 
-        // Create output directory based on NZB filename
-        let output_dir = if config.download.create_subfolders {
-            // Use NZB filename (without extension) as folder name
-            let folder_name = nzb_path
-                .file_stem()
-                .and_then(|s| s.to_str())
-                .unwrap_or("download")
-                .to_string();
-            config.download.dir.join(folder_name)
+    Ok(())
+}
+
+/// NZB paths from a batch file, one per line
+///
+/// Blank lines and `#` comments are skipped. Relative paths are taken from the batch file's
+/// directory, so a list can sit next to the NZBs it names.
+fn read_batch_file(path: &Path) -> Result<Vec<PathBuf>> {
+    let base = path.parent().unwrap_or(Path::new(""));
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            if is_url(Path::new(line)) {
+                PathBuf::from(line)
+            } else {
+                base.join(line)
+            }
+        })
+        .collect())
+}
+
+/// Print one NZB's JSON result, or keep it for the batch report
+fn emit_json(report: Option<&Mutex<Vec<BatchEntry>>>, entry: BatchEntry) -> Result<()> {
+    if let Some(report) = report {
+        report.lock().expect("batch report lock").push(entry);
+        return Ok(());
+    }
+
+    let json = match &entry {
+        BatchEntry::Download(summary) => {
+            serde_json::to_string_pretty(&Envelope::new("download", summary))?
+        }
+        BatchEntry::Error { error, .. } => {
+            serde_json::to_string_pretty(&Envelope::new("error", error))?
+        }
+    };
+    println!("{}", json);
+    Ok(())
+}
+
+/// Download and post-process a single NZB, returning whether it came out complete
+///
+/// An `http(s)://` link is fetched first, named as the server names it, and kept under the
+/// cache folder so the path reported for it stays valid. Per-NZB failures are reported and
+/// swallowed so the remaining NZBs still run. JSON results go into `report` when one is
+/// given, instead of being printed.
+async fn process_nzb(
+    cli: &Cli,
+    config: &Config,
+    downloader: &Downloader,
+    history: Option<&Mutex<NzbHistory>>,
+    job_permits: &Arc<Semaphore>,
+    nzb_path: &Path,
+    report: Option<&Mutex<Vec<BatchEntry>>>,
+) -> Result<bool> {
+    if !is_url(nzb_path) {
+        return process_nzb_file(
+            cli,
+            config,
+            downloader,
+            history,
+            job_permits,
+            nzb_path,
+            report,
+        )
+        .await;
+    }
+
+    // A folder per fetch keeps two links serving the same file name apart
+    static FETCHES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    let fetch_dir = dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("dl-nzb")
+        .join("fetched")
+        .join(format!(
+            "{}-{}-{}",
+            chrono::Local::now().format("%Y%m%d-%H%M%S"),
+            std::process::id(),
+            FETCHES.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        ));
+    let url = nzb_path.to_string_lossy();
+    let fetched = match fetch_nzb(&url, &fetch_dir).await {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("Failed to fetch {}: {}", redact_url(&url), e);
+            if report.is_some() {
+                let error = ErrorOutput::from_error(&e);
+                emit_json(
+                    report,
+                    BatchEntry::Error {
+                        nzb: PathBuf::from(redact_url(&url)),
+                        error,
+                    },
+                )?;
+            }
+            let _ = tokio::fs::remove_dir_all(&fetch_dir).await;
+            return Ok(false);
+        }
+    };
+
+    process_nzb_file(
+        cli,
+        config,
+        downloader,
+        history,
+        job_permits,
+        &fetched,
+        report,
+    )
+    .await
+}
+
+/// Download and post-process the NZB file at `nzb_path`
+async fn process_nzb_file(
+    cli: &Cli,
+    config: &Config,
+    downloader: &Downloader,
+    history: Option<&Mutex<NzbHistory>>,
+    job_permits: &Arc<Semaphore>,
+    nzb_path: &Path,
+    report: Option<&Mutex<Vec<BatchEntry>>>,
+) -> Result<bool> {
+    let nzb = match Nzb::from_file(nzb_path) {
+        Ok(nzb) => nzb,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", nzb_path.display(), e);
+            if report.is_some() {
+                let error = ErrorOutput::from_error(&e);
+                emit_json(
+                    report,
+                    BatchEntry::Error {
+                        nzb: nzb_path.to_path_buf(),
+                        error,
+                    },
+                )?;
+            }
+            return Ok(false);
+        }
+    };
+    let nzb_hash = nzb.content_hash();
+
+    // Skip NZBs already downloaded under another name, unless forced
+    let already_done =
+        !cli.force && history.is_some_and(|h| h.lock().expect("history lock").contains(&nzb_hash));
+    if already_done {
+        let e = DlNzbError::from(DownloadError::DuplicateNzb { hash: nzb_hash });
+        if cli.json {
+            let error = ErrorOutput::from_error(&e);
+            emit_json(
+                report,
+                BatchEntry::Error {
+                    nzb: nzb_path.to_path_buf(),
+                    error,
+                },
+            )?;
         } else {
-            config.download.dir.clone()
-        };
+            eprintln!("Skipping {}: {}", nzb_path.display(), e);
+        }
+        return Ok(false);
+    }
 
-        std::fs::create_dir_all(&output_dir)?;
+    // Skip NZBs with too little recovery data to ride out missing articles; obfuscated
+    // volume names leave the amount unknown, which isn't a reason to skip
+    let required = config.download.min_recovery_percent;
+    let percent = nzb.recovery_percent();
+    if let Some(percent) = percent.filter(|&p| required > 0.0 && p < required) {
+        let e = DlNzbError::from(DownloadError::InsufficientRecovery { percent, required });
+        if cli.json {
+            let error = ErrorOutput::from_error(&e);
+            emit_json(
+                report,
+                BatchEntry::Error {
+                    nzb: nzb_path.to_path_buf(),
+                    error,
+                },
+            )?;
+        } else {
+            eprintln!("Skipping {}: {}", nzb_path.display(), e);
+        }
+        return Ok(false);
+    }
 
-        // Update config for this download
-        let mut download_config = config.clone();
-        download_config.download.dir = output_dir.clone();
-        download_config.download.force_redownload = cli.force;
+    // Create output directory based on NZB filename
+    let output_dir = if config.download.create_subfolders {
+        // Use NZB filename (without extension) as folder name
+        let folder_name = nzb_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("download")
+            .to_string();
+        config.download.dir.join(folder_name)
+    } else {
+        config.download.dir.clone()
+    };
 
-        // Track timing for JSON output
-        let download_start = std::time::Instant::now();
+    std::fs::create_dir_all(&output_dir)?;
 
-        // Download the NZB with updated config
-        match downloader.download_nzb(&nzb, download_config.clone()).await {
-            Ok((results, _progress_bar)) => {
-                let download_time = download_start.elapsed();
+    // Update config for this download
+    let mut download_config = config.clone();
+    download_config.download.dir = output_dir.clone();
+    download_config.download.force_redownload = cli.force;
+    download_config.download.output_name = cli.output_name.clone();
+    if config.download.max_concurrent_nzbs > 1 {
+        // The pool is shared, so one NZB must not shrink it under another
+        download_config.tuning.scale_pool_to_nzb = false;
+    }
 
-                if cli.print_names {
-                    for result in &results {
-                        println!("{}", result.path.display());
+    // Track timing for JSON output
+    let download_start = std::time::Instant::now();
+    // The pool outlives this NZB, so its eviction count is only read as a difference
+    let evicted_before = downloader.evicted_connections();
+    let file_waits_before = downloader.file_limit().map_or(0, |limit| limit.waits());
+
+    // Download the NZB with updated config
+    let complete = match downloader.download_nzb(&nzb, download_config.clone()).await {
+        Ok((mut results, _progress_bar)) => {
+            let connections = downloader.connection_count();
+
+            // Fetch whatever failed from the alternate NZB before anything inspects the results
+            let mut segments_filled = 0;
+            if let Some(fill_path) = &cli.fill_from {
+                if results.iter().any(|r| r.segments_failed > 0) {
+                    match Nzb::from_file(fill_path) {
+                        Ok(secondary) => {
+                            match downloader
+                                .fill_from_nzb(&nzb, &secondary, &mut results, &download_config)
+                                .await
+                            {
+                                Ok(filled) => segments_filled = filled,
+                                Err(e) => {
+                                    eprintln!("Filling from {} failed: {}", fill_path.display(), e)
+                                }
+                            }
+                        }
+                        Err(e) => eprintln!("Failed to load {}: {}", fill_path.display(), e),
+                    }
+                    if !cli.json {
+                        println!(
+                            "Filled {} segment(s) from {}",
+                            segments_filled,
+                            fill_path.display()
+                        );
                     }
                 }
+            }
 
-                // Post-processing
-                let mut post_result = PostProcessingResult {
-                    par2_verified: false,
-                    par2_repaired: false,
-                    rar_extracted: false,
-                    files_renamed: 0,
-                };
+            let download_time = download_start.elapsed();
 
-                if config.post_processing.auto_par2_repair
-                    || config.post_processing.auto_extract_rar
-                {
-                    let processor = PostProcessor::new(
-                        download_config.post_processing.clone(),
-                        download_config.tuning.large_file_threshold,
-                    );
-                    if let Err(e) = processor.process_downloads(&results).await {
+            if cli.print_names {
+                for result in &results {
+                    println!("{}", result.path.display());
+                }
+            }
+
+            let retention = if config.download.track_retention {
+                track_retention(&nzb, &results, &config.usenet.server)
+            } else {
+                None
+            };
+
+            // Post-processing
+            let mut post_result = PostProcessingResult {
+                par2_verified: false,
+                par2_repaired: false,
+                rar_extracted: false,
+                files_renamed: 0,
+                duplicates_merged: Vec::new(),
+                bytes_saved: 0,
+                archives_skipped: Vec::new(),
+                entries_skipped: Vec::new(),
+                partial_files_cleaned: Vec::new(),
+            };
+
+            let mut post_processing_ok = true;
+            if config.post_processing.auto_par2_repair
+                || config.post_processing.par2_verify_only
+                || config.post_processing.auto_extract_rar
+                || config.post_processing.duplicate_files != DuplicateAction::Keep
+            {
+                let mut processor = PostProcessor::new(
+                    download_config.post_processing.clone(),
+                    download_config.tuning.large_file_threshold,
+                )
+                .with_events(cli.json)
+                .with_job_permits(job_permits.clone());
+                if let Some(limit) = downloader.file_limit() {
+                    processor = processor.with_file_limit(limit.clone());
+                }
+                match processor.process_downloads(&mut results, &output_dir).await {
+                    Ok(outcome) => {
+                        post_result.par2_verified = outcome.par2_verified;
+                        post_result.rar_extracted = config.post_processing.auto_extract_rar;
+                        post_result.bytes_saved = outcome.duplicates.iter().map(|d| d.size).sum();
+                        post_result.duplicates_merged = outcome.duplicates;
+                        post_result.archives_skipped = outcome.skipped_archives;
+                        post_result.entries_skipped = outcome.skipped_entries;
+                        post_result.partial_files_cleaned = outcome.partial_files_cleaned;
+
+                        // PAR2 vouching for a file outweighs the segments it failed
+                        if config.post_processing.trust_par2_verification {
+                            mark_par2_repaired(&mut results, &outcome.par2_intact);
+                        }
+                    }
+                    Err(e) => {
+                        post_processing_ok = false;
                         if !cli.json {
                             eprintln!("Post-processing error: {}", e);
                         }
-                    } else {
-                        post_result.par2_verified = config.post_processing.auto_par2_repair;
-                        post_result.rar_extracted = config.post_processing.auto_extract_rar;
                     }
                 }
+            }
 
-                // Output results
-                if cli.json {
-                    let total_size: u64 = results.iter().map(|r| r.size).sum();
-                    let summary = DownloadSummary {
-                        nzb: nzb_path.clone(),
-                        output_dir: output_dir.clone(),
-                        success: results.iter().all(|r| r.segments_failed == 0),
-                        total_size,
-                        download_time_seconds: download_time.as_secs_f64(),
-                        average_speed_mbps: if download_time.as_secs() > 0 {
-                            (total_size as f64 / 1024.0 / 1024.0) / download_time.as_secs_f64()
-                        } else {
-                            0.0
-                        },
-                        files: results
-                            .iter()
-                            .map(|r| DownloadFileResult {
-                                filename: r.filename.clone(),
-                                path: r.path.clone(),
-                                size: r.size,
-                                segments_downloaded: r.segments_downloaded,
-                                segments_failed: r.segments_failed,
-                                success: r.segments_failed == 0,
-                            })
-                            .collect(),
-                        post_processing: post_result,
-                    };
-                    println!("{}", serde_json::to_string_pretty(&summary)?);
+            if let Some(history) = history {
+                if results.iter().all(|r| r.is_complete()) {
+                    if let Err(e) = history.lock().expect("history lock").record(&nzb_hash) {
+                        tracing::warn!("Failed to update NZB history: {}", e);
+                    }
+                }
+            }
+
+            // Classify the content, then sort the folder by category if configured
+            let release_name = nzb_path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("download");
+            let category = classify_download(&output_dir, release_name);
+            let output_dir =
+                if config.download.category_folders && config.download.create_subfolders {
+                    route_to_category(&output_dir, &config.download.dir, category, &mut results)
                 } else {
-                    print_final_summary(&nzb, &results, &output_dir);
+                    output_dir.clone()
+                };
+
+            // Hand the finished download to the user's post-download command
+            let total_size: u64 = results.iter().map(|r| r.size).sum();
+            let success = results.iter().all(|r| r.is_complete());
+            let hook_output = if config.post_processing.post_download_command.is_empty() {
+                None
+            } else {
+                let context = HookContext {
+                    nzb: nzb_path,
+                    nzb_hash: &nzb_hash,
+                    output_dir: &output_dir,
+                    category: category.as_str(),
+                    success: success && post_processing_ok,
+                    total_size,
+                };
+                let timeout = (config.post_processing.post_download_timeout > 0).then(|| {
+                    std::time::Duration::from_secs(config.post_processing.post_download_timeout)
+                });
+                let output = run_post_download_command(
+                    &config.post_processing.post_download_command,
+                    &context,
+                    timeout,
+                )
+                .await;
+                if !cli.json {
+                    print_hook_output(&output);
                 }
+                Some(output)
+            };
 
-                all_results.extend(results);
+            // Output results
+            if cli.json {
+                let summary = DownloadSummary {
+                    nzb: nzb_path.to_path_buf(),
+                    nzb_hash: nzb_hash.clone(),
+                    output_dir: output_dir.clone(),
+                    success,
+                    connections,
+                    total_size,
+                    download_time_seconds: download_time.as_secs_f64(),
+                    average_speed_mbps: if download_time.as_secs() > 0 {
+                        (total_size as f64 / 1024.0 / 1024.0) / download_time.as_secs_f64()
+                    } else {
+                        0.0
+                    },
+                    segments_filled,
+                    evicted_connections: downloader
+                        .evicted_connections()
+                        .saturating_sub(evicted_before),
+                    max_open_files_reached: downloader
+                        .file_limit()
+                        .is_some_and(|limit| limit.waits() > file_waits_before),
+                    category,
+                    files: results
+                        .iter()
+                        .map(|r| DownloadFileResult {
+                            filename: r.filename.clone(),
+                            path: r.path.clone(),
+                            size: r.size,
+                            segments_downloaded: r.segments_downloaded,
+                            segments_failed: r.segments_failed,
+                            success: r.is_complete(),
+                            download_time_seconds: r.download_time.as_secs_f64(),
+                            average_speed_mbps: r.average_speed,
+                            par2_handoff: r.par2_handoff,
+                            par2_repaired: r.par2_repaired,
+                            served_by: r.served_by.clone(),
+                            failure_reasons: r
+                                .failure_breakdown()
+                                .into_iter()
+                                .map(|(reason, count)| (reason.as_str().to_string(), count))
+                                .collect(),
+                        })
+                        .collect(),
+                    post_processing: post_result,
+                    retention,
+                    post_download_command: hook_output,
+                };
+                emit_json(report, BatchEntry::Download(Box::new(summary)))?;
+            } else {
+                let evicted = downloader
+                    .evicted_connections()
+                    .saturating_sub(evicted_before);
+                if evicted > 0 {
+                    progress::stage_warning(&format!(
+                        "Replaced {} connection(s) that kept returning damaged articles",
+                        evicted
+                    ));
+                }
+                print_final_summary(&nzb, &results, &output_dir);
+                if let Some(estimate) = &retention {
+                    progress::stage_summary(true, "\x1b[36m", &estimate.describe());
+                }
             }
-            Err(e) => {
-                if cli.json {
-                    let error_output = ErrorOutput::from_error(&e);
-                    println!("{}", serde_json::to_string_pretty(&error_output)?);
-                } else {
-                    eprintln!("Download failed for {}: {}", nzb_path.display(), e);
-                    if !cli.keep_partial {
-                        eprintln!("Note: Partial files may remain. Use --keep-partial to explicitly keep them.");
-                    }
+
+            success && post_processing_ok
+        }
+        Err(e) => {
+            if cli.json {
+                let error = ErrorOutput::from_error(&e);
+                emit_json(
+                    report,
+                    BatchEntry::Error {
+                        nzb: nzb_path.to_path_buf(),
+                        error,
+                    },
+                )?;
+            } else {
+                eprintln!("Download failed for {}: {}", nzb_path.display(), e);
+                if !cli.keep_partial {
+                    eprintln!("Note: Partial files may remain. Use --keep-partial to explicitly keep them.");
                 }
             }
+
+            false
+        }
+    };
+
+    Ok(complete)
+}
+
+/// Log what this download says about `server`'s retention and estimate it for the NZB's
+/// main group
+///
+/// NZBs without post dates are skipped; a log that can't be written is only a warning.
+fn track_retention(
+    nzb: &Nzb,
+    results: &[dl_nzb::download::DownloadResult],
+    server: &str,
+) -> Option<RetentionEstimate> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    let observation = RetentionObservation::from_download(nzb, results, server, now)?;
+    let logged = Config::retention_path().and_then(|path| {
+        RetentionLog::record(&path, &observation)?;
+        RetentionLog::load(&path)
+    });
+    match logged {
+        Ok(log) => log.estimate(&observation.server, &observation.group),
+        Err(e) => {
+            tracing::warn!("Failed to update retention log: {}", e);
+            None
         }
     }
+}
 
-    // Terminal bell to notify completion (skip in quiet/json mode)
-    if !cli.quiet && !cli.json {
-        print!("\x07");
+/// Report how the post-download command went, with its error output if it failed
+fn print_hook_output(output: &dl_nzb::processing::HookOutput) {
+    if output.succeeded() {
+        progress::stage_summary(true, "\x1b[36m", "Post-download command finished");
+        return;
     }
 
-    Ok(())
+    match output.exit_code {
+        None if output.timed_out => {
+            progress::stage_warning("Post-download command timed out and was killed")
+        }
+        Some(code) => {
+            progress::stage_warning(&format!("Post-download command exited with code {}", code))
+        }
+        None => progress::stage_warning("Post-download command did not complete"),
+    }
+    for line in output.stderr.lines() {
+        progress::stage_detail(line);
+    }
+}
+
+/// Count incomplete files that PAR2 verified or repaired as complete
+///
+/// Only files a successful PAR2 set named are marked; a set can't vouch for files outside
+/// it. Names are matched against both the file on disk and the name from the NZB, since
+/// PAR2 knows files by their original names.
+fn mark_par2_repaired(results: &mut [dl_nzb::download::DownloadResult], intact: &[String]) {
+    for result in results.iter_mut().filter(|r| !r.is_complete()) {
+        let on_disk = result.path.file_name().and_then(|n| n.to_str());
+        if intact
+            .iter()
+            .any(|name| Some(name.as_str()) == on_disk || *name == result.filename)
+        {
+            result.par2_repaired = true;
+        }
+    }
+}
+
+/// Move a finished NZB folder to `<download dir>/<category>/`, updating the result paths
+///
+/// Returns the folder's location afterwards, which is unchanged if it couldn't be moved.
+fn route_to_category(
+    output_dir: &std::path::Path,
+    download_dir: &std::path::Path,
+    category: Category,
+    results: &mut [dl_nzb::download::DownloadResult],
+) -> std::path::PathBuf {
+    let Some(name) = output_dir.file_name() else {
+        return output_dir.to_path_buf();
+    };
+    let category_dir = download_dir.join(category.as_str());
+    let target = category_dir.join(name);
+
+    if target.exists() {
+        eoutln!(
+            "\x1b[33m⚠ Not moving {} into {}: {} already exists\x1b[0m",
+            output_dir.display(),
+            category_dir.display(),
+            target.display()
+        );
+        return output_dir.to_path_buf();
+    }
+    let moved =
+        std::fs::create_dir_all(&category_dir).and_then(|_| std::fs::rename(output_dir, &target));
+    if let Err(e) = moved {
+        eoutln!(
+            "\x1b[33m⚠ Could not move {} into {}: {}\x1b[0m",
+            output_dir.display(),
+            category_dir.display(),
+            e
+        );
+        return output_dir.to_path_buf();
+    }
+
+    for result in results.iter_mut() {
+        if let Ok(relative) = result.path.strip_prefix(output_dir) {
+            result.path = target.join(relative);
+        }
+    }
+    target
 }
 
 /// Print a final summary after all processing is complete
@@ -480,7 +1331,13 @@ fn print_final_summary(
     // Calculate total stats
     let total_size: u64 = results.iter().map(|r| r.size).sum();
     let total_time: Duration = results.iter().map(|r| r.download_time).sum();
-    let failed_count = results.iter().filter(|r| r.segments_failed > 0).count();
+    let failed_count = results.iter().filter(|r| !r.is_complete()).count();
+    // Files that stopped retrying early, so PAR2 was left to finish them
+    let handed_off: Vec<&str> = results
+        .iter()
+        .filter(|r| r.par2_handoff)
+        .map(|r| r.filename.as_str())
+        .collect();
 
     // Find the main video/media file (largest non-PAR2, non-RAR file)
     let main_file = std::fs::read_dir(output_dir).ok().and_then(|entries| {
@@ -497,45 +1354,183 @@ fn print_final_summary(
             .max_by_key(|e| e.metadata().ok().map(|m| m.len()).unwrap_or(0))
     });
 
-    println!();
+    match progress::summary_format() {
+        SummaryFormat::None => return,
+        SummaryFormat::Minimal => {
+            if failed_count == 0 {
+                let (name, size) = match &main_file {
+                    Some(file) => (
+                        file.file_name().to_string_lossy().to_string(),
+                        file.metadata().ok().map(|m| m.len()).unwrap_or(0),
+                    ),
+                    None => (output_dir.display().to_string(), total_size),
+                };
+                outln!(
+                    "Complete: {} ({} in {:.0}s)",
+                    name,
+                    human_bytes(size as f64),
+                    total_time.as_secs_f64()
+                );
+            } else {
+                outln!(
+                    "Completed with {} file(s) having errors: {}",
+                    failed_count,
+                    output_dir.display()
+                );
+            }
+            return;
+        }
+        SummaryFormat::Plain => {
+            outln!("");
+            if failed_count == 0 {
+                let size = main_file
+                    .as_ref()
+                    .and_then(|file| file.metadata().ok())
+                    .map(|m| m.len())
+                    .unwrap_or(total_size);
+                match &main_file {
+                    Some(file) => outln!("Complete: {}", file.file_name().to_string_lossy()),
+                    None => outln!("Complete"),
+                }
+                outln!("  {}", output_dir.display());
+                outln!(
+                    "  {} in {:.0}s",
+                    human_bytes(size as f64),
+                    total_time.as_secs_f64()
+                );
+            } else {
+                outln!(
+                    "Completed with {} file{} having errors",
+                    failed_count,
+                    if failed_count == 1 { "" } else { "s" }
+                );
+                outln!("  {}", output_dir.display());
+            }
+            for name in &handed_off {
+                outln!("  Left to PAR2: {}", name);
+            }
+            return;
+        }
+        SummaryFormat::Fancy => outln!(""),
+    }
 
     if failed_count == 0 {
         if let Some(file) = main_file {
             let filename = file.file_name().to_string_lossy().to_string();
             let file_size = file.metadata().ok().map(|m| m.len()).unwrap_or(0);
 
-            println!("\x1b[1;32m✓ Complete:\x1b[0m \x1b[37m{}\x1b[0m", filename);
-            println!(
+            outln!("\x1b[1;32m✓ Complete:\x1b[0m \x1b[37m{}\x1b[0m", filename);
+            outln!(
                 "  \x1b[90m└─\x1b[0m \x1b[34m{}\x1b[0m",
                 output_dir.display()
             );
-            println!(
+            outln!(
                 "  \x1b[90m└─\x1b[0m \x1b[36m{}\x1b[0m in \x1b[35m{:.0}s\x1b[0m",
                 human_bytes(file_size as f64),
                 total_time.as_secs_f64()
             );
         } else {
             // No main file found, just show stats
-            println!("\x1b[1;32m✓ Complete\x1b[0m");
-            println!(
+            outln!("\x1b[1;32m✓ Complete\x1b[0m");
+            outln!(
                 "  \x1b[90m└─\x1b[0m \x1b[34m{}\x1b[0m",
                 output_dir.display()
             );
-            println!(
+            outln!(
                 "  \x1b[90m└─\x1b[0m \x1b[36m{}\x1b[0m in \x1b[35m{:.0}s\x1b[0m",
                 human_bytes(total_size as f64),
                 total_time.as_secs_f64()
             );
         }
     } else {
-        println!(
+        outln!(
             "\x1b[1;33m! Completed with {} file{} having errors\x1b[0m",
             failed_count,
             if failed_count == 1 { "" } else { "s" }
         );
-        println!(
+        outln!(
             "  \x1b[90m└─\x1b[0m \x1b[34m{}\x1b[0m",
             output_dir.display()
         );
     }
+    for name in &handed_off {
+        outln!("  \x1b[90m└─\x1b[0m \x1b[33mLeft to PAR2:\x1b[0m {}", name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segment_map_orders_segments_by_number() {
+        let xml = r#"
+        <nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+            <file poster="p" date="0" subject="&quot;file.bin&quot; yEnc (1/2)">
+                <groups>
+                    <group>alt.binaries.test</group>
+                    <group>alt.binaries.misc</group>
+                </groups>
+                <segments>
+                    <segment bytes="40" number="2">b@test</segment>
+                    <segment bytes="100" number="1">a@test</segment>
+                </segments>
+            </file>
+        </nzb>
+        "#;
+        let nzb: Nzb = xml.trim().parse().unwrap();
+        let map = segment_map(Path::new("release.nzb"), &nzb);
+
+        assert_eq!(map.nzb, Path::new("release.nzb"));
+        let file = &map.files[0];
+        assert_eq!(file.filename, "file.bin");
+        assert_eq!(file.total_size, 140);
+        assert_eq!(file.groups, ["alt.binaries.misc", "alt.binaries.test"]);
+        let segments: Vec<(u32, &str)> = file
+            .segments
+            .iter()
+            .map(|segment| (segment.number, segment.message_id.as_str()))
+            .collect();
+        assert_eq!(segments, [(1, "a@test"), (2, "b@test")]);
+        assert!(file
+            .segments
+            .iter()
+            .all(|segment| segment.group == "alt.binaries.misc"));
+    }
+
+    #[test]
+    fn test_only_files_par2_vouched_for_are_marked_repaired() {
+        let result = |name: &str, failed: usize| dl_nzb::download::DownloadResult {
+            filename: name.to_string(),
+            path: std::path::PathBuf::from("/dl").join(name),
+            size: 100,
+            segments_downloaded: 10 - failed,
+            segments_failed: failed,
+            download_time: std::time::Duration::ZERO,
+            average_speed: 0.0,
+            failed_message_ids: Vec::new(),
+            failure_reasons: std::collections::HashMap::new(),
+            size_mismatch: false,
+            par2_handoff: false,
+            par2_repaired: false,
+            served_by: std::collections::BTreeMap::new(),
+            named_by_user: false,
+        };
+        let mut results = vec![
+            result("show.part1.rar", 2),
+            result("show.part2.rar", 0),
+            result("extras.rar", 1),
+        ];
+
+        mark_par2_repaired(
+            &mut results,
+            &["show.part1.rar".to_string(), "show.part2.rar".to_string()],
+        );
+
+        assert!(results[0].par2_repaired);
+        // Complete files need no vouching, and another set's file gets none
+        assert!(!results[1].par2_repaired);
+        assert!(!results[2].par2_repaired);
+        assert!(!results[2].is_complete());
+    }
 }
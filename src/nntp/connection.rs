@@ -1,5 +1,5 @@
 use bytes::Bytes;
-use std::sync::Arc;
+use std::sync::{Arc, Once};
 use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
 use tokio::time::{timeout, Duration};
@@ -15,6 +15,36 @@ pub struct AsyncNntpConnection {
     writer: Box<dyn AsyncWrite + Unpin + Send>,
     reader: BufReader<Box<dyn AsyncRead + Unpin + Send>>,
     current_group: Option<String>,
+    /// Whether the server advertised RFC 3977 (VERSION 2), which allows pipelining; cached
+    pipelining: Option<bool>,
+    /// Line buffer reused across article bodies
+    line_buf: Vec<u8>,
+    /// Decode buffer reused across article bodies
+    decoded_buf: Vec<u8>,
+    /// Check yEnc CRCs and retry alternate decodes on mismatch
+    crc_retry: bool,
+    /// Encoded data lines kept for re-decoding when `crc_retry` is on
+    raw_buf: Vec<u8>,
+    /// Realign with a DATE sentinel after a malformed pipelined response
+    resync: bool,
+    /// Bodies in a row that failed size or CRC validation
+    invalid_bodies: u32,
+    /// Consecutive invalid bodies after which the connection counts as degraded (0 = never)
+    invalid_body_limit: u32,
+    /// Part number from the `=ybegin` line of the last body read
+    last_part: Option<u32>,
+    /// Whole-file `size=` from the `=ybegin` line of the last body read
+    last_file_size: Option<u64>,
+    /// File size the bodies of the last pipelined download declared in their yEnc headers
+    declared_file_size: Option<u64>,
+    /// Bodies whose yEnc part number disagreed with their NZB segment number
+    part_mismatches: Vec<PartMismatch>,
+    /// Message-ids the server answered 430 (no such article) for since last taken
+    missing_articles: Vec<String>,
+    /// (segment number, reason) for segments the last pipelined download failed to fetch
+    segment_failures: Vec<(u32, SegmentFailure)>,
+    /// Whether the greeting was 200 (posting allowed) rather than 201
+    posting_allowed: bool,
 }
 
 /// Request for pipelined downloading
@@ -25,6 +55,62 @@ pub struct SegmentRequest {
     pub segment_number: u32,
 }
 
+/// A body whose yEnc header names a different part than its NZB segment number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PartMismatch {
+    /// Segment number from the NZB
+    pub segment: u32,
+    /// Part number from the `=ybegin` line
+    pub part: u32,
+}
+
+/// Why a segment couldn't be downloaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SegmentFailure {
+    /// The server has no such article (430); only a re-post or another server helps
+    Missing,
+    /// The server didn't answer in time
+    Timeout,
+    /// The body's yEnc CRC didn't match under any decoding
+    Crc,
+    /// The body was truncated or otherwise undecodable
+    Decode,
+    /// The connection failed or the response stream went out of sync
+    Connection,
+}
+
+impl SegmentFailure {
+    /// Classify the error a segment failed with
+    pub fn from_error(error: &DlNzbError) -> Self {
+        match error {
+            DlNzbError::Nntp(NntpError::ArticleNotFound { .. }) => Self::Missing,
+            DlNzbError::Nntp(NntpError::Timeout { .. }) => Self::Timeout,
+            DlNzbError::Nntp(NntpError::CrcMismatch { .. }) => Self::Crc,
+            DlNzbError::Nntp(NntpError::YencDecode(_)) => Self::Decode,
+            _ => Self::Connection,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Missing => "missing",
+            Self::Timeout => "timeout",
+            Self::Crc => "crc",
+            Self::Decode => "decode",
+            Self::Connection => "connection",
+        }
+    }
+}
+
+/// A pipelined body: the decoded data, or why it couldn't be fetched
+type BodyResult = (u32, std::result::Result<Bytes, SegmentFailure>);
+
+/// Tracing target for the protocol transcript, enabled with `--trace-nntp`
+pub const TRANSCRIPT_TARGET: &str = "nntp";
+
+/// The cleartext warning is shown once per process rather than for every pooled connection
+static PLAINTEXT_WARNING: Once = Once::new();
+
 impl AsyncNntpConnection {
     /// Create a new NNTP connection with optional shared TLS connector
     ///
@@ -34,10 +120,31 @@ impl AsyncNntpConnection {
         config: &UsenetConfig,
         tls_connector: Option<Arc<TlsConnector>>,
     ) -> Result<Self> {
+        config.check_plaintext_allowed()?;
+        if !config.uses_tls() {
+            PLAINTEXT_WARNING.call_once(|| {
+                tracing::warn!(
+                    "Connecting to {} without TLS: credentials are sent in cleartext",
+                    config.server
+                );
+            });
+        }
+
         let addr = format!("{}:{}", config.server, config.port);
 
+        // Resolve separately so a bad hostname isn't reported as a refused connection
+        let addrs: Vec<std::net::SocketAddr> =
+            timeout(Duration::from_secs(30), tokio::net::lookup_host(&addr))
+                .await
+                .map_err(|_| NntpError::Timeout { seconds: 30 })?
+                .map_err(|e| NntpError::DnsFailed {
+                    server: config.server.clone(),
+                    source: e,
+                })?
+                .collect();
+
         // Connect with timeout
-        let tcp_stream = timeout(Duration::from_secs(30), TcpStream::connect(&addr))
+        let mut tcp_stream = timeout(Duration::from_secs(30), TcpStream::connect(&addrs[..]))
             .await
             .map_err(|_| NntpError::Timeout { seconds: 30 })?
             .map_err(|e| NntpError::ConnectionFailed {
@@ -49,11 +156,18 @@ impl AsyncNntpConnection {
         // Set socket options for better performance
         tcp_stream.set_nodelay(true)?;
 
+        // STARTTLS happens in plaintext, greeting included, before the stream is wrapped
+        let starttls = !config.ssl && config.starttls;
+        let mut posting_allowed = false;
+        if starttls {
+            posting_allowed = negotiate_starttls(&mut tcp_stream).await?;
+        }
+
         // Wrap in TLS if needed
         let (reader, writer): (
             Box<dyn AsyncRead + Unpin + Send>,
             Box<dyn AsyncWrite + Unpin + Send>,
-        ) = if config.ssl {
+        ) = if config.uses_tls() {
             // Use shared connector if provided, otherwise create a new one
             let connector = if let Some(shared_connector) = tls_connector {
                 shared_connector
@@ -68,10 +182,10 @@ impl AsyncNntpConnection {
                 Arc::new(TlsConnector::from(native_connector))
             };
 
-            // Perform TLS handshake
+            // Perform TLS handshake; SNI and hostname checks use tls_sni when set
             let tls_stream = timeout(
                 Duration::from_secs(30),
-                connector.connect(&config.server, tcp_stream),
+                connector.connect(config.tls_host(), tcp_stream),
             )
             .await
             .map_err(|_| NntpError::Timeout { seconds: 30 })?
@@ -92,25 +206,102 @@ impl AsyncNntpConnection {
             writer,
             reader,
             current_group: None,
+            pipelining: None,
+            line_buf: Vec::with_capacity(8 * 1024),
+            decoded_buf: Vec::with_capacity(1024 * 1024),
+            crc_retry: config.yenc_crc_retry,
+            resync: config.pipeline_resync,
+            raw_buf: Vec::new(),
+            invalid_bodies: 0,
+            invalid_body_limit: config.evict_after_invalid_bodies,
+            last_part: None,
+            last_file_size: None,
+            declared_file_size: None,
+            part_mismatches: Vec::new(),
+            missing_articles: Vec::new(),
+            segment_failures: Vec::new(),
+            posting_allowed,
         };
 
-        // Initialize connection
-        conn.initialize(config).await?;
+        // Initialize connection; after STARTTLS the server sends no second greeting
+        conn.initialize(config, !starttls).await?;
 
         Ok(conn)
     }
 
-    async fn initialize(&mut self, config: &UsenetConfig) -> Result<()> {
+    async fn initialize(&mut self, config: &UsenetConfig, read_greeting: bool) -> Result<()> {
         // Read server greeting
-        let response = self.read_response().await?;
-        if !response.starts_with("200") && !response.starts_with("201") {
-            return Err(
-                NntpError::ProtocolError(format!("Server greeting failed: {}", response)).into(),
-            );
+        if read_greeting {
+            let response = self.read_response().await?;
+            self.posting_allowed = check_greeting(&response)?;
         }
 
+        // Switch to reader mode; some servers only accept it once authenticated
+        let reader_mode_pending = config.mode_reader && !self.mode_reader().await?;
+
         // Authenticate
-        self.authenticate(config).await
+        self.authenticate(config).await?;
+
+        if reader_mode_pending {
+            self.mode_reader().await?;
+        }
+
+        for command in &config.post_connect_commands {
+            self.post_connect_command(command).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Send a user-configured command after login
+    ///
+    /// A multi-line response (e.g. to LIST or HELP) is read up to its terminator so the
+    /// connection stays in step. A rejection isn't fatal: as with MODE READER, the server
+    /// may simply not support or need the command.
+    async fn post_connect_command(&mut self, command: &str) -> Result<()> {
+        self.send_command(command).await?;
+        let response = timeout(Duration::from_secs(10), self.read_response())
+            .await
+            .map_err(|_| NntpError::Timeout { seconds: 10 })??;
+
+        if is_multiline_response(command, &response) {
+            timeout(Duration::from_secs(10), self.skip_data_block())
+                .await
+                .map_err(|_| NntpError::Timeout { seconds: 10 })??;
+        } else if !response.starts_with('2') {
+            tracing::warn!("{} not accepted: {}", command, response);
+        }
+
+        Ok(())
+    }
+
+    /// Read past a multi-line data block, up to its terminating `.` line (or EOF)
+    async fn skip_data_block(&mut self) -> Result<()> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = self.reader.read_until(b'\n', &mut line).await?;
+            if read == 0 || line == b".\r\n" || line == b".\n" || line == b"." {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Send MODE READER, returning false if the server requires authentication first
+    async fn mode_reader(&mut self) -> Result<bool> {
+        self.send_command("MODE READER").await?;
+        let response = self.read_response().await?;
+
+        if response.starts_with("200") || response.starts_with("201") {
+            Ok(true)
+        } else if response.starts_with("480") {
+            // 480 = authentication required
+            Ok(false)
+        } else {
+            // Not fatal - the server simply doesn't support or need reader mode
+            tracing::debug!("MODE READER not accepted: {}", response);
+            Ok(true)
+        }
     }
 
     async fn authenticate(&mut self, config: &UsenetConfig) -> Result<()> {
@@ -126,20 +317,10 @@ impl AsyncNntpConnection {
             let response = self.read_response().await?;
 
             if !response.starts_with("281") {
-                // Sanitize response to avoid leaking sensitive info
-                let sanitized = response.split_whitespace().next().unwrap_or("Unknown");
-                return Err(NntpError::AuthFailed(format!(
-                    "Authentication failed ({})",
-                    sanitized
-                ))
-                .into());
+                return Err(classify_auth_failure(&response).into());
             }
         } else if !response.starts_with("281") {
-            // Sanitize response to avoid leaking sensitive info
-            let sanitized = response.split_whitespace().next().unwrap_or("Unknown");
-            return Err(
-                NntpError::AuthFailed(format!("Authentication failed ({})", sanitized)).into(),
-            );
+            return Err(classify_auth_failure(&response).into());
         }
 
         Ok(())
@@ -147,7 +328,44 @@ impl AsyncNntpConnection {
 
     /// Download a segment and return the decoded data
     pub async fn download_segment(&mut self, message_id: &str, group: &str) -> Result<Bytes> {
-        // Select group if different from current
+        self.select_group(group).await?;
+
+        // Request article body
+        self.send_command(&format!("BODY <{}>", message_id)).await?;
+        let mut response = timeout(Duration::from_secs(10), self.read_response())
+            .await
+            .map_err(|_| NntpError::Timeout { seconds: 10 })??;
+
+        // 412 = no newsgroup selected: the server dropped our selection, so select and retry once
+        if response.starts_with("412") {
+            self.current_group = None;
+            self.select_group(group).await?;
+            self.send_command(&format!("BODY <{}>", message_id)).await?;
+            response = timeout(Duration::from_secs(10), self.read_response())
+                .await
+                .map_err(|_| NntpError::Timeout { seconds: 10 })??;
+        }
+
+        if !response.starts_with("222") {
+            if response.starts_with("430") {
+                self.missing_articles.push(message_id.to_string());
+            }
+            return Err(NntpError::ArticleNotFound {
+                message_id: message_id.to_string(),
+            }
+            .into());
+        }
+
+        // Read and decode the body in one pass
+        let decoded = timeout(Duration::from_secs(30), self.read_decoded_body())
+            .await
+            .map_err(|_| NntpError::Timeout { seconds: 30 })??;
+
+        Ok(decoded)
+    }
+
+    /// Select a newsgroup unless it is already the current one
+    async fn select_group(&mut self, group: &str) -> Result<()> {
         if self.current_group.as_deref() != Some(group) {
             self.send_command(&format!("GROUP {}", group)).await?;
             let response = timeout(Duration::from_secs(10), self.read_response())
@@ -162,27 +380,27 @@ impl AsyncNntpConnection {
             self.current_group = Some(group.to_string());
         }
 
-        // Request article body
-        self.send_command(&format!("BODY <{}>", message_id)).await?;
+        Ok(())
+    }
+
+    /// Check whether the server carries a newsgroup by selecting it
+    ///
+    /// On success the group stays selected, so following BODY requests skip the GROUP round-trip.
+    pub async fn group_exists(&mut self, group: &str) -> Result<bool> {
+        self.send_command(&format!("GROUP {}", group)).await?;
         let response = timeout(Duration::from_secs(10), self.read_response())
             .await
             .map_err(|_| NntpError::Timeout { seconds: 10 })??;
-        if !response.starts_with("222") {
-            return Err(NntpError::ArticleNotFound {
-                message_id: message_id.to_string(),
-            }
-            .into());
-        }
-
-        // Read and decode the body
-        let encoded_data = timeout(Duration::from_secs(30), self.read_article_body())
-            .await
-            .map_err(|_| NntpError::Timeout { seconds: 30 })??;
 
-        // Simple yEnc decoding
-        let decoded = self.decode_yenc_simple(&encoded_data)?;
-
-        Ok(Bytes::from(decoded))
+        if response.starts_with("211") {
+            self.current_group = Some(group.to_string());
+            Ok(true)
+        } else if response.starts_with("411") {
+            // 411 = no such newsgroup
+            Ok(false)
+        } else {
+            Err(NntpError::ProtocolError(format!("Unexpected GROUP response: {}", response)).into())
+        }
     }
 
     /// Read article body until termination
@@ -201,8 +419,9 @@ impl AsyncNntpConnection {
                 break; // EOF
             }
 
-            // Check for termination (single dot followed by newline)
-            if line == b".\r\n" || line == b".\n" {
+            // Check for termination (single dot followed by newline, or a bare dot at EOF
+            // from servers that close without the final CRLF)
+            if line == b".\r\n" || line == b".\n" || line == b"." {
                 break;
             }
 
@@ -226,12 +445,169 @@ impl AsyncNntpConnection {
         Ok(body)
     }
 
+    /// Read an article body and yEnc-decode it as lines arrive
+    ///
+    /// Produces the same output as `read_article_body` followed by `decode_yenc_simple`, but
+    /// decodes straight out of a reused line buffer instead of collecting the encoded body first.
+    async fn read_decoded_body(&mut self) -> Result<Bytes> {
+        let mut decoded = std::mem::take(&mut self.decoded_buf);
+        decoded.clear();
+        let mut line = std::mem::take(&mut self.line_buf);
+        let mut raw = std::mem::take(&mut self.raw_buf);
+        raw.clear();
+        let mut in_data = false;
+        let mut finished = false;
+        let mut seen_part = false;
+        let mut header_size = None;
+        let mut expected_crc = None;
+        let mut expected_size = None;
+        self.last_part = None;
+        self.last_file_size = None;
+
+        let result = loop {
+            line.clear();
+
+            let bytes_read = match self.reader.read_until(b'\n', &mut line).await {
+                Ok(n) => n,
+                Err(e) => break Err(e.into()),
+            };
+            if bytes_read == 0 || line == b".\r\n" || line == b".\n" || line == b"." {
+                break Ok(());
+            }
+
+            // After =yend keep draining up to the terminator so the connection stays in sync
+            if finished {
+                continue;
+            }
+
+            let mut content: &[u8] = &line;
+            if content.starts_with(b"..") {
+                content = &content[1..];
+            }
+            if let Some(stripped) = content.strip_suffix(b"\r\n") {
+                content = stripped;
+            } else if let Some(stripped) = content.strip_suffix(b"\n") {
+                content = stripped;
+            }
+
+            if content.starts_with(b"=ybegin") {
+                in_data = true;
+                self.last_part = yenc_header_part(content);
+                header_size = yenc_size(content);
+                self.last_file_size = header_size;
+            } else if content.starts_with(b"=yend") {
+                finished = true;
+                expected_size = yenc_size(content);
+                if self.crc_retry {
+                    // Some encoders drop =ypart from multi-part posts; a part smaller than
+                    // the file its header announces still gives them away
+                    let multipart =
+                        seen_part || (header_size.is_some() && header_size != expected_size);
+                    expected_crc = yenc_trailer_crc(content, multipart);
+                }
+            } else if content.starts_with(b"=ypart") {
+                seen_part = true;
+                continue;
+            } else if in_data && !content.is_empty() {
+                Self::decode_yenc_line_simd(content, &mut decoded);
+                if self.crc_retry {
+                    raw.extend_from_slice(content);
+                    raw.push(b'\n');
+                }
+            }
+        };
+
+        let result = match (result, expected_crc) {
+            (Ok(()), Some(expected)) if crc32(&decoded) != expected => {
+                Self::redecode_matching_crc(&raw, expected)
+                    .map(Bytes::from)
+                    .ok_or_else(|| NntpError::CrcMismatch { expected }.into())
+            }
+            (result, _) => result.map(|()| Bytes::copy_from_slice(&decoded)),
+        };
+
+        // A body cut short on the wire decodes to fewer bytes than its trailer declares
+        let result = match (result, expected_size) {
+            (Ok(decoded), Some(size)) if decoded.len() as u64 != size => {
+                Err(NntpError::YencDecode(format!(
+                    "yEnc body truncated ({} of {} bytes)",
+                    decoded.len(),
+                    size
+                ))
+                .into())
+            }
+            (result, _) => result,
+        };
+
+        // Only validation failures count towards degrading the connection, not I/O errors
+        match &result {
+            Ok(_) => self.invalid_bodies = 0,
+            Err(DlNzbError::Nntp(NntpError::CrcMismatch { .. } | NntpError::YencDecode(_))) => {
+                self.invalid_bodies += 1
+            }
+            Err(_) => {}
+        }
+
+        self.line_buf = line;
+        self.raw_buf = raw;
+        self.decoded_buf = decoded;
+        result
+    }
+
+    /// Whether the server allows posting on this connection (200 greeting, not 201)
+    ///
+    /// dl-nzb only downloads; this is recorded for features that care, such as health checks.
+    pub fn posting_allowed(&self) -> bool {
+        self.posting_allowed
+    }
+
+    /// Whether recent bodies have consistently failed validation on this connection
+    ///
+    /// A route that truncates everything it carries poisons every segment it touches;
+    /// the pool replaces a degraded connection instead of handing it out again.
+    pub fn is_degraded(&self) -> bool {
+        self.invalid_body_limit > 0 && self.invalid_bodies >= self.invalid_body_limit
+    }
+
+    /// Segments of the last pipelined download whose yEnc part number differed from their
+    /// NZB segment number
+    ///
+    /// Some NZB generators number segments wrongly; the yEnc header written by the poster
+    /// is what tells where the data really belongs.
+    pub fn take_part_mismatches(&mut self) -> Vec<PartMismatch> {
+        std::mem::take(&mut self.part_mismatches)
+    }
+
+    /// Segments the last pipelined download failed to fetch, with the reason for each
+    pub fn take_segment_failures(&mut self) -> Vec<(u32, SegmentFailure)> {
+        std::mem::take(&mut self.segment_failures)
+    }
+
+    /// Message-ids answered 430 (no such article) by BODY or STAT since the last call
+    pub fn take_missing_articles(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.missing_articles)
+    }
+
+    /// Re-decode a body's data lines under looser yEnc interpretations
+    ///
+    /// Some encoders leave bare CRs in the data or split an escape across a line break.
+    /// Returns the first interpretation whose CRC matches the trailer.
+    fn redecode_matching_crc(raw: &[u8], expected: u32) -> Option<Vec<u8>> {
+        [(true, false), (false, true), (true, true)]
+            .into_iter()
+            .map(|(keep_cr, join_escapes)| decode_yenc_variant(raw, keep_cr, join_escapes))
+            .find(|decoded| crc32(decoded) == expected)
+    }
+
     /// SIMD-accelerated yEnc decoder
     ///
+    /// Two-stage reference for `read_decoded_body`, which the download paths use.
+    ///
     /// Uses vectorized operations to decode 16-32 bytes at a time on supported platforms:
     /// - x86_64: SSE2 (always available on 64-bit x86)
     /// - aarch64: NEON (always available on 64-bit ARM)
     /// - Fallback: Optimized scalar for other platforms
+    #[cfg(test)]
     fn decode_yenc_simple(&self, data: &[u8]) -> Result<Vec<u8>> {
         // Pre-allocate based on expected output size
         let mut decoded = Vec::with_capacity(data.len());
@@ -370,6 +746,7 @@ impl AsyncNntpConnection {
     }
 
     async fn send_command(&mut self, command: &str) -> Result<()> {
+        tracing::trace!(target: TRANSCRIPT_TARGET, "> {}", redact_command(command));
         self.writer.write_all(command.as_bytes()).await?;
         self.writer.write_all(b"\r\n").await?;
         self.writer.flush().await?;
@@ -387,6 +764,7 @@ impl AsyncNntpConnection {
             response.truncate(response.len() - 1);
         }
 
+        tracing::trace!(target: TRANSCRIPT_TARGET, "< {}", response);
         Ok(response)
     }
 
@@ -401,6 +779,40 @@ impl AsyncNntpConnection {
         }
     }
 
+    /// Whole-file size the yEnc headers of the last pipelined download declared
+    ///
+    /// NZB segment sizes count encoded bytes; this is the decoded size the poster announced.
+    pub fn take_declared_file_size(&mut self) -> Option<u64> {
+        self.declared_file_size.take()
+    }
+
+    /// Query the server's CAPABILITIES list
+    ///
+    /// Returns an empty list for servers that predate RFC 3977 and don't support the command.
+    pub async fn capabilities(&mut self) -> Result<Vec<String>> {
+        self.send_command("CAPABILITIES").await?;
+        let response = timeout(Duration::from_secs(10), self.read_response())
+            .await
+            .map_err(|_| NntpError::Timeout { seconds: 10 })??;
+        if !response.starts_with("101") {
+            return Ok(Vec::new());
+        }
+
+        let mut capabilities = Vec::new();
+        loop {
+            let line = timeout(Duration::from_secs(10), self.read_response())
+                .await
+                .map_err(|_| NntpError::Timeout { seconds: 10 })??;
+            // Stop at the terminator (or EOF, which reads as an empty line)
+            if line == "." || line.is_empty() {
+                break;
+            }
+            capabilities.push(line);
+        }
+
+        Ok(capabilities)
+    }
+
     /// Download multiple segments using pipelining for maximum throughput
     ///
     /// This sends multiple BODY commands before waiting for responses,
@@ -415,22 +827,54 @@ impl AsyncNntpConnection {
 
         // Switch to the group if needed (all requests should be from same group)
         let group = &requests[0].group;
-        if self.current_group.as_deref() != Some(group) {
-            self.send_command(&format!("GROUP {}", group)).await?;
-            let response = timeout(Duration::from_secs(10), self.read_response())
-                .await
-                .map_err(|_| NntpError::Timeout { seconds: 10 })??;
-            if !response.starts_with("211") {
-                return Err(NntpError::GroupNotFound {
-                    group: group.to_string(),
-                }
-                .into());
+        self.segment_failures.clear();
+        self.select_group(group).await?;
+
+        self.part_mismatches.clear();
+        self.declared_file_size = None;
+        let (mut results, retry) = self.pipeline_bodies(requests).await?;
+
+        // 412 = no newsgroup selected: the server dropped our selection mid-pipeline.
+        // Requests discarded by a resync are retried the same way; select the group again
+        // and retry just those requests once.
+        if !retry.is_empty() {
+            tracing::debug!(
+                "{} request(s) need a retry, re-selecting group {}",
+                retry.len(),
+                group
+            );
+            self.current_group = None;
+            self.select_group(group).await?;
+
+            let retry_requests: Vec<SegmentRequest> =
+                retry.iter().map(|&i| requests[i].clone()).collect();
+            let (retried, _) = self.pipeline_bodies(&retry_requests).await?;
+            for (&i, result) in retry.iter().zip(retried) {
+                results[i] = result;
             }
-            self.current_group = Some(group.to_string());
         }
 
+        self.segment_failures = results
+            .iter()
+            .filter_map(|(number, result)| result.as_ref().err().map(|&reason| (*number, reason)))
+            .collect();
+        Ok(results
+            .into_iter()
+            .map(|(number, result)| (number, result.ok()))
+            .collect())
+    }
+
+    /// Send BODY for every request, then read the responses in order
+    ///
+    /// Also returns the indices of requests to retry: those answered with 412, which carry
+    /// no body, and those whose responses were discarded by a resync.
+    async fn pipeline_bodies(
+        &mut self,
+        requests: &[SegmentRequest],
+    ) -> Result<(Vec<BodyResult>, Vec<usize>)> {
         // Pipeline all BODY requests - send them all without waiting
         for req in requests {
+            tracing::trace!(target: TRANSCRIPT_TARGET, "> BODY <{}>", req.message_id);
             self.writer
                 .write_all(format!("BODY <{}>\r\n", req.message_id).as_bytes())
                 .await?;
@@ -439,13 +883,24 @@ impl AsyncNntpConnection {
 
         // Now read all responses in order
         let mut results = Vec::with_capacity(requests.len());
+        let mut retry = Vec::new();
+        let mut desynced_at = None;
 
-        for req in requests {
+        for (i, req) in requests.iter().enumerate() {
             // Read response code
             let response = match timeout(Duration::from_secs(10), self.read_response()).await {
                 Ok(Ok(r)) => r,
-                _ => {
-                    results.push((req.segment_number, None));
+                failed => {
+                    let reason = match failed {
+                        Err(_) => SegmentFailure::Timeout,
+                        _ => SegmentFailure::Connection,
+                    };
+                    if self.resync {
+                        // The response may still arrive and be taken for the next one
+                        desynced_at = Some((i, reason));
+                        break;
+                    }
+                    results.push((req.segment_number, Err(reason)));
                     continue;
                 }
             };
@@ -453,38 +908,158 @@ impl AsyncNntpConnection {
             if !response.starts_with("222") {
                 // Article not found or error - we still need to read the body if server sent one
                 // to keep the connection in sync for remaining pipelined responses
-                if response.starts_with("430") || response.starts_with("423") {
+                if response.starts_with("412") {
+                    // 412 = no newsgroup selected, no body follows; the caller retries these
+                    retry.push(i);
+                    results.push((req.segment_number, Err(SegmentFailure::Connection)));
+                    continue;
+                } else if response.starts_with("430") || response.starts_with("423") {
                     // 430 = no such article, 423 = no such article number
                     // These don't send a body, safe to skip
-                    results.push((req.segment_number, None));
+                    if response.starts_with("430") {
+                        self.missing_articles.push(req.message_id.clone());
+                    }
+                    results.push((req.segment_number, Err(SegmentFailure::Missing)));
                     continue;
+                } else if self.resync {
+                    tracing::debug!("Unexpected BODY response, resyncing: {}", response);
+                    desynced_at = Some((i, SegmentFailure::Connection));
+                    break;
                 } else {
                     // Unknown response, try to read body anyway to avoid desync
                     let _ = timeout(Duration::from_secs(30), self.read_article_body()).await;
-                    results.push((req.segment_number, None));
+                    results.push((req.segment_number, Err(SegmentFailure::Connection)));
                     continue;
                 }
             }
 
             // Read and decode the body
-            let encoded_data =
-                match timeout(Duration::from_secs(30), self.read_article_body()).await {
-                    Ok(Ok(data)) => data,
-                    _ => {
-                        results.push((req.segment_number, None));
-                        continue;
+            match timeout(Duration::from_secs(30), self.read_decoded_body()).await {
+                Ok(Ok(decoded)) => {
+                    if let Some(part) = self.last_part.filter(|&p| p != req.segment_number) {
+                        self.part_mismatches.push(PartMismatch {
+                            segment: req.segment_number,
+                            part,
+                        });
                     }
-                };
-
-            // Decode yEnc
-            match self.decode_yenc_simple(&encoded_data) {
-                Ok(decoded) => {
-                    results.push((req.segment_number, Some(Bytes::from(decoded))));
+                    if self.last_file_size.is_some() {
+                        self.declared_file_size = self.last_file_size;
+                    }
+                    results.push((req.segment_number, Ok(decoded)));
+                }
+                Err(_) if self.resync => {
+                    // Timed out mid-body; the rest of it would be read as the next response
+                    desynced_at = Some((i, SegmentFailure::Timeout));
+                    break;
                 }
                 Err(_) => {
-                    results.push((req.segment_number, None));
+                    results.push((req.segment_number, Err(SegmentFailure::Timeout)));
+                }
+                Ok(Err(e)) => {
+                    results.push((req.segment_number, Err(SegmentFailure::from_error(&e))));
+                }
+            }
+        }
+
+        // Everything after the bad response is unreliable: realign on the DATE reply, fail
+        // the request that went wrong and retry the ones whose responses were discarded
+        if let Some((i, reason)) = desynced_at {
+            self.resync_with_date().await?;
+            results.push((requests[i].segment_number, Err(reason)));
+            for (j, req) in requests.iter().enumerate().skip(i + 1) {
+                retry.push(j);
+                results.push((req.segment_number, Err(SegmentFailure::Connection)));
+            }
+        }
+
+        Ok((results, retry))
+    }
+
+    /// Realign the response stream by sending DATE and discarding everything before its reply
+    ///
+    /// DATE answers with a single `111 yyyymmddhhmmss` line, which is easy to recognise among
+    /// leftover body lines and status codes from earlier pipelined requests.
+    async fn resync_with_date(&mut self) -> Result<()> {
+        self.send_command("DATE").await?;
+
+        let realign = async {
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                if self.reader.read_until(b'\n', &mut line).await? == 0 {
+                    return Err(NntpError::ProtocolError(
+                        "Connection closed while resyncing".to_string(),
+                    )
+                    .into());
+                }
+                if is_date_response(&line) {
+                    return Ok(());
                 }
             }
+        };
+
+        timeout(Duration::from_secs(30), realign)
+            .await
+            .map_err(|_| NntpError::Timeout { seconds: 30 })?
+    }
+
+    /// Check which articles exist by message-id
+    ///
+    /// Overview (XOVER/XZVER) lookups are keyed by article number, which NZBs don't carry.
+    /// STATs are pipelined when the server advertises RFC 3977 and sent one at a time otherwise.
+    pub async fn stat_articles(&mut self, message_ids: &[String]) -> Result<Vec<bool>> {
+        if self.supports_pipelining().await? {
+            return self.stat_articles_pipelined(message_ids).await;
+        }
+
+        let mut results = Vec::with_capacity(message_ids.len());
+        for message_id in message_ids {
+            self.send_command(&format!("STAT <{}>", message_id)).await?;
+            let response = timeout(Duration::from_secs(10), self.read_response())
+                .await
+                .map_err(|_| NntpError::Timeout { seconds: 10 })??;
+            if response.starts_with("430") {
+                self.missing_articles.push(message_id.clone());
+            }
+            results.push(response.starts_with("223"));
+        }
+
+        Ok(results)
+    }
+
+    /// Whether the server is RFC 3977 compliant and so must accept pipelined commands
+    async fn supports_pipelining(&mut self) -> Result<bool> {
+        if let Some(supported) = self.pipelining {
+            return Ok(supported);
+        }
+        let supported = advertises_rfc3977(&self.capabilities().await?);
+        self.pipelining = Some(supported);
+        Ok(supported)
+    }
+
+    /// Check which articles exist using pipelined STAT commands
+    ///
+    /// All STATs are sent before reading any reply; callers should go through
+    /// [`stat_articles`](Self::stat_articles) unless the server is known to support pipelining.
+    pub async fn stat_articles_pipelined(&mut self, message_ids: &[String]) -> Result<Vec<bool>> {
+        for message_id in message_ids {
+            tracing::trace!(target: TRANSCRIPT_TARGET, "> STAT <{}>", message_id);
+            self.writer
+                .write_all(format!("STAT <{}>\r\n", message_id).as_bytes())
+                .await?;
+        }
+        self.writer.flush().await?;
+
+        let mut results = Vec::with_capacity(message_ids.len());
+        for message_id in message_ids {
+            let response = timeout(Duration::from_secs(10), self.read_response())
+                .await
+                .map_err(|_| NntpError::Timeout { seconds: 10 })??;
+            // 223 = article exists, 430 = no such article
+            if response.starts_with("430") {
+                self.missing_articles.push(message_id.clone());
+            }
+            results.push(response.starts_with("223"));
         }
 
         Ok(results)
@@ -498,3 +1073,755 @@ impl AsyncNntpConnection {
         Ok(())
     }
 }
+
+/// Check a CAPABILITIES list for protocol version 2 (RFC 3977)
+fn advertises_rfc3977(capabilities: &[String]) -> bool {
+    capabilities.iter().any(|line| {
+        let mut words = line.split_whitespace();
+        words
+            .next()
+            .is_some_and(|w| w.eq_ignore_ascii_case("VERSION"))
+            && words.any(|v| v == "2")
+    })
+}
+
+/// Check the server's greeting, returning whether posting is allowed (200) or not (201)
+///
+/// A 502 or 400 whose text asks to come back later is the provider turning away a
+/// connection over its limit rather than a protocol problem. Any other 502 is access being
+/// refused outright, e.g. from an address the provider doesn't serve, which retrying won't
+/// fix. Any other 4xx means the server is temporarily unable to serve us, which is worth
+/// retrying, unlike a malformed greeting.
+fn check_greeting(response: &str) -> Result<bool> {
+    if response.starts_with("200") || response.starts_with("201") {
+        Ok(response.starts_with("200"))
+    } else if (response.starts_with("502") || response.starts_with("400"))
+        && is_throttle_response(response)
+    {
+        Err(NntpError::TooManyConnections {
+            code: response[..3].to_string(),
+            retry_after: retry_hint(response),
+        }
+        .into())
+    } else if response.starts_with("502") {
+        Err(NntpError::AuthFailed("Access denied by server (502)".to_string()).into())
+    } else if response.starts_with('4') && response.len() >= 3 {
+        Err(NntpError::ServerBusy {
+            code: response[..3].to_string(),
+            retry_after: retry_hint(response),
+        }
+        .into())
+    } else {
+        Err(NntpError::ProtocolError(format!("Server greeting failed: {}", response)).into())
+    }
+}
+
+/// Upgrade a plaintext connection with STARTTLS (RFC 4642)
+///
+/// Reads the greeting, checks CAPABILITIES for STARTTLS when the server supports the
+/// command, then waits for the 382 go-ahead. The server sends nothing more until the TLS
+/// handshake, so no buffered data is lost when the reader is dropped. Returns whether the
+/// greeting allowed posting. The exchange goes to the transcript like any other.
+async fn negotiate_starttls(stream: &mut TcpStream) -> Result<bool> {
+    let (read_half, mut write_half) = stream.split();
+    let mut reader = BufReader::new(read_half);
+
+    let posting_allowed = check_greeting(&read_line_timeout(&mut reader).await?)?;
+
+    write_line(&mut write_half, "CAPABILITIES").await?;
+    if read_line_timeout(&mut reader).await?.starts_with("101") {
+        let mut offered = false;
+        loop {
+            let line = read_line_timeout(&mut reader).await?;
+            if line == "." || line.is_empty() {
+                break;
+            }
+            offered |= line.eq_ignore_ascii_case("STARTTLS");
+        }
+        if !offered {
+            return Err(NntpError::TlsError("Server does not offer STARTTLS".to_string()).into());
+        }
+    }
+
+    write_line(&mut write_half, "STARTTLS").await?;
+    let response = read_line_timeout(&mut reader).await?;
+    if !response.starts_with("382") {
+        return Err(NntpError::TlsError(format!("STARTTLS refused: {}", response)).into());
+    }
+
+    Ok(posting_allowed)
+}
+
+/// Send one command line before the connection is wrapped, logging it to the transcript
+async fn write_line<W: AsyncWrite + Unpin>(writer: &mut W, command: &str) -> Result<()> {
+    tracing::trace!(target: TRANSCRIPT_TARGET, "> {}", command);
+    writer
+        .write_all(format!("{}\r\n", command).as_bytes())
+        .await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Read one response line without its line ending, giving up after 30 seconds
+async fn read_line_timeout<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> Result<String> {
+    let mut line = String::new();
+    timeout(Duration::from_secs(30), reader.read_line(&mut line))
+        .await
+        .map_err(|_| NntpError::Timeout { seconds: 30 })??;
+    let line = line.trim_end_matches(['\r', '\n']).to_string();
+    tracing::trace!(target: TRANSCRIPT_TARGET, "< {}", line);
+    Ok(line)
+}
+
+/// Whether a line is a DATE reply: `111` followed by a 14-digit timestamp
+fn is_date_response(line: &[u8]) -> bool {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    line.len() == 18 && line.starts_with(b"111 ") && line[4..].iter().all(|b| b.is_ascii_digit())
+}
+
+/// Scalar yEnc decode of newline-separated data lines with configurable strictness
+///
+/// `keep_cr` decodes bare CRs as data instead of dropping them; `join_escapes` applies an
+/// `=` at the end of a line to the first byte of the next line.
+fn decode_yenc_variant(raw: &[u8], keep_cr: bool, join_escapes: bool) -> Vec<u8> {
+    let mut output = Vec::with_capacity(raw.len());
+    let mut escaped = false;
+
+    for line in raw.split(|&b| b == b'\n') {
+        if !join_escapes {
+            escaped = false;
+        }
+        for &byte in line {
+            if escaped {
+                output.push(byte.wrapping_sub(64).wrapping_sub(42));
+                escaped = false;
+            } else if byte == b'=' {
+                escaped = true;
+            } else if byte != b'\r' || keep_cr {
+                output.push(byte.wrapping_sub(42));
+            }
+        }
+    }
+
+    output
+}
+
+/// Read the part CRC from an `=yend` line
+///
+/// Single-part posts (no `=ypart`) may only carry the whole-file `crc32`, which equals the part CRC.
+fn yenc_trailer_crc(line: &[u8], multipart: bool) -> Option<u32> {
+    let text = std::str::from_utf8(line).ok()?;
+    let value = |key: &str| {
+        text.split_whitespace()
+            .find_map(|field| field.strip_prefix(key))
+            .and_then(|hex| u32::from_str_radix(hex.get(..8).unwrap_or(hex), 16).ok())
+    };
+
+    value("pcrc32=").or_else(|| if multipart { None } else { value("crc32=") })
+}
+
+/// Read the part number from an `=ybegin` line (absent for single-part posts)
+fn yenc_header_part(line: &[u8]) -> Option<u32> {
+    std::str::from_utf8(line)
+        .ok()?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("part="))
+        .and_then(|part| part.parse().ok())
+}
+
+/// Read the `size=` field of an `=ybegin` line (whole file) or `=yend` line (this part)
+fn yenc_size(line: &[u8]) -> Option<u64> {
+    std::str::from_utf8(line)
+        .ok()?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("size="))
+        .and_then(|size| size.parse().ok())
+}
+
+/// CRC-32 as used by yEnc trailers
+pub(super) fn crc32(data: &[u8]) -> u32 {
+    crc32fast::hash(data)
+}
+
+/// Whether a response is followed by a multi-line data block (RFC 3977 section 3.2)
+///
+/// 211 only starts one as the answer to LISTGROUP; after GROUP it is a single line.
+fn is_multiline_response(command: &str, response: &str) -> bool {
+    match response.get(..3).unwrap_or("") {
+        "100" | "101" | "215" | "220" | "221" | "222" | "224" | "225" | "230" | "231" | "282" => {
+            true
+        }
+        "211" => command
+            .split_whitespace()
+            .next()
+            .is_some_and(|verb| verb.eq_ignore_ascii_case("LISTGROUP")),
+        _ => false,
+    }
+}
+
+/// Hide AUTHINFO arguments (user name, password, SASL data) in the transcript
+fn redact_command(command: &str) -> std::borrow::Cow<'_, str> {
+    let mut words = command.split_whitespace();
+    match (words.next(), words.next()) {
+        (Some(verb), Some(kind)) if verb.eq_ignore_ascii_case("AUTHINFO") => {
+            format!("{} {} <redacted>", verb, kind).into()
+        }
+        _ => command.into(),
+    }
+}
+
+/// Map a failed AUTHINFO response to a specific error
+///
+/// 502 means the account is at its connection limit. 481/482 are normally plain rejections,
+/// but providers also use them for rate limiting, which is told apart by the response text.
+fn classify_auth_failure(response: &str) -> NntpError {
+    // Only the status code is reported, to avoid leaking sensitive info
+    let code = response.split_whitespace().next().unwrap_or("Unknown");
+    let retry_after = retry_hint(response);
+
+    match code {
+        "502" => NntpError::TooManyConnections {
+            code: code.to_string(),
+            retry_after,
+        },
+        "481" | "482" if is_throttle_response(response) => NntpError::AuthThrottled {
+            code: code.to_string(),
+            retry_after,
+        },
+        _ => NntpError::AuthFailed(format!("Authentication failed ({})", code)),
+    }
+}
+
+/// Whether a response's text reads like rate limiting rather than a hard failure
+fn is_throttle_response(response: &str) -> bool {
+    let text = response.to_lowercase();
+    ["throttl", "too many", "try again", "later", "limit"]
+        .iter()
+        .any(|hint| text.contains(hint))
+}
+
+/// Longest wait taken from a server's retry hint, so a bogus value can't stall a download
+const MAX_RETRY_HINT: Duration = Duration::from_secs(15 * 60);
+
+/// Parse how long to wait from a throttling response such as "try again in 30 seconds"
+///
+/// There's no formal Retry-After in NNTP, so this looks for a number followed by a time
+/// unit ("30s", "2 minutes"), or a bare number right after "after" ("retry after 60"),
+/// which is taken as seconds.
+pub(crate) fn retry_hint(response: &str) -> Option<Duration> {
+    let text = response.to_lowercase();
+    // Skip the status code
+    let words: Vec<&str> = text
+        .split_whitespace()
+        .skip(1)
+        .map(|word| word.trim_matches(|c: char| !c.is_ascii_alphanumeric()))
+        .collect();
+
+    for (i, word) in words.iter().enumerate() {
+        let digits = word.len() - word.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 {
+            continue;
+        }
+        let Ok(amount) = word[..digits].parse::<u64>() else {
+            continue;
+        };
+        let unit = match &word[digits..] {
+            "" => words.get(i + 1).copied().unwrap_or(""),
+            suffix => suffix,
+        };
+        let seconds = match unit {
+            "s" | "sec" | "secs" | "second" | "seconds" => amount,
+            "m" | "min" | "mins" | "minute" | "minutes" => amount.saturating_mul(60),
+            "h" | "hr" | "hour" | "hours" => amount.saturating_mul(3600),
+            _ if i > 0 && words[i - 1] == "after" && word.len() == digits => amount,
+            _ => continue,
+        };
+        return Some(Duration::from_secs(seconds).min(MAX_RETRY_HINT));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a connection that reads canned server output
+    fn connection_with_input(input: &[u8]) -> AsyncNntpConnection {
+        let reader: Box<dyn AsyncRead + Unpin + Send> =
+            Box::new(std::io::Cursor::new(input.to_vec()));
+        AsyncNntpConnection {
+            writer: Box::new(tokio::io::sink()),
+            reader: BufReader::new(reader),
+            current_group: None,
+            pipelining: None,
+            line_buf: Vec::new(),
+            decoded_buf: Vec::new(),
+            crc_retry: false,
+            raw_buf: Vec::new(),
+            resync: true,
+            invalid_bodies: 0,
+            invalid_body_limit: 0,
+            last_part: None,
+            last_file_size: None,
+            declared_file_size: None,
+            part_mismatches: Vec::new(),
+            missing_articles: Vec::new(),
+            segment_failures: Vec::new(),
+            posting_allowed: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_post_connect_command_skips_multiline_responses() {
+        let mut conn = connection_with_input(
+            b"215 list follows\r\nalt.binaries.test 2 1 y\r\n\r\n.\r\n\
+              211 3 1 3 alt.binaries.test\r\n\
+              211 3 1 3 alt.binaries.test list follows\r\n1\r\n2\r\n3\r\n.\r\n\
+              500 what?\r\n\
+              200 ok\r\n",
+        );
+
+        conn.post_connect_command("LIST").await.unwrap();
+        conn.post_connect_command("GROUP alt.binaries.test")
+            .await
+            .unwrap();
+        conn.post_connect_command("LISTGROUP alt.binaries.test")
+            .await
+            .unwrap();
+        conn.post_connect_command("XFOO").await.unwrap();
+        assert_eq!(conn.read_response().await.unwrap(), "200 ok");
+    }
+
+    #[tokio::test]
+    async fn test_body_terminated_by_bare_dot_at_eof() {
+        let mut conn = connection_with_input(b"line one\r\n..dotted\r\n.");
+
+        let body = timeout(Duration::from_secs(1), conn.read_article_body())
+            .await
+            .expect("bare dot at EOF should terminate without waiting for the body timeout")
+            .unwrap();
+
+        assert_eq!(body, b"line one\n.dotted\n");
+    }
+
+    #[tokio::test]
+    async fn test_streaming_decode_matches_two_stage() {
+        let mut input = Vec::new();
+        input.extend_from_slice(b"=ybegin part=1 line=128 size=18 name=test.bin\r\n");
+        input.extend_from_slice(b"=ypart begin=1 end=18\r\n");
+        // 19, 214 and 224 encode to '=', NUL and LF, which must be escaped
+        let raw: Vec<u8> = (0u8..12).chain([19, 214, 224]).collect();
+        let mut data_line = Vec::new();
+        for &b in &raw {
+            let enc = b.wrapping_add(42);
+            if matches!(enc, 0 | b'\n' | b'\r' | b'=') {
+                data_line.push(b'=');
+                data_line.push(enc.wrapping_add(64));
+            } else {
+                data_line.push(enc);
+            }
+        }
+        input.extend_from_slice(&data_line);
+        input.extend_from_slice(b"\r\n");
+        // Dot-stuffed data line ('.' decodes to 4)
+        input.extend_from_slice(b"..*+\r\n");
+        input.extend_from_slice(b"=yend size=18 part=1 pcrc32=00000000\r\n");
+        input.extend_from_slice(b"trailing junk\r\n.\r\n");
+
+        let mut two_stage = connection_with_input(&input);
+        let body = two_stage.read_article_body().await.unwrap();
+        let expected = two_stage.decode_yenc_simple(&body).unwrap();
+
+        let mut streaming = connection_with_input(&input);
+        let decoded = streaming.read_decoded_body().await.unwrap();
+
+        assert_eq!(decoded, expected);
+        assert_eq!(&decoded[..raw.len()], &raw[..]);
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_records_yenc_part_mismatch() {
+        let mut input = Vec::new();
+        input.extend_from_slice(b"211 2 1 2 alt.test\r\n");
+        for part in [2, 2] {
+            input.extend_from_slice(b"222 body\r\n");
+            input.extend_from_slice(
+                format!("=ybegin part={} line=128 size=2 name=x\r\n", part).as_bytes(),
+            );
+            input.extend_from_slice(b"=ypart begin=2 end=2\r\n+\r\n=yend size=1 part=2\r\n.\r\n");
+        }
+        let mut conn = connection_with_input(&input);
+
+        let requests: Vec<SegmentRequest> = [1, 2]
+            .into_iter()
+            .map(|n| SegmentRequest {
+                message_id: format!("seg{}@test", n),
+                group: "alt.test".to_string(),
+                segment_number: n,
+            })
+            .collect();
+        let results = conn.download_segments_pipelined(&requests).await.unwrap();
+
+        assert!(results.iter().all(|(_, data)| data.is_some()));
+        assert_eq!(
+            conn.take_part_mismatches(),
+            vec![PartMismatch {
+                segment: 1,
+                part: 2,
+            }]
+        );
+        assert!(conn.take_part_mismatches().is_empty());
+        assert_eq!(conn.take_declared_file_size(), Some(2));
+        assert_eq!(conn.take_declared_file_size(), None);
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_reports_failure_reasons() {
+        let mut input = Vec::new();
+        input.extend_from_slice(b"211 2 1 2 alt.test\r\n");
+        input.extend_from_slice(b"430 no such article\r\n");
+        input.extend_from_slice(b"222 body\r\n=ybegin line=128 size=3 name=x\r\n+\r\n");
+        input.extend_from_slice(b"=yend size=3\r\n.\r\n");
+        let mut conn = connection_with_input(&input);
+
+        let requests: Vec<SegmentRequest> = [1, 2]
+            .into_iter()
+            .map(|n| SegmentRequest {
+                message_id: format!("seg{}@test", n),
+                group: "alt.test".to_string(),
+                segment_number: n,
+            })
+            .collect();
+        conn.download_segments_pipelined(&requests).await.unwrap();
+
+        assert_eq!(
+            conn.take_segment_failures(),
+            vec![(1, SegmentFailure::Missing), (2, SegmentFailure::Decode)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_records_articles_answered_430() {
+        let mut input = Vec::new();
+        input.extend_from_slice(b"211 2 1 2 alt.test\r\n");
+        input.extend_from_slice(b"430 no such article\r\n");
+        input.extend_from_slice(b"423 no such article number\r\n");
+        input.extend_from_slice(b"223 0 <seg1@test>\r\n430 no such article\r\n");
+        let mut conn = connection_with_input(&input);
+
+        let requests: Vec<SegmentRequest> = [1, 2]
+            .into_iter()
+            .map(|n| SegmentRequest {
+                message_id: format!("seg{}@test", n),
+                group: "alt.test".to_string(),
+                segment_number: n,
+            })
+            .collect();
+        let results = conn.download_segments_pipelined(&requests).await.unwrap();
+        assert!(results.iter().all(|(_, data)| data.is_none()));
+        assert_eq!(conn.take_missing_articles(), vec!["seg1@test".to_string()]);
+
+        let ids = vec!["seg1@test".to_string(), "seg3@test".to_string()];
+        let exists = conn.stat_articles_pipelined(&ids).await.unwrap();
+        assert_eq!(exists, vec![true, false]);
+        assert_eq!(conn.take_missing_articles(), vec!["seg3@test".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_retries_after_412() {
+        let body = b"=ybegin line=128 size=1 name=x\r\n+\r\n=yend size=1\r\n.\r\n";
+        let mut input = Vec::new();
+        input.extend_from_slice(b"412 No newsgroup selected\r\n");
+        input.extend_from_slice(b"222 0 <b@test>\r\n");
+        input.extend_from_slice(body);
+        // Group re-selection, then the retried BODY
+        input.extend_from_slice(b"211 10 1 10 alt.binaries.test\r\n");
+        input.extend_from_slice(b"222 0 <a@test>\r\n");
+        input.extend_from_slice(body);
+
+        let mut conn = connection_with_input(&input);
+        conn.current_group = Some("alt.binaries.test".to_string());
+        let requests: Vec<SegmentRequest> = ["a@test", "b@test"]
+            .iter()
+            .enumerate()
+            .map(|(i, id)| SegmentRequest {
+                message_id: id.to_string(),
+                group: "alt.binaries.test".to_string(),
+                segment_number: i as u32 + 1,
+            })
+            .collect();
+
+        let results = conn.download_segments_pipelined(&requests).await.unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, 1);
+        assert_eq!(results[0].1.as_deref(), Some(&[1u8][..]));
+        assert_eq!(results[1].1.as_deref(), Some(&[1u8][..]));
+        assert_eq!(conn.take_declared_file_size(), Some(1));
+        assert_eq!(conn.take_declared_file_size(), None);
+    }
+
+    #[tokio::test]
+    async fn test_decode_buffer_reused_between_bodies() {
+        // A long body followed by a short one on the same connection
+        let mut input = Vec::new();
+        input.extend_from_slice(b"=ybegin line=128 size=6 name=a.bin\r\n");
+        input.extend_from_slice(b"[[[[[[\r\n=yend size=6\r\n.\r\n");
+        input.extend_from_slice(b"=ybegin line=128 size=2 name=b.bin\r\n");
+        input.extend_from_slice(b"++\r\n=yend size=2\r\n.\r\n");
+
+        let mut conn = connection_with_input(&input);
+        let first = conn.read_decoded_body().await.unwrap();
+        let second = conn.read_decoded_body().await.unwrap();
+
+        // '[' and '+' decode to 49 and 1; nothing from the first body leaks into the second
+        assert_eq!(first, vec![49u8; 6]);
+        assert_eq!(second, vec![1u8; 2]);
+    }
+
+    #[test]
+    fn test_advertises_rfc3977() {
+        let caps = |lines: &[&str]| lines.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        assert!(advertises_rfc3977(&caps(&["VERSION 2", "READER"])));
+        assert!(advertises_rfc3977(&caps(&["READER", "VERSION 2 3"])));
+        assert!(!advertises_rfc3977(&caps(&["READER", "POST"])));
+        assert!(!advertises_rfc3977(&caps(&["VERSION 3"])));
+        // Pre-RFC 3977 servers return no capabilities at all
+        assert!(!advertises_rfc3977(&[]));
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_resyncs_after_unexpected_response() {
+        let body = b"=ybegin line=128 size=1 name=x\r\n+\r\n=yend size=1\r\n.\r\n";
+        let mut input = Vec::new();
+        input.extend_from_slice(b"222 0 <a@test>\r\n");
+        input.extend_from_slice(body);
+        // A malformed response with a stray multi-line payload and no terminator
+        input.extend_from_slice(b"599 Something odd\r\ngarbage\r\n");
+        input.extend_from_slice(b"222 0 <c@test>\r\n");
+        input.extend_from_slice(body);
+        // DATE reply, then group re-selection and the retried BODY
+        input.extend_from_slice(b"111 20261016120000\r\n");
+        input.extend_from_slice(b"211 10 1 10 alt.binaries.test\r\n");
+        input.extend_from_slice(b"222 0 <c@test>\r\n");
+        input.extend_from_slice(body);
+
+        let mut conn = connection_with_input(&input);
+        conn.current_group = Some("alt.binaries.test".to_string());
+        let requests: Vec<SegmentRequest> = ["a@test", "b@test", "c@test"]
+            .iter()
+            .enumerate()
+            .map(|(i, id)| SegmentRequest {
+                message_id: id.to_string(),
+                group: "alt.binaries.test".to_string(),
+                segment_number: i as u32 + 1,
+            })
+            .collect();
+
+        let results = conn.download_segments_pipelined(&requests).await.unwrap();
+
+        let numbers: Vec<u32> = results.iter().map(|(n, _)| *n).collect();
+        assert_eq!(numbers, vec![1, 2, 3]);
+        assert!(results[0].1.is_some());
+        assert!(results[1].1.is_none());
+        assert_eq!(results[2].1.as_deref(), Some(&[1u8][..]));
+    }
+
+    #[test]
+    fn test_is_date_response() {
+        assert!(is_date_response(b"111 20261016120000\r\n"));
+        assert!(!is_date_response(b"111 2026\r\n"));
+        assert!(!is_date_response(b"222 0 <a@test>\r\n"));
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[tokio::test]
+    async fn test_crc_mismatch_retries_with_escape_across_lines() {
+        // Raw bytes [1, 19]: 19 encodes to an escaped '=' whose escape got split over two lines
+        let expected = crc32(&[1, 19]);
+        let input = format!(
+            "=ybegin part=1 line=128 size=2 name=x\r\n=ypart begin=1 end=2\r\n+=\r\n}}\r\n=yend size=2 part=1 pcrc32={:08x}\r\n.\r\n",
+            expected
+        );
+
+        let mut conn = connection_with_input(input.as_bytes());
+        conn.crc_retry = true;
+        let decoded = conn.read_decoded_body().await.unwrap();
+        assert_eq!(decoded, vec![1, 19]);
+
+        // Without a matching interpretation the segment fails
+        let input = input.replace(&format!("{:08x}", expected), "00000000");
+        let mut conn = connection_with_input(input.as_bytes());
+        conn.crc_retry = true;
+        assert!(conn.read_decoded_body().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_single_part_without_ypart() {
+        // Data right after =ybegin, no =ypart, irregular line lengths and only a whole-file CRC
+        let raw: Vec<u8> = (0u8..19).collect();
+        let encoded: Vec<u8> = raw.iter().map(|b| b.wrapping_add(42)).collect();
+        let mut input = b"=ybegin line=128 size=19 name=x\r\n".to_vec();
+        for chunk in [
+            &encoded[..3],
+            &encoded[3..4],
+            &encoded[4..11],
+            &encoded[11..],
+        ] {
+            input.extend_from_slice(chunk);
+            input.extend_from_slice(b"\r\n");
+        }
+        input.extend_from_slice(
+            format!("=yend size=19 crc32={:08x}\r\n.\r\n", crc32(&raw)).as_bytes(),
+        );
+
+        let mut two_stage = connection_with_input(&input);
+        let body = two_stage.read_article_body().await.unwrap();
+        assert_eq!(two_stage.decode_yenc_simple(&body).unwrap(), raw);
+
+        let mut conn = connection_with_input(&input);
+        conn.crc_retry = true;
+        assert_eq!(conn.read_decoded_body().await.unwrap(), raw);
+        assert_eq!(conn.last_part, None);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_without_ypart_ignores_file_crc() {
+        // The trailer's crc32 covers the whole 100-byte file, not this 2-byte part
+        let input = b"=ybegin part=2 line=128 size=100 name=x\r\n+,\r\n=yend size=2 part=2 crc32=deadbeef\r\n.\r\n";
+
+        let mut conn = connection_with_input(input);
+        conn.crc_retry = true;
+        assert_eq!(conn.read_decoded_body().await.unwrap(), vec![1, 2]);
+        assert_eq!(conn.last_part, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_bodies_degrade_connection() {
+        let truncated = b"=ybegin line=128 size=3 name=x\r\n+,\r\n=yend size=3\r\n.\r\n";
+        let complete = b"=ybegin line=128 size=2 name=x\r\n+,\r\n=yend size=2\r\n.\r\n";
+        let mut input = Vec::new();
+        input.extend_from_slice(truncated);
+        input.extend_from_slice(complete);
+        input.extend_from_slice(truncated);
+        input.extend_from_slice(truncated);
+
+        let mut conn = connection_with_input(&input);
+        conn.invalid_body_limit = 2;
+
+        assert!(conn.read_decoded_body().await.is_err());
+        assert!(!conn.is_degraded());
+        // A good body resets the count
+        assert_eq!(conn.read_decoded_body().await.unwrap(), vec![1, 2]);
+        assert!(conn.read_decoded_body().await.is_err());
+        assert!(!conn.is_degraded());
+        assert!(conn.read_decoded_body().await.is_err());
+        assert!(conn.is_degraded());
+    }
+
+    #[tokio::test]
+    async fn test_unresolvable_server_reports_dns_failure() {
+        let config = UsenetConfig {
+            server: "dl-nzb-test.invalid".to_string(),
+            ..UsenetConfig::default()
+        };
+
+        let result = AsyncNntpConnection::connect(&config, None).await;
+        assert!(matches!(
+            result,
+            Err(DlNzbError::Nntp(NntpError::DnsFailed { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_transcript_redacts_credentials() {
+        assert_eq!(
+            redact_command("AUTHINFO PASS hunter2"),
+            "AUTHINFO PASS <redacted>"
+        );
+        assert_eq!(
+            redact_command("authinfo user alice"),
+            "authinfo user <redacted>"
+        );
+        assert_eq!(
+            redact_command("GROUP alt.binaries.test"),
+            "GROUP alt.binaries.test"
+        );
+    }
+
+    #[test]
+    fn test_classify_auth_failure() {
+        assert!(matches!(
+            classify_auth_failure("502 Too many connections for user"),
+            NntpError::TooManyConnections {
+                retry_after: None,
+                ..
+            }
+        ));
+        assert!(matches!(
+            classify_auth_failure("481 Authentication throttled, try again later"),
+            NntpError::AuthThrottled {
+                retry_after: None,
+                ..
+            }
+        ));
+        assert!(matches!(
+            classify_auth_failure("481 Too many logins, try again in 2 minutes"),
+            NntpError::AuthThrottled {
+                retry_after: Some(d),
+                ..
+            } if d == Duration::from_secs(120)
+        ));
+        assert!(matches!(
+            classify_auth_failure("481 Authentication rejected"),
+            NntpError::AuthFailed(_)
+        ));
+    }
+
+    #[test]
+    fn test_check_greeting() {
+        let error = |greeting| match check_greeting(greeting) {
+            Err(DlNzbError::Nntp(e)) => e,
+            other => panic!("unexpected {:?}", other),
+        };
+
+        assert!(check_greeting("200 news.example ready").unwrap());
+        assert!(!check_greeting("201 news.example ready, no posting").unwrap());
+        assert!(matches!(
+            error("502 Too many connections"),
+            NntpError::TooManyConnections { .. }
+        ));
+        assert!(matches!(
+            error("502 Connection limit reached"),
+            NntpError::TooManyConnections { .. }
+        ));
+        assert!(matches!(
+            error("502 Access denied"),
+            NntpError::AuthFailed(_)
+        ));
+        assert!(matches!(
+            error("400 Service temporarily unavailable"),
+            NntpError::ServerBusy { .. }
+        ));
+    }
+
+    #[test]
+    fn test_retry_hint() {
+        let secs = |s| Some(Duration::from_secs(s));
+        assert_eq!(retry_hint("502 Try again in 30 seconds"), secs(30));
+        assert_eq!(
+            retry_hint("400 Too many connections, wait 5min."),
+            secs(300)
+        );
+        assert_eq!(retry_hint("502 Limit reached; retry after 45"), secs(45));
+        assert_eq!(retry_hint("502 Retry after 2h"), secs(15 * 60));
+        // Numbers that aren't a wait are ignored
+        assert_eq!(retry_hint("502 Max 20 connections, try again later"), None);
+        assert_eq!(retry_hint("502 Too many connections"), None);
+    }
+}
@@ -0,0 +1,454 @@
+//! In-process NNTP server for protocol-level tests
+//!
+//! [`MockServer`] listens on a local port and answers the commands dl-nzb sends with
+//! scripted responses: articles are served yEnc-encoded from memory, and failures (430,
+//! stalls, delays, truncated or corrupt bodies, dropped connections) can be injected per
+//! article.
+//! Each injected fault answers one request, so a retry sees the article again.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use super::connection::crc32;
+use crate::config::{Connections, UsenetConfig};
+
+/// A failure to answer a BODY request with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Fault {
+    /// 430 no such article
+    Missing,
+    /// Never answer, leaving the client to time out
+    Stall,
+    /// Send only the first half of the encoded data
+    Truncate,
+    /// Send the body with a wrong part CRC in the trailer
+    BadCrc,
+    /// Close the connection instead of answering
+    Disconnect,
+    /// Answer normally after a pause, letting requests on other connections overtake it
+    Delay(Duration),
+}
+
+/// An article body and the yEnc part header it's served with
+struct MockArticle {
+    data: Vec<u8>,
+    part: u32,
+    /// 1-based offset of `data` within the file, as `=ypart begin=` gives it
+    begin: u64,
+    file_size: u64,
+}
+
+#[derive(Default)]
+struct MockState {
+    greeting: String,
+    /// Reply to AUTHINFO PASS
+    auth_reply: String,
+    groups: HashSet<String>,
+    articles: HashMap<String, MockArticle>,
+    faults: HashMap<String, VecDeque<Fault>>,
+    /// Every command received, across all connections
+    commands: Vec<String>,
+    connections: usize,
+}
+
+/// Scripted NNTP server running on a background task
+pub(crate) struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockServer {
+    /// Start listening on a free local port
+    pub(crate) async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(MockState {
+            greeting: "200 mock server ready".to_string(),
+            auth_reply: "281 authentication accepted".to_string(),
+            ..MockState::default()
+        }));
+
+        let accept_state = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                accept_state.lock().unwrap().connections += 1;
+                tokio::spawn(serve(stream, accept_state.clone()));
+            }
+        });
+
+        Self { addr, state }
+    }
+
+    /// Plaintext config pointing at this server
+    pub(crate) fn config(&self) -> UsenetConfig {
+        UsenetConfig {
+            server: self.addr.ip().to_string(),
+            port: self.addr.port(),
+            username: "user".to_string(),
+            password: "pass".to_string(),
+            ssl: false,
+            allow_insecure_plaintext: true,
+            connections: Connections::Fixed(2),
+            ..UsenetConfig::default()
+        }
+    }
+
+    /// Replace the greeting sent to new connections
+    pub(crate) fn set_greeting(&self, greeting: &str) {
+        self.state.lock().unwrap().greeting = greeting.to_string();
+    }
+
+    /// Replace the reply to AUTHINFO PASS
+    pub(crate) fn set_auth_reply(&self, reply: &str) {
+        self.state.lock().unwrap().auth_reply = reply.to_string();
+    }
+
+    pub(crate) fn add_group(&self, group: &str) {
+        self.state.lock().unwrap().groups.insert(group.to_string());
+    }
+
+    /// Serve `data` as the body of `message_id` (without angle brackets)
+    pub(crate) fn add_article(&self, message_id: &str, data: &[u8]) {
+        self.add_part(message_id, data, 1, 1, data.len() as u64);
+    }
+
+    /// Serve `data` as part `part` of a `file_size`-byte file, starting at 1-based `begin`
+    pub(crate) fn add_part(
+        &self,
+        message_id: &str,
+        data: &[u8],
+        part: u32,
+        begin: u64,
+        file_size: u64,
+    ) {
+        self.state.lock().unwrap().articles.insert(
+            message_id.to_string(),
+            MockArticle {
+                data: data.to_vec(),
+                part,
+                begin,
+                file_size,
+            },
+        );
+    }
+
+    /// Answer the next BODY request for `message_id` with `fault`
+    ///
+    /// Faults queue up, so injecting two fails the first two requests.
+    pub(crate) fn inject(&self, message_id: &str, fault: Fault) {
+        self.state
+            .lock()
+            .unwrap()
+            .faults
+            .entry(message_id.to_string())
+            .or_default()
+            .push_back(fault);
+    }
+
+    /// Commands received so far, with AUTHINFO arguments included
+    pub(crate) fn commands(&self) -> Vec<String> {
+        self.state.lock().unwrap().commands.clone()
+    }
+
+    /// Connections accepted so far
+    pub(crate) fn connections(&self) -> usize {
+        self.state.lock().unwrap().connections
+    }
+}
+
+/// What to send back for one command
+enum Reply {
+    Send(Vec<u8>),
+    Delayed(Duration, Vec<u8>),
+    Stall,
+    Close,
+}
+
+async fn serve(stream: TcpStream, state: Arc<Mutex<MockState>>) {
+    let (read_half, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(read_half);
+
+    let greeting = format!("{}\r\n", state.lock().unwrap().greeting);
+    if writer.write_all(greeting.as_bytes()).await.is_err() || !greeting.starts_with("20") {
+        return;
+    }
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        let command = line.trim_end().to_string();
+        let reply = respond(&command, &mut state.lock().unwrap());
+
+        match reply {
+            Reply::Delayed(pause, bytes) => {
+                tokio::time::sleep(pause).await;
+                if writer.write_all(&bytes).await.is_err() {
+                    return;
+                }
+            }
+            Reply::Send(bytes) => {
+                if writer.write_all(&bytes).await.is_err() {
+                    return;
+                }
+                if command.eq_ignore_ascii_case("QUIT") {
+                    return;
+                }
+            }
+            Reply::Stall => std::future::pending::<()>().await,
+            Reply::Close => return,
+        }
+    }
+}
+
+fn respond(command: &str, state: &mut MockState) -> Reply {
+    state.commands.push(command.to_string());
+    let mut words = command.split_whitespace();
+    let verb = words.next().unwrap_or("").to_ascii_uppercase();
+    let arg = words.next().unwrap_or("");
+    let id = arg.trim_start_matches('<').trim_end_matches('>');
+
+    let line = |text: &str| Reply::Send(format!("{}\r\n", text).into_bytes());
+    match verb.as_str() {
+        "MODE" => line("200 reader mode"),
+        "AUTHINFO" if arg.eq_ignore_ascii_case("USER") => line("381 password required"),
+        "AUTHINFO" => line(&state.auth_reply),
+        "CAPABILITIES" => line("101 capabilities\r\nVERSION 2\r\nREADER\r\n."),
+        "DATE" => line("111 20261016120000"),
+        "NOOP" => line("200 ok"),
+        "QUIT" => line("205 bye"),
+        "GROUP" if state.groups.contains(arg) => line(&format!("211 1 1 1 {}", arg)),
+        "GROUP" => line("411 no such group"),
+        "STAT" if state.articles.contains_key(id) => line(&format!("223 0 <{}>", id)),
+        "STAT" => line("430 no such article"),
+        "BODY" => {
+            let fault = state.faults.get_mut(id).and_then(VecDeque::pop_front);
+            match (fault, state.articles.get(id)) {
+                (Some(Fault::Stall), _) => Reply::Stall,
+                (Some(Fault::Disconnect), _) => Reply::Close,
+                (Some(Fault::Missing), _) | (_, None) => line("430 no such article"),
+                (fault, Some(article)) => {
+                    let mut reply = format!("222 0 <{}>\r\n", id).into_bytes();
+                    reply.extend_from_slice(&yenc_body(article, fault));
+                    match fault {
+                        Some(Fault::Delay(pause)) => Reply::Delayed(pause, reply),
+                        _ => Reply::Send(reply),
+                    }
+                }
+            }
+        }
+        _ => line("500 unknown command"),
+    }
+}
+
+/// Encode an article as a yEnc body, dot-stuffed and terminated
+fn yenc_body(article: &MockArticle, fault: Option<Fault>) -> Vec<u8> {
+    let data = &article.data;
+    let sent = match fault {
+        Some(Fault::Truncate) => &data[..data.len() / 2],
+        _ => data,
+    };
+    let crc = match fault {
+        Some(Fault::BadCrc) => !crc32(data),
+        _ => crc32(data),
+    };
+
+    let mut body = format!(
+        "=ybegin part={} line=128 size={} name=mock\r\n=ypart begin={} end={}\r\n",
+        article.part,
+        article.file_size,
+        article.begin,
+        article.begin + data.len() as u64 - 1
+    )
+    .into_bytes();
+    for chunk in sent.chunks(128) {
+        let mut encoded = Vec::with_capacity(chunk.len() + 8);
+        for &byte in chunk {
+            let value = byte.wrapping_add(42);
+            if matches!(value, 0 | b'\n' | b'\r' | b'=') {
+                encoded.extend_from_slice(&[b'=', value.wrapping_add(64)]);
+            } else {
+                encoded.push(value);
+            }
+        }
+        if encoded.first() == Some(&b'.') {
+            body.push(b'.');
+        }
+        body.extend_from_slice(&encoded);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(
+        format!(
+            "=yend size={} part={} pcrc32={:08x}\r\n.\r\n",
+            data.len(),
+            article.part,
+            crc
+        )
+        .as_bytes(),
+    );
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{DlNzbError, NntpError};
+    use crate::nntp::{AsyncNntpConnection, SegmentFailure, SegmentRequest};
+
+    fn requests(ids: &[&str]) -> Vec<SegmentRequest> {
+        ids.iter()
+            .enumerate()
+            .map(|(i, id)| SegmentRequest {
+                message_id: id.to_string(),
+                group: "alt.binaries.test".to_string(),
+                segment_number: i as u32 + 1,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_download_with_injected_faults() {
+        let server = MockServer::start().await;
+        server.add_group("alt.binaries.test");
+        // Every byte value, so escapes and dot-stuffing are exercised
+        let data: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+        let ids = ["a@test", "b@test", "c@test"];
+        for id in ids {
+            server.add_article(id, &data);
+        }
+        server.inject("b@test", Fault::Missing);
+        server.inject("c@test", Fault::Truncate);
+
+        let mut conn = AsyncNntpConnection::connect(&server.config(), None)
+            .await
+            .unwrap();
+        let results = conn
+            .download_segments_pipelined(&requests(&ids))
+            .await
+            .unwrap();
+
+        assert_eq!(results[0].1.as_deref(), Some(&data[..]));
+        assert_eq!(
+            conn.take_segment_failures(),
+            vec![(2, SegmentFailure::Missing), (3, SegmentFailure::Decode)]
+        );
+
+        // Faults answer once, so the retry gets every article
+        let results = conn
+            .download_segments_pipelined(&requests(&ids))
+            .await
+            .unwrap();
+        assert!(results
+            .iter()
+            .all(|(_, body)| body.as_deref() == Some(&data[..])));
+        assert!(server
+            .commands()
+            .contains(&"GROUP alt.binaries.test".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_bad_crc_fails_with_crc_retry() {
+        let server = MockServer::start().await;
+        server.add_group("alt.binaries.test");
+        server.add_article("a@test", b"some data");
+        server.inject("a@test", Fault::BadCrc);
+
+        let mut config = server.config();
+        config.yenc_crc_retry = true;
+        let mut conn = AsyncNntpConnection::connect(&config, None).await.unwrap();
+        conn.download_segments_pipelined(&requests(&["a@test"]))
+            .await
+            .unwrap();
+        assert_eq!(conn.take_segment_failures(), vec![(1, SegmentFailure::Crc)]);
+    }
+
+    #[tokio::test]
+    async fn test_stall_and_disconnect() {
+        let server = MockServer::start().await;
+        server.add_group("alt.binaries.test");
+        server.add_article("a@test", b"data");
+        server.inject("a@test", Fault::Disconnect);
+        server.inject("a@test", Fault::Stall);
+
+        let mut conn = AsyncNntpConnection::connect(&server.config(), None)
+            .await
+            .unwrap();
+        assert!(conn
+            .download_segment("a@test", "alt.binaries.test")
+            .await
+            .is_err());
+
+        let mut conn = AsyncNntpConnection::connect(&server.config(), None)
+            .await
+            .unwrap();
+        let stalled = tokio::time::timeout(
+            Duration::from_millis(200),
+            conn.download_segment("a@test", "alt.binaries.test"),
+        );
+        assert!(stalled.await.is_err());
+        assert_eq!(server.connections(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_greeting_posting_and_busy() {
+        let server = MockServer::start().await;
+        let conn = AsyncNntpConnection::connect(&server.config(), None)
+            .await
+            .unwrap();
+        assert!(conn.posting_allowed());
+
+        server.set_greeting("201 reader ready, no posting");
+        let conn = AsyncNntpConnection::connect(&server.config(), None)
+            .await
+            .unwrap();
+        assert!(!conn.posting_allowed());
+
+        // A busy server is worth retrying, unlike a malformed greeting
+        server.set_greeting("400 Service temporarily unavailable, retry after 5");
+        let Err(DlNzbError::Nntp(error)) =
+            AsyncNntpConnection::connect(&server.config(), None).await
+        else {
+            panic!("busy greeting accepted");
+        };
+        assert!(matches!(error, NntpError::ServerBusy { .. }));
+        assert!(error.is_transient());
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(5)));
+
+        server.set_greeting("hello");
+        let Err(DlNzbError::Nntp(error)) =
+            AsyncNntpConnection::connect(&server.config(), None).await
+        else {
+            panic!("malformed greeting accepted");
+        };
+        assert!(!error.is_transient());
+    }
+
+    #[tokio::test]
+    async fn test_throttled_login() {
+        let server = MockServer::start().await;
+        server.set_auth_reply("481 Too many logins, try again in 30 seconds");
+
+        let result = AsyncNntpConnection::connect(&server.config(), None).await;
+        assert!(matches!(
+            result,
+            Err(DlNzbError::Nntp(NntpError::AuthThrottled {
+                retry_after: Some(wait),
+                ..
+            })) if wait == Duration::from_secs(30)
+        ));
+
+        server.set_greeting("502 Connection limit reached");
+        let result = AsyncNntpConnection::connect(&server.config(), None).await;
+        assert!(matches!(
+            result,
+            Err(DlNzbError::Nntp(NntpError::TooManyConnections { .. }))
+        ));
+    }
+}
@@ -4,7 +4,11 @@
 //! health checks, and optimized yEnc decoding.
 
 mod connection;
+#[cfg(test)]
+pub(crate) mod mock;
 mod pool;
 
-pub use connection::{AsyncNntpConnection, SegmentRequest};
-pub use pool::{NntpPool, NntpPoolBuilder, NntpPoolExt, PooledConnection};
+pub use connection::{
+    AsyncNntpConnection, PartMismatch, SegmentFailure, SegmentRequest, TRANSCRIPT_TARGET,
+};
+pub use pool::{NntpPool, NntpPoolBuilder, NntpPoolExt, PoolSizeLease, PooledConnection};
@@ -3,29 +3,103 @@
 //! This module provides a robust connection pool that handles connection lifecycle,
 //! health checks, and automatic reconnection.
 
-use super::connection::AsyncNntpConnection;
+use super::connection::{AsyncNntpConnection, PartMismatch, SegmentFailure};
 use crate::config::UsenetConfig;
 use crate::error::{DlNzbError, NntpError};
 use async_trait::async_trait;
 use bytes::Bytes;
-use deadpool::managed::{Manager, Pool, RecycleResult};
-use std::sync::Arc;
+use deadpool::managed::{Manager, Pool, PoolError, RecycleResult};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tokio::time::Duration;
 
 /// Maximum concurrent connection creation attempts to avoid overwhelming the server
 const MAX_CONCURRENT_CONNECTION_CREATION: usize = 10;
 
+/// How long a 430 answer is trusted before the article may be requested again
+const MISSING_ARTICLE_TTL: Duration = Duration::from_secs(30 * 60);
+
 /// Connection manager for deadpool with rate-limited creation
 pub struct NntpConnectionManager {
     config: Arc<UsenetConfig>,
     tls_connector: Option<Arc<tokio_native_tls::TlsConnector>>,
     creation_semaphore: Arc<tokio::sync::Semaphore>,
+    /// Connections replaced because their bodies kept failing validation
+    evicted: AtomicUsize,
+    /// Message-ids this server recently answered 430 for, so they aren't requested again
+    missing: MissingArticles,
+    sizing: Mutex<PoolSizing>,
+}
+
+/// Size requests of the downloads sharing a pool
+#[derive(Default)]
+struct PoolSizing {
+    wants: HashMap<u64, usize>,
+    next_lease: u64,
+    /// Cap learned from the provider refusing further connections
+    provider_limit: Option<usize>,
+}
+
+impl PoolSizing {
+    /// Resize the pool to the largest request, within the provider's limit
+    fn apply(&self, pool: &NntpPool) -> usize {
+        let current = pool.status().max_size;
+        let wanted = self.wants.values().max().copied().unwrap_or(current);
+        let target = self
+            .provider_limit
+            .map_or(wanted, |limit| wanted.min(limit))
+            .max(1);
+        if target != current {
+            pool.resize(target);
+        }
+        target
+    }
+}
+
+/// Message-ids known to be missing on a server, shared by all of a pool's connections
+///
+/// Entries expire after [`MISSING_ARTICLE_TTL`], so a long batch run doesn't accumulate
+/// every missing article it has seen, and an article that finished propagating late is
+/// tried again by a later NZB. Nothing is persisted.
+#[derive(Clone)]
+struct MissingArticles {
+    seen: Arc<Mutex<HashMap<String, Instant>>>,
+    ttl: Duration,
+}
+
+impl Default for MissingArticles {
+    fn default() -> Self {
+        Self {
+            seen: Arc::default(),
+            ttl: MISSING_ARTICLE_TTL,
+        }
+    }
+}
+
+impl MissingArticles {
+    fn contains(&self, message_id: &str) -> bool {
+        self.seen
+            .lock()
+            .expect("missing articles lock")
+            .get(message_id)
+            .is_some_and(|seen| seen.elapsed() < self.ttl)
+    }
+
+    /// Remember `message_ids` as missing, dropping entries that have expired
+    fn extend(&self, message_ids: Vec<String>) {
+        let now = Instant::now();
+        let mut seen = self.seen.lock().expect("missing articles lock");
+        seen.retain(|_, at| now.duration_since(*at) < self.ttl);
+        seen.extend(message_ids.into_iter().map(|id| (id, now)));
+    }
 }
 
 impl NntpConnectionManager {
     pub fn new(config: UsenetConfig) -> Result<Self, DlNzbError> {
         // Create shared TLS connector for session reuse
-        let tls_connector = if config.ssl {
+        let tls_connector = if config.uses_tls() {
             let mut tls_builder = native_tls::TlsConnector::builder();
             if !config.verify_ssl_certs {
                 tls_builder.danger_accept_invalid_certs(true);
@@ -50,8 +124,21 @@ impl NntpConnectionManager {
             config: Arc::new(config),
             tls_connector,
             creation_semaphore,
+            sizing: Mutex::new(PoolSizing::default()),
+            evicted: AtomicUsize::new(0),
+            missing: MissingArticles::default(),
         })
     }
+
+    /// Number of degraded connections replaced so far
+    pub fn evicted_connections(&self) -> usize {
+        self.evicted.load(Ordering::Relaxed)
+    }
+
+    /// Whether the server already answered 430 for this message-id
+    pub fn is_known_missing(&self, message_id: &str) -> bool {
+        self.missing.contains(message_id)
+    }
 }
 
 impl Manager for NntpConnectionManager {
@@ -81,6 +168,15 @@ impl Manager for NntpConnectionManager {
         conn: &mut AsyncNntpConnection,
         _metrics: &deadpool::managed::Metrics,
     ) -> RecycleResult<DlNzbError> {
+        // A connection whose bodies keep arriving damaged is replaced even if it answers NOOP
+        if conn.is_degraded() {
+            self.evicted.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!("Replacing a connection whose recent articles all failed validation");
+            return Err(deadpool::managed::RecycleError::Backend(
+                NntpError::UnhealthyConnection.into(),
+            ));
+        }
+
         // Check if connection is still healthy
         if conn.is_healthy().await {
             Ok(())
@@ -96,8 +192,14 @@ impl Manager for NntpConnectionManager {
 pub type NntpPool = Pool<NntpConnectionManager>;
 
 /// Pooled NNTP connection with convenience methods
+///
+/// Articles the server has already answered 430 for are not requested again; they are
+/// reported missing straight away.
 pub struct PooledConnection {
     conn: deadpool::managed::Object<NntpConnectionManager>,
+    missing: MissingArticles,
+    /// Reasons for the segments the last pipelined download failed to fetch
+    segment_failures: Vec<(u32, SegmentFailure)>,
 }
 
 impl PooledConnection {
@@ -107,7 +209,46 @@ impl PooledConnection {
         message_id: &str,
         group: &str,
     ) -> Result<Bytes, DlNzbError> {
-        self.conn.download_segment(message_id, group).await
+        if self.is_known_missing(message_id) {
+            return Err(NntpError::ArticleNotFound {
+                message_id: message_id.to_string(),
+            }
+            .into());
+        }
+        let result = self.conn.download_segment(message_id, group).await;
+        self.record_missing();
+        result
+    }
+
+    /// Check whether the server carries a newsgroup
+    pub async fn group_exists(&mut self, group: &str) -> Result<bool, DlNzbError> {
+        self.conn.group_exists(group).await
+    }
+
+    /// Check which articles exist, pipelining STAT when the server supports it
+    pub async fn stat_articles(&mut self, message_ids: &[String]) -> Result<Vec<bool>, DlNzbError> {
+        let known: Vec<bool> = message_ids
+            .iter()
+            .map(|id| self.is_known_missing(id))
+            .collect();
+        let unknown: Vec<String> = message_ids
+            .iter()
+            .zip(&known)
+            .filter(|(_, &known)| !known)
+            .map(|(id, _)| id.clone())
+            .collect();
+        let mut exists = if unknown.is_empty() {
+            Vec::new()
+        } else {
+            self.conn.stat_articles(&unknown).await?
+        }
+        .into_iter();
+        self.record_missing();
+
+        Ok(known
+            .iter()
+            .map(|&known| !known && exists.next().unwrap_or(false))
+            .collect())
     }
 
     /// Download multiple segments using pipelining
@@ -115,7 +256,81 @@ impl PooledConnection {
         &mut self,
         requests: &[crate::nntp::SegmentRequest],
     ) -> Result<Vec<(u32, Option<Bytes>)>, DlNzbError> {
-        self.conn.download_segments_pipelined(requests).await
+        let (known, unknown): (Vec<_>, Vec<_>) = requests
+            .iter()
+            .cloned()
+            .partition(|req| self.is_known_missing(&req.message_id));
+        self.segment_failures.clear();
+        let results = if unknown.is_empty() {
+            Ok(Vec::new())
+        } else {
+            self.conn.download_segments_pipelined(&unknown).await
+        };
+        self.record_missing();
+        let mut results = results?;
+
+        self.segment_failures = self.conn.take_segment_failures();
+        self.segment_failures.extend(
+            known
+                .iter()
+                .map(|req| (req.segment_number, SegmentFailure::Missing)),
+        );
+        results.extend(known.iter().map(|req| (req.segment_number, None)));
+        Ok(results)
+    }
+
+    /// Segments the last pipelined download failed to fetch, with the reason for each
+    pub fn take_segment_failures(&mut self) -> Vec<(u32, SegmentFailure)> {
+        std::mem::take(&mut self.segment_failures)
+    }
+
+    fn is_known_missing(&self, message_id: &str) -> bool {
+        self.missing.contains(message_id)
+    }
+
+    /// Add the articles the server just answered 430 for to the pool's missing set
+    fn record_missing(&mut self) {
+        let missing = self.conn.take_missing_articles();
+        if !missing.is_empty() {
+            self.missing.extend(missing);
+        }
+    }
+
+    /// Segments of the last pipelined download whose yEnc part number differed from their
+    /// NZB segment number
+    pub fn take_part_mismatches(&mut self) -> Vec<PartMismatch> {
+        self.conn.take_part_mismatches()
+    }
+
+    /// Whole-file size the yEnc headers of the last pipelined download declared
+    pub fn take_declared_file_size(&mut self) -> Option<u64> {
+        self.conn.take_declared_file_size()
+    }
+}
+
+/// One download's claim on the size of a shared pool
+///
+/// The pool is sized to the largest claim among active downloads, so concurrent NZBs never
+/// shrink it under each other. Dropping the lease withdraws its claim.
+pub struct PoolSizeLease {
+    pool: NntpPool,
+    id: u64,
+}
+
+impl PoolSizeLease {
+    /// Ask for `size` connections, returning the size the pool ends up with
+    pub fn want(&self, size: usize) -> usize {
+        let mut sizing = self.pool.manager().sizing.lock().unwrap();
+        sizing.wants.insert(self.id, size);
+        sizing.apply(&self.pool)
+    }
+}
+
+impl Drop for PoolSizeLease {
+    fn drop(&mut self) {
+        let mut sizing = self.pool.manager().sizing.lock().unwrap();
+        sizing.wants.remove(&self.id);
+        sizing.apply(&self.pool);
     }
 }
 
@@ -129,7 +344,7 @@ pub struct NntpPoolBuilder {
 impl NntpPoolBuilder {
     pub fn new(config: UsenetConfig) -> Self {
         Self {
-            max_size: config.connections as usize,
+            max_size: config.connections.count() as usize,
             config,
             timeouts: deadpool::managed::Timeouts {
                 wait: Some(Duration::from_secs(30)), // Reduced from 120s for faster failure
@@ -172,6 +387,18 @@ impl NntpPoolBuilder {
 pub trait NntpPoolExt {
     /// Get a connection from the pool
     async fn get_connection(&self) -> Result<PooledConnection, DlNzbError>;
+
+    /// Take a lease through which a download requests its pool size
+    fn size_lease(&self) -> PoolSizeLease;
+
+    /// Cap the pool at `open` connections after the provider refused more
+    fn limit_to(&self, open: usize);
+
+    /// Connections replaced after consistently failing article validation
+    fn evicted_connections(&self) -> usize;
+
+    /// Whether the server answered 430 for this message-id within the last half hour
+    fn is_known_missing(&self, message_id: &str) -> bool;
 }
 
 #[async_trait]
@@ -179,13 +406,45 @@ impl NntpPoolExt for NntpPool {
     async fn get_connection(&self) -> Result<PooledConnection, DlNzbError> {
         let conn = self.get().await.map_err(|e| {
             tracing::debug!("Failed to get connection from pool: {}", e);
-            NntpError::ConnectionFailed {
-                server: "pool".to_string(),
-                port: 0,
-                source: std::io::Error::other(e),
+            match e {
+                // Keep backend errors intact so callers can react to e.g. connection limits
+                PoolError::Backend(e) => e,
+                e => NntpError::ConnectionFailed {
+                    server: "pool".to_string(),
+                    port: 0,
+                    source: std::io::Error::other(e),
+                }
+                .into(),
             }
         })?;
-        Ok(PooledConnection { conn })
+        Ok(PooledConnection {
+            conn,
+            missing: self.manager().missing.clone(),
+            segment_failures: Vec::new(),
+        })
+    }
+
+    fn size_lease(&self) -> PoolSizeLease {
+        let mut sizing = self.manager().sizing.lock().unwrap();
+        sizing.next_lease += 1;
+        PoolSizeLease {
+            pool: self.clone(),
+            id: sizing.next_lease,
+        }
+    }
+
+    fn limit_to(&self, open: usize) {
+        let mut sizing = self.manager().sizing.lock().unwrap();
+        sizing.provider_limit = Some(open.max(1));
+        sizing.apply(self);
+    }
+
+    fn evicted_connections(&self) -> usize {
+        self.manager().evicted_connections()
+    }
+
+    fn is_known_missing(&self, message_id: &str) -> bool {
+        self.manager().is_known_missing(message_id)
     }
 }
 
@@ -201,4 +460,50 @@ mod tests {
         // Pool creation should succeed even if we can't connect
         assert!(result.is_ok() || result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_size_leases_share_pool() {
+        let pool = NntpPoolBuilder::new(UsenetConfig::default())
+            .max_size(10)
+            .build()
+            .unwrap();
+
+        // Two concurrent downloads: the pool follows the larger request
+        let small = pool.size_lease();
+        let large = pool.size_lease();
+        assert_eq!(small.want(2), 2);
+        assert_eq!(large.want(8), 8);
+        assert_eq!(small.want(3), 8);
+        assert_eq!(pool.status().max_size, 8);
+
+        // The smaller download finishing must not shrink the other one's pool
+        drop(small);
+        assert_eq!(pool.status().max_size, 8);
+
+        // A provider limit caps every request
+        pool.limit_to(5);
+        assert_eq!(pool.status().max_size, 5);
+        assert_eq!(large.want(20), 5);
+
+        drop(large);
+        assert_eq!(pool.status().max_size, 5);
+    }
+
+    #[test]
+    fn test_missing_articles_expire() {
+        let missing = MissingArticles::default();
+        missing.extend(vec!["a@test".to_string()]);
+        assert!(missing.contains("a@test"));
+        assert!(!missing.contains("b@test"));
+
+        // Expired entries are no longer trusted, and are pruned when more are recorded
+        let expired = MissingArticles {
+            ttl: Duration::ZERO,
+            ..missing.clone()
+        };
+        assert!(!expired.contains("a@test"));
+        expired.extend(vec!["b@test".to_string()]);
+        let seen = missing.seen.lock().unwrap();
+        assert_eq!(seen.keys().collect::<Vec<_>>(), ["b@test"]);
+    }
 }
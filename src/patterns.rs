@@ -75,9 +75,72 @@ pub mod rar {
     }
 }
 
+/// Split and spanned ZIP archive patterns
+///
+/// Detection only: ZIP archives aren't extracted, so these just keep split and spanned
+/// volumes from being mistaken for standalone `.zip` files.
+pub mod zip {
+    use super::*;
+
+    /// Matches any volume of a split ZIP (.zip.001, .zip.002, etc.)
+    static SPLIT_REGEX: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)\.zip\.\d{3}$").expect("valid regex"));
+
+    /// Matches the first volume of a split ZIP (.zip.001)
+    static SPLIT_FIRST_REGEX: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)\.zip\.0*1$").expect("valid regex"));
+
+    /// Matches any volume of a spanned ZIP (.z01, .z02, etc.)
+    static SPANNED_REGEX: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)\.z\d{2}$").expect("valid regex"));
+
+    /// Matches the first volume of a spanned ZIP (.z01)
+    static SPANNED_FIRST_REGEX: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)\.z0*1$").expect("valid regex"));
+
+    /// Matches the base name of a ZIP volume (before .zip.NNN, .zNN or .zip)
+    static BASE_NAME_REGEX: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"(?i)(.*?)(?:\.zip\.\d{3}|\.z\d{2}|\.zip)$").expect("valid regex")
+    });
+
+    /// Check if a file is one volume of a split or spanned ZIP rather than a standalone .zip
+    pub fn is_split_volume(filename: &str) -> bool {
+        SPLIT_REGEX.is_match(filename) || SPANNED_REGEX.is_match(filename)
+    }
+
+    /// Check if path is the first volume of a split or spanned ZIP
+    pub fn is_first_volume(path: &Path) -> bool {
+        let filename = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name,
+            None => return false,
+        };
+
+        SPLIT_FIRST_REGEX.is_match(filename) || SPANNED_FIRST_REGEX.is_match(filename)
+    }
+
+    /// Extract base name from a ZIP volume for finding related parts
+    pub fn extract_base_name(filename: &str) -> Option<&str> {
+        BASE_NAME_REGEX
+            .captures(filename)
+            .and_then(|caps| caps.get(1))
+            .map(|m| m.as_str())
+    }
+}
+
 /// PAR2 file patterns
 pub mod par2 {
-    use std::path::Path;
+    use super::*;
+
+    /// Every PAR2 packet starts with this, so a PAR2 file does too whatever its name
+    pub const MAGIC: &[u8] = b"PAR2\0PKT";
+
+    /// Matches the set name of a PAR2 file (before .volNN+NN.par2 or .par2)
+    static SET_NAME_REGEX: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)^(.*?)(?:\.vol\d+[+-]\d+)?\.par2$").expect("valid regex"));
+
+    /// Matches the recovery block count of a PAR2 volume (the NN after + in .volMM+NN.par2)
+    static VOLUME_BLOCKS_REGEX: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)\.vol\d+\+(\d+)\.par2$").expect("valid regex"));
 
     /// Check if path is a PAR2 file
     pub fn is_par2_file(path: &Path) -> bool {
@@ -87,6 +150,32 @@ pub mod par2 {
             .unwrap_or(false)
     }
 
+    /// Check if a file starts with a PAR2 packet, for PAR2 files posted under obfuscated names
+    pub fn has_par2_magic(path: &Path) -> bool {
+        read_header(path).is_some()
+    }
+
+    /// Recovery set ID from the file's first packet header; files of one set share it
+    pub fn recovery_set_id(path: &Path) -> Option<[u8; 16]> {
+        read_header(path).map(|header| {
+            let mut id = [0u8; 16];
+            id.copy_from_slice(&header[32..48]);
+            id
+        })
+    }
+
+    /// First packet header: magic, length, packet hash, recovery set ID, type
+    fn read_header(path: &Path) -> Option<[u8; 64]> {
+        use std::io::Read;
+
+        let mut header = [0u8; 64];
+        std::fs::File::open(path)
+            .ok()?
+            .read_exact(&mut header)
+            .ok()?;
+        header.starts_with(MAGIC).then_some(header)
+    }
+
     /// Check if this is the main (index) PAR2 file (without .vol)
     pub fn is_main_par2(path: &Path) -> bool {
         is_par2_file(path)
@@ -96,6 +185,39 @@ pub mod par2 {
                 .map(|name| !name.to_lowercase().contains(".vol"))
                 .unwrap_or(false)
     }
+
+    /// Number of recovery blocks a PAR2 volume declares in its name, `None` for index files
+    pub fn recovery_block_count(filename: &str) -> Option<u32> {
+        VOLUME_BLOCKS_REGEX
+            .captures(filename)
+            .and_then(|caps| caps.get(1))
+            .and_then(|m| m.as_str().parse().ok())
+    }
+
+    /// Name of the PAR2 set a file belongs to, shared by its index and volume files
+    pub fn set_name(path: &Path) -> Option<String> {
+        let filename = path.file_name()?.to_str()?;
+        SET_NAME_REGEX
+            .captures(filename)
+            .and_then(|caps| caps.get(1))
+            .map(|m| m.as_str().to_lowercase())
+    }
+}
+
+/// TV release naming patterns
+pub mod tv {
+    use super::*;
+
+    /// Matches episode markers (S01E02, 1x02) and season packs (S01, Season 1)
+    static EPISODE_REGEX: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"(?i)(?:^|[^a-z0-9])(?:s\d{1,2}(?:e\d{1,3})?|\d{1,2}x\d{2,3}|season[ ._-]?\d{1,2})(?:[^a-z0-9]|$)")
+            .expect("valid regex")
+    });
+
+    /// Check if a release or file name looks like a TV episode or season
+    pub fn is_episode(name: &str) -> bool {
+        EPISODE_REGEX.is_match(name)
+    }
 }
 
 /// Extension checking utilities
@@ -166,6 +288,89 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_zip_first_volume() {
+        assert!(zip::is_first_volume(&PathBuf::from("archive.zip.001")));
+        assert!(zip::is_first_volume(&PathBuf::from("archive.z01")));
+        assert!(zip::is_first_volume(&PathBuf::from("Archive.Z01")));
+
+        assert!(!zip::is_first_volume(&PathBuf::from("archive.zip.002")));
+        assert!(!zip::is_first_volume(&PathBuf::from("archive.z02")));
+        assert!(!zip::is_first_volume(&PathBuf::from("archive.zip")));
+    }
+
+    #[test]
+    fn test_zip_split_volumes_are_not_standalone() {
+        assert!(zip::is_split_volume("archive.zip.001"));
+        assert!(zip::is_split_volume("archive.zip.014"));
+        assert!(zip::is_split_volume("archive.z01"));
+        assert!(zip::is_split_volume("archive.z12"));
+
+        assert!(!zip::is_split_volume("archive.zip"));
+        assert!(!zip::is_split_volume("archive.r01"));
+
+        assert_eq!(zip::extract_base_name("my.file.zip.001"), Some("my.file"));
+        assert_eq!(zip::extract_base_name("my.file.z01"), Some("my.file"));
+    }
+
+    #[test]
+    fn test_par2_set_name() {
+        let set = |name: &str| par2::set_name(&PathBuf::from(name));
+        assert_eq!(set("Show.S01E01.par2"), Some("show.s01e01".to_string()));
+        assert_eq!(
+            set("Show.S01E01.vol03+04.par2"),
+            Some("show.s01e01".to_string())
+        );
+        assert_eq!(
+            set("Show.S01E02.vol00+01.PAR2"),
+            Some("show.s01e02".to_string())
+        );
+        assert_eq!(set("Show.S01E01.mkv"), None);
+    }
+
+    #[test]
+    fn test_par2_recovery_block_count() {
+        assert_eq!(par2::recovery_block_count("Show.vol03+04.par2"), Some(4));
+        assert_eq!(
+            par2::recovery_block_count("Show.VOL127+128.PAR2"),
+            Some(128)
+        );
+        assert_eq!(par2::recovery_block_count("Show.par2"), None);
+        assert_eq!(par2::recovery_block_count("Show.vol03+04.par2.1"), None);
+    }
+
+    #[test]
+    fn test_par2_detected_by_magic_without_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut packet = par2::MAGIC.to_vec();
+        packet.extend_from_slice(&64u64.to_le_bytes());
+        packet.extend_from_slice(&[0u8; 16]);
+        packet.extend_from_slice(&[7u8; 16]);
+        packet.extend_from_slice(b"PAR 2.0\0Main\0\0\0\0");
+
+        let obfuscated = dir.path().join("a8f3e1c09b");
+        std::fs::write(&obfuscated, &packet).unwrap();
+        assert!(!par2::is_par2_file(&obfuscated));
+        assert!(par2::has_par2_magic(&obfuscated));
+        assert_eq!(par2::recovery_set_id(&obfuscated), Some([7u8; 16]));
+
+        let other = dir.path().join("d41d8cd98f");
+        std::fs::write(&other, [b'R'; 128]).unwrap();
+        assert!(!par2::has_par2_magic(&other));
+        assert_eq!(par2::recovery_set_id(&other), None);
+    }
+
+    #[test]
+    fn test_tv_episode_names() {
+        assert!(tv::is_episode("Show.Name.S01E02.1080p.mkv"));
+        assert!(tv::is_episode("show name 1x02"));
+        assert!(tv::is_episode("Show.Name.S02.COMPLETE"));
+        assert!(tv::is_episode("Show Season 3"));
+
+        assert!(!tv::is_episode("Movie.Name.2019.1080p.BluRay"));
+        assert!(!tv::is_episode("Movie.Name.1920x1080"));
+    }
+
     #[test]
     fn test_is_same_archive() {
         assert!(rar::is_same_archive("archive", "archive.part02.rar"));
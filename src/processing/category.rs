@@ -0,0 +1,137 @@
+//! Content categorization
+//!
+//! Classifies a finished download as movie, TV, music, or other from the file types it
+//! contains and the release name, so downstream tools can sort it without re-inspecting it.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::file_extension;
+use crate::patterns::{par2 as par2_patterns, rar as rar_patterns, tv};
+
+const VIDEO_EXTENSIONS: &[&str] = &[
+    "mkv", "mp4", "avi", "mov", "wmv", "m4v", "mpg", "mpeg", "m2ts", "ts", "webm", "flv",
+];
+
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "flac", "wav", "aac", "ogg", "m4a", "opus", "wma"];
+
+/// Broad kind of content a download holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Movie,
+    Tv,
+    Music,
+    Other,
+}
+
+impl Category {
+    /// Lowercase name, also used for category subfolders
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Movie => "movie",
+            Category::Tv => "tv",
+            Category::Music => "music",
+            Category::Other => "other",
+        }
+    }
+}
+
+impl std::fmt::Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Classify the files in a download directory
+///
+/// Whichever of video or audio makes up more bytes decides the category; video is TV when
+/// the release name or any video file name carries an episode or season marker. Files
+/// without a known extension are identified by their magic bytes.
+pub fn classify_download(dir: &Path, release_name: &str) -> Category {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files);
+
+    let mut video_bytes = 0u64;
+    let mut audio_bytes = 0u64;
+    let mut episode_named = tv::is_episode(release_name);
+
+    for path in &files {
+        let size = path.metadata().map(|m| m.len()).unwrap_or(0);
+        match file_extension::detected_extension(path).as_deref() {
+            Some(ext) if VIDEO_EXTENSIONS.contains(&ext) => {
+                video_bytes += size;
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                episode_named |= tv::is_episode(name);
+            }
+            Some(ext) if AUDIO_EXTENSIONS.contains(&ext) => audio_bytes += size,
+            _ => {}
+        }
+    }
+
+    categorize(video_bytes, audio_bytes, episode_named)
+}
+
+fn categorize(video_bytes: u64, audio_bytes: u64, episode_named: bool) -> Category {
+    if video_bytes == 0 && audio_bytes == 0 {
+        Category::Other
+    } else if video_bytes >= audio_bytes {
+        if episode_named {
+            Category::Tv
+        } else {
+            Category::Movie
+        }
+    } else {
+        Category::Music
+    }
+}
+
+/// Collect content files recursively, leaving out archives and recovery data
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            if !par2_patterns::is_par2_file(&path) && !rar_patterns::is_rar_related(name) {
+                files.push(path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_categorize_by_dominant_content() {
+        assert_eq!(categorize(0, 0, false), Category::Other);
+        assert_eq!(categorize(4_000, 100, false), Category::Movie);
+        assert_eq!(categorize(4_000, 100, true), Category::Tv);
+        assert_eq!(categorize(100, 4_000, true), Category::Music);
+    }
+
+    #[test]
+    fn test_classify_download_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("Show.S01E01.mkv"), vec![0u8; 2048]).unwrap();
+        std::fs::write(dir.path().join("theme.mp3"), vec![0u8; 16]).unwrap();
+        std::fs::write(dir.path().join("show.part01.rar"), vec![0u8; 8192]).unwrap();
+
+        assert_eq!(classify_download(dir.path(), "abc123"), Category::Tv);
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("01 - Track.flac"), vec![0u8; 2048]).unwrap();
+        std::fs::write(dir.path().join("cover.jpg"), vec![0u8; 4096]).unwrap();
+
+        assert_eq!(
+            classify_download(dir.path(), "Artist - Album"),
+            Category::Music
+        );
+    }
+}
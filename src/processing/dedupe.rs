@@ -0,0 +1,238 @@
+//! Duplicate file merging
+//!
+//! Some NZBs carry the same content under two names (mirrors, alternates). Files of equal
+//! size are only candidates: they count as duplicates once their contents match byte for
+//! byte, so a size collision can never cost data.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use super::manifest::MANIFEST_FILE_NAME;
+use super::rar::is_preserved;
+use crate::config::DuplicateAction;
+use crate::error::DlNzbError;
+
+type Result<T> = std::result::Result<T, DlNzbError>;
+
+/// A file whose contents matched another one and was merged into it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Duplicate {
+    /// The file that was hardlinked or removed
+    pub path: PathBuf,
+    /// The copy that was kept
+    pub original: PathBuf,
+    pub size: u64,
+}
+
+/// Find files among `files` with identical contents and merge them
+///
+/// Only the given files are compared, so a download never touches files it didn't write in
+/// a shared folder. Of each set of identical files the first by path is kept; the others are
+/// replaced with a hardlink to it or removed, depending on `action`. Empty and missing files
+/// are left alone.
+pub fn deduplicate(
+    files: &[PathBuf],
+    action: DuplicateAction,
+    preserved: &[regex::Regex],
+) -> Result<Vec<Duplicate>> {
+    if action == DuplicateAction::Keep {
+        return Ok(Vec::new());
+    }
+
+    let mut by_size: BTreeMap<u64, Vec<PathBuf>> = BTreeMap::new();
+    for path in files {
+        if path.file_name().and_then(|n| n.to_str()) == Some(MANIFEST_FILE_NAME)
+            || is_preserved(path, preserved)
+        {
+            continue;
+        }
+        let size = match path.symlink_metadata() {
+            Ok(meta) if meta.is_file() => meta.len(),
+            _ => continue,
+        };
+        if size > 0 {
+            let paths = by_size.entry(size).or_default();
+            if !paths.contains(path) {
+                paths.push(path.clone());
+            }
+        }
+    }
+
+    let mut duplicates = Vec::new();
+    for (size, mut paths) in by_size.into_iter().filter(|(_, p)| p.len() > 1) {
+        paths.sort();
+
+        // Each file joins the first kept file it matches, or is kept itself
+        let mut kept: Vec<PathBuf> = Vec::new();
+        for path in paths {
+            let mut original = None;
+            for candidate in &kept {
+                if !is_same_file(candidate, &path) && same_contents(candidate, &path)? {
+                    original = Some(candidate.clone());
+                    break;
+                }
+            }
+
+            match original {
+                Some(original) => {
+                    merge(&original, &path, action)?;
+                    duplicates.push(Duplicate {
+                        path,
+                        original,
+                        size,
+                    });
+                }
+                None => kept.push(path),
+            }
+        }
+    }
+
+    Ok(duplicates)
+}
+
+/// Replace `duplicate` with a hardlink to `original`, or delete it
+fn merge(original: &Path, duplicate: &Path, action: DuplicateAction) -> Result<()> {
+    match action {
+        DuplicateAction::Keep => {}
+        DuplicateAction::Remove => std::fs::remove_file(duplicate)?,
+        DuplicateAction::Hardlink => {
+            // Link under a temporary name first, so the duplicate is never missing
+            let temp = duplicate.with_file_name(format!(
+                ".{}.dl-nzb-link",
+                duplicate
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("duplicate")
+            ));
+            std::fs::hard_link(original, &temp)?;
+            if let Err(e) = std::fs::rename(&temp, duplicate) {
+                let _ = std::fs::remove_file(&temp);
+                return Err(e.into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Compare two files byte for byte
+fn same_contents(a: &Path, b: &Path) -> Result<bool> {
+    let mut a = BufReader::with_capacity(1024 * 1024, File::open(a)?);
+    let mut b = BufReader::with_capacity(1024 * 1024, File::open(b)?);
+    let mut buf_a = vec![0u8; 64 * 1024];
+    let mut buf_b = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = a.read(&mut buf_a)?;
+        if read == 0 {
+            // Sizes are equal, so `b` is at its end too unless it changed underneath us
+            return Ok(b.read(&mut buf_b[..1])? == 0);
+        }
+        b.read_exact(&mut buf_b[..read])?;
+        if buf_a[..read] != buf_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Whether two paths are already links to the same file, e.g. from an earlier run
+#[cfg(unix)]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (a.metadata(), b.metadata()) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_a: &Path, _b: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::manifest;
+    use super::*;
+
+    #[test]
+    fn test_only_identical_contents_are_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("mirror")).unwrap();
+        std::fs::write(dir.path().join("a.mkv"), b"same content").unwrap();
+        std::fs::write(dir.path().join("mirror").join("b.mkv"), b"same content").unwrap();
+        // Same size, different bytes
+        std::fs::write(dir.path().join("c.mkv"), b"other conten").unwrap();
+
+        let files = manifest::list_files(dir.path());
+        let duplicates = deduplicate(&files, DuplicateAction::Remove, &[]).unwrap();
+
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].original, dir.path().join("a.mkv"));
+        assert_eq!(duplicates[0].path, dir.path().join("mirror").join("b.mkv"));
+        assert_eq!(duplicates[0].size, 12);
+        assert!(!dir.path().join("mirror").join("b.mkv").exists());
+        assert!(dir.path().join("c.mkv").exists());
+    }
+
+    #[test]
+    fn test_preserved_files_are_not_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.srr"), b"same content").unwrap();
+        std::fs::write(dir.path().join("b.srr"), b"same content").unwrap();
+
+        let preserved = [super::super::rar::glob_regex("*.srr")];
+        let files = manifest::list_files(dir.path());
+        let duplicates = deduplicate(&files, DuplicateAction::Remove, &preserved).unwrap();
+
+        assert!(duplicates.is_empty());
+        assert!(dir.path().join("b.srr").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlinked_duplicates_are_not_merged_twice() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.bin"), b"payload").unwrap();
+        std::fs::write(dir.path().join("b.bin"), b"payload").unwrap();
+
+        let files = manifest::list_files(dir.path());
+        let duplicates = deduplicate(&files, DuplicateAction::Hardlink, &[]).unwrap();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(
+            std::fs::read(dir.path().join("b.bin")).unwrap(),
+            b"payload".to_vec()
+        );
+        assert!(is_same_file(
+            &dir.path().join("a.bin"),
+            &dir.path().join("b.bin")
+        ));
+
+        assert!(deduplicate(&files, DuplicateAction::Hardlink, &[])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_only_given_files_are_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        // Another download's copy in the same folder
+        std::fs::write(dir.path().join("other.mkv"), b"same content").unwrap();
+        std::fs::write(dir.path().join("mine.mkv"), b"same content").unwrap();
+        std::fs::write(dir.path().join("mine.copy.mkv"), b"same content").unwrap();
+
+        let files = [
+            dir.path().join("mine.mkv"),
+            dir.path().join("mine.copy.mkv"),
+        ];
+        let duplicates = deduplicate(&files, DuplicateAction::Remove, &[]).unwrap();
+
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].path, dir.path().join("mine.mkv"));
+        assert!(dir.path().join("other.mkv").exists());
+        assert!(dir.path().join("mine.copy.mkv").exists());
+    }
+}
@@ -4,20 +4,28 @@
 //! to more meaningful names based on the NZB name.
 
 use super::file_extension;
+use super::par2_index::{self, Par2FileEntry};
+use crate::config::PoorNameFallback;
 use crate::error::{DlNzbError, PostProcessingError};
+use crate::patterns::par2 as par2_patterns;
+use crate::patterns::zip as zip_patterns;
 use std::fs;
 use std::path::{Path, PathBuf};
 
 type Result<T> = std::result::Result<T, DlNzbError>;
 
 /// Check if a filename looks obfuscated (random/meaningless)
-fn is_probably_obfuscated(filename: &str) -> bool {
+pub fn is_probably_obfuscated(filename: &str) -> bool {
     // Remove extension for analysis
     let name_without_ext = Path::new(filename)
         .file_stem()
         .and_then(|s| s.to_str())
         .unwrap_or(filename);
+    is_meaningless(name_without_ext)
+}
 
+/// Check if a name without extension looks random/meaningless
+fn is_meaningless(name_without_ext: &str) -> bool {
     // Check for patterns that suggest obfuscation
     let lowercase = name_without_ext.to_lowercase();
 
@@ -74,6 +82,113 @@ fn is_probably_obfuscated(filename: &str) -> bool {
     false
 }
 
+/// Names that say nothing about the release, e.g. a client's default download folder
+const GENERIC_NAMES: &[&str] = &[
+    "download",
+    "downloads",
+    "nzb",
+    "complete",
+    "incomplete",
+    "untitled",
+    "new folder",
+    "tmp",
+    "temp",
+];
+
+/// Check if an NZB or directory name is too poor to rename files after
+///
+/// Only generic names and bare hashes count: real titles can be short (`Dune`), numeric
+/// (`1917`) or digit-heavy (`Show.S01E01.2023.1080p.WEB.h264-GRP`), which the file name
+/// heuristics in `is_meaningless` would reject.
+fn is_poor_name(name: &str) -> bool {
+    let trimmed = name.trim();
+    let lowercase = trimmed
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c == '_' || c == ' ' || c == '-')
+        .to_lowercase();
+    trimmed.is_empty() || GENERIC_NAMES.contains(&lowercase.as_str()) || is_hash_like(trimmed)
+}
+
+/// A hex digest or UUID, e.g. `a1b2c3d4e5f6a7b8c9d0`
+fn is_hash_like(name: &str) -> bool {
+    name.len() >= 16 && name.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+/// Release title from the directory's .nfo, if one gives a confident name
+///
+/// A non-obfuscated .nfo file name wins; otherwise the first release-style token
+/// (`Name.With.Dots-GROUP`) in its text, as NFO banners usually carry one.
+fn nfo_title(directory: &Path) -> Option<String> {
+    let mut nfos: Vec<PathBuf> = fs::read_dir(directory)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && get_ext(path).eq_ignore_ascii_case(".nfo"))
+        .collect();
+    nfos.sort();
+
+    for nfo in &nfos {
+        let stem = nfo.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        if !is_poor_name(stem) && !is_meaningless(stem) {
+            return Some(stem.to_string());
+        }
+    }
+
+    nfos.iter().find_map(|nfo| {
+        let content = fs::read(nfo).ok()?;
+        let text = String::from_utf8_lossy(&content[..content.len().min(64 * 1024)]);
+        text.split_whitespace()
+            .map(|token| token.trim_matches(|c: char| !c.is_ascii_alphanumeric()))
+            .find(|token| is_release_title(token))
+            .map(str::to_string)
+    })
+}
+
+/// A token shaped like a scene release name, e.g. `Movie.Title.2020.1080p.BluRay-GROUP`
+fn is_release_title(token: &str) -> bool {
+    token.len() >= 8
+        && token.matches('.').count() >= 2
+        && !token.contains("..")
+        && !token.contains("://")
+        && !token.starts_with("www.")
+        && token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        && !is_poor_name(token)
+}
+
+/// Name from the main file's detected type and the current time, e.g. `video_2024-05-01_120000`
+fn content_name(ext: &str) -> String {
+    let kind = match ext.trim_start_matches('.').to_lowercase().as_str() {
+        "mkv" | "mp4" | "avi" | "m4v" | "mov" | "wmv" | "ts" | "m2ts" | "webm" | "mpg" => "video",
+        "mp3" | "flac" | "m4a" | "aac" | "ogg" | "wav" | "opus" => "audio",
+        "iso" | "img" | "bin" => "disc",
+        "jpg" | "jpeg" | "png" | "gif" | "webp" => "image",
+        "epub" | "pdf" | "mobi" | "azw3" | "cbz" | "cbr" => "book",
+        _ => "file",
+    };
+    format!(
+        "{}_{}",
+        kind,
+        chrono::Local::now().format("%Y-%m-%d_%H%M%S")
+    )
+}
+
+/// Name to give the main file, or `None` to leave it as is
+fn target_name(
+    directory: &Path,
+    useful_name: &str,
+    ext: &str,
+    fallback: PoorNameFallback,
+) -> Option<String> {
+    if !is_poor_name(useful_name) || fallback == PoorNameFallback::Keep {
+        return Some(useful_name.to_string());
+    }
+    match fallback {
+        PoorNameFallback::Skip => None,
+        _ => Some(nfo_title(directory).unwrap_or_else(|| content_name(ext))),
+    }
+}
+
 /// Get the file extension including the dot
 fn get_ext(path: &Path) -> String {
     path.extension()
@@ -92,6 +207,23 @@ fn get_file_size(path: &Path) -> u64 {
     fs::metadata(path).map(|m| m.len()).unwrap_or(0)
 }
 
+/// Extension to give a file without a popular one, if it can be told
+///
+/// Parts of a split file are numbered from their siblings when that's certain, whatever
+/// the first part's magic bytes say; `None` if a part is already numbered that way.
+/// Anything else is identified by its magic bytes.
+fn missing_extension(file: &Path, siblings: &[(PathBuf, u64)]) -> Option<String> {
+    let split_part = file_extension::guess_extension_from_context(file, siblings)
+        .filter(|guess| guess.confidence == file_extension::Confidence::High);
+    match split_part {
+        Some(guess) if file.extension().and_then(|e| e.to_str()) == Some(&guess.extension[1..]) => {
+            None
+        }
+        Some(guess) => Some(guess.extension),
+        None => file_extension::what_is_most_likely_extension(file),
+    }
+}
+
 /// Find the biggest file in the list
 fn get_biggest_file(files: &[PathBuf]) -> Option<(PathBuf, u64)> {
     files
@@ -136,7 +268,7 @@ fn rename_file(old_path: &Path, new_path: &Path) -> Result<PathBuf> {
 }
 
 /// Sanitize a name to be filesystem-safe
-fn sanitize_name(name: &str) -> String {
+pub fn sanitize_name(name: &str) -> String {
     name.chars()
         .map(|c| match c {
             '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
@@ -146,20 +278,135 @@ fn sanitize_name(name: &str) -> String {
         .collect()
 }
 
+/// Maximum filename length in bytes (255 on most filesystems, minus room for a `_N` suffix)
+const MAX_FILENAME_BYTES: usize = 250;
+
+/// Clamp a filename to a filesystem-safe byte length
+///
+/// The extension is preserved and the stem is cut on a char boundary.
+pub fn clamp_filename(name: &str) -> String {
+    if name.len() <= MAX_FILENAME_BYTES {
+        return name.to_string();
+    }
+
+    let (stem, ext) = match name.rfind('.') {
+        Some(idx) if idx > 0 && name.len() - idx <= 16 => name.split_at(idx),
+        _ => (name, ""),
+    };
+
+    let mut end = MAX_FILENAME_BYTES.saturating_sub(ext.len()).min(stem.len());
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    format!("{}{}", &stem[..end], ext)
+}
+
+/// Pair obfuscated files with NZB-listed names by exact size
+///
+/// A pair is only made when the size is unique among the files and matches exactly one
+/// non-obfuscated NZB name, so anything ambiguous falls back to the biggest-file heuristic.
+fn match_nzb_names(
+    files: &[(PathBuf, u64)],
+    nzb_files: &[(String, u64)],
+) -> Vec<(PathBuf, String)> {
+    files
+        .iter()
+        .filter_map(|(file, size)| {
+            let name = file.file_name()?.to_str()?;
+            if !is_probably_obfuscated(name) || files.iter().filter(|(_, s)| s == size).count() != 1
+            {
+                return None;
+            }
+
+            let mut candidates = nzb_files.iter().filter(|(_, s)| s == size);
+            let (nzb_name, _) = candidates.next()?;
+            if candidates.next().is_some() || nzb_name == name || is_probably_obfuscated(nzb_name) {
+                return None;
+            }
+
+            Some((file.clone(), nzb_name.clone()))
+        })
+        .collect()
+}
+
+/// Pair files with PAR2-described names by size and 16 KiB hash
+///
+/// Unlike size alone this tells equally sized files (such as RAR volumes) apart. Files that
+/// already carry a PAR2-listed name are left alone, as is any file or entry matched twice.
+fn match_par2_names(files: &[(PathBuf, u64)], entries: &[Par2FileEntry]) -> Vec<(PathBuf, String)> {
+    let matches: Vec<(PathBuf, String)> = files
+        .iter()
+        .filter_map(|(file, size)| {
+            let name = file.file_name()?.to_str()?;
+            if par2_patterns::is_par2_file(file) || entries.iter().any(|e| e.name == name) {
+                return None;
+            }
+
+            // Only hash files whose size matches something
+            let sized: Vec<&Par2FileEntry> = entries.iter().filter(|e| e.size == *size).collect();
+            if sized.is_empty() {
+                return None;
+            }
+            let hash = par2_index::hash16k(file).ok()?;
+            let mut candidates = sized.into_iter().filter(|e| e.hash16k == hash);
+            let entry = candidates.next()?;
+            if candidates.next().is_some() {
+                return None;
+            }
+
+            Some((file.clone(), entry.name.clone()))
+        })
+        .collect();
+
+    matches
+        .iter()
+        .filter(|(_, name)| matches.iter().filter(|(_, n)| n == name).count() == 1)
+        .cloned()
+        .collect()
+}
+
+/// How `deobfuscate_files` picks and names files
+#[derive(Debug, Clone, Copy)]
+pub struct DeobfuscateOptions<'a> {
+    /// Pass over files with an excluded extension (PAR2, NFO, ...) when picking the largest
+    /// file; otherwise an excluded largest file stops the rename
+    pub skip_excluded: bool,
+    /// Also replace a popular extension that the file's magic bytes confidently contradict
+    pub fix_mislabeled: bool,
+    /// Name for the largest file when `useful_name` is itself generic or a hash
+    pub fallback: PoorNameFallback,
+    /// Files the user named (`--output-name`), which keep their name and extension
+    pub keep: &'a [PathBuf],
+}
+
 pub struct DeobfuscateResult {
     pub files_renamed: usize,
     pub extensions_fixed: usize,
+    /// Every rename made, as (old path, new path) in order
+    pub renamed: Vec<(PathBuf, PathBuf)>,
 }
 
 /// Deobfuscate files in a directory
 ///
 /// This function:
 /// 1. Adds missing extensions to files based on magic bytes
-/// 2. Renames the largest obfuscated file to a meaningful name
-/// 3. Renames related files (same basename) to match
-pub fn deobfuscate_files(directory: &Path, useful_name: &str) -> Result<DeobfuscateResult> {
+/// 2. Renames files matching a PAR2-described file by size and 16 KiB hash, then obfuscated
+///    files matching an NZB-listed name by size (if `nzb_files` is given)
+/// 3. Renames the largest obfuscated file to a meaningful name
+/// 4. Renames related files (same basename) to match
+///
+/// See [`DeobfuscateOptions`] for how steps 1 and 3 can be tuned. Files in `keep` are
+/// never renamed, though they still count when comparing sizes.
+pub fn deobfuscate_files(
+    directory: &Path,
+    useful_name: &str,
+    nzb_files: &[(String, u64)],
+    options: DeobfuscateOptions<'_>,
+) -> Result<DeobfuscateResult> {
     let mut files_renamed = 0;
     let mut extensions_fixed = 0;
+    let mut renamed = Vec::new();
 
     // Get all files in directory (not recursively)
     let mut file_list: Vec<PathBuf> = fs::read_dir(directory)?
@@ -172,6 +419,7 @@ pub fn deobfuscate_files(directory: &Path, useful_name: &str) -> Result<Deobfusc
         return Ok(DeobfuscateResult {
             files_renamed: 0,
             extensions_fixed: 0,
+            renamed: Vec::new(),
         });
     }
 
@@ -190,6 +438,7 @@ pub fn deobfuscate_files(directory: &Path, useful_name: &str) -> Result<Deobfusc
                     return Ok(DeobfuscateResult {
                         files_renamed: 0,
                         extensions_fixed: 0,
+                        renamed: Vec::new(),
                     });
                 }
             }
@@ -197,24 +446,45 @@ pub fn deobfuscate_files(directory: &Path, useful_name: &str) -> Result<Deobfusc
     }
 
     // Step 1: Fix missing extensions
+    let sized_files: Vec<(PathBuf, u64)> = file_list
+        .iter()
+        .map(|f| (f.clone(), get_file_size(f)))
+        .collect();
     let mut new_file_list = Vec::new();
     for file in &file_list {
-        if file_extension::has_popular_extension(file) {
-            // Extension looks fine
-            new_file_list.push(file.clone());
-        } else if let Some(new_ext) = file_extension::what_is_most_likely_extension(file) {
-            // Detected file type - add extension
+        let is_zip_volume = file
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(zip_patterns::is_split_volume);
+        let new_ext = if is_zip_volume {
+            // Split ZIP volumes carry ZIP magic but must keep .001/.z01
+            None
+        } else if options.keep.contains(file) {
+            None
+        } else if file_extension::has_popular_extension(file) {
+            // Extension looks fine, unless the content clearly says otherwise
+            options
+                .fix_mislabeled
+                .then(|| file_extension::mislabeled_extension(file))
+                .flatten()
+        } else {
+            missing_extension(file, &sized_files)
+        };
+
+        if let Some(new_ext) = new_ext {
+            // Detected file type - add or correct the extension
             let new_path = file.with_extension(&new_ext[1..]); // Remove leading dot
             let new_path = get_unique_filename(&new_path);
 
             tracing::debug!(
-                "Adding extension: {} -> {}",
+                "Setting extension: {} -> {}",
                 file.display(),
                 new_path.display()
             );
             match rename_file(file, &new_path) {
-                Ok(renamed) => {
-                    new_file_list.push(renamed);
+                Ok(new_path) => {
+                    renamed.push((file.clone(), new_path.clone()));
+                    new_file_list.push(new_path);
                     extensions_fixed += 1;
                 }
                 Err(e) => {
@@ -228,11 +498,68 @@ pub fn deobfuscate_files(directory: &Path, useful_name: &str) -> Result<Deobfusc
     }
     file_list = new_file_list;
 
-    // Step 2: Find biggest file and check if it needs deobfuscation
-    let Some((biggest_file, biggest_size)) = get_biggest_file(&file_list) else {
+    // Step 2: Restore NZB-listed names for files that can be matched confidently
+    if !nzb_files.is_empty() {
+        let sized: Vec<(PathBuf, u64)> = file_list
+            .iter()
+            .filter(|f| !options.keep.contains(f))
+            .map(|f| (f.clone(), get_file_size(f)))
+            .collect();
+
+        // PAR2 descriptions identify files by content, so they take precedence over sizes
+        let par2_entries: Vec<Par2FileEntry> = file_list
+            .iter()
+            .filter(|f| par2_patterns::is_main_par2(f))
+            .filter_map(|f| par2_index::file_entries(f).ok())
+            .flatten()
+            .collect();
+        let mut matches = match_par2_names(&sized, &par2_entries);
+        let unmatched: Vec<(PathBuf, u64)> = sized
+            .into_iter()
+            .filter(|(f, _)| !matches.iter().any(|(m, _)| m == f))
+            .collect();
+        matches.extend(match_nzb_names(&unmatched, nzb_files));
+
+        for (file, nzb_name) in matches {
+            let new_path = file
+                .parent()
+                .unwrap_or_else(|| Path::new("."))
+                .join(clamp_filename(&sanitize_name(&nzb_name)));
+            let new_path = get_unique_filename(&new_path);
+
+            tracing::debug!(
+                "Matched NZB name: {} -> {}",
+                file.display(),
+                new_path.display()
+            );
+            match rename_file(&file, &new_path) {
+                Ok(new_path) => {
+                    if let Some(entry) = file_list.iter_mut().find(|f| **f == file) {
+                        *entry = new_path.clone();
+                    }
+                    renamed.push((file, new_path));
+                    files_renamed += 1;
+                }
+                Err(e) => tracing::debug!("Failed to rename {}: {}", file.display(), e),
+            }
+        }
+    }
+
+    // Step 3: Find biggest file and check if it needs deobfuscation
+    let candidates: Vec<PathBuf> = if options.skip_excluded {
+        file_list
+            .iter()
+            .filter(|f| !file_extension::EXCLUDED_FILE_EXTS.contains(&get_ext(f).as_str()))
+            .cloned()
+            .collect()
+    } else {
+        file_list.clone()
+    };
+    let Some((biggest_file, biggest_size)) = get_biggest_file(&candidates) else {
         return Ok(DeobfuscateResult {
             files_renamed,
             extensions_fixed,
+            renamed,
         });
     };
 
@@ -246,6 +573,7 @@ pub fn deobfuscate_files(directory: &Path, useful_name: &str) -> Result<Deobfusc
         return Ok(DeobfuscateResult {
             files_renamed,
             extensions_fixed,
+            renamed,
         });
     }
 
@@ -255,6 +583,18 @@ pub fn deobfuscate_files(directory: &Path, useful_name: &str) -> Result<Deobfusc
         .and_then(|s| s.to_str())
         .unwrap_or("");
 
+    if options.keep.contains(&biggest_file) {
+        tracing::debug!(
+            "Biggest file {} was named by the user",
+            biggest_file.display()
+        );
+        return Ok(DeobfuscateResult {
+            files_renamed,
+            extensions_fixed,
+            renamed,
+        });
+    }
+
     if !is_probably_obfuscated(filename) {
         tracing::debug!(
             "Biggest file {} doesn't look obfuscated",
@@ -263,11 +603,12 @@ pub fn deobfuscate_files(directory: &Path, useful_name: &str) -> Result<Deobfusc
         return Ok(DeobfuscateResult {
             files_renamed,
             extensions_fixed,
+            renamed,
         });
     }
 
     // Check if it's significantly bigger than the second biggest file
-    let second_biggest_size = file_list
+    let second_biggest_size = candidates
         .iter()
         .filter(|f| *f != &biggest_file)
         .map(|f| get_file_size(f))
@@ -284,12 +625,24 @@ pub fn deobfuscate_files(directory: &Path, useful_name: &str) -> Result<Deobfusc
         return Ok(DeobfuscateResult {
             files_renamed,
             extensions_fixed,
+            renamed,
         });
     }
 
-    // Step 3: Rename the biggest file
-    let sanitized_name = sanitize_name(useful_name);
-    let new_name = format!("{}{}", sanitized_name, ext);
+    // Step 4: Rename the biggest file
+    let Some(name) = target_name(directory, useful_name, &ext, options.fallback) else {
+        tracing::debug!(
+            "No good name source for {}, leaving it as is",
+            biggest_file.display()
+        );
+        return Ok(DeobfuscateResult {
+            files_renamed,
+            extensions_fixed,
+            renamed,
+        });
+    };
+    let sanitized_name = sanitize_name(&name);
+    let new_name = clamp_filename(&format!("{}{}", sanitized_name, ext));
     let new_path = biggest_file
         .parent()
         .unwrap_or_else(|| Path::new("."))
@@ -303,7 +656,8 @@ pub fn deobfuscate_files(directory: &Path, useful_name: &str) -> Result<Deobfusc
     );
 
     match rename_file(&biggest_file, &new_path) {
-        Ok(_) => {
+        Ok(new_path) => {
+            renamed.push((biggest_file.clone(), new_path));
             files_renamed += 1;
         }
         Err(e) => {
@@ -311,16 +665,17 @@ pub fn deobfuscate_files(directory: &Path, useful_name: &str) -> Result<Deobfusc
             return Ok(DeobfuscateResult {
                 files_renamed,
                 extensions_fixed,
+                renamed,
             });
         }
     }
 
-    // Step 4: Find and rename related files (same basename)
+    // Step 5: Find and rename related files (same basename)
     let basename = get_basename(&biggest_file);
     let basename_str = basename.to_string_lossy();
 
     for file in &file_list {
-        if *file == biggest_file {
+        if *file == biggest_file || options.keep.contains(file) {
             continue;
         }
 
@@ -333,7 +688,7 @@ pub fn deobfuscate_files(directory: &Path, useful_name: &str) -> Result<Deobfusc
                 .to_string_lossy()
                 .replace(&basename_str.to_string(), "");
 
-            let new_name = format!("{}{}", sanitized_name, remaining);
+            let new_name = clamp_filename(&format!("{}{}", sanitized_name, remaining));
             let new_path = file
                 .parent()
                 .unwrap_or_else(|| Path::new("."))
@@ -347,7 +702,10 @@ pub fn deobfuscate_files(directory: &Path, useful_name: &str) -> Result<Deobfusc
             );
 
             match rename_file(file, &new_path) {
-                Ok(_) => files_renamed += 1,
+                Ok(new_path) => {
+                    renamed.push((file.clone(), new_path));
+                    files_renamed += 1;
+                }
                 Err(e) => tracing::debug!("Failed to rename {}: {}", file.display(), e),
             }
         }
@@ -356,6 +714,7 @@ pub fn deobfuscate_files(directory: &Path, useful_name: &str) -> Result<Deobfusc
     Ok(DeobfuscateResult {
         files_renamed,
         extensions_fixed,
+        renamed,
     })
 }
 
@@ -372,6 +731,312 @@ mod tests {
         assert!(!is_probably_obfuscated("My.Document.pdf"));
     }
 
+    #[test]
+    fn test_match_nzb_names() {
+        let files = vec![
+            (PathBuf::from("/dl/a1b2c3d4e5f6a7b8.bin"), 1000),
+            (PathBuf::from("/dl/f7f8f9000111.bin"), 2000),
+            (PathBuf::from("/dl/Readable_Name.nfo"), 3000),
+        ];
+        let nzb_files = vec![
+            ("Great_Movie_2023.mkv".to_string(), 1000),
+            ("Other_Release.mkv".to_string(), 2000),
+            ("Same_Size_Release.mkv".to_string(), 2000),
+        ];
+
+        // Only the unambiguous size match is paired; duplicate NZB sizes fall back
+        assert_eq!(
+            match_nzb_names(&files, &nzb_files),
+            vec![(
+                PathBuf::from("/dl/a1b2c3d4e5f6a7b8.bin"),
+                "Great_Movie_2023.mkv".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_match_par2_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &[u8]| {
+            let path = dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            (path, content.len() as u64)
+        };
+        // Equally sized volumes only differ in content
+        let first = write("9f8e7d6c5b4a.bin", &[1; 100]);
+        let second = write("0a1b2c3d4e5f.bin", &[2; 100]);
+        let named = write("Great.Movie.2023.nfo", b"info");
+        let unknown = write("aabbccddeeff.bin", &[3; 100]);
+
+        let entry = |name: &str, path: &Path, size: u64| Par2FileEntry {
+            name: name.to_string(),
+            size,
+            hash16k: par2_index::hash16k(path).unwrap(),
+        };
+        let entries = vec![
+            entry("Great.Movie.2023.part1.rar", &first.0, 100),
+            entry("Great.Movie.2023.part2.rar", &second.0, 100),
+            entry("Great.Movie.2023.nfo", &named.0, 4),
+        ];
+
+        let files = vec![first.clone(), second.clone(), named, unknown];
+        assert_eq!(
+            match_par2_names(&files, &entries),
+            vec![
+                (first.0, "Great.Movie.2023.part1.rar".to_string()),
+                (second.0, "Great.Movie.2023.part2.rar".to_string()),
+            ]
+        );
+
+        // Identical copies can't be told apart
+        let copy = write("ffeeddccbbaa.bin", &[1; 100]);
+        let files = vec![(dir.path().join("9f8e7d6c5b4a.bin"), 100), copy];
+        assert!(match_par2_names(&files, &entries).is_empty());
+    }
+
+    #[test]
+    fn test_excluded_biggest_file_is_passed_over() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a1b2c3d4e5f6a7b8.par2"), vec![0u8; 4000]).unwrap();
+        std::fs::write(dir.path().join("f7f8f9abc123.mkv"), vec![0u8; 2000]).unwrap();
+        std::fs::write(dir.path().join("f7f8f9abc123.srt"), vec![0u8; 100]).unwrap();
+
+        let result = deobfuscate_files(
+            dir.path(),
+            "Show.S01E01",
+            &[],
+            DeobfuscateOptions {
+                skip_excluded: false,
+                fix_mislabeled: false,
+                fallback: PoorNameFallback::Keep,
+                keep: &[],
+            },
+        )
+        .unwrap();
+        assert_eq!(result.files_renamed, 0);
+
+        let result = deobfuscate_files(
+            dir.path(),
+            "Show.S01E01",
+            &[],
+            DeobfuscateOptions {
+                skip_excluded: true,
+                fix_mislabeled: false,
+                fallback: PoorNameFallback::Keep,
+                keep: &[],
+            },
+        )
+        .unwrap();
+        assert_eq!(result.files_renamed, 2);
+        assert!(dir.path().join("Show.S01E01.mkv").exists());
+        assert!(dir.path().join("Show.S01E01.srt").exists());
+        assert!(dir.path().join("a1b2c3d4e5f6a7b8.par2").exists());
+    }
+
+    #[test]
+    fn test_user_named_file_is_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        // A short name with no extension, which would otherwise get both fixed
+        let named = dir.path().join("film");
+        let mut mkv = b"\x1aE\xdf\xa3\x9fB\x86\x81\x01".to_vec();
+        mkv.resize(2000, 0);
+        std::fs::write(&named, &mkv).unwrap();
+        std::fs::write(dir.path().join("f7f8f9abc123.srt"), vec![0u8; 100]).unwrap();
+
+        let result = deobfuscate_files(
+            dir.path(),
+            "Show.S01E01",
+            &[("Show.S01E01.mkv".to_string(), 2000)],
+            DeobfuscateOptions {
+                skip_excluded: true,
+                fix_mislabeled: true,
+                fallback: PoorNameFallback::Keep,
+                keep: std::slice::from_ref(&named),
+            },
+        )
+        .unwrap();
+        assert_eq!(result.extensions_fixed, 0);
+        assert!(result.renamed.iter().all(|(old, _)| *old != named));
+        assert!(named.exists());
+    }
+
+    #[test]
+    fn test_mislabeled_extension_fixed_only_when_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Show.S01E01.txt");
+        std::fs::write(&path, b"\x1aE\xdf\xa3\x9fB\x86\x81\x01").unwrap();
+
+        let result = deobfuscate_files(
+            dir.path(),
+            "Show.S01E01",
+            &[],
+            DeobfuscateOptions {
+                skip_excluded: true,
+                fix_mislabeled: false,
+                fallback: PoorNameFallback::Keep,
+                keep: &[],
+            },
+        )
+        .unwrap();
+        assert_eq!(result.extensions_fixed, 0);
+        assert!(path.exists());
+
+        let result = deobfuscate_files(
+            dir.path(),
+            "Show.S01E01",
+            &[],
+            DeobfuscateOptions {
+                skip_excluded: true,
+                fix_mislabeled: true,
+                fallback: PoorNameFallback::Keep,
+                keep: &[],
+            },
+        )
+        .unwrap();
+        assert_eq!(result.extensions_fixed, 1);
+        assert!(dir.path().join("Show.S01E01.mkv").exists());
+    }
+
+    #[test]
+    fn test_poor_name() {
+        assert!(is_poor_name("download"));
+        assert!(is_poor_name("Downloads_2"));
+        assert!(is_poor_name("a1b2c3d4e5f6a7b8c9d0"));
+        assert!(is_poor_name("0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0"));
+        assert!(!is_poor_name("Show.S01E01"));
+        assert!(!is_poor_name("Great Movie 2023"));
+        // Real titles the file name heuristics would call meaningless
+        assert!(!is_poor_name("Show.S01E01.2023.1080p.WEB.h264-GRP"));
+        assert!(!is_poor_name("Dune"));
+        assert!(!is_poor_name("1917"));
+        assert!(!is_poor_name("Decade"));
+    }
+
+    #[test]
+    fn test_digit_heavy_release_name_is_used() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("f7f8f9abc123.mkv"), vec![0u8; 2000]).unwrap();
+
+        let result = deobfuscate_files(
+            dir.path(),
+            "Show.S01E01.2023.1080p.WEB.h264-GRP",
+            &[],
+            DeobfuscateOptions {
+                skip_excluded: true,
+                fix_mislabeled: false,
+                fallback: PoorNameFallback::Skip,
+                keep: &[],
+            },
+        )
+        .unwrap();
+        assert_eq!(result.files_renamed, 1);
+        assert!(dir
+            .path()
+            .join("Show.S01E01.2023.1080p.WEB.h264-GRP.mkv")
+            .exists());
+    }
+
+    #[test]
+    fn test_poor_name_fallback() {
+        let setup = || {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::write(dir.path().join("f7f8f9abc123.mkv"), vec![0u8; 2000]).unwrap();
+            dir
+        };
+
+        // Without an .nfo, the name comes from the file's type
+        let dir = setup();
+        let result = deobfuscate_files(
+            dir.path(),
+            "download",
+            &[],
+            DeobfuscateOptions {
+                skip_excluded: true,
+                fix_mislabeled: false,
+                fallback: PoorNameFallback::Derive,
+                keep: &[],
+            },
+        )
+        .unwrap();
+        assert_eq!(result.files_renamed, 1);
+        let name = result.renamed[0].1.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("video_") && name.ends_with(".mkv"));
+
+        // The .nfo's release title wins when there is one
+        let dir = setup();
+        std::fs::write(
+            dir.path().join("a1b2c3d4e5f6a7b8.nfo"),
+            "  ####  Great.Movie.2023.1080p.BluRay-GRP  ####\r\n  www.example.com\r\n",
+        )
+        .unwrap();
+        deobfuscate_files(
+            dir.path(),
+            "download",
+            &[],
+            DeobfuscateOptions {
+                skip_excluded: true,
+                fix_mislabeled: false,
+                fallback: PoorNameFallback::Derive,
+                keep: &[],
+            },
+        )
+        .unwrap();
+        assert!(dir
+            .path()
+            .join("Great.Movie.2023.1080p.BluRay-GRP.mkv")
+            .exists());
+
+        let dir = setup();
+        let result = deobfuscate_files(
+            dir.path(),
+            "download",
+            &[],
+            DeobfuscateOptions {
+                skip_excluded: true,
+                fix_mislabeled: false,
+                fallback: PoorNameFallback::Skip,
+                keep: &[],
+            },
+        )
+        .unwrap();
+        assert_eq!(result.files_renamed, 0);
+        assert!(dir.path().join("f7f8f9abc123.mkv").exists());
+
+        let dir = setup();
+        deobfuscate_files(
+            dir.path(),
+            "download",
+            &[],
+            DeobfuscateOptions {
+                skip_excluded: true,
+                fix_mislabeled: false,
+                fallback: PoorNameFallback::Keep,
+                keep: &[],
+            },
+        )
+        .unwrap();
+        assert!(dir.path().join("download.mkv").exists());
+    }
+
+    #[test]
+    fn test_clamp_filename() {
+        // Short names are untouched
+        assert_eq!(clamp_filename("movie.mkv"), "movie.mkv");
+
+        // Long ASCII names keep their extension
+        let long = format!("{}.mkv", "a".repeat(300));
+        let clamped = clamp_filename(&long);
+        assert_eq!(clamped.len(), MAX_FILENAME_BYTES);
+        assert!(clamped.ends_with(".mkv"));
+
+        // Multibyte names are cut on a char boundary
+        let multibyte = format!("{}.mkv", "é".repeat(300));
+        let clamped = clamp_filename(&multibyte);
+        assert!(clamped.len() <= MAX_FILENAME_BYTES);
+        assert!(clamped.ends_with(".mkv"));
+        assert!(clamped.trim_end_matches(".mkv").chars().all(|c| c == 'é'));
+    }
+
     #[test]
     fn test_sanitize_name() {
         assert_eq!(sanitize_name("File/Name:Test"), "File_Name_Test");
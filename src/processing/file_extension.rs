@@ -5,7 +5,7 @@
 
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Common/popular file extensions that are considered meaningful
 const POPULAR_EXTENSIONS: &[&str] = &[
@@ -20,7 +20,7 @@ const POPULAR_EXTENSIONS: &[&str] = &[
     "epub", "mobi", "azw", "azw3", "fb2", "cbr", "cbz", // Subtitles
     "srt", "sub", "idx", "ass", "ssa", "vtt", // Executables
     "exe", "dll", "dmg", "app", "apk", "deb", "rpm", // Data
-    "nfo", "sfv", "nzb", "torrent",
+    "nfo", "sfv", "nzb", "torrent", "par2",
 ];
 
 /// DVD/Bluray directories that should prevent deobfuscation
@@ -29,6 +29,14 @@ pub const IGNORED_MOVIE_FOLDERS: &[&str] = &["VIDEO_TS", "AUDIO_TS", "BDMV", "CE
 /// File extensions to exclude from deobfuscation
 pub const EXCLUDED_FILE_EXTS: &[&str] = &[".par2", ".sfv", ".nfo", ".txt", ".srr"];
 
+/// Whether a file is a ReScene `.srr`, which can rebuild the original RAR volumes from the
+/// extracted content for re-seeding
+pub fn is_rescene_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("srr"))
+}
+
 /// Magic bytes for common file types
 struct MagicBytes {
     bytes: &'static [u8],
@@ -172,6 +180,12 @@ const MAGIC_BYTES: &[MagicBytes] = &[
         extension: ".docx",
         offset: 0,
     }, // Also matches ZIP, needs further validation
+    // Recovery data
+    MagicBytes {
+        bytes: b"PAR2\0PKT",
+        extension: ".par2",
+        offset: 0,
+    },
     // ISO
     MagicBytes {
         bytes: b"CD001",
@@ -267,6 +281,287 @@ pub fn what_is_most_likely_extension<P: AsRef<Path>>(path: P) -> Option<String>
     None
 }
 
+/// Detected types whose signatures are specific enough to overrule a file's extension
+///
+/// Short or ambiguous signatures (BMP's "BM", MP3 frame sync, MPEG start codes) turn up in
+/// other data by chance and are left out.
+const CONFIDENT_DETECTIONS: &[&str] = &[
+    "mkv", "mp4", "m4v", "m4a", "mov", "avi", "wav", "webp", "png", "gif", "jpg", "zip", "docx",
+    "xlsx", "pptx", "epub", "rar", "7z", "gz", "pdf", "flac", "ogg", "par2", "iso",
+];
+
+/// Extensions that name the same container, so detecting one of them isn't a disagreement
+const EXTENSION_FAMILIES: &[&[&str]] = &[
+    &["mkv", "webm"],
+    &["mp4", "m4v", "m4a", "mov"],
+    &[
+        "zip", "docx", "xlsx", "pptx", "epub", "cbz", "apk", "odt", "ods", "odp",
+    ],
+    &["rar", "cbr"],
+    &["jpg", "jpeg"],
+    &["gz", "tgz"],
+    &["ogg", "opus", "oga"],
+];
+
+/// The extension a file should have when its magic bytes confidently contradict the
+/// popular one it has, e.g. a Matroska video named `.txt`
+///
+/// Returns `None` when the name has no popular extension, detection isn't confident, or
+/// the detected type agrees with the name (including variants of the same container).
+pub fn mislabeled_extension<P: AsRef<Path>>(path: P) -> Option<String> {
+    let path = path.as_ref();
+    if !has_popular_extension(path) {
+        return None;
+    }
+    let current = path.extension()?.to_str()?.to_lowercase();
+    let detected = what_is_most_likely_extension(path)?;
+    let detected_bare = detected.trim_start_matches('.');
+
+    let same_family = EXTENSION_FAMILIES
+        .iter()
+        .any(|family| family.contains(&current.as_str()) && family.contains(&detected_bare));
+    if detected_bare == current || same_family || !CONFIDENT_DETECTIONS.contains(&detected_bare) {
+        return None;
+    }
+    Some(detected)
+}
+
+/// Lowercase extension without the dot, from the name or else the file's magic bytes
+pub fn detected_extension<P: AsRef<Path>>(path: P) -> Option<String> {
+    let path = path.as_ref();
+    if has_popular_extension(path) {
+        return path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+    }
+
+    what_is_most_likely_extension(path).map(|ext| ext.trim_start_matches('.').to_string())
+}
+
+/// How strongly the context supports a guessed extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+/// Extension guessed from a file's siblings rather than its contents
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionGuess {
+    /// With the leading dot, like [`what_is_most_likely_extension`]
+    pub extension: String,
+    pub confidence: Confidence,
+}
+
+/// Guess the extension of a file from the files next to it
+///
+/// For content the magic-byte table can't identify, e.g. the parts of an encrypted split
+/// archive. Files named `<base>.<n>` that share a base and are numbered without gaps from
+/// 0 or 1 are parts of one split file, and each gets its 1-based part number as a
+/// three-digit extension (`.001`, `.002`, ...). Confidence is high with at least three
+/// parts that are all the same size except a smaller last one, medium with only two such
+/// parts, and low when the sizes vary. `siblings` holds every file in the directory with
+/// its size, `path` included. A file that already has a three-digit extension is left
+/// alone: splitters number from `.000` as well as `.001`, and joiners expect either.
+pub fn guess_extension_from_context(
+    path: &Path,
+    siblings: &[(PathBuf, u64)],
+) -> Option<ExtensionGuess> {
+    let part_number = |path: &Path| -> Option<u32> {
+        let ext = path.extension()?.to_str()?;
+        if ext.is_empty() || ext.len() > 4 || !ext.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        ext.parse().ok()
+    };
+
+    let number = part_number(path)?;
+    if path.extension().is_some_and(|ext| ext.len() == 3) {
+        return None;
+    }
+    let base = path.with_extension("");
+    let mut parts: Vec<(u32, u64)> = siblings
+        .iter()
+        .filter(|(sibling, _)| sibling.with_extension("") == base)
+        .filter_map(|(sibling, size)| Some((part_number(sibling)?, *size)))
+        .collect();
+    parts.sort_unstable();
+
+    // Numbered 0..n or 1..n, each number once
+    let first = parts.first()?.0;
+    if parts.len() < 2
+        || first > 1
+        || parts
+            .iter()
+            .enumerate()
+            .any(|(i, &(n, _))| n != first + i as u32)
+    {
+        return None;
+    }
+
+    let part_size = parts[0].1;
+    let last_size = parts[parts.len() - 1].1;
+    let even = part_size > 0
+        && last_size > 0
+        && last_size <= part_size
+        && parts[..parts.len() - 1]
+            .iter()
+            .all(|&(_, size)| size == part_size);
+    let confidence = match (even, parts.len()) {
+        (false, _) => Confidence::Low,
+        (true, 2) => Confidence::Medium,
+        (true, _) => Confidence::High,
+    };
+
+    Some(ExtensionGuess {
+        extension: format!(".{:03}", number - first + 1),
+        confidence,
+    })
+}
+
+/// Outcome of a media container sanity check
+#[derive(Debug, PartialEq)]
+pub enum ContainerCheck {
+    /// Container structure looks intact
+    Valid,
+    /// Container structure is broken, with the reason
+    Invalid(String),
+    /// Not a container format we know how to inspect
+    Unknown,
+}
+
+/// Check that a media file's container structure is intact
+///
+/// Goes one step past magic bytes: MP4 top-level boxes must tile the file and include
+/// `moov` and `mdat`, and MKV must have an EBML header followed by a Segment that fits.
+pub fn check_media_container<P: AsRef<Path>>(path: P) -> ContainerCheck {
+    let path = path.as_ref();
+    let result = match what_is_most_likely_extension(path).as_deref() {
+        Some(".mp4" | ".m4v" | ".mov") => check_mp4(path),
+        Some(".mkv") => check_mkv(path),
+        _ => return ContainerCheck::Unknown,
+    };
+
+    match result {
+        Ok(()) => ContainerCheck::Valid,
+        Err(reason) => ContainerCheck::Invalid(reason),
+    }
+}
+
+/// Walk MP4 top-level boxes, requiring them to fit the file and include `moov` and `mdat`
+fn check_mp4(path: &Path) -> std::result::Result<(), String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let file_len = file.metadata().map_err(|e| e.to_string())?.len();
+
+    let mut offset = 0u64;
+    let mut has_moov = false;
+    let mut has_mdat = false;
+
+    while offset < file_len {
+        let mut header = [0u8; 8];
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut header))
+            .map_err(|_| format!("truncated box header at offset {}", offset))?;
+
+        let box_type = String::from_utf8_lossy(&header[4..8]).to_string();
+        let box_size = match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+            // Box extends to end of file
+            0 => file_len - offset,
+            // 64-bit size follows the type
+            1 => {
+                let mut large = [0u8; 8];
+                file.read_exact(&mut large)
+                    .map_err(|_| format!("truncated {} box header", box_type))?;
+                u64::from_be_bytes(large)
+            }
+            size => size as u64,
+        };
+
+        if box_size < 8
+            || offset
+                .checked_add(box_size)
+                .map_or(true, |end| end > file_len)
+        {
+            return Err(format!("{} box overruns the file", box_type));
+        }
+
+        match &header[4..8] {
+            b"moov" => has_moov = true,
+            b"mdat" => has_mdat = true,
+            _ => {}
+        }
+        offset += box_size;
+    }
+
+    match (has_moov, has_mdat) {
+        (true, true) => Ok(()),
+        (false, _) => Err("missing moov box".to_string()),
+        (_, false) => Err("missing mdat box".to_string()),
+    }
+}
+
+/// Check for an EBML header followed by a Segment element that fits the file
+fn check_mkv(path: &Path) -> std::result::Result<(), String> {
+    const EBML_ID: u64 = 0x1A45_DFA3;
+    const SEGMENT_ID: u64 = 0x1853_8067;
+
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let file_len = file.metadata().map_err(|e| e.to_string())?.len();
+
+    if read_ebml_id(&mut file) != Some(EBML_ID) {
+        return Err("missing EBML header".to_string());
+    }
+    let (header_size, _) = read_ebml_size(&mut file).ok_or("truncated EBML header")?;
+    file.seek(SeekFrom::Current(header_size as i64))
+        .map_err(|e| e.to_string())?;
+
+    if read_ebml_id(&mut file) != Some(SEGMENT_ID) {
+        return Err("missing Segment element".to_string());
+    }
+    let (segment_size, size_len) = read_ebml_size(&mut file).ok_or("truncated Segment header")?;
+    let segment_start = file.stream_position().map_err(|e| e.to_string())?;
+
+    // All-ones size means "unknown" (live/streamed files), which can't be checked
+    let unknown_size = (1u64 << (7 * size_len)) - 1;
+    if segment_size != unknown_size && segment_start + segment_size > file_len {
+        return Err("Segment overruns the file".to_string());
+    }
+
+    Ok(())
+}
+
+/// Read an EBML element ID (IDs keep their length marker bit)
+fn read_ebml_id(file: &mut File) -> Option<u64> {
+    read_vint_raw(file).map(|(id, _)| id)
+}
+
+/// Read an EBML element size, returning the value and its encoded length
+fn read_ebml_size(file: &mut File) -> Option<(u64, usize)> {
+    let (value, len) = read_vint_raw(file)?;
+    Some((value & ((1u64 << (7 * len)) - 1), len))
+}
+
+/// Read the raw bytes of an EBML vint (marker bit included) and its length
+fn read_vint_raw(file: &mut File) -> Option<(u64, usize)> {
+    let mut first = [0u8; 1];
+    file.read_exact(&mut first).ok()?;
+    let len = first[0].leading_zeros() as usize + 1;
+    if len > 8 {
+        return None;
+    }
+
+    let mut value = first[0] as u64;
+    for _ in 1..len {
+        let mut byte = [0u8; 1];
+        file.read_exact(&mut byte).ok()?;
+        value = (value << 8) | byte[0] as u64;
+    }
+
+    Some((value, len))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -283,6 +578,73 @@ mod tests {
         assert!(!has_popular_extension("noext"));
     }
 
+    #[test]
+    fn test_split_parts_guessed_from_siblings() {
+        let parts = |sizes: &[u64]| -> Vec<(PathBuf, u64)> {
+            sizes
+                .iter()
+                .enumerate()
+                .map(|(i, &size)| (PathBuf::from(format!("/dl/f3a9c1.{}", i + 1)), size))
+                .chain([(PathBuf::from("/dl/other.bin"), 7)])
+                .collect()
+        };
+
+        let even = parts(&[100, 100, 100, 40]);
+        let guess = guess_extension_from_context(Path::new("/dl/f3a9c1.3"), &even).unwrap();
+        assert_eq!(guess.extension, ".003");
+        assert_eq!(guess.confidence, Confidence::High);
+
+        let two = parts(&[100, 40]);
+        let guess = guess_extension_from_context(Path::new("/dl/f3a9c1.1"), &two).unwrap();
+        assert_eq!(guess.confidence, Confidence::Medium);
+
+        let uneven = parts(&[100, 60, 100]);
+        let guess = guess_extension_from_context(Path::new("/dl/f3a9c1.1"), &uneven).unwrap();
+        assert_eq!(guess.confidence, Confidence::Low);
+
+        let mut gap = parts(&[100, 100, 100]);
+        gap.remove(1);
+        assert!(guess_extension_from_context(Path::new("/dl/f3a9c1.1"), &gap).is_none());
+        assert!(guess_extension_from_context(Path::new("/dl/other.bin"), &even).is_none());
+
+        // A set numbered from .000 keeps its names rather than shifting to .001
+        let zero_based: Vec<(PathBuf, u64)> = (0..3)
+            .map(|i| (PathBuf::from(format!("/dl/movie.mkv.{:03}", i)), 100))
+            .collect();
+        assert!(
+            guess_extension_from_context(Path::new("/dl/movie.mkv.000"), &zero_based).is_none()
+        );
+    }
+
+    #[test]
+    fn test_is_rescene_file() {
+        assert!(is_rescene_file(Path::new("Movie.2020.1080p-GRP.srr")));
+        assert!(is_rescene_file(Path::new("movie.SRR")));
+        assert!(!is_rescene_file(Path::new("movie.srt")));
+        assert!(!is_rescene_file(Path::new("srr")));
+    }
+
+    #[test]
+    fn test_mislabeled_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let mkv_header = b"\x1aE\xdf\xa3\x9fB\x86\x81\x01";
+        for name in ["movie.txt", "movie.webm", "movie.mkv", "movie.bin"] {
+            std::fs::write(dir.path().join(name), mkv_header).unwrap();
+        }
+        std::fs::write(dir.path().join("image.txt"), b"BM not really a bitmap").unwrap();
+        std::fs::write(dir.path().join("track.opus"), b"OggS\x00\x02").unwrap();
+
+        let check = |name: &str| mislabeled_extension(dir.path().join(name));
+        assert_eq!(check("movie.txt"), Some(".mkv".to_string()));
+        // Same container, matching name, or no popular extension to contradict
+        assert_eq!(check("movie.webm"), None);
+        assert_eq!(check("movie.mkv"), None);
+        assert_eq!(check("movie.bin"), None);
+        assert_eq!(check("track.opus"), None);
+        // A two-byte signature isn't enough to overrule the name
+        assert_eq!(check("image.txt"), None);
+    }
+
     #[test]
     fn test_mkv_detection() {
         // Create a temporary file with MKV magic bytes
@@ -321,4 +683,70 @@ mod tests {
         let detected = what_is_most_likely_extension(temp.path());
         assert_eq!(detected, Some(".rar".to_string()));
     }
+
+    fn mp4_box(box_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(box_type);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn test_mp4_container_check() {
+        let mut valid = NamedTempFile::new().unwrap();
+        valid.write_all(&mp4_box(b"ftyp", b"isom\0\0\0\0")).unwrap();
+        valid.write_all(&mp4_box(b"moov", &[0; 16])).unwrap();
+        valid.write_all(&mp4_box(b"mdat", &[0; 64])).unwrap();
+        valid.flush().unwrap();
+        assert_eq!(check_media_container(valid.path()), ContainerCheck::Valid);
+
+        // mdat claims more data than the file holds
+        let mut truncated = NamedTempFile::new().unwrap();
+        truncated
+            .write_all(&mp4_box(b"ftyp", b"isom\0\0\0\0"))
+            .unwrap();
+        truncated.write_all(&mp4_box(b"moov", &[0; 16])).unwrap();
+        truncated.write_all(&1000u32.to_be_bytes()).unwrap();
+        truncated.write_all(b"mdat").unwrap();
+        truncated.write_all(&[0; 64]).unwrap();
+        truncated.flush().unwrap();
+        assert!(matches!(
+            check_media_container(truncated.path()),
+            ContainerCheck::Invalid(_)
+        ));
+    }
+
+    #[test]
+    fn test_mp4_64bit_box_size_check() {
+        let head = [
+            mp4_box(b"ftyp", b"isom\0\0\0\0"),
+            mp4_box(b"moov", &[0; 16]),
+        ]
+        .concat();
+
+        // A largesize near u64::MAX must not overflow the end offset
+        let mut huge = NamedTempFile::new().unwrap();
+        huge.write_all(&head).unwrap();
+        huge.write_all(&1u32.to_be_bytes()).unwrap();
+        huge.write_all(b"mdat").unwrap();
+        huge.write_all(&u64::MAX.to_be_bytes()).unwrap();
+        huge.write_all(&[0; 64]).unwrap();
+        huge.flush().unwrap();
+        assert!(matches!(
+            check_media_container(huge.path()),
+            ContainerCheck::Invalid(_)
+        ));
+
+        // File ends inside the largesize field
+        let mut cut = NamedTempFile::new().unwrap();
+        cut.write_all(&head).unwrap();
+        cut.write_all(&1u32.to_be_bytes()).unwrap();
+        cut.write_all(b"mdat").unwrap();
+        cut.write_all(&[0; 4]).unwrap();
+        cut.flush().unwrap();
+        assert!(matches!(
+            check_media_container(cut.path()),
+            ContainerCheck::Invalid(_)
+        ));
+    }
 }
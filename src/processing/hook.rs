@@ -0,0 +1,189 @@
+//! Post-download command
+//!
+//! Runs a user-configured program once an NZB has been downloaded and post-processed, the
+//! way SABnzbd/NZBGet post-processing scripts work. The program is executed directly with
+//! its arguments, never through a shell, and details of the download arrive as `DL_NZB_*`
+//! environment variables.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// What the post-download command did
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookOutput {
+    pub command: Vec<String>,
+    /// `None` when the program couldn't be started or was killed by a signal
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    /// The program ran past `post_download_timeout` and was killed
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+impl HookOutput {
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Details of a finished download passed to the command
+pub struct HookContext<'a> {
+    pub nzb: &'a Path,
+    pub nzb_hash: &'a str,
+    pub output_dir: &'a Path,
+    pub category: &'a str,
+    pub success: bool,
+    pub total_size: u64,
+}
+
+impl HookContext<'_> {
+    fn env(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("DL_NZB_NZB", self.nzb.display().to_string()),
+            ("DL_NZB_NZB_HASH", self.nzb_hash.to_string()),
+            ("DL_NZB_OUTPUT_DIR", self.output_dir.display().to_string()),
+            ("DL_NZB_CATEGORY", self.category.to_string()),
+            (
+                "DL_NZB_STATUS",
+                if self.success { "success" } else { "failure" }.to_string(),
+            ),
+            ("DL_NZB_TOTAL_SIZE", self.total_size.to_string()),
+        ]
+    }
+}
+
+/// Run the command (program followed by its arguments) and capture what it printed
+///
+/// The command runs in the output directory. A program that fails to start is reported
+/// in `stderr` with no exit code rather than as an error, so the download still counts.
+/// One still running after `timeout` (if any) is killed and reported the same way.
+pub async fn run_post_download_command(
+    command: &[String],
+    context: &HookContext<'_>,
+    timeout: Option<Duration>,
+) -> HookOutput {
+    let mut output = HookOutput {
+        command: command.to_vec(),
+        exit_code: None,
+        stdout: String::new(),
+        stderr: String::new(),
+        timed_out: false,
+    };
+    let Some((program, args)) = command.split_first() else {
+        return output;
+    };
+
+    // Dropping the output future on timeout kills the program
+    let run = Command::new(program)
+        .args(args)
+        .envs(context.env())
+        .current_dir(context.output_dir)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let result = match timeout {
+        Some(limit) => match tokio::time::timeout(limit, run).await {
+            Ok(result) => result,
+            Err(_) => {
+                output.timed_out = true;
+                output.stderr = format!(
+                    "'{}' was killed after running for {} seconds",
+                    program,
+                    limit.as_secs()
+                );
+                return output;
+            }
+        },
+        None => run.await,
+    };
+
+    match result {
+        Ok(result) => {
+            output.exit_code = result.status.code();
+            output.stdout = String::from_utf8_lossy(&result.stdout).into_owned();
+            output.stderr = String::from_utf8_lossy(&result.stderr).into_owned();
+        }
+        Err(e) => {
+            output.stderr = format!("Failed to run '{}': {}", program, e);
+        }
+    }
+
+    output
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_command_sees_download_env_without_shell() {
+        let dir = tempfile::tempdir().unwrap();
+        let context = HookContext {
+            nzb: Path::new("release.nzb"),
+            nzb_hash: "abc",
+            output_dir: dir.path(),
+            category: "tv",
+            success: true,
+            total_size: 42,
+        };
+
+        let command = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "echo \"$DL_NZB_CATEGORY $DL_NZB_STATUS $1\"; exit 3".to_string(),
+            "hook".to_string(),
+            "$(touch injected)".to_string(),
+        ];
+        let output = run_post_download_command(&command, &context, None).await;
+
+        assert_eq!(output.exit_code, Some(3));
+        assert_eq!(output.stdout, "tv success $(touch injected)\n");
+        assert!(!dir.path().join("injected").exists());
+    }
+
+    #[tokio::test]
+    async fn test_missing_program_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let context = HookContext {
+            nzb: Path::new("release.nzb"),
+            nzb_hash: "abc",
+            output_dir: dir.path(),
+            category: "other",
+            success: false,
+            total_size: 0,
+        };
+
+        let output =
+            run_post_download_command(&["/nonexistent/dl-nzb-hook".to_string()], &context, None)
+                .await;
+        assert!(!output.succeeded());
+        assert!(output.exit_code.is_none());
+        assert!(output.stderr.contains("/nonexistent/dl-nzb-hook"));
+    }
+
+    #[tokio::test]
+    async fn test_command_is_killed_after_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let context = HookContext {
+            nzb: Path::new("release.nzb"),
+            nzb_hash: "abc",
+            output_dir: dir.path(),
+            category: "other",
+            success: true,
+            total_size: 0,
+        };
+
+        let command = ["sleep".to_string(), "30".to_string()];
+        let started = std::time::Instant::now();
+        let output =
+            run_post_download_command(&command, &context, Some(Duration::from_millis(100))).await;
+
+        assert!(output.timed_out);
+        assert!(!output.succeeded());
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
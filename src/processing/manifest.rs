@@ -0,0 +1,134 @@
+//! Manifest of a finished download
+//!
+//! Lists every file left in the download directory after post-processing, so downstream
+//! tools get a complete inventory without scanning it themselves.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use super::file_extension;
+use crate::error::DlNzbError;
+
+type Result<T> = std::result::Result<T, DlNzbError>;
+
+/// Manifest file name, written at the top of the download directory
+pub const MANIFEST_FILE_NAME: &str = "dl-nzb-manifest.json";
+
+/// Where a file in the download directory came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileSource {
+    Download,
+    Extracted,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the download directory
+    pub path: PathBuf,
+    pub size: u64,
+    /// Extension from the name, or detected from the file's magic bytes
+    pub file_type: Option<String>,
+    pub source: FileSource,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub files: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Scan the download directory, marking files in `extracted` as coming from extraction
+    pub fn scan(download_dir: &Path, extracted: &HashSet<PathBuf>) -> Self {
+        let mut paths = list_files(download_dir);
+        paths.sort();
+
+        let files = paths
+            .into_iter()
+            .filter(|path| path.file_name().and_then(|n| n.to_str()) != Some(MANIFEST_FILE_NAME))
+            .map(|path| ManifestEntry {
+                size: path.metadata().map(|m| m.len()).unwrap_or(0),
+                file_type: file_extension::detected_extension(&path),
+                source: if extracted.contains(&path) {
+                    FileSource::Extracted
+                } else {
+                    FileSource::Download
+                },
+                path: path
+                    .strip_prefix(download_dir)
+                    .map(Path::to_path_buf)
+                    .unwrap_or(path),
+            })
+            .collect();
+
+        Self { files }
+    }
+
+    /// Write the manifest as pretty JSON into the download directory
+    pub fn write(&self, download_dir: &Path) -> Result<PathBuf> {
+        let path = download_dir.join(MANIFEST_FILE_NAME);
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(&path, json)?;
+        Ok(path)
+    }
+}
+
+/// List every file under a directory, recursively
+pub fn list_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files);
+    files
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_marks_extracted_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("Subs")).unwrap();
+        std::fs::write(dir.path().join("movie.mkv"), vec![0u8; 64]).unwrap();
+        std::fs::write(dir.path().join("Subs").join("en.srt"), b"1").unwrap();
+        std::fs::write(dir.path().join("movie.nfo"), b"info").unwrap();
+        std::fs::write(dir.path().join(MANIFEST_FILE_NAME), b"{}").unwrap();
+
+        let extracted: HashSet<PathBuf> = [
+            dir.path().join("movie.mkv"),
+            dir.path().join("Subs").join("en.srt"),
+        ]
+        .into_iter()
+        .collect();
+        let manifest = Manifest::scan(dir.path(), &extracted);
+
+        assert_eq!(manifest.files.len(), 3);
+        let entry = |path: PathBuf| manifest.files.iter().find(|e| e.path == path).unwrap();
+        let movie = entry(PathBuf::from("movie.mkv"));
+        assert_eq!(movie.size, 64);
+        assert_eq!(movie.file_type.as_deref(), Some("mkv"));
+        assert_eq!(movie.source, FileSource::Extracted);
+        assert_eq!(
+            entry(Path::new("Subs").join("en.srt")).source,
+            FileSource::Extracted
+        );
+        assert_eq!(
+            entry(PathBuf::from("movie.nfo")).source,
+            FileSource::Download
+        );
+    }
+}
@@ -1,11 +1,24 @@
 //! Post-processing functionality
 //!
-//! This module handles PAR2 verification/repair, RAR extraction, and file deobfuscation.
+//! This module handles PAR2 verification/repair, RAR extraction, file deobfuscation,
+//! duplicate merging and content categorization.
 
+mod category;
+mod dedupe;
 mod deobfuscate;
 mod file_extension;
+mod hook;
+mod manifest;
 mod par2;
+mod par2_index;
 mod post_processor;
 mod rar;
+mod release_sets;
 
-pub use post_processor::PostProcessor;
+pub use category::{classify_download, Category};
+pub use dedupe::Duplicate;
+pub use deobfuscate::{clamp_filename, is_probably_obfuscated, sanitize_name};
+pub use hook::{run_post_download_command, HookContext, HookOutput};
+pub use manifest::{FileSource, Manifest, ManifestEntry, MANIFEST_FILE_NAME};
+pub use post_processor::{PostProcessor, ProcessingOutcome};
+pub use release_sets::{detect_release_sets, ReleaseSet};
@@ -8,6 +8,8 @@ use tokio::process::Command;
 
 use crate::config::PostProcessingConfig;
 use crate::error::DlNzbError;
+use crate::json_output::ProgressEvent;
+use crate::patterns::par2 as par2_patterns;
 use crate::progress;
 
 type Result<T> = std::result::Result<T, DlNzbError>;
@@ -23,6 +25,28 @@ pub enum Par2Status {
     Failed,
 }
 
+/// What a PAR2 run concluded, and about which files
+#[derive(Debug, Clone, PartialEq)]
+pub struct Par2Outcome {
+    pub status: Par2Status,
+    /// Names of the files the set protects, from par2's `Target:` lines; on success every
+    /// one of them is intact, whether it verified or was repaired
+    pub targets: Vec<String>,
+    /// Names of the targets par2 reported damaged or missing
+    pub corrupt: Vec<String>,
+}
+
+impl Par2Status {
+    /// Stable name used in machine-readable output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Par2Status::NoPar2Files => "no_par2_files",
+            Par2Status::Success => "success",
+            Par2Status::Failed => "failed",
+        }
+    }
+}
+
 /// Find the par2 binary, checking bundled location first, then PATH
 fn find_par2_binary() -> Result<PathBuf> {
     // Check for bundled binary relative to executable
@@ -66,15 +90,23 @@ fn find_par2_binary() -> Result<PathBuf> {
 }
 
 /// Run PAR2 verification and repair on downloaded files
+///
+/// With `par2_verify_only` set and `auto_par2_repair` off, files are only verified and any
+/// damaged or missing files are reported instead of repaired.
 pub async fn repair_with_par2(
     config: &PostProcessingConfig,
     _download_dir: &Path,
     downloaded_par2_files: &[PathBuf],
     progress_bar: &ProgressBar,
-) -> Result<Par2Status> {
+    emit_events: bool,
+) -> Result<Par2Outcome> {
     if downloaded_par2_files.is_empty() {
         progress_bar.finish_and_clear();
-        return Ok(Par2Status::NoPar2Files);
+        return Ok(Par2Outcome {
+            status: Par2Status::NoPar2Files,
+            targets: Vec::new(),
+            corrupt: Vec::new(),
+        });
     }
 
     // Find the main PAR2 file (index file without .vol)
@@ -83,17 +115,30 @@ pub async fn repair_with_par2(
         DlNzbError::PostProcessing(crate::error::PostProcessingError::NoRarArchives)
     })?;
 
+    let verify_only = config.verifies_par2_only();
+
+    // par2 only finds files sharing the main file's name; others of the same recovery set
+    // (obfuscated ones) are passed explicitly so their recovery blocks are loaded
+    let main_set_name = par2_patterns::set_name(main_par2);
+    let main_set_id = par2_patterns::recovery_set_id(main_par2);
+    let extra_par2_files: Vec<&PathBuf> = downloaded_par2_files[1..]
+        .iter()
+        .filter(|path| par2_patterns::set_name(path) != main_set_name)
+        .filter(|path| main_set_id.is_some() && par2_patterns::recovery_set_id(path) == main_set_id)
+        .collect();
+
     // Find par2 binary
     let par2_bin = find_par2_binary()?;
 
     progress_bar.set_message("Verifying PAR2...");
     progress::apply_style(progress_bar, progress::ProgressStyle::Par2Verify);
 
-    // Run par2 repair command
+    // Run par2 repair (or verify) command
     // par2cmdline-turbo uses: par2 repair <par2file>
     let mut child = Command::new(&par2_bin)
-        .arg("repair")
+        .arg(if verify_only { "verify" } else { "repair" })
         .arg(main_par2)
+        .args(&extra_par2_files)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
@@ -115,15 +160,24 @@ pub async fn repair_with_par2(
     let mut repair_needed = false;
     let mut repair_possible = true;
     let mut files_verified = 0u64;
+    let mut files_corrupt = 0u64;
     let mut total_files = 0u64;
+    let mut targets = Vec::new();
+    let mut corrupt = Vec::new();
 
     while let Ok(Some(line)) = reader.next_line().await {
+        let target = target_name(&line);
+        if let Some(name) = target {
+            targets.push(name.to_string());
+        }
+
         // Parse progress from par2cmdline-turbo output
         // Common patterns:
         // "Loading \"file.par2\"."
         // "Verifying source files:"
         // "Target: \"filename\" - found."
         // "Target: \"filename\" - damaged."
+        // "Target: \"filename\" - missing."
         // "Repair is required."
         // "Repair is possible."
         // "Repair complete."
@@ -137,8 +191,18 @@ pub async fn repair_with_par2(
             if total_files > 0 {
                 progress_bar.set_position(files_verified);
             }
-        } else if line.contains("Target:") && line.contains("damaged") {
+            if emit_events {
+                ProgressEvent::Par2Progress {
+                    files_verified,
+                    total_files,
+                }
+                .emit();
+            }
+        } else if line.contains("Target:") && (line.contains("damaged") || line.contains("missing"))
+        {
             repair_needed = true;
+            files_corrupt += 1;
+            corrupt.extend(target.map(str::to_string));
             progress_bar.set_message("Damaged files found...");
             progress::apply_style(progress_bar, progress::ProgressStyle::Par2Warning);
         } else if line.contains("Repair is required") {
@@ -179,39 +243,73 @@ pub async fn repair_with_par2(
     // 2 = repair needed but not possible
     // Other = error
 
-    let result = if status.success() || status.code() == Some(0) {
+    let result = if verify_only {
+        if status.success() && files_corrupt == 0 {
+            progress::stage_summary(
+                true,
+                "\x1b[33m",
+                &format!(
+                    "PAR2 verified {} file{}",
+                    files_verified,
+                    if files_verified == 1 { "" } else { "s" }
+                ),
+            );
+            Par2Status::Success
+        } else {
+            progress::stage_summary(
+                false,
+                "\x1b[31m",
+                &format!(
+                    "PAR2 verification: {} verified, {} corrupt",
+                    files_verified, files_corrupt
+                ),
+            );
+            Par2Status::Failed
+        }
+    } else if status.success() || status.code() == Some(0) {
+        // PAR2 files are deleted by the post-processor once extraction has also succeeded
         if repair_needed {
-            // Delete PAR2 files if configured
-            if config.delete_par2_after_repair {
-                for par2_path in downloaded_par2_files {
-                    if par2_path.exists() {
-                        let _ = std::fs::remove_file(par2_path);
-                    }
-                }
-            }
-            println!("  └─ \x1b[33m✓ PAR2 repaired successfully\x1b[0m");
+            progress::stage_summary(true, "\x1b[33m", "PAR2 repaired successfully");
         } else {
-            // Delete PAR2 files if configured
-            if config.delete_par2_after_repair {
-                for par2_path in downloaded_par2_files {
-                    if par2_path.exists() {
-                        let _ = std::fs::remove_file(par2_path);
-                    }
-                }
-            }
-            println!("  └─ \x1b[33m✓ PAR2 verified\x1b[0m");
+            progress::stage_summary(true, "\x1b[33m", "PAR2 verified");
         }
         Par2Status::Success
     } else if !repair_possible {
-        println!("  └─ \x1b[31m✗ PAR2 repair not possible - insufficient recovery data\x1b[0m");
+        progress::stage_summary(
+            false,
+            "\x1b[31m",
+            "PAR2 repair not possible - insufficient recovery data",
+        );
         Par2Status::Failed
     } else {
         let code = status.code().unwrap_or(-1);
-        println!("  └─ \x1b[31m✗ PAR2 failed (exit code: {})\x1b[0m", code);
+        progress::stage_summary(
+            false,
+            "\x1b[31m",
+            &format!("PAR2 failed (exit code: {})", code),
+        );
         Par2Status::Failed
     };
 
-    Ok(result)
+    if emit_events {
+        ProgressEvent::Par2Result {
+            status: result.as_str().to_string(),
+            repair_needed,
+        }
+        .emit();
+    }
+
+    Ok(Par2Outcome {
+        status: result,
+        targets,
+        corrupt,
+    })
+}
+
+/// The file name in a par2 line like `Target: "movie.mkv" - found.`
+fn target_name(line: &str) -> Option<&str> {
+    let quoted = line.trim_start().strip_prefix("Target: \"")?;
+    Some(&quoted[..quoted.rfind("\" - ")?])
 }
 
 /// Parse file count from par2 output like "Scanning 15 source files"
@@ -226,3 +324,18 @@ fn parse_file_count(line: &str) -> Option<u64> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_name() {
+        assert_eq!(
+            target_name("Target: \"Movie \"Cut\".mkv\" - damaged. Found 12 of 15 data blocks."),
+            Some("Movie \"Cut\".mkv")
+        );
+        assert_eq!(target_name("Target: \"a.rar\" - found."), Some("a.rar"));
+        assert_eq!(target_name("Repair is required."), None);
+    }
+}
@@ -0,0 +1,195 @@
+//! PAR2 file description packets
+//!
+//! Every PAR2 file lists the files of its recovery set with their size and the MD5 of
+//! their first 16 KiB ("hash16k"), which identifies a file even after it has been renamed.
+
+use md5::{Digest, Md5};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+const PACKET_MAGIC: &[u8; 8] = b"PAR2\0PKT";
+const FILE_DESC_TYPE: &[u8; 16] = b"PAR 2.0\0FileDesc";
+const PACKET_HEADER_LEN: usize = 64;
+
+/// Bytes covered by the PAR2 partial hash
+const HASH16K_LEN: u64 = 16 * 1024;
+
+/// Index files are small; anything bigger is a recovery volume not worth reading whole
+const MAX_INDEX_SIZE: u64 = 16 * 1024 * 1024;
+
+/// A file described by a PAR2 recovery set
+#[derive(Debug, Clone, PartialEq)]
+pub struct Par2FileEntry {
+    pub name: String,
+    pub size: u64,
+    pub hash16k: [u8; 16],
+}
+
+/// Read the file descriptions from a PAR2 index file
+pub fn file_entries(path: &Path) -> std::io::Result<Vec<Par2FileEntry>> {
+    let mut data = Vec::new();
+    File::open(path)?
+        .take(MAX_INDEX_SIZE)
+        .read_to_end(&mut data)?;
+    Ok(parse_file_entries(&data))
+}
+
+/// MD5 of the first 16 KiB of a file, as stored in PAR2 file descriptions
+pub fn hash16k(path: &Path) -> std::io::Result<[u8; 16]> {
+    let mut head = Vec::with_capacity(HASH16K_LEN as usize);
+    File::open(path)?.take(HASH16K_LEN).read_to_end(&mut head)?;
+    Ok(md5(&head))
+}
+
+/// Collect the file description packets, skipping duplicates and damaged packets
+fn parse_file_entries(data: &[u8]) -> Vec<Par2FileEntry> {
+    let mut entries: Vec<([u8; 16], Par2FileEntry)> = Vec::new();
+    let mut pos = 0;
+
+    while let Some(found) = find_magic(&data[pos..]) {
+        let start = pos + found;
+        let header = &data[start..];
+        let Some(len) = header
+            .get(8..16)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()) as usize)
+        else {
+            break;
+        };
+
+        // Damaged length: resync on the next magic
+        let Some(packet) = header.get(..len).filter(|_| len >= PACKET_HEADER_LEN) else {
+            pos = start + PACKET_MAGIC.len();
+            continue;
+        };
+        pos = start + len;
+
+        if &packet[48..64] != FILE_DESC_TYPE {
+            continue;
+        }
+        // File ID (16), full MD5 (16), hash16k (16), length (8), NUL-padded name
+        let body = &packet[PACKET_HEADER_LEN..];
+        if body.len() < 56 {
+            continue;
+        }
+        let file_id: [u8; 16] = body[..16].try_into().unwrap();
+        if entries.iter().any(|(id, _)| *id == file_id) {
+            continue;
+        }
+        let name = &body[56..];
+        let name_len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        entries.push((
+            file_id,
+            Par2FileEntry {
+                name: String::from_utf8_lossy(&name[..name_len]).into_owned(),
+                size: u64::from_le_bytes(body[48..56].try_into().unwrap()),
+                hash16k: body[32..48].try_into().unwrap(),
+            },
+        ));
+    }
+
+    entries.into_iter().map(|(_, entry)| entry).collect()
+}
+
+fn find_magic(data: &[u8]) -> Option<usize> {
+    data.windows(PACKET_MAGIC.len())
+        .position(|w| w == PACKET_MAGIC)
+}
+
+/// MD5 digest, as PAR2 uses for its file hashes
+fn md5(data: &[u8]) -> [u8; 16] {
+    Md5::digest(data).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(digest: [u8; 16]) -> String {
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Build a FileDesc packet; the packet hash and set ID are not checked when reading
+    fn file_desc_packet(id: u8, name: &str, size: u64, hash16k: [u8; 16]) -> Vec<u8> {
+        let mut body = vec![id; 16];
+        body.extend_from_slice(&[0; 16]);
+        body.extend_from_slice(&hash16k);
+        body.extend_from_slice(&size.to_le_bytes());
+        body.extend_from_slice(name.as_bytes());
+        while body.len() % 4 != 0 {
+            body.push(0);
+        }
+
+        let mut packet = PACKET_MAGIC.to_vec();
+        packet.extend_from_slice(&((PACKET_HEADER_LEN + body.len()) as u64).to_le_bytes());
+        packet.extend_from_slice(&[0; 32]);
+        packet.extend_from_slice(FILE_DESC_TYPE);
+        packet.extend_from_slice(&body);
+        packet
+    }
+
+    #[test]
+    fn test_md5() {
+        assert_eq!(hex(md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(hex(md5(b"abc")), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            hex(md5(
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890"
+            )),
+            "57edf4a22be3c955ac49da2e2107b67a"
+        );
+    }
+
+    #[test]
+    fn test_parse_file_entries() {
+        let mut data = file_desc_packet(1, "Great.Movie.2023.mkv", 123_456, [7; 16]);
+        // Other packet types and repeated descriptions are skipped
+        let mut main = PACKET_MAGIC.to_vec();
+        main.extend_from_slice(&(PACKET_HEADER_LEN as u64 + 8).to_le_bytes());
+        main.extend_from_slice(&[0; 32]);
+        main.extend_from_slice(b"PAR 2.0\0Main\0\0\0\0");
+        main.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&main);
+        data.extend_from_slice(&file_desc_packet(
+            1,
+            "Great.Movie.2023.mkv",
+            123_456,
+            [7; 16],
+        ));
+        // Garbage between packets and a truncated trailing packet
+        data.extend_from_slice(b"junk");
+        data.extend_from_slice(&file_desc_packet(2, "Great.Movie.2023.nfo", 42, [9; 16]));
+        data.extend_from_slice(&file_desc_packet(3, "cut", 1, [0; 16])[..40]);
+
+        assert_eq!(
+            parse_file_entries(&data),
+            vec![
+                Par2FileEntry {
+                    name: "Great.Movie.2023.mkv".to_string(),
+                    size: 123_456,
+                    hash16k: [7; 16],
+                },
+                Par2FileEntry {
+                    name: "Great.Movie.2023.nfo".to_string(),
+                    size: 42,
+                    hash16k: [9; 16],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hash16k_reads_only_the_first_16k() {
+        let dir = tempfile::tempdir().unwrap();
+        let small = dir.path().join("small.bin");
+        let large = dir.path().join("large.bin");
+        std::fs::write(&small, b"abc").unwrap();
+        let mut content = vec![0x5a; HASH16K_LEN as usize];
+        let expected = md5(&content);
+        content.extend_from_slice(&[1; 1000]);
+        std::fs::write(&large, &content).unwrap();
+
+        assert_eq!(hash16k(&small).unwrap(), md5(b"abc"));
+        assert_eq!(hash16k(&large).unwrap(), expected);
+    }
+}
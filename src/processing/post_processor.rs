@@ -3,97 +3,585 @@
 //! Coordinates PAR2 verification/repair, RAR extraction, and deobfuscation.
 
 use indicatif::ProgressBar;
+use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::Semaphore;
 
+use super::dedupe::{self, Duplicate};
+use super::deobfuscate::clamp_filename;
+use super::file_extension::{self, ContainerCheck};
+use super::manifest::{self, Manifest};
 use super::par2::{self, Par2Status};
-use super::rar::{self, RarExtractor};
-use crate::config::PostProcessingConfig;
+use super::rar::{self, ExtractionOutcome, RarExtractor};
+use super::release_sets::{self, ReleaseSet};
+use crate::config::{DuplicateAction, PostProcessingConfig};
 use crate::download::DownloadResult;
 use crate::error::DlNzbError;
+use crate::file_handles::FileHandleLimit;
+use crate::outln;
 use crate::patterns::par2 as par2_patterns;
+use crate::patterns::rar as rar_patterns;
+use crate::progress;
 
 type Result<T> = std::result::Result<T, DlNzbError>;
 
+/// What post-processing a download found and did
+#[derive(Debug, Default)]
+pub struct ProcessingOutcome {
+    /// Files merged with an identical copy under `duplicate_files`
+    pub duplicates: Vec<Duplicate>,
+    /// Every PAR2 set verified or was repaired, so the content is known to be intact
+    pub par2_verified: bool,
+    /// Names of the files a successful PAR2 set verified or repaired
+    pub par2_intact: Vec<String>,
+    /// Archives the archive filter kept from being extracted
+    pub skipped_archives: Vec<String>,
+    /// Archive entries the entry filter kept from being extracted
+    pub skipped_entries: Vec<String>,
+    /// Files from failed extractions that were deleted or quarantined
+    pub partial_files_cleaned: Vec<PathBuf>,
+    /// Files renamed on disk, as (old, new) paths in the order they were renamed
+    pub renamed: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Point download results at the new paths of files that were renamed
+fn apply_renames(results: &mut [DownloadResult], renamed: &[(PathBuf, PathBuf)]) {
+    for (old, new) in renamed {
+        for result in results.iter_mut().filter(|r| &r.path == old) {
+            result.path = new.clone();
+        }
+    }
+}
+
+/// Group PAR2 files by set, with each set's index file first so it is used as the entry point
+fn group_par2_sets(par2_files: &[PathBuf]) -> Vec<Vec<PathBuf>> {
+    let mut sets: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for path in par2_files {
+        let name = par2_patterns::set_name(path).unwrap_or_default();
+        sets.entry(name).or_default().push(path.clone());
+    }
+
+    sets.into_values()
+        .map(|mut set| {
+            set.sort_by_key(|path| !par2_patterns::is_main_par2(path));
+            set
+        })
+        .collect()
+}
+
+/// Move a release set's files into `set_dir`, pointing their download results at the new
+/// paths and returning the set's results
+fn move_release_set(
+    results: &mut [DownloadResult],
+    set: &ReleaseSet,
+    set_dir: &Path,
+) -> Result<Vec<DownloadResult>> {
+    std::fs::create_dir_all(set_dir)?;
+
+    let mut moved = Vec::new();
+    for result in results.iter_mut().filter(|r| set.files.contains(&r.path)) {
+        let Some(filename) = result.path.file_name() else {
+            continue;
+        };
+        let path = set_dir.join(filename);
+        std::fs::rename(&result.path, &path)?;
+        result.path = path;
+        moved.push(result.clone());
+    }
+    Ok(moved)
+}
+
+/// Find PAR2 files posted under obfuscated names and give them a `.par2` extension
+///
+/// par2cmdline only loads recovery data from files named `*.par2`. The renamed files are
+/// grouped by recovery set ID, smallest first since that is normally the index file, and
+/// returned along with each rename as (old, new) paths.
+fn restore_obfuscated_par2<'a>(
+    paths: impl Iterator<Item = &'a Path>,
+) -> (Vec<Vec<PathBuf>>, Vec<(PathBuf, PathBuf)>) {
+    let mut sets: BTreeMap<[u8; 16], Vec<(u64, PathBuf)>> = BTreeMap::new();
+    let mut renames = Vec::new();
+    for path in paths.filter(|path| !par2_patterns::is_par2_file(path)) {
+        let Some(set_id) = par2_patterns::recovery_set_id(path) else {
+            continue;
+        };
+
+        let mut renamed = path.as_os_str().to_owned();
+        renamed.push(".par2");
+        let renamed = PathBuf::from(renamed);
+        if renamed.exists() {
+            continue;
+        }
+        if let Err(e) = std::fs::rename(path, &renamed) {
+            tracing::debug!("Failed to rename {}: {}", path.display(), e);
+            continue;
+        }
+
+        let size = renamed.metadata().map(|m| m.len()).unwrap_or(0);
+        sets.entry(set_id)
+            .or_default()
+            .push((size, renamed.clone()));
+        renames.push((path.to_path_buf(), renamed));
+    }
+
+    let sets = sets
+        .into_values()
+        .map(|mut set| {
+            set.sort();
+            set.into_iter().map(|(_, path)| path).collect()
+        })
+        .collect();
+    (sets, renames)
+}
+
 pub struct PostProcessor {
     config: PostProcessingConfig,
+    /// `preserve_files` globs, compiled
+    preserved: Vec<regex::Regex>,
     large_file_threshold: u64,
+    emit_events: bool,
+    job_permits: Option<Arc<Semaphore>>,
+    file_limit: Option<Arc<FileHandleLimit>>,
 }
 
 impl PostProcessor {
     pub fn new(config: PostProcessingConfig, large_file_threshold: u64) -> Self {
         Self {
+            preserved: config
+                .preserve_files
+                .iter()
+                .map(|g| rar::glob_regex(g))
+                .collect(),
             config,
             large_file_threshold,
+            emit_events: false,
+            job_permits: None,
+            file_limit: None,
         }
     }
 
-    pub async fn process_downloads(&self, results: &[DownloadResult]) -> Result<()> {
+    /// Share a limit on concurrent PAR2/extraction jobs with other processors
+    ///
+    /// A permit is held for each CPU-heavy step, so at most N run at once across NZBs.
+    pub fn with_job_permits(mut self, permits: Arc<Semaphore>) -> Self {
+        self.job_permits = Some(permits);
+        self
+    }
+
+    /// Share a cap on open output files with the downloader; extraction takes a slot per archive
+    pub fn with_file_limit(mut self, limit: Arc<FileHandleLimit>) -> Self {
+        self.file_limit = Some(limit);
+        self
+    }
+
+    /// Wait for a PAR2/extraction slot when a job limit is shared
+    async fn acquire_job_permit(&self) -> Option<tokio::sync::OwnedSemaphorePermit> {
+        match &self.job_permits {
+            Some(permits) => permits.clone().acquire_owned().await.ok(),
+            None => None,
+        }
+    }
+
+    /// Stream PAR2 and extraction progress as NDJSON events on stdout
+    pub fn with_events(mut self, enabled: bool) -> Self {
+        self.emit_events = enabled;
+        self
+    }
+
+    /// Repair, extract and tidy up a finished download in `download_dir`, the NZB's output
+    /// folder, returning the PAR2 verdict and the duplicates it merged
+    ///
+    /// The folder is passed in rather than taken from a result's path, since output rules
+    /// can route files into subfolders of it. With `split_release_sets`, a download holding
+    /// several releases has each one moved into its own folder and processed there under
+    /// its own name; the moved files' results are updated to their new paths, as are those
+    /// of files renamed while processing.
+    pub async fn process_downloads(
+        &self,
+        results: &mut [DownloadResult],
+        download_dir: &Path,
+    ) -> Result<ProcessingOutcome> {
         if results.is_empty() {
-            return Ok(());
+            return Ok(ProcessingOutcome::default());
+        }
+
+        let useful_name = download_dir
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("download");
+
+        if self.config.split_release_sets {
+            let paths: Vec<PathBuf> = results.iter().map(|r| r.path.clone()).collect();
+            let sets = release_sets::detect_release_sets(&paths);
+            if sets.len() > 1 {
+                return self
+                    .process_release_sets(results, download_dir, &sets)
+                    .await;
+            }
+        }
+
+        let outcome = self
+            .process_directory(results, download_dir, useful_name)
+            .await?;
+        apply_renames(results, &outcome.renamed);
+        Ok(outcome)
+    }
+
+    /// Process each release set in a folder of its own, reporting how each one went
+    ///
+    /// Files that belong to no set stay where they are. A set that fails doesn't stop the
+    /// others; the first error is returned once all have been tried.
+    async fn process_release_sets(
+        &self,
+        results: &mut [DownloadResult],
+        download_dir: &Path,
+        sets: &[ReleaseSet],
+    ) -> Result<ProcessingOutcome> {
+        outln!("  Found {} release sets", sets.len());
+
+        let mut combined = ProcessingOutcome {
+            par2_verified: true,
+            ..ProcessingOutcome::default()
+        };
+        let mut first_error = None;
+        for (i, set) in sets.iter().enumerate() {
+            outln!("  Release set {}/{}: {}", i + 1, sets.len(), set.name);
+
+            let set_dir = download_dir.join(clamp_filename(&set.name));
+            let outcome = match move_release_set(results, set, &set_dir) {
+                Ok(set_results) => {
+                    self.process_directory(&set_results, &set_dir, &set.name)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+
+            match outcome {
+                Ok(mut outcome) => {
+                    progress::stage_summary(true, "\x1b[32m", &format!("{} done", set.name));
+                    apply_renames(results, &outcome.renamed);
+                    combined.renamed.append(&mut outcome.renamed);
+                    combined.duplicates.append(&mut outcome.duplicates);
+                    combined.par2_verified &= outcome.par2_verified;
+                    combined.par2_intact.append(&mut outcome.par2_intact);
+                    combined
+                        .skipped_archives
+                        .append(&mut outcome.skipped_archives);
+                    combined
+                        .skipped_entries
+                        .append(&mut outcome.skipped_entries);
+                    combined
+                        .partial_files_cleaned
+                        .append(&mut outcome.partial_files_cleaned);
+                }
+                Err(e) => {
+                    progress::stage_summary(false, "\x1b[31m", &format!("{}: {}", set.name, e));
+                    first_error.get_or_insert(e);
+                }
+            }
         }
 
-        let download_dir = results[0].path.parent().unwrap_or(Path::new("."));
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(combined),
+        }
+    }
 
+    /// Repair, extract and tidy up the files of one release in `download_dir`
+    async fn process_directory(
+        &self,
+        results: &[DownloadResult],
+        download_dir: &Path,
+        useful_name: &str,
+    ) -> Result<ProcessingOutcome> {
         // Collect PAR2 files from download results
-        let downloaded_par2_files: Vec<PathBuf> = results
+        let mut downloaded_par2_files: Vec<PathBuf> = results
             .iter()
             .filter(|r| par2_patterns::is_par2_file(&r.path))
             .map(|r| r.path.clone())
             .collect();
 
-        let useful_name = download_dir
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("download");
+        let par2_enabled = self.config.auto_par2_repair || self.config.par2_verify_only;
+
+        // PAR2 files can be obfuscated too; those are recognized by their packet header
+        let (obfuscated_sets, mut renamed) = if par2_enabled {
+            restore_obfuscated_par2(results.iter().map(|r| r.path.as_path()))
+        } else {
+            Default::default()
+        };
+        if !renamed.is_empty() {
+            outln!("  Found {} PAR2 file(s) by content", renamed.len());
+        }
 
         // Run PAR2 repair if configured
-        let par2_status = if self.config.auto_par2_repair {
-            let bar = ProgressBar::new(100);
-            bar.enable_steady_tick(Duration::from_millis(100));
+        let par2 = async {
+            if !par2_enabled {
+                return Ok((Par2Status::NoPar2Files, Vec::new(), Vec::new()));
+            }
 
-            par2::repair_with_par2(&self.config, download_dir, &downloaded_par2_files, &bar).await?
-        } else {
-            Par2Status::NoPar2Files
+            let _permit = self.acquire_job_permit().await;
+            self.run_par2(download_dir, &downloaded_par2_files, &obfuscated_sets)
+                .await
         };
 
-        // Check archive integrity
-        let archive_files_with_failures = self.check_archive_integrity(results, download_dir)?;
+        // The archive integrity check only looks at download results, so it runs while PAR2
+        // hashes; it prints nothing, leaving the terminal to PAR2's progress bar. Files
+        // PAR2 renames can't change its verdict, which only counts without PAR2 files.
+        let (par2, archive_files_with_failures) =
+            tokio::join!(par2, self.check_archive_integrity(results, download_dir));
+        let (par2_status, par2_intact, par2_corrupt) = par2?;
+        downloaded_par2_files.extend(obfuscated_sets.into_iter().flatten());
+        let archive_files_with_failures = archive_files_with_failures?;
+
+        // Verifying without repair fails the set for any damaged file, but archives it
+        // found intact are as safe to extract as without PAR2
+        let archives_verified = par2_status == Par2Status::NoPar2Files
+            || (self.config.verifies_par2_only()
+                && !par2_corrupt
+                    .iter()
+                    .any(|name| rar_patterns::is_rar_related(name)));
 
         // Extract RAR archives only if safe
         let should_extract = self.config.auto_extract_rar
-            && ((archive_files_with_failures.is_empty() && par2_status == Par2Status::NoPar2Files)
+            && ((archive_files_with_failures.is_empty() && archives_verified)
                 || par2_status == Par2Status::Success);
 
-        if should_extract {
-            let bar = ProgressBar::new(100);
+        // Files present before extraction, to tell extracted files apart in the manifest
+        let before_extraction: Option<HashSet<PathBuf>> = self
+            .config
+            .write_manifest
+            .then(|| manifest::list_files(download_dir).into_iter().collect());
+
+        let extraction = if should_extract {
+            let _permit = self.acquire_job_permit().await;
+            let bar = progress::track(ProgressBar::new(100));
             bar.enable_steady_tick(Duration::from_millis(100));
 
-            let extractor = RarExtractor::new(self.config.clone(), self.large_file_threshold);
-            extractor.extract_archives(download_dir, &bar).await?;
-        }
+            let mut extractor = RarExtractor::new(self.config.clone(), self.large_file_threshold)
+                .with_events(self.emit_events);
+            if let Some(limit) = &self.file_limit {
+                extractor = extractor.with_file_limit(limit.clone());
+            }
+            Some(extractor.extract_archives(download_dir, &bar).await?)
+        } else {
+            None
+        };
+
+        let mut extracted_files: HashSet<PathBuf> = match &before_extraction {
+            Some(before) => manifest::list_files(download_dir)
+                .into_iter()
+                .filter(|path| !before.contains(path))
+                .collect(),
+            None => HashSet::new(),
+        };
+
+        // What this NZB put in the folder, the only files duplicate merging may touch
+        let mut own_files: HashSet<PathBuf> = results
+            .iter()
+            .map(|r| r.path.clone())
+            .chain(extraction.iter().flat_map(|e| e.outputs.iter().cloned()))
+            .collect();
 
         // Deobfuscate file names if configured
         if self.config.deobfuscate_file_names {
-            self.run_deobfuscation(download_dir, useful_name)?;
+            // Sizes from the download results let renamed files be traced back to NZB entries
+            let nzb_files: Vec<(String, u64)> = if self.config.deobfuscate_match_nzb {
+                results
+                    .iter()
+                    .map(|r| (r.filename.clone(), r.size))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            let keep: Vec<PathBuf> = results
+                .iter()
+                .filter(|r| r.named_by_user)
+                .map(|r| r.path.clone())
+                .collect();
+            let renames = self.run_deobfuscation(download_dir, useful_name, &nzb_files, &keep)?;
+            for (old, new) in renames {
+                if extracted_files.remove(&old) {
+                    extracted_files.insert(new.clone());
+                }
+                if own_files.remove(&old) {
+                    own_files.insert(new.clone());
+                }
+                renamed.push((old, new));
+            }
+        }
+
+        // Sanity-check the primary media file's container structure
+        let content_ok = if self.config.verify_media_container {
+            self.run_container_check(download_dir)?
+        } else {
+            true
+        };
+
+        self.delete_recovery_data(
+            download_dir,
+            &downloaded_par2_files,
+            par2_status,
+            extraction.as_ref(),
+            content_ok,
+        );
+
+        let own_files: Vec<PathBuf> = own_files.into_iter().collect();
+        let duplicates = self.merge_duplicates(download_dir, &own_files);
+
+        // Point out ReScene files, which cleanup never deletes, as they can rebuild the RARs
+        for result in results
+            .iter()
+            .filter(|r| file_extension::is_rescene_file(&r.path) && r.path.exists())
+        {
+            progress::stage_detail(&format!(
+                "Kept ReScene file {} for rebuilding the archives",
+                result.filename
+            ));
+        }
+
+        if self.config.write_manifest {
+            let manifest = Manifest::scan(download_dir, &extracted_files);
+            match manifest.write(download_dir) {
+                Ok(path) => tracing::debug!("Wrote manifest to {}", path.display()),
+                Err(e) => tracing::warn!("Failed to write manifest: {}", e),
+            }
+        }
+
+        let skipped_archives = extraction
+            .iter()
+            .flat_map(|outcome| &outcome.skipped)
+            .filter_map(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect();
+        let (skipped_entries, partial_files_cleaned) = extraction
+            .map(|outcome| (outcome.skipped_entries, outcome.cleaned))
+            .unwrap_or_default();
+
+        Ok(ProcessingOutcome {
+            duplicates,
+            par2_verified: par2_status == Par2Status::Success,
+            par2_intact,
+            skipped_archives,
+            skipped_entries,
+            partial_files_cleaned,
+            renamed,
+        })
+    }
+
+    /// Merge files with identical contents per `duplicate_files` and report the space saved
+    fn merge_duplicates(&self, download_dir: &Path, files: &[PathBuf]) -> Vec<Duplicate> {
+        if self.config.duplicate_files == DuplicateAction::Keep {
+            return Vec::new();
+        }
+
+        let duplicates =
+            match dedupe::deduplicate(files, self.config.duplicate_files, &self.preserved) {
+                Ok(duplicates) => duplicates,
+                Err(e) => {
+                    tracing::warn!("Failed to merge duplicate files: {}", e);
+                    return Vec::new();
+                }
+            };
+
+        if !duplicates.is_empty() {
+            let saved: u64 = duplicates.iter().map(|d| d.size).sum();
+            progress::stage_summary(
+                true,
+                "\x1b[36m",
+                &format!(
+                    "Merged {} duplicate file(s), saved {}",
+                    duplicates.len(),
+                    human_bytes::human_bytes(saved as f64)
+                ),
+            );
+            let relative = |path: &Path| {
+                path.strip_prefix(download_dir)
+                    .unwrap_or(path)
+                    .display()
+                    .to_string()
+            };
+            for duplicate in &duplicates {
+                progress::stage_detail(&format!(
+                    "{} = {}",
+                    relative(&duplicate.path),
+                    relative(&duplicate.original)
+                ));
+            }
         }
 
-        Ok(())
+        duplicates
     }
 
-    /// Check if any RAR files have failed segments
-    fn check_archive_integrity(
+    /// Run PAR2 once per set, or once over every PAR2 file with `par2_per_set` off
+    ///
+    /// NZBs can carry several independent sets (e.g. one per episode); each only protects
+    /// its own files, so running a single set would leave the others unchecked. Obfuscated
+    /// sets, found by content, always run on their own since their names don't group them.
+    /// Returns the combined verdict, the files of the sets that succeeded and the files
+    /// reported damaged or missing.
+    async fn run_par2(
+        &self,
+        download_dir: &Path,
+        par2_files: &[PathBuf],
+        obfuscated_sets: &[Vec<PathBuf>],
+    ) -> Result<(Par2Status, Vec<String>, Vec<String>)> {
+        let mut sets: Vec<Vec<PathBuf>> = if self.config.par2_per_set {
+            group_par2_sets(par2_files)
+        } else if par2_files.is_empty() {
+            Vec::new()
+        } else {
+            vec![par2_files.to_vec()]
+        };
+        sets.extend(obfuscated_sets.iter().cloned());
+
+        let mut status = Par2Status::NoPar2Files;
+        let mut intact = Vec::new();
+        let mut corrupt = Vec::new();
+        for set in &sets {
+            if sets.len() > 1 {
+                if let Some(name) = set.first().and_then(|p| par2_patterns::set_name(p)) {
+                    outln!("  PAR2 set: {}", name);
+                }
+            }
+
+            let bar = progress::track(ProgressBar::new(100));
+            bar.enable_steady_tick(Duration::from_millis(100));
+            let outcome =
+                par2::repair_with_par2(&self.config, download_dir, set, &bar, self.emit_events)
+                    .await?;
+            if outcome.status == Par2Status::Success {
+                intact.extend(outcome.targets);
+            }
+            corrupt.extend(outcome.corrupt);
+
+            status = match (status, outcome.status) {
+                (Par2Status::Failed, _) | (_, Par2Status::Failed) => Par2Status::Failed,
+                (Par2Status::NoPar2Files, other) => other,
+                (current, _) => current,
+            };
+        }
+
+        Ok((status, intact, corrupt))
+    }
+
+    /// Check if any RAR files have failed segments or a wrong assembled size
+    async fn check_archive_integrity(
         &self,
         results: &[DownloadResult],
         download_dir: &Path,
     ) -> Result<Vec<String>> {
         let mut failed_rar_files = Vec::new();
 
-        let rar_files: Vec<PathBuf> = std::fs::read_dir(download_dir)?
-            .filter_map(|entry| entry.ok())
-            .map(|entry| entry.path())
-            .filter(|path| rar::is_rar_archive(path))
-            .collect();
+        let mut rar_files = Vec::new();
+        let mut entries = tokio::fs::read_dir(download_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if rar::is_rar_archive(&path) {
+                rar_files.push(path);
+            }
+        }
 
         for rar_path in rar_files {
             let filename = rar_path
@@ -108,7 +596,7 @@ impl PostProcessor {
                     .map(|n| n == filename)
                     .unwrap_or(false)
             }) {
-                if result.segments_failed > 0 {
+                if !result.is_complete() {
                     failed_rar_files.push(filename.to_string());
                 }
             }
@@ -117,11 +605,97 @@ impl PostProcessor {
         Ok(failed_rar_files)
     }
 
-    /// Run deobfuscation on extracted files
-    fn run_deobfuscation(&self, download_dir: &Path, useful_name: &str) -> Result<()> {
+    /// Apply the delete flags, only once the end state is known to be good
+    ///
+    /// RAR parts go once every archive extracted and the content passed its check. PAR2
+    /// files additionally wait for extraction, so they're still around if it has to be
+    /// redone after a repair.
+    fn delete_recovery_data(
+        &self,
+        download_dir: &Path,
+        par2_files: &[PathBuf],
+        par2_status: Par2Status,
+        extraction: Option<&ExtractionOutcome>,
+        content_ok: bool,
+    ) {
+        let extraction_ok = match extraction {
+            Some(outcome) => outcome.failed == 0,
+            None => !self.config.auto_extract_rar,
+        };
+
+        if let Some(outcome) = extraction {
+            if self.config.delete_rar_after_extract && extraction_ok && content_ok {
+                for archive in &outcome.extracted {
+                    let dir = archive.parent().unwrap_or(download_dir);
+                    if let Err(e) = rar::delete_rar_parts(archive, dir, &self.preserved) {
+                        tracing::debug!("Failed to delete {}: {}", archive.display(), e);
+                    }
+                }
+            }
+        }
+
+        if self.config.delete_par2_after_repair
+            && par2_status == Par2Status::Success
+            && extraction_ok
+            && content_ok
+        {
+            for par2_path in par2_files {
+                if par2_path.exists() && !rar::is_preserved(par2_path, &self.preserved) {
+                    let _ = std::fs::remove_file(par2_path);
+                }
+            }
+        }
+    }
+
+    /// Check the container of the largest file in the download directory
+    ///
+    /// Returns false only when the container is known to be broken.
+    fn run_container_check(&self, download_dir: &Path) -> Result<bool> {
+        let largest = std::fs::read_dir(download_dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_file())
+            .max_by_key(|entry| entry.metadata().map(|m| m.len()).unwrap_or(0));
+
+        let Some(entry) = largest else {
+            return Ok(true);
+        };
+        let filename = entry.file_name().to_string_lossy().to_string();
+
+        match file_extension::check_media_container(entry.path()) {
+            ContainerCheck::Valid => {
+                progress::stage_summary(
+                    true,
+                    "\x1b[32m",
+                    &format!("Media container OK: {}", filename),
+                );
+                Ok(true)
+            }
+            ContainerCheck::Invalid(reason) => {
+                progress::stage_summary(
+                    false,
+                    "\x1b[31m",
+                    &format!("Media container check failed for {}: {}", filename, reason),
+                );
+                Ok(false)
+            }
+            ContainerCheck::Unknown => {
+                tracing::debug!("No container check available for {}", filename);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Run deobfuscation on extracted files, returning the renames it made
+    fn run_deobfuscation(
+        &self,
+        download_dir: &Path,
+        useful_name: &str,
+        nzb_files: &[(String, u64)],
+        keep: &[PathBuf],
+    ) -> Result<Vec<(PathBuf, PathBuf)>> {
         use indicatif::ProgressStyle as IndicatifStyle;
 
-        let spinner = ProgressBar::new_spinner();
+        let spinner = progress::track(ProgressBar::new_spinner());
         spinner.set_style(
             IndicatifStyle::with_template("{spinner:.cyan} {msg}")
                 .unwrap()
@@ -130,7 +704,18 @@ impl PostProcessor {
         spinner.enable_steady_tick(Duration::from_millis(80));
         spinner.set_message("Deobfuscating...");
 
-        match super::deobfuscate::deobfuscate_files(download_dir, useful_name) {
+        let mut renamed = Vec::new();
+        match super::deobfuscate::deobfuscate_files(
+            download_dir,
+            useful_name,
+            nzb_files,
+            super::deobfuscate::DeobfuscateOptions {
+                skip_excluded: self.config.deobfuscate_skip_excluded,
+                fix_mislabeled: self.config.fix_mislabeled_extensions,
+                fallback: self.config.poor_name_fallback,
+                keep,
+            },
+        ) {
             Ok(result) => {
                 if result.files_renamed > 0 || result.extensions_fixed > 0 {
                     let mut msg = Vec::new();
@@ -141,10 +726,15 @@ impl PostProcessor {
                         msg.push(format!("{} renamed", result.files_renamed));
                     }
                     spinner.finish_and_clear();
-                    println!("  \x1b[36m✓ Deobfuscated ({})\x1b[0m", msg.join(", "));
+                    progress::stage_summary(
+                        true,
+                        "\x1b[36m",
+                        &format!("Deobfuscated ({})", msg.join(", ")),
+                    );
                 } else {
                     spinner.finish_and_clear();
                 }
+                renamed = result.renamed;
             }
             Err(e) => {
                 tracing::debug!("Deobfuscation failed: {}", e);
@@ -152,6 +742,165 @@ impl PostProcessor {
             }
         }
 
-        Ok(())
+        Ok(renamed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn downloaded(path: PathBuf) -> DownloadResult {
+        DownloadResult {
+            filename: path.file_name().unwrap().to_string_lossy().into_owned(),
+            size: std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+            path,
+            segments_downloaded: 1,
+            segments_failed: 0,
+            download_time: Duration::ZERO,
+            average_speed: 0.0,
+            failed_message_ids: Vec::new(),
+            failure_reasons: std::collections::HashMap::new(),
+            size_mismatch: false,
+            par2_handoff: false,
+            par2_repaired: false,
+            served_by: BTreeMap::new(),
+            named_by_user: false,
+        }
+    }
+
+    fn par2_packet(set_id: u8, padding: usize) -> Vec<u8> {
+        let mut packet = par2_patterns::MAGIC.to_vec();
+        packet.extend_from_slice(&64u64.to_le_bytes());
+        packet.extend_from_slice(&[0u8; 16]);
+        packet.extend_from_slice(&[set_id; 16]);
+        packet.extend_from_slice(b"PAR 2.0\0Main\0\0\0\0");
+        packet.resize(packet.len() + padding, 0);
+        packet
+    }
+
+    #[test]
+    fn test_restore_obfuscated_par2_groups_by_set() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        std::fs::write(path("b7e1f0"), par2_packet(1, 4096)).unwrap();
+        std::fs::write(path("09ac3d"), par2_packet(1, 0)).unwrap();
+        std::fs::write(path("5d2e8a"), par2_packet(2, 0)).unwrap();
+        std::fs::write(path("c4f9e2"), vec![0u8; 128]).unwrap();
+        std::fs::write(path("named.par2"), par2_packet(3, 0)).unwrap();
+
+        let files = ["b7e1f0", "09ac3d", "5d2e8a", "c4f9e2", "named.par2"].map(path);
+        let (sets, renamed) = restore_obfuscated_par2(files.iter().map(|p| p.as_path()));
+
+        assert_eq!(
+            sets,
+            vec![
+                vec![path("09ac3d.par2"), path("b7e1f0.par2")],
+                vec![path("5d2e8a.par2")],
+            ]
+        );
+        assert!(path("c4f9e2").exists());
+        assert!(!path("b7e1f0").exists());
+
+        let mut results = files.map(downloaded);
+        apply_renames(&mut results, &renamed);
+        let paths = results.map(|r| r.path);
+        assert_eq!(
+            paths,
+            [
+                "b7e1f0.par2",
+                "09ac3d.par2",
+                "5d2e8a.par2",
+                "c4f9e2",
+                "named.par2"
+            ]
+            .map(path)
+        );
+        assert!(paths.iter().all(|path| path.exists()));
+    }
+
+    #[tokio::test]
+    async fn test_filtered_archives_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("extras.rar");
+        std::fs::write(&archive, b"Rar!").unwrap();
+
+        let config = PostProcessingConfig {
+            auto_par2_repair: false,
+            auto_extract_rar: true,
+            extract_exclude: vec!["extras".to_string()],
+            ..PostProcessingConfig::default()
+        };
+        let processor = PostProcessor::new(config, u64::MAX);
+        let outcome = processor
+            .process_downloads(&mut [downloaded(archive.clone())], dir.path())
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.skipped_archives, ["extras.rar"]);
+        assert!(outcome.skipped_entries.is_empty());
+        assert!(archive.exists());
+    }
+
+    #[tokio::test]
+    async fn test_processes_the_given_folder_not_the_first_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("Subs")).unwrap();
+        let subtitle = dir.path().join("Subs").join("movie.srt");
+        std::fs::write(&subtitle, b"1").unwrap();
+        let archive = dir.path().join("extras.rar");
+        std::fs::write(&archive, b"Rar!").unwrap();
+
+        let config = PostProcessingConfig {
+            auto_par2_repair: false,
+            auto_extract_rar: true,
+            extract_exclude: vec!["extras".to_string()],
+            ..PostProcessingConfig::default()
+        };
+        let processor = PostProcessor::new(config, u64::MAX);
+        let outcome = processor
+            .process_downloads(&mut [downloaded(subtitle), downloaded(archive)], dir.path())
+            .await
+            .unwrap();
+
+        // An output rule put the first file in a subfolder; the archive is still found
+        assert_eq!(outcome.skipped_archives, ["extras.rar"]);
+    }
+
+    #[tokio::test]
+    async fn test_release_set_moves_update_result_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut results: Vec<DownloadResult> = ["alpha.rar", "beta.rar", "notes.txt"]
+            .into_iter()
+            .map(|name| {
+                let path = dir.path().join(name);
+                std::fs::write(&path, name).unwrap();
+                downloaded(path)
+            })
+            .collect();
+
+        let config = PostProcessingConfig {
+            auto_par2_repair: false,
+            auto_extract_rar: false,
+            deobfuscate_file_names: false,
+            split_release_sets: true,
+            ..PostProcessingConfig::default()
+        };
+        let processor = PostProcessor::new(config, u64::MAX);
+        processor
+            .process_downloads(&mut results, dir.path())
+            .await
+            .unwrap();
+
+        let paths: Vec<PathBuf> = results.iter().map(|r| r.path.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                dir.path().join("alpha").join("alpha.rar"),
+                dir.path().join("beta").join("beta.rar"),
+                dir.path().join("notes.txt"),
+            ]
+        );
+        assert!(paths.iter().all(|path| path.exists()));
     }
 }
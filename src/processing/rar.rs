@@ -1,37 +1,196 @@
 //! RAR archive extraction functionality
 
 use indicatif::ProgressBar;
+use std::collections::HashSet;
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 use unrar::Archive;
 
-use crate::config::PostProcessingConfig;
+use super::deobfuscate::clamp_filename;
+use crate::config::{FilesystemCase, PartialExtraction, PostProcessingConfig};
 use crate::error::DlNzbError;
+use crate::file_handles::FileHandleLimit;
+use crate::json_output::ProgressEvent;
 use crate::patterns::rar as rar_patterns;
 use crate::progress;
 
 type Result<T> = std::result::Result<T, DlNzbError>;
 
+/// Folder in the output directory that `partial_extraction = "quarantine"` moves files into
+const QUARANTINE_DIR: &str = ".dl-nzb-partial";
+
+/// Outcome of extracting the archives in a directory
+#[derive(Debug, Default)]
+pub struct ExtractionOutcome {
+    /// Top-level archives that extracted, left in place for the caller to delete
+    pub extracted: Vec<PathBuf>,
+    /// Top-level archives that couldn't be extracted
+    pub failed: usize,
+    /// Archives left alone by `extract_include` / `extract_exclude`
+    pub skipped: Vec<PathBuf>,
+    /// Archive entries left out by `extract_entry_include` / `extract_entry_exclude`
+    pub skipped_entries: Vec<String>,
+    /// Files the extracted archives unpacked to, including ones an earlier run extracted
+    pub outputs: Vec<PathBuf>,
+    /// Files written by failed extractions that were then deleted or quarantined
+    pub cleaned: Vec<PathBuf>,
+}
+
+/// Which archives to extract, from `extract_include` / `extract_exclude`
+struct ArchiveFilter {
+    include: Vec<regex::Regex>,
+    exclude: Vec<regex::Regex>,
+}
+
+impl ArchiveFilter {
+    fn new(config: &PostProcessingConfig) -> Self {
+        // Patterns are validated when the config loads, so failures here can be dropped
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .filter_map(|p| {
+                    regex::RegexBuilder::new(p)
+                        .case_insensitive(true)
+                        .build()
+                        .ok()
+                })
+                .collect()
+        };
+        Self {
+            include: compile(&config.extract_include),
+            exclude: compile(&config.extract_exclude),
+        }
+    }
+
+    /// An archive is extracted if it matches an include pattern (or there are none)
+    /// and no exclude pattern
+    fn allows(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|re| re.is_match(name)))
+            && !self.exclude.iter().any(|re| re.is_match(name))
+    }
+}
+
+/// Which files inside an archive to extract, from `extract_entry_include` /
+/// `extract_entry_exclude`
+#[derive(Clone)]
+struct EntryFilter {
+    include: Vec<regex::Regex>,
+    exclude: Vec<regex::Regex>,
+}
+
+impl EntryFilter {
+    fn new(config: &PostProcessingConfig) -> Self {
+        let compile = |globs: &[String]| globs.iter().map(|g| glob_regex(g)).collect();
+        Self {
+            include: compile(&config.extract_entry_include),
+            exclude: compile(&config.extract_entry_exclude),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// An entry is extracted if its path matches an include glob (or there are none) and
+    /// no exclude glob
+    fn allows(&self, entry: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|re| re.is_match(entry)))
+            && !self.exclude.iter().any(|re| re.is_match(entry))
+    }
+}
+
+/// Compile a glob (`*` for any run of characters, `?` for one) into a case-insensitive regex
+/// over the whole entry path, so `*.mkv` also matches `Movie/movie.mkv`
+pub(super) fn glob_regex(glob: &str) -> regex::Regex {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    pattern.push('$');
+    regex::RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .expect("escaped glob is a valid regex")
+}
+
+/// Whether a file's name matches one of the `preserve_files` globs
+pub(super) fn is_preserved(path: &Path, preserved: &[regex::Regex]) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    preserved.iter().any(|re| re.is_match(&name))
+}
+
+/// An archive's files, split by whether the entry filter let them through
+#[derive(Debug, Default)]
+struct ArchiveEntries {
+    extracted: Vec<String>,
+    skipped: Vec<String>,
+    /// Files this attempt wrote, including one it may have left half-written
+    written: Vec<PathBuf>,
+    /// Where each extracted entry is on disk, including ones an earlier run extracted
+    outputs: Vec<PathBuf>,
+    /// Extraction stopped on an error before the end of the archive
+    broken: bool,
+}
+
 /// RAR extraction configuration
 pub struct RarExtractor {
     config: PostProcessingConfig,
+    entry_filter: EntryFilter,
+    preserved: Vec<regex::Regex>,
     large_file_threshold: u64,
+    emit_events: bool,
+    file_limit: Option<Arc<FileHandleLimit>>,
 }
 
 impl RarExtractor {
     pub fn new(config: PostProcessingConfig, large_file_threshold: u64) -> Self {
         Self {
+            entry_filter: EntryFilter::new(&config),
+            preserved: config
+                .preserve_files
+                .iter()
+                .map(|g| glob_regex(g))
+                .collect(),
             config,
             large_file_threshold,
+            emit_events: false,
+            file_limit: None,
         }
     }
 
+    /// Emit extraction progress as NDJSON events
+    pub fn with_events(mut self, enabled: bool) -> Self {
+        self.emit_events = enabled;
+        self
+    }
+
+    /// Wait for a slot from `limit` before extracting each archive
+    pub fn with_file_limit(mut self, limit: Arc<FileHandleLimit>) -> Self {
+        self.file_limit = Some(limit);
+        self
+    }
+
     /// Extract all RAR archives in the directory
+    ///
+    /// With `nested_extraction_depth` set, archives that come out of an archive are extracted
+    /// too, level by level up to that depth, and removed once extracted. Only files an
+    /// extraction wrote are looked at, so other downloads sharing the directory are left
+    /// alone. Top-level archives are never deleted here, since the extracted content hasn't
+    /// been checked yet.
     pub async fn extract_archives(
         &self,
         download_dir: &Path,
         progress_bar: &ProgressBar,
-    ) -> Result<()> {
+    ) -> Result<ExtractionOutcome> {
         progress_bar.set_message("Scanning for RAR archives...");
 
         let rar_files: Vec<PathBuf> = std::fs::read_dir(download_dir)?
@@ -42,52 +201,144 @@ impl RarExtractor {
 
         if rar_files.is_empty() {
             progress_bar.finish_and_clear();
-            return Ok(());
+            return Ok(ExtractionOutcome::default());
         }
 
-        let total_archives = rar_files.len() as u64;
-        progress_bar.set_length(total_archives);
         progress::apply_style(progress_bar, progress::ProgressStyle::Extract);
 
+        let filter = ArchiveFilter::new(&self.config);
+        let mut outcome = ExtractionOutcome::default();
+        let mut seen: HashSet<PathBuf> = rar_files.iter().cloned().collect();
+        let mut pending = rar_files;
+        // Everything extraction produced, so nested archives only ever come from here
+        let mut produced: HashSet<PathBuf> = HashSet::new();
+        let mut archives_total = 0;
         let mut extracted_count = 0;
+        let mut files_extracted = 0;
+        let mut files_skipped = 0;
+        let mut depth = 0;
 
-        for (index, rar_path) in rar_files.iter().enumerate() {
-            let filename = rar_path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown");
+        while !pending.is_empty() {
+            archives_total += pending.len();
+            let mut nested = Vec::new();
 
-            progress_bar.set_position(index as u64);
-            progress_bar.set_message(format!("Extracting {}", filename));
+            for rar_path in &pending {
+                let filename = rar_path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("unknown");
+                if !filter.allows(filename) {
+                    progress::bar_detail(
+                        progress_bar,
+                        &format!("Not extracting (filtered): {}", filename),
+                    );
+                    outcome.skipped.push(rar_path.clone());
+                    continue;
+                }
+                progress_bar.set_message(format!("Extracting {}", filename));
 
-            if self
-                .extract_archive(rar_path, download_dir, progress_bar)
-                .await?
-            {
-                extracted_count += 1;
-                if self.config.delete_rar_after_extract {
-                    delete_rar_parts(rar_path, download_dir)?;
+                // Nested archives are extracted next to where they were unpacked
+                let output_dir = rar_path.parent().unwrap_or(download_dir);
+                let entries = self
+                    .extract_archive(rar_path, output_dir, progress_bar)
+                    .await?;
+                if entries.broken {
+                    let cleaned =
+                        self.clean_partial(rar_path, &entries.written, output_dir, progress_bar);
+                    outcome.cleaned.extend(cleaned);
+                    if depth == 0 {
+                        outcome.failed += 1;
+                    }
+                    continue;
+                }
+                if !self.entry_filter.is_empty() {
+                    report_entries(progress_bar, filename, &entries);
                 }
+                // An archive whose entries were all filtered out still counts as handled
+                if !entries.extracted.is_empty() || !entries.skipped.is_empty() {
+                    extracted_count += 1;
+                    files_extracted += entries.extracted.len();
+                    files_skipped += entries.skipped.len();
+                    outcome.skipped_entries.extend(entries.skipped);
+                    // Intermediate archives are only a wrapper around the real content
+                    if depth > 0 {
+                        delete_produced_parts(rar_path, &produced, &self.preserved);
+                    } else {
+                        outcome.extracted.push(rar_path.clone());
+                    }
+                    nested.extend(
+                        entries
+                            .outputs
+                            .iter()
+                            .filter(|path| is_rar_archive(path))
+                            .cloned(),
+                    );
+                    produced.extend(entries.outputs);
+                } else if depth == 0 {
+                    outcome.failed += 1;
+                }
+            }
+
+            // The depth cap also stops archives that keep unpacking into more archives
+            depth += 1;
+            if depth > self.config.nested_extraction_depth {
+                break;
             }
+
+            pending = nested
+                .into_iter()
+                .filter(|path| seen.insert(path.clone()))
+                .collect();
+        }
+        outcome.outputs = produced.into_iter().collect();
+
+        if self.emit_events {
+            ProgressEvent::ExtractResult {
+                archives_extracted: extracted_count,
+                archives_total,
+                files_extracted: files_extracted as u64,
+                skipped_archives: outcome
+                    .skipped
+                    .iter()
+                    .filter_map(|path| path.file_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .collect(),
+                skipped_entries: outcome.skipped_entries.clone(),
+                partial_files_cleaned: outcome.cleaned.clone(),
+            }
+            .emit();
         }
 
-        progress_bar.set_position(total_archives);
         progress_bar.finish_with_message("  ");
-        println!(
-            "  └─ \x1b[32m✓ Extracted {} archive{}\x1b[0m",
-            extracted_count,
-            if extracted_count == 1 { "" } else { "s" }
+        progress::stage_summary(
+            true,
+            "\x1b[32m",
+            &format!(
+                "Extracted {} archive{} ({} file{}{})",
+                extracted_count,
+                if extracted_count == 1 { "" } else { "s" },
+                files_extracted,
+                if files_extracted == 1 { "" } else { "s" },
+                if files_skipped > 0 {
+                    format!(", {} skipped", files_skipped)
+                } else {
+                    String::new()
+                }
+            ),
         );
-        Ok(())
+        Ok(outcome)
     }
 
     /// Extract a single RAR archive with progress tracking
+    ///
+    /// Returns the entries extracted and skipped by the entry filter, both empty if the
+    /// archive couldn't be read.
     async fn extract_archive(
         &self,
         archive_path: &Path,
         output_dir: &Path,
         progress_bar: &ProgressBar,
-    ) -> Result<bool> {
+    ) -> Result<ArchiveEntries> {
         use tokio::sync::mpsc;
 
         // First pass: Get total unpacked size for byte-level progress
@@ -95,26 +346,37 @@ impl RarExtractor {
             Ok(mut listing) => {
                 let mut count = 0u64;
                 let mut bytes = 0u64;
+                let mut skipped = Vec::new();
 
                 while let Some(entry_result) = listing.next() {
                     match entry_result {
                         Ok(entry) => {
-                            if !entry.is_directory() {
+                            if entry.is_directory() {
+                                continue;
+                            }
+                            let name = entry_name(&entry.filename);
+                            if self.entry_filter.allows(&name) {
                                 count += 1;
                                 bytes += entry.unpacked_size;
+                            } else {
+                                skipped.push(name);
                             }
                         }
-                        Err(_) => return Ok(false),
+                        Err(_) => return Ok(ArchiveEntries::default()),
                     }
                 }
 
+                // Nothing to unpack: either an empty archive or every entry filtered out
                 if count == 0 {
-                    return Ok(false);
+                    return Ok(ArchiveEntries {
+                        skipped,
+                        ..ArchiveEntries::default()
+                    });
                 }
 
                 (count, bytes)
             }
-            Err(_) => return Ok(false),
+            Err(_) => return Ok(ArchiveEntries::default()),
         };
 
         progress_bar.set_length(total_bytes);
@@ -135,24 +397,60 @@ impl RarExtractor {
                 path: PathBuf,
                 base_bytes: u64,
             },
+            Collision {
+                original: String,
+                renamed: String,
+            },
             Done {
-                success: bool,
+                entries: ArchiveEntries,
             },
         }
 
         let (tx, mut rx) = mpsc::channel::<ProgressMsg>(32);
+        let archive_name = archive_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown")
+            .to_string();
+        let emit_progress = |bytes_extracted: u64| {
+            if self.emit_events {
+                ProgressEvent::ExtractProgress {
+                    archive: archive_name.clone(),
+                    bytes_extracted,
+                    total_bytes,
+                }
+                .emit();
+            }
+        };
         let archive_path = archive_path.to_path_buf();
         let output_dir = output_dir.to_path_buf();
         let large_file_threshold = self.large_file_threshold;
+        let force_reextract = self.config.force_reextract;
+        let flatten = self.config.flatten_extraction;
+        let fold_case = match self.config.filesystem_case {
+            FilesystemCase::Auto => is_case_insensitive(&output_dir),
+            FilesystemCase::Sensitive => false,
+            FilesystemCase::Insensitive => true,
+        };
+        let entry_filter = self.entry_filter.clone();
 
+        // The archive writes one entry at a time, so it holds one slot while it runs
+        let file_handle = match &self.file_limit {
+            Some(limit) => Some(limit.acquire().await),
+            None => None,
+        };
         let extraction_handle = tokio::task::spawn_blocking(move || {
+            let _file_handle = file_handle;
             let mut bytes_extracted = 0u64;
             let mut extracted_files = 0u64;
+            let mut entries = ArchiveEntries::default();
+            let mut used_names = HashSet::new();
 
             let mut archive = match Archive::new(&archive_path).open_for_processing() {
                 Ok(a) => a,
                 Err(_) => {
-                    let _ = tx.blocking_send(ProgressMsg::Done { success: false });
+                    entries.broken = true;
+                    let _ = tx.blocking_send(ProgressMsg::Done { entries });
                     return;
                 }
             };
@@ -164,13 +462,21 @@ impl RarExtractor {
                         let filename = entry.filename.clone();
                         let file_size = entry.unpacked_size;
 
-                        if entry.is_directory() {
+                        let name = entry_name(&filename);
+                        let filtered_out = !entry.is_directory() && !entry_filter.allows(&name);
+                        if filtered_out {
+                            entries.skipped.push(name.clone());
+                        }
+                        if entry.is_directory() || filtered_out {
                             match header.skip() {
                                 Ok(next) => {
                                     archive = next;
                                     continue;
                                 }
-                                Err(_) => break,
+                                Err(_) => {
+                                    entries.broken = true;
+                                    break;
+                                }
                             }
                         }
 
@@ -186,9 +492,13 @@ impl RarExtractor {
                             total: file_count,
                         });
 
-                        let safe_filename: PathBuf = filename
+                        let mut safe_filename: PathBuf = filename
                             .components()
                             .filter(|c| matches!(c, std::path::Component::Normal(_)))
+                            .map(|c| match c.as_os_str().to_str() {
+                                Some(part) => OsString::from(clamp_filename(part)),
+                                None => c.as_os_str().to_os_string(),
+                            })
                             .collect();
 
                         if safe_filename.as_os_str().is_empty() {
@@ -197,8 +507,29 @@ impl RarExtractor {
                                     archive = next;
                                     continue;
                                 }
-                                Err(_) => break,
+                                Err(_) => {
+                                    entries.broken = true;
+                                    break;
+                                }
+                            }
+                        }
+
+                        // Drop the archive's folders, renaming entries whose names collide;
+                        // on a case-insensitive filesystem, names differing only in case do
+                        let target = if flatten {
+                            safe_filename.file_name().map(PathBuf::from)
+                        } else {
+                            fold_case.then(|| safe_filename.clone())
+                        };
+                        if let Some(target) = target {
+                            let unique = unique_entry_path(&target, &mut used_names);
+                            if unique != target {
+                                let _ = tx.blocking_send(ProgressMsg::Collision {
+                                    original: safe_filename.to_string_lossy().into_owned(),
+                                    renamed: unique.to_string_lossy().into_owned(),
+                                });
                             }
+                            safe_filename = unique;
                         }
 
                         let output_path = output_dir.join(&safe_filename);
@@ -206,6 +537,26 @@ impl RarExtractor {
                             let _ = std::fs::create_dir_all(parent);
                         }
 
+                        if !force_reextract && is_already_extracted(&output_path, file_size) {
+                            match header.skip() {
+                                Ok(next) => {
+                                    archive = next;
+                                    bytes_extracted += file_size;
+                                    extracted_files += 1;
+                                    entries.extracted.push(name);
+                                    entries.outputs.push(output_path);
+                                    let _ = tx.blocking_send(ProgressMsg::FileComplete {
+                                        bytes: bytes_extracted,
+                                    });
+                                    continue;
+                                }
+                                Err(_) => {
+                                    entries.broken = true;
+                                    break;
+                                }
+                            }
+                        }
+
                         if file_size > large_file_threshold {
                             let _ = tx.blocking_send(ProgressMsg::MonitorFile {
                                 path: output_path.clone(),
@@ -213,30 +564,37 @@ impl RarExtractor {
                             });
                         }
 
+                        entries.written.push(output_path.clone());
                         match header.extract_to(&output_path) {
                             Ok(next) => {
                                 archive = next;
                                 bytes_extracted += file_size;
                                 extracted_files += 1;
+                                entries.extracted.push(name);
+                                entries.outputs.push(output_path);
                                 let _ = tx.blocking_send(ProgressMsg::FileComplete {
                                     bytes: bytes_extracted,
                                 });
                             }
-                            Err(_) => break,
+                            Err(_) => {
+                                entries.broken = true;
+                                break;
+                            }
                         }
                     }
                     Ok(None) => break,
-                    Err(_) => break,
+                    Err(_) => {
+                        entries.broken = true;
+                        break;
+                    }
                 }
             }
 
-            let _ = tx.blocking_send(ProgressMsg::Done {
-                success: extracted_files > 0,
-            });
+            let _ = tx.blocking_send(ProgressMsg::Done { entries });
         });
 
         let mut current_monitor: Option<(PathBuf, u64)> = None;
-        let mut result = false;
+        let mut result = ArchiveEntries::default();
 
         loop {
             if let Some((ref path, base_bytes)) = current_monitor {
@@ -248,13 +606,20 @@ impl RarExtractor {
                             }
                             Some(ProgressMsg::FileComplete { bytes }) => {
                                 progress_bar.set_position(bytes);
+                                emit_progress(bytes);
                                 current_monitor = None;
                             }
                             Some(ProgressMsg::MonitorFile { path, base_bytes }) => {
                                 current_monitor = Some((path, base_bytes));
                             }
-                            Some(ProgressMsg::Done { success }) => {
-                                result = success;
+                            Some(ProgressMsg::Collision { original, renamed }) => {
+                                progress::bar_warning(
+                                    progress_bar,
+                                    &format!("Name collision: {} saved as {}", original, renamed),
+                                );
+                            }
+                            Some(ProgressMsg::Done { entries }) => {
+                                result = entries;
                                 break;
                             }
                             None => break,
@@ -274,12 +639,19 @@ impl RarExtractor {
                     }
                     Some(ProgressMsg::FileComplete { bytes }) => {
                         progress_bar.set_position(bytes);
+                        emit_progress(bytes);
                     }
                     Some(ProgressMsg::MonitorFile { path, base_bytes }) => {
                         current_monitor = Some((path, base_bytes));
                     }
-                    Some(ProgressMsg::Done { success }) => {
-                        result = success;
+                    Some(ProgressMsg::Collision { original, renamed }) => {
+                        progress::bar_warning(
+                            progress_bar,
+                            &format!("Name collision: {} saved as {}", original, renamed),
+                        );
+                    }
+                    Some(ProgressMsg::Done { entries }) => {
+                        result = entries;
                         break;
                     }
                     None => break,
@@ -292,6 +664,135 @@ impl RarExtractor {
 
         Ok(result)
     }
+
+    /// Delete or quarantine the files a failed extraction wrote, per `partial_extraction`
+    ///
+    /// Left behind, they would be taken for finished content by deobfuscation and by the
+    /// resume check of the next run. Returns the files that were cleaned up.
+    fn clean_partial(
+        &self,
+        archive_path: &Path,
+        written: &[PathBuf],
+        output_dir: &Path,
+        progress_bar: &ProgressBar,
+    ) -> Vec<PathBuf> {
+        if written.is_empty() {
+            return Vec::new();
+        }
+        let archive = archive_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let quarantine = match self.config.partial_extraction {
+            PartialExtraction::Keep => {
+                progress::bar_warning(
+                    progress_bar,
+                    &format!(
+                        "Extraction of {} failed partway; left {} file{} in place",
+                        archive,
+                        written.len(),
+                        if written.len() == 1 { "" } else { "s" }
+                    ),
+                );
+                return Vec::new();
+            }
+            PartialExtraction::Delete => None,
+            PartialExtraction::Quarantine => {
+                let stem = archive_path.file_stem().unwrap_or(archive_path.as_os_str());
+                Some(output_dir.join(QUARANTINE_DIR).join(stem))
+            }
+        };
+
+        let mut cleaned = Vec::new();
+        for path in written {
+            let result = match &quarantine {
+                None => std::fs::remove_file(path),
+                Some(dir) => {
+                    let target = dir.join(path.strip_prefix(output_dir).unwrap_or(path));
+                    target
+                        .parent()
+                        .map_or(Ok(()), std::fs::create_dir_all)
+                        .and_then(|_| std::fs::rename(path, &target))
+                }
+            };
+            match result {
+                Ok(()) => cleaned.push(path.clone()),
+                // The entry failed before anything was written
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => progress::bar_warning(
+                    progress_bar,
+                    &format!("Could not clean up {}: {}", path.display(), e),
+                ),
+            }
+            // Drop folders the extraction created that are now empty
+            for dir in path.ancestors().skip(1) {
+                if dir == output_dir
+                    || !dir.starts_with(output_dir)
+                    || std::fs::remove_dir(dir).is_err()
+                {
+                    break;
+                }
+            }
+        }
+
+        let plural = if cleaned.len() == 1 { "" } else { "s" };
+        let action = match &quarantine {
+            None => format!("removed {} file{}", cleaned.len(), plural),
+            Some(dir) => format!(
+                "moved {} file{} to {}",
+                cleaned.len(),
+                plural,
+                dir.display()
+            ),
+        };
+        progress::bar_warning(
+            progress_bar,
+            &format!("Extraction of {} failed partway; {}", archive, action),
+        );
+        for path in &cleaned {
+            let name = path.strip_prefix(output_dir).unwrap_or(path);
+            progress::bar_detail(progress_bar, &name.display().to_string());
+        }
+        cleaned
+    }
+}
+
+/// An entry's path inside the archive with `/` separators, as entry globs see it
+fn entry_name(filename: &Path) -> String {
+    filename.to_string_lossy().replace('\\', "/")
+}
+
+/// List what came out of an archive and what the entry filter left inside it
+fn report_entries(progress_bar: &ProgressBar, archive: &str, entries: &ArchiveEntries) {
+    if !entries.extracted.is_empty() {
+        progress::bar_detail(
+            progress_bar,
+            &format!(
+                "Extracted from {}: {}",
+                archive,
+                entries.extracted.join(", ")
+            ),
+        );
+    }
+    if !entries.skipped.is_empty() {
+        progress::bar_detail(
+            progress_bar,
+            &format!(
+                "Skipped in {} (filtered): {}",
+                archive,
+                entries.skipped.join(", ")
+            ),
+        );
+    }
+}
+
+/// Whether a previous run already extracted an entry to `path`
+///
+/// Same resume rule as downloads: a file of the expected size is sufficient.
+fn is_already_extracted(path: &Path, size: u64) -> bool {
+    std::fs::metadata(path)
+        .map(|m| m.is_file() && m.len() == size)
+        .unwrap_or(false)
 }
 
 /// Check if a path is a RAR archive (first part only for multi-part)
@@ -299,8 +800,52 @@ pub fn is_rar_archive(path: &Path) -> bool {
     rar_patterns::is_extractable_archive(path)
 }
 
-/// Delete all parts of a RAR archive
-fn delete_rar_parts(rar_path: &Path, download_dir: &Path) -> Result<()> {
+/// Pick a path for an entry that no earlier entry of the archive has used, ignoring case
+///
+/// Collisions get a numeric suffix (`name_1.ext`) in archive order, so re-running the
+/// extraction maps every entry to the same name and the resume check still applies.
+fn unique_entry_path(path: &Path, used: &mut HashSet<String>) -> PathBuf {
+    let key = |path: &Path| path.to_string_lossy().to_lowercase();
+    if used.insert(key(path)) {
+        return path.to_path_buf();
+    }
+
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(&name);
+    let ext = path.extension().and_then(|s| s.to_str());
+    (1..)
+        .map(|i| match ext {
+            Some(ext) => path.with_file_name(format!("{}_{}.{}", stem, i, ext)),
+            None => path.with_file_name(format!("{}_{}", stem, i)),
+        })
+        .find(|candidate| used.insert(key(candidate)))
+        .expect("unbounded suffix search always finds a free name")
+}
+
+/// Whether `dir` is on a filesystem that matches names case-insensitively
+///
+/// Probes with a temporary lowercase file and checks whether its uppercase name resolves.
+/// When the probe can't be written, falls back to the platform's usual default.
+fn is_case_insensitive(dir: &Path) -> bool {
+    let probe_name = format!(".dl-nzb-case-probe-{}", std::process::id());
+    let probe = dir.join(&probe_name);
+    if std::fs::write(&probe, b"").is_err() {
+        return cfg!(any(windows, target_os = "macos"));
+    }
+    let insensitive = dir.join(probe_name.to_uppercase()).exists();
+    let _ = std::fs::remove_file(&probe);
+    insensitive
+}
+
+/// Delete all parts of a RAR archive, except files matching a `preserved` glob
+pub fn delete_rar_parts(
+    rar_path: &Path,
+    download_dir: &Path,
+    preserved: &[regex::Regex],
+) -> Result<()> {
     let filename = match rar_path.file_name().and_then(|n| n.to_str()) {
         Some(name) => name,
         None => return Ok(()),
@@ -311,7 +856,9 @@ fn delete_rar_parts(rar_path: &Path, download_dir: &Path) -> Result<()> {
     if let Ok(entries) = std::fs::read_dir(download_dir) {
         for entry in entries.filter_map(|e| e.ok()) {
             let entry_name = entry.file_name().to_string_lossy().to_string();
-            if rar_patterns::is_same_archive(base_name, &entry_name) {
+            if rar_patterns::is_same_archive(base_name, &entry_name)
+                && !is_preserved(&entry.path(), preserved)
+            {
                 let _ = std::fs::remove_file(entry.path());
             }
         }
@@ -319,3 +866,204 @@ fn delete_rar_parts(rar_path: &Path, download_dir: &Path) -> Result<()> {
 
     Ok(())
 }
+
+/// Delete the parts of a nested archive, but only files that an extraction produced
+fn delete_produced_parts(rar_path: &Path, produced: &HashSet<PathBuf>, preserved: &[regex::Regex]) {
+    let Some(filename) = rar_path.file_name().and_then(|n| n.to_str()) else {
+        return;
+    };
+    let base_name = rar_patterns::extract_base_name(filename).unwrap_or(filename);
+    let dir = rar_path.parent();
+
+    for path in produced {
+        let is_part = path.parent() == dir
+            && path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|name| rar_patterns::is_same_archive(base_name, name));
+        if is_part && !is_preserved(path, preserved) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flattened_name_renames_collisions() {
+        let mut used = HashSet::new();
+        let mut unique = |name: &str| unique_entry_path(Path::new(name), &mut used);
+        assert_eq!(unique("movie.mkv"), PathBuf::from("movie.mkv"));
+        assert_eq!(unique("sample.mkv"), PathBuf::from("sample.mkv"));
+        assert_eq!(unique("Movie.mkv"), PathBuf::from("Movie_1.mkv"));
+        assert_eq!(unique("movie.mkv"), PathBuf::from("movie_2.mkv"));
+    }
+
+    #[test]
+    fn test_case_colliding_entries_keep_their_folders() {
+        let entries = [
+            "Readme.txt",
+            "README.TXT",
+            "docs/a.txt",
+            "DOCS/A.txt",
+            "docs/b.txt",
+        ];
+        let mut used = HashSet::new();
+        let paths: Vec<PathBuf> = entries
+            .iter()
+            .map(|entry| unique_entry_path(Path::new(entry), &mut used))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "Readme.txt",
+                "README_1.TXT",
+                "docs/a.txt",
+                "DOCS/A_1.txt",
+                "docs/b.txt"
+            ]
+            .map(PathBuf::from)
+        );
+
+        // The probe cleans up after itself whatever the answer
+        let dir = tempfile::tempdir().unwrap();
+        is_case_insensitive(dir.path());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_delete_produced_parts_leaves_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        for name in [
+            "inner.part1.rar",
+            "inner.part2.rar",
+            "inner.part3.rar",
+            "inner.part4.rar",
+            "movie.mkv",
+        ] {
+            std::fs::write(path(name), b"").unwrap();
+        }
+
+        // inner.part4.rar wasn't extracted from anything: another download's file
+        let produced: HashSet<PathBuf> = ["inner.part1.rar", "inner.part2.rar", "inner.part3.rar"]
+            .iter()
+            .map(|name| path(name))
+            .collect();
+        let preserved = vec![glob_regex("*.part3.rar")];
+        delete_produced_parts(&path("inner.part1.rar"), &produced, &preserved);
+
+        assert!(!path("inner.part1.rar").exists());
+        assert!(!path("inner.part2.rar").exists());
+        assert!(path("inner.part3.rar").exists());
+        assert!(path("inner.part4.rar").exists());
+        assert!(path("movie.mkv").exists());
+    }
+
+    #[test]
+    fn test_is_already_extracted_requires_matching_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("movie.mkv");
+        assert!(!is_already_extracted(&path, 0));
+
+        std::fs::write(&path, b"12345").unwrap();
+        assert!(is_already_extracted(&path, 5));
+        assert!(!is_already_extracted(&path, 6));
+
+        // A directory with the entry's name is not an extracted file
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        assert!(!is_already_extracted(&dir.path().join("sub"), 0));
+    }
+
+    #[test]
+    fn test_clean_partial_deletes_or_quarantines_written_files() {
+        let extractor = |mode| {
+            let config = PostProcessingConfig {
+                partial_extraction: mode,
+                ..PostProcessingConfig::default()
+            };
+            RarExtractor::new(config, u64::MAX)
+        };
+        let setup = || {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::create_dir(dir.path().join("sub")).unwrap();
+            std::fs::write(dir.path().join("a.mkv"), b"a").unwrap();
+            std::fs::write(dir.path().join("sub/b.srt"), b"b").unwrap();
+            std::fs::write(dir.path().join("keep.nfo"), b"").unwrap();
+            let written = vec![
+                dir.path().join("a.mkv"),
+                dir.path().join("sub/b.srt"),
+                dir.path().join("never-written.txt"),
+            ];
+            (dir, written)
+        };
+        let archive = Path::new("movie.part01.rar");
+        let bar = ProgressBar::hidden();
+
+        let (dir, written) = setup();
+        let kept =
+            extractor(PartialExtraction::Keep).clean_partial(archive, &written, dir.path(), &bar);
+        assert!(kept.is_empty());
+        assert!(dir.path().join("a.mkv").exists());
+
+        let (dir, written) = setup();
+        let cleaned =
+            extractor(PartialExtraction::Delete).clean_partial(archive, &written, dir.path(), &bar);
+        assert_eq!(cleaned, written[..2]);
+        assert!(!dir.path().join("a.mkv").exists());
+        assert!(!dir.path().join("sub").exists());
+        assert!(dir.path().join("keep.nfo").exists());
+
+        let (dir, written) = setup();
+        let cleaned = extractor(PartialExtraction::Quarantine).clean_partial(
+            archive,
+            &written,
+            dir.path(),
+            &bar,
+        );
+        assert_eq!(cleaned, written[..2]);
+        let quarantine = dir.path().join(QUARANTINE_DIR).join("movie.part01");
+        assert!(quarantine.join("a.mkv").exists());
+        assert!(quarantine.join("sub/b.srt").exists());
+        assert!(!dir.path().join("sub").exists());
+    }
+
+    #[test]
+    fn test_archive_filter() {
+        let config = PostProcessingConfig {
+            extract_include: vec![r"\.rar$".to_string()],
+            extract_exclude: vec!["extras".to_string()],
+            ..PostProcessingConfig::default()
+        };
+        let filter = ArchiveFilter::new(&config);
+
+        assert!(filter.allows("Movie.part01.RAR"));
+        assert!(!filter.allows("Movie.Extras.rar"));
+        assert!(!filter.allows("bonus.zip"));
+        assert!(ArchiveFilter::new(&PostProcessingConfig::default()).allows("bonus.zip"));
+    }
+
+    #[test]
+    fn test_entry_filter_globs() {
+        let config = PostProcessingConfig {
+            extract_entry_include: vec!["*.mkv".to_string(), "*.srt".to_string()],
+            extract_entry_exclude: vec!["*sample*".to_string()],
+            ..PostProcessingConfig::default()
+        };
+        let filter = EntryFilter::new(&config);
+
+        assert!(filter.allows("Movie.2024.MKV"));
+        assert!(filter.allows("Subs/en.srt"));
+        assert!(!filter.allows("Sample/movie-sample.mkv"));
+        assert!(!filter.allows("movie.nfo"));
+        assert!(!filter.allows("movie.mkv.nfo"));
+        assert!(EntryFilter::new(&PostProcessingConfig::default()).is_empty());
+
+        // Only `*` and `?` are special
+        let exact = glob_regex("cd?/[a].txt");
+        assert!(exact.is_match("CD1/[a].txt"));
+        assert!(!exact.is_match("cd1/a.txt"));
+    }
+}
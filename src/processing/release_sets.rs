@@ -0,0 +1,152 @@
+//! Release sets sharing one download folder
+//!
+//! An NZB can carry two unrelated releases, e.g. two movies posted together. Their archives
+//! and PAR2 files name each set; every other file joins the set whose name it starts with.
+//! Each set can then be post-processed on its own, under its own name.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::patterns::{par2 as par2_patterns, rar as rar_patterns};
+
+/// Files belonging to one release
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseSet {
+    /// Base name shared by the set's archives and PAR2 files, as posted
+    pub name: String,
+    pub files: Vec<PathBuf>,
+}
+
+/// Group downloaded files into release sets
+///
+/// Sets are named by RAR base names and PAR2 set names; a set whose name extends another's
+/// (`movie.sample` next to `movie`) is part of it. Other files join the set with the
+/// longest name they start with. Files matching no set are left out, unless there is only
+/// one set. Sets come back sorted by name.
+pub fn detect_release_sets(files: &[PathBuf]) -> Vec<ReleaseSet> {
+    // Lowercased set name -> name as posted
+    let mut names: BTreeMap<String, String> = BTreeMap::new();
+    for path in files {
+        if let Some(name) = anchor_name(path) {
+            names.entry(name.to_lowercase()).or_insert(name);
+        }
+    }
+
+    // Keys in order, so a shorter name comes before the names extending it
+    let keys: Vec<String> = names.keys().cloned().collect();
+    let mut roots: Vec<String> = Vec::new();
+    for key in keys {
+        if !roots.iter().any(|root| extends(&key, root)) {
+            roots.push(key);
+        }
+    }
+
+    let mut sets: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    let mut unmatched = Vec::new();
+    for path in files {
+        let filename = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        match roots
+            .iter()
+            .filter(|root| extends(&filename, root))
+            .max_by_key(|root| root.len())
+        {
+            Some(root) => sets.entry(root.clone()).or_default().push(path.clone()),
+            None => unmatched.push(path.clone()),
+        }
+    }
+
+    if sets.len() == 1 {
+        if let Some(files) = sets.values_mut().next() {
+            files.append(&mut unmatched);
+        }
+    }
+
+    sets.into_iter()
+        .map(|(key, mut files)| {
+            files.sort();
+            ReleaseSet {
+                name: names.remove(&key).unwrap_or(key),
+                files,
+            }
+        })
+        .collect()
+}
+
+/// Set name carried by an archive or PAR2 file
+fn anchor_name(path: &Path) -> Option<String> {
+    let filename = path.file_name()?.to_str()?;
+    if par2_patterns::is_par2_file(path) {
+        let key = par2_patterns::set_name(path)?;
+        // `set_name` lowercases; keep the name as posted when the lengths still line up
+        return Some(match filename.get(..key.len()) {
+            Some(name) if name.eq_ignore_ascii_case(&key) => name.to_string(),
+            _ => key,
+        });
+    }
+    if !rar_patterns::is_rar_related(filename) {
+        return None;
+    }
+    match rar_patterns::extract_base_name(filename) {
+        Some(base) => Some(base.to_string()),
+        // Old-style volumes (.r00, .r01) are named after the .rar they continue
+        None => Some(path.file_stem()?.to_str()?.to_string()),
+    }
+}
+
+/// Whether `name` is `set` or starts with it followed by a dot
+fn extends(name: &str, set: &str) -> bool {
+    name.strip_prefix(set)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_releases_in_one_folder() {
+        let files: Vec<PathBuf> = [
+            "Movie.A.2020.part01.rar",
+            "Movie.A.2020.part02.rar",
+            "Movie.A.2020.par2",
+            "Movie.A.2020.vol00+01.par2",
+            "Movie.A.2020.nfo",
+            "Movie.A.2020.sample.rar",
+            "movie.b.2021.rar",
+            "movie.b.2021.r00",
+            "Movie.B.2021.PAR2",
+            "readme.txt",
+        ]
+        .iter()
+        .map(|name| Path::new("/dl").join(name))
+        .collect();
+
+        let sets = detect_release_sets(&files);
+
+        assert_eq!(sets.len(), 2);
+        assert_eq!(sets[0].name, "Movie.A.2020");
+        assert_eq!(sets[0].files.len(), 6);
+        assert!(sets[0]
+            .files
+            .contains(&Path::new("/dl").join("Movie.A.2020.sample.rar")));
+        assert_eq!(sets[1].name, "movie.b.2021");
+        assert_eq!(sets[1].files.len(), 3);
+    }
+
+    #[test]
+    fn test_single_release_keeps_loose_files() {
+        let files: Vec<PathBuf> = ["show.rar", "show.par2", "abc123.mkv"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+
+        let sets = detect_release_sets(&files);
+
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].files.len(), 3);
+    }
+}
@@ -1,11 +1,264 @@
 //! Centralized progress reporting
 //!
 //! Provides a unified interface for displaying progress across downloads and post-processing.
+//!
+//! Progress bars are only drawn on a terminal. When output is piped to a log, or the
+//! program runs under systemd or cron, download progress comes as periodic plain lines
+//! instead and [`outln!`](crate::outln) drops the ANSI colors from status output.
 
+use crate::config::SummaryFormat;
 use human_bytes::human_bytes;
-use indicatif::{ProgressBar, ProgressStyle as IndicatifStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle as IndicatifStyle};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::borrow::Cow;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 
+/// Set while stdout carries JSON (`--json`), so status lines must go elsewhere
+static JSON_STDOUT: AtomicBool = AtomicBool::new(false);
+
+/// How often a plain progress line is printed when stderr isn't a terminal
+pub const PLAIN_PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// `println!` that drops ANSI colors when stdout isn't a terminal, and goes to stderr
+/// while stdout carries JSON
+#[macro_export]
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        if $crate::progress::json_stdout() {
+            eprintln!("{}", $crate::progress::plain_stderr(&format!($($arg)*)))
+        } else {
+            println!("{}", $crate::progress::plain(&format!($($arg)*)))
+        }
+    };
+}
+
+/// `eprintln!` that drops ANSI colors when stderr isn't a terminal
+#[macro_export]
+macro_rules! eoutln {
+    ($($arg:tt)*) => {
+        eprintln!("{}", $crate::progress::plain_stderr(&format!($($arg)*)))
+    };
+}
+
+/// Keep stdout machine-readable: status lines printed with [`outln!`](crate::outln) go to
+/// stderr from now on
+pub fn set_json_stdout(enabled: bool) {
+    JSON_STDOUT.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether stdout is reserved for JSON output
+pub fn json_stdout() -> bool {
+    JSON_STDOUT.load(Ordering::Relaxed)
+}
+
+/// Shared display, so bars of NZBs processed concurrently stack instead of overdrawing
+static BARS: Lazy<MultiProgress> = Lazy::new(MultiProgress::new);
+
+/// Draw a bar through the shared display
+pub fn track(bar: ProgressBar) -> ProgressBar {
+    if bar.is_hidden() {
+        bar
+    } else {
+        BARS.add(bar)
+    }
+}
+
+/// Whether progress bars get drawn, i.e. stderr is a terminal
+pub fn is_interactive() -> bool {
+    std::io::stderr().is_terminal()
+}
+
+/// Remove ANSI color codes from text bound for stdout, unless stdout is a terminal
+pub fn plain(text: &str) -> Cow<'_, str> {
+    static STDOUT_IS_TERMINAL: Lazy<bool> = Lazy::new(|| std::io::stdout().is_terminal());
+    if *STDOUT_IS_TERMINAL {
+        Cow::Borrowed(text)
+    } else {
+        strip_ansi(text)
+    }
+}
+
+/// Remove ANSI color codes from text bound for stderr, unless stderr is a terminal
+pub fn plain_stderr(text: &str) -> Cow<'_, str> {
+    if is_interactive() {
+        Cow::Borrowed(text)
+    } else {
+        strip_ansi(text)
+    }
+}
+
+fn strip_ansi(text: &str) -> Cow<'_, str> {
+    static ANSI_REGEX: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"\x1b\[[0-9;]*m").expect("valid regex"));
+    ANSI_REGEX.replace_all(text, "")
+}
+
+static SUMMARY_FORMAT: OnceLock<SummaryFormat> = OnceLock::new();
+
+/// Choose how result summaries are printed, once per process; fancy until set
+pub fn set_summary_format(format: SummaryFormat) {
+    let _ = SUMMARY_FORMAT.set(format);
+}
+
+/// How result summaries are printed
+pub fn summary_format() -> SummaryFormat {
+    SUMMARY_FORMAT.get().copied().unwrap_or_default()
+}
+
+/// Print the result of a stage, e.g. `  └─ ✓ PAR2 verified`, in the chosen summary format
+///
+/// `color` is the ANSI color of the fancy line.
+pub fn stage_summary(ok: bool, color: &str, text: &str) {
+    if let Some(line) = stage_summary_line(summary_format(), ok, color, text) {
+        outln!("{}", line);
+    }
+}
+
+/// The line [`stage_summary`] prints; minimal keeps only failures and none drops everything
+fn stage_summary_line(format: SummaryFormat, ok: bool, color: &str, text: &str) -> Option<String> {
+    match format {
+        SummaryFormat::Fancy => Some(format!(
+            "  └─ {}{} {}\x1b[0m",
+            color,
+            if ok { "✓" } else { "✗" },
+            text
+        )),
+        SummaryFormat::Plain => Some(format!("  {} {}", if ok { "ok:" } else { "failed:" }, text)),
+        SummaryFormat::Minimal if !ok => Some(format!("failed: {}", text)),
+        SummaryFormat::Minimal | SummaryFormat::None => None,
+    }
+}
+
+/// Print a warning, e.g. `  ⚠ Replaced 2 connections`, in the chosen summary format
+pub fn stage_warning(text: &str) {
+    if let Some(line) = stage_warning_line(summary_format(), text) {
+        outln!("{}", line);
+    }
+}
+
+/// The line [`stage_warning`] prints; minimal keeps warnings, like failures
+fn stage_warning_line(format: SummaryFormat, text: &str) -> Option<String> {
+    match format {
+        SummaryFormat::Fancy => Some(format!("  \x1b[33m⚠ {}\x1b[0m", text)),
+        SummaryFormat::Plain => Some(format!("  warning: {}", text)),
+        SummaryFormat::Minimal => Some(format!("warning: {}", text)),
+        SummaryFormat::None => None,
+    }
+}
+
+/// Print a detail under a stage's result, e.g. `    ↳ a.mkv = b.mkv`; fancy and plain only
+pub fn stage_detail(text: &str) {
+    if let Some(line) = stage_detail_line(summary_format(), text) {
+        outln!("{}", line);
+    }
+}
+
+fn stage_detail_line(format: SummaryFormat, text: &str) -> Option<String> {
+    match format {
+        SummaryFormat::Fancy => Some(format!("    \x1b[90m↳ {}\x1b[0m", text)),
+        SummaryFormat::Plain => Some(format!("    {}", text)),
+        SummaryFormat::Minimal | SummaryFormat::None => None,
+    }
+}
+
+/// [`stage_warning`] for a stage still showing `bar`, printed above the bar while it is drawn
+pub fn bar_warning(bar: &ProgressBar, text: &str) {
+    if let Some(line) = stage_warning_line(summary_format(), text) {
+        print_above(bar, &line);
+    }
+}
+
+/// [`stage_detail`] for a stage still showing `bar`, printed above the bar while it is drawn
+pub fn bar_detail(bar: &ProgressBar, text: &str) {
+    if let Some(line) = stage_detail_line(summary_format(), text) {
+        print_above(bar, &line);
+    }
+}
+
+fn print_above(bar: &ProgressBar, line: &str) {
+    if bar.is_hidden() {
+        outln!("{}", line);
+    } else {
+        bar.println(line);
+    }
+}
+
+/// One line of download progress without colors, e.g. `45% 2.3 GB/5.1 GB 30 MB/s`
+pub fn plain_progress_line(bytes_done: u64, total_bytes: u64, bytes_per_sec: f64) -> String {
+    let percent = (bytes_done.min(total_bytes) * 100)
+        .checked_div(total_bytes)
+        .unwrap_or(0);
+    format!(
+        "{}% {}/{} {}/s",
+        percent,
+        human_bytes(bytes_done as f64),
+        human_bytes(total_bytes as f64),
+        human_bytes(bytes_per_sec)
+    )
+}
+
+/// Print a plain progress line for `bar` to stderr every [`PLAIN_PROGRESS_INTERVAL`]
+///
+/// For when the bar can't be drawn; runs until the task is aborted.
+pub async fn report_plain_progress(bar: ProgressBar) {
+    loop {
+        tokio::time::sleep(PLAIN_PROGRESS_INTERVAL).await;
+        eprintln!(
+            "{}",
+            plain_progress_line(bar.position(), bar.length().unwrap_or(0), bar.per_sec())
+        );
+    }
+}
+
+/// Snapshot of download progress passed to a [`DownloadProgressCallback`]
+#[derive(Debug, Clone)]
+pub struct DownloadProgress<'a> {
+    pub bytes_done: u64,
+    pub total_bytes: u64,
+    /// File the latest segments belong to
+    pub current_file: &'a str,
+    /// Recent throughput in bytes per second
+    pub bytes_per_sec: f64,
+}
+
+/// Receives download progress, so embedders can report it without indicatif
+pub type DownloadProgressCallback = Arc<dyn Fn(&DownloadProgress<'_>) + Send + Sync>;
+
+/// Download progress bar paired with an optional callback that gets the same updates
+#[derive(Clone)]
+pub struct DownloadReporter {
+    bar: ProgressBar,
+    callback: Option<DownloadProgressCallback>,
+}
+
+impl DownloadReporter {
+    pub fn new(bar: ProgressBar, callback: Option<DownloadProgressCallback>) -> Self {
+        Self { bar, callback }
+    }
+
+    /// The underlying bar, which also keeps count when hidden
+    pub fn bar(&self) -> &ProgressBar {
+        &self.bar
+    }
+
+    /// Advance the bar and pass the new totals to the callback, if one is set
+    pub fn advance(&self, bytes: u64, current_file: &str) {
+        self.bar.inc(bytes);
+        if let Some(callback) = &self.callback {
+            callback(&DownloadProgress {
+                bytes_done: self.bar.position(),
+                total_bytes: self.bar.length().unwrap_or(0),
+                current_file,
+                bytes_per_sec: self.bar.per_sec(),
+            });
+        }
+    }
+}
+
 /// Progress display style
 #[derive(Debug, Clone, Copy)]
 pub enum ProgressStyle {
@@ -20,7 +273,7 @@ pub enum ProgressStyle {
 
 /// Create a progress bar with the specified style
 pub fn create_progress_bar(total: u64, style: ProgressStyle) -> ProgressBar {
-    let bar = ProgressBar::new(total);
+    let bar = track(ProgressBar::new(total));
     apply_style(&bar, style);
     bar.enable_steady_tick(Duration::from_millis(100));
     bar
@@ -132,3 +385,59 @@ pub fn format_download_summary(
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_progress_line() {
+        let line = plain_progress_line(450, 1000, 30.0);
+        assert!(line.starts_with("45% "));
+        assert!(!line.contains('\x1b'));
+        assert!(plain_progress_line(5, 0, 0.0).starts_with("0% "));
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi("  └─ \x1b[1;32m✓ Downloaded\x1b[0m 1 GB"),
+            "  └─ ✓ Downloaded 1 GB"
+        );
+    }
+
+    #[test]
+    fn test_stage_summary_formats() {
+        let line = |format, ok| stage_summary_line(format, ok, "\x1b[33m", "PAR2 verified");
+
+        assert_eq!(
+            line(SummaryFormat::Fancy, true).unwrap(),
+            "  └─ \x1b[33m✓ PAR2 verified\x1b[0m"
+        );
+        assert_eq!(
+            line(SummaryFormat::Plain, false).unwrap(),
+            "  failed: PAR2 verified"
+        );
+        assert_eq!(line(SummaryFormat::Minimal, true), None);
+        assert_eq!(
+            line(SummaryFormat::Minimal, false).unwrap(),
+            "failed: PAR2 verified"
+        );
+        assert_eq!(line(SummaryFormat::None, false), None);
+    }
+
+    #[test]
+    fn test_stage_warning_formats() {
+        let line = |format| stage_warning_line(format, "Reached max_open_files (64)");
+
+        assert_eq!(
+            line(SummaryFormat::Fancy).unwrap(),
+            "  \x1b[33m⚠ Reached max_open_files (64)\x1b[0m"
+        );
+        assert_eq!(
+            line(SummaryFormat::Minimal).unwrap(),
+            "warning: Reached max_open_files (64)"
+        );
+        assert_eq!(line(SummaryFormat::None), None);
+    }
+}